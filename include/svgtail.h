@@ -0,0 +1,44 @@
+/* C interface to svgtail's loading, watching and rendering, built with
+ *
+ *     cargo rustc --release --lib --features ffi --crate-type cdylib
+ *
+ * (or --crate-type staticlib). See src/ffi.rs for the details. */
+
+#ifndef SVGTAIL_H
+#define SVGTAIL_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct svgtail_watcher svgtail_watcher;
+
+/* Called from a background thread with the watched path and, if the changed
+ * document doesn't load, why; error is NULL if it does. */
+typedef void (*svgtail_callback)(void *user, const char *path, const char *error);
+
+/* Render the document at path into out, width * height * 4 bytes of RGBA,
+ * scaled to fit and centred with transparency around it. Returns 0, or -1
+ * with the reason in svgtail_last_error(). */
+int svgtail_render(const char *path, uint32_t width, uint32_t height, uint8_t *out);
+
+/* Why the last call on this thread failed, or NULL. Valid until the next
+ * failing call on the thread. */
+const char *svgtail_last_error(void);
+
+/* Call callback whenever the document at path changes as the viewer would
+ * reload it, ignoring writes that leave it as it was. Returns NULL on
+ * failure, with the reason in svgtail_last_error(). */
+svgtail_watcher *svgtail_watch(const char *path, svgtail_callback callback, void *user);
+
+/* Stop watching, waiting for a callback under way to return; none follow.
+ * Not to be called from the callback. */
+void svgtail_unwatch(svgtail_watcher *watcher);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,132 @@
+//! Announcing reloads, parse errors and zoom changes to screen readers over
+//! AT-SPI. The window is exposed as a single node, and each announcement is
+//! added to it as a new live region, which screen readers speak as it
+//! appears. Nothing is sent until an assistive technology is listening.
+
+use std::path::Path;
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use accesskit::{
+    ActionHandler, ActionRequest, ActivationHandler, DeactivationHandler, Live, Node, NodeId, Role,
+    TreeId, TreeInfo, TreeUpdate,
+};
+use accesskit_unix::Adapter;
+
+use crate::action::{Action, Event};
+use crate::units::Units;
+use crate::viewer::ActionSender;
+
+const WINDOW: NodeId = NodeId(0);
+
+/// How long zooming must pause before the new zoom is announced, so holding
+/// `+` doesn't read out every step
+const ZOOM_SETTLE: Duration = Duration::from_millis(600);
+
+/// The latest announcement, kept to answer a screen reader that starts later
+#[derive(Default)]
+struct Latest {
+    /// Id of the live region holding the announcement
+    id: u64,
+    message: Option<(String, Live)>,
+}
+
+impl Latest {
+    fn tree(&self) -> TreeUpdate {
+        let mut window = Node::new(Role::Window);
+        window.set_label("svgtail");
+        let mut nodes = Vec::new();
+        if let Some((text, live)) = &self.message {
+            let mut status = Node::new(Role::Status);
+            status.set_label(text.as_str());
+            status.set_live(*live);
+            window.set_children([NodeId(self.id)]);
+            nodes.push((NodeId(self.id), status));
+        }
+        nodes.push((WINDOW, window));
+        TreeUpdate {
+            nodes,
+            tree: Some(TreeInfo::new(WINDOW)),
+            tree_id: TreeId::ROOT,
+            focus: WINDOW,
+        }
+    }
+}
+
+struct Activation(Arc<Mutex<Latest>>);
+
+impl ActivationHandler for Activation {
+    fn request_initial_tree(&mut self) -> Option<TreeUpdate> {
+        Some(self.0.lock().unwrap().tree())
+    }
+}
+
+/// The window offers no actions.
+struct Inert;
+
+impl ActionHandler for Inert {
+    fn do_action(&mut self, _request: ActionRequest) {}
+}
+
+impl DeactivationHandler for Inert {
+    fn deactivate_accessibility(&mut self) {}
+}
+
+/// Announce the viewer's state changes from a background thread
+pub fn spawn(viewer: ActionSender, units: Units) {
+    let (events, rx) = mpsc::channel();
+    if viewer.send(Action::Subscribe(events)).is_err() {
+        return;
+    }
+    thread::spawn(move || {
+        let latest = Arc::new(Mutex::new(Latest::default()));
+        let mut adapter = Adapter::new(Activation(Arc::clone(&latest)), Inert, Inert);
+        let mut announce = |text: String, live: Live| {
+            let mut latest = latest.lock().unwrap();
+            // A new node each time, so a message repeated word for word is
+            // still spoken.
+            latest.id += 1;
+            latest.message = Some((text, live));
+            adapter.update_if_active(|| latest.tree());
+        };
+        let mut announced_zoom = None;
+        let mut zoom: Option<(f32, Instant)> = None;
+        loop {
+            let event = match zoom {
+                Some((_, since)) => rx.recv_timeout(ZOOM_SETTLE.saturating_sub(since.elapsed())),
+                None => rx.recv().map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+            };
+            match event {
+                Ok(Event::Loaded(path)) => {
+                    announce(format!("Loaded {}", name(&path)), Live::Polite)
+                }
+                Ok(Event::Reloaded(path)) => {
+                    announce(format!("Reloaded {}", name(&path)), Live::Polite)
+                }
+                Ok(Event::LoadFailed(path, e)) => announce(
+                    format!("{} failed to load: {e}", name(&path)),
+                    Live::Assertive,
+                ),
+                Ok(Event::ViewChanged { zoom: z, .. }) => {
+                    zoom = (announced_zoom != Some(z)).then(|| (z, Instant::now()));
+                }
+                Ok(Event::Key(_) | Event::Click { .. }) => {}
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    if let Some((z, _)) = zoom.take() {
+                        announced_zoom = Some(z);
+                        announce(format!("Zoom {}", units.percent(z)), Live::Polite);
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    });
+}
+
+fn name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
@@ -0,0 +1,68 @@
+//! Aids for low-vision users: a high-contrast rendering of the window and a
+//! cursor large enough to find at a glance
+
+use resvg::tiny_skia::{Color, Paint, PathBuilder, Pixmap, Stroke, Transform};
+
+/// How strongly everything but white is darkened; faint lines and pale
+/// fills come out several times darker while black and white stay put
+const INK_GAMMA: f32 = 4.0;
+
+/// Radius of the large cursor's ring, in screen pixels
+const CURSOR_RADIUS: f32 = 20.0;
+
+/// Which way round a high-contrast window is drawn
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Contrast {
+    /// Dark ink on white
+    #[default]
+    Light,
+    /// Light ink on black
+    Dark,
+}
+
+impl Contrast {
+    /// Turn a minifb `0RGB` buffer into strongly contrasting greys
+    pub fn apply(self, buffer: &mut [u32]) {
+        let levels: [u8; 256] = std::array::from_fn(|luma| {
+            let v = (luma as f32 / 255.0).powf(INK_GAMMA);
+            let v = match self {
+                Contrast::Light => v,
+                Contrast::Dark => 1.0 - v,
+            };
+            (v * 255.0).round() as u8
+        });
+        for px in buffer {
+            let [_, r, g, b] = px.to_be_bytes();
+            let luma = (2126 * r as u32 + 7152 * g as u32 + 722 * b as u32) / 10000;
+            let v = levels[luma as usize];
+            *px = u32::from_be_bytes([0, v, v, v]);
+        }
+    }
+}
+
+/// Draw a large ring and crosshair centred on `at`
+pub fn draw_cursor(pixmap: &mut Pixmap, at: (f32, f32)) {
+    let (x, y) = at;
+    let mut pb = PathBuilder::new();
+    pb.push_circle(x, y, CURSOR_RADIUS);
+    for (dx, dy) in [(1.0, 0.0), (-1.0, 0.0), (0.0, 1.0), (0.0, -1.0)] {
+        // Arms from just outside the pointer to beyond the ring
+        pb.move_to(x + dx * 6.0, y + dy * 6.0);
+        pb.line_to(x + dx * CURSOR_RADIUS * 1.6, y + dy * CURSOR_RADIUS * 1.6);
+    }
+    let Some(path) = pb.finish() else {
+        return;
+    };
+    // A dark line inside a light one shows against any background, as
+    // around the loupe.
+    for (width, color) in [(7.0, Color::WHITE), (3.0, Color::BLACK)] {
+        let mut paint = Paint::default();
+        paint.set_color(color);
+        paint.anti_alias = true;
+        let stroke = Stroke {
+            width,
+            ..Default::default()
+        };
+        pixmap.stroke_path(&path, &paint, &stroke, Transform::identity(), None);
+    }
+}
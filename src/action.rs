@@ -0,0 +1,96 @@
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+
+use resvg::tiny_skia::Color;
+
+use crate::highlight::Rule;
+use crate::overlay::Shape;
+use crate::select::Selector;
+
+/// Changes to the viewer requested by a hook or control command
+#[derive(Clone, Debug)]
+pub enum Action {
+    Reload,
+    /// Switch to a different file
+    Open(PathBuf),
+    /// Show a file beside those already open, switching to it
+    OpenTab(PathBuf),
+    /// Fit the document to the window again
+    Reset,
+    Quit,
+    SetZoom(f32),
+    SetPan(f32, f32),
+    SetBackground(Color),
+    /// Pan and zoom so the element with this id fills the view
+    Focus(String),
+    Draw(Shape),
+    ClearOverlays,
+    /// Outline or tint the elements this rule matches, in this and later
+    /// versions of the document
+    Highlight(Rule),
+    ClearHighlights,
+    /// Leave out the elements matching this, through later reloads
+    Hide(Selector),
+    /// Show only the elements matching this and any others soloed
+    Solo(Selector),
+    /// Undo every hide and solo
+    ShowAll,
+    /// Save the current view as a PNG, optionally reporting the outcome
+    Screenshot(PathBuf, Option<Sender<Result<(), String>>>),
+    /// Save the current view with its review marks as a PNG, captioned with
+    /// the file, the time and the zoom, optionally reporting the outcome
+    ExportReview(PathBuf, Option<Sender<Result<(), String>>>),
+    /// Show the crop tool with this rectangle: x, y, width and height in
+    /// document units
+    Crop(f32, f32, f32, f32),
+    /// Lock the crop to this width/height ratio, or unlock it
+    CropAspect(Option<f32>),
+    /// Save the crop as PNG, or SVG for a `.svg` path, optionally reporting
+    /// the outcome
+    ExportCrop(PathBuf, Option<Sender<Result<(), String>>>),
+    GetState(Sender<Snapshot>),
+    /// Receive an [`Event`] for every subsequent change
+    Subscribe(Sender<Event>),
+}
+
+/// Notifications sent to subscribers
+#[derive(Clone, Debug)]
+pub enum Event {
+    Loaded(PathBuf),
+    Reloaded(PathBuf),
+    LoadFailed(PathBuf, String),
+    ViewChanged {
+        zoom: f32,
+        pan: (f32, f32),
+    },
+    /// A key the viewer has no binding for was pressed, named as in
+    /// `on_key` hooks, such as `Q` or `F1`. Modifier keys alone aren't sent.
+    Key(String),
+    /// A mouse button was pressed at `at` in window pixels, over `point` in
+    /// page coordinates of the document shown there, if one is loaded
+    Click {
+        button: Button,
+        at: (f32, f32),
+        point: Option<(f32, f32)>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Button {
+    Left,
+    Middle,
+    Right,
+}
+
+/// Point-in-time description of the viewer
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub path: PathBuf,
+    /// Document size, if a document is loaded
+    pub doc_size: Option<(f32, f32)>,
+    pub window_size: (usize, usize),
+    pub zoom: f32,
+    pub pan: (f32, f32),
+    pub fit_scale: f32,
+    pub auto_fit: bool,
+}
@@ -0,0 +1,243 @@
+//! Review marks drawn over the view: arrows, short notes and freehand
+//! strokes. They are placed in document units, so they stay on what they
+//! point at as the view moves, but drawn at a fixed size on screen like the
+//! other overlays, and kept beside the document in `NAME.annotations`, one
+//! mark per line:
+//!
+//! ```text
+//! arrow 10 20 40 60
+//! note 40 60 the gap is too wide
+//! freehand 5 5 6 7 8 10
+//! ```
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use resvg::tiny_skia::{Color, Paint, Pixmap, Rect, Transform};
+use resvg::usvg;
+
+use crate::overlay::{self, Shape};
+
+/// Length of an arrow's head in screen pixels
+const HEAD: f32 = 12.0;
+/// Screen pixels the pointer moves before a press draws rather than placing
+/// a note, and between the points of a freehand stroke
+const STEP: f32 = 3.0;
+
+/// What marks are drawn in
+fn color() -> Color {
+    Color::from_rgba8(0xFF, 0x3B, 0x30, 0xFF)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mark {
+    Arrow { from: (f32, f32), to: (f32, f32) },
+    Note { at: (f32, f32), text: String },
+    Freehand { points: Vec<(f32, f32)> },
+}
+
+/// Drawing marks: dragging draws freehand, or an arrow with Shift, and a
+/// click starts a note typed where it was placed
+#[derive(Debug, Default)]
+pub struct Pen {
+    /// The mark being dragged out, and where the drag started on screen
+    drawing: Option<(Mark, (f32, f32))>,
+    /// A note being typed, and where it goes
+    pub typing: Option<((f32, f32), String)>,
+}
+
+impl Pen {
+    /// Start a mark at `point` (document units), under the pointer at
+    /// `screen`
+    pub fn press(&mut self, point: (f32, f32), screen: (f32, f32), arrow: bool) {
+        let mark = match arrow {
+            true => Mark::Arrow {
+                from: point,
+                to: point,
+            },
+            false => Mark::Freehand {
+                points: vec![point],
+            },
+        };
+        self.drawing = Some((mark, screen));
+    }
+
+    /// Follow the pointer, now over `point`, with the mark being drawn, if
+    /// any, `transform` mapping it to the screen
+    pub fn drag(&mut self, point: (f32, f32), transform: Transform) {
+        let Some((mark, _)) = &mut self.drawing else {
+            return;
+        };
+        match mark {
+            Mark::Arrow { to, .. } => *to = point,
+            Mark::Freehand { points } => {
+                let last = points.last().copied().unwrap_or(point);
+                if distance(last, point) * transform.sx >= STEP {
+                    points.push(point);
+                }
+            }
+            Mark::Note { .. } => {}
+        }
+    }
+
+    /// Finish the mark being drawn as the button is released at `screen`,
+    /// returning it, or start typing a note if the pointer barely moved
+    pub fn release(&mut self, screen: (f32, f32)) -> Option<Mark> {
+        let (mark, start) = self.drawing.take()?;
+        if distance(start, screen) >= STEP {
+            return Some(mark);
+        }
+        let at = match mark {
+            Mark::Arrow { from, .. } => from,
+            Mark::Freehand { points } => points[0],
+            Mark::Note { at, .. } => at,
+        };
+        self.typing = Some((at, String::new()));
+        None
+    }
+
+    pub fn dragging(&self) -> bool {
+        self.drawing.is_some()
+    }
+
+    /// Draw the mark being drawn or typed, mapping document coordinates
+    /// through `transform`, with a caret after a note's text
+    pub fn draw(&self, pixmap: &mut Pixmap, transform: Transform, opts: &usvg::Options) {
+        if let Some((mark, _)) = &self.drawing {
+            draw(pixmap, std::slice::from_ref(mark), transform, opts);
+        }
+        if let Some((at, text)) = &self.typing {
+            let note = Mark::Note {
+                at: *at,
+                text: format!("{text}▏"),
+            };
+            draw(pixmap, &[note], transform, opts);
+        }
+    }
+}
+
+fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+/// Draw `marks`, mapping document coordinates through `transform`
+pub fn draw(pixmap: &mut Pixmap, marks: &[Mark], transform: Transform, opts: &usvg::Options) {
+    let color = color();
+    let screen = |(x, y): (f32, f32)| {
+        let mut p = resvg::tiny_skia::Point::from_xy(x, y);
+        transform.map_point(&mut p);
+        (p.x, p.y)
+    };
+    let mut shapes = Vec::new();
+    for mark in marks {
+        match mark {
+            Mark::Arrow { from, to } => {
+                let (from, to) = (screen(*from), screen(*to));
+                shapes.push(Shape::Line { from, to, color });
+                let len = distance(from, to);
+                if len > 0.0 {
+                    let (ux, uy) = ((to.0 - from.0) / len, (to.1 - from.1) / len);
+                    let (back, side) = (HEAD, HEAD / 2.0);
+                    for side in [side, -side] {
+                        let barb = (to.0 - ux * back - uy * side, to.1 - uy * back + ux * side);
+                        shapes.push(Shape::Line {
+                            from: to,
+                            to: barb,
+                            color,
+                        });
+                    }
+                }
+            }
+            Mark::Freehand { points } => {
+                for pair in points.windows(2) {
+                    let (from, to) = (screen(pair[0]), screen(pair[1]));
+                    shapes.push(Shape::Line { from, to, color });
+                }
+            }
+            Mark::Note { at, text } => {
+                // On a light backing, so it reads over any drawing
+                let (x, y) = screen(*at);
+                let width = text.chars().count() as f32 * 8.0 + 8.0;
+                let mut paint = Paint::default();
+                paint.set_color_rgba8(0xFF, 0xFF, 0xFF, 0xE0);
+                if let Some(rect) = Rect::from_xywh(x - 4.0, y - 2.0, width, 22.0) {
+                    pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+                }
+                shapes.push(Shape::Text {
+                    at: (x, y),
+                    text: text.clone(),
+                    color,
+                });
+            }
+        }
+    }
+    overlay::draw(pixmap, &shapes, Transform::identity(), opts);
+}
+
+/// Where the marks on `doc` are kept: `NAME.annotations` beside it
+pub fn sidecar(doc: &Path) -> PathBuf {
+    let stem = doc.file_stem().unwrap_or_default().to_string_lossy();
+    doc.with_file_name(format!("{stem}.annotations"))
+}
+
+/// The marks saved for `doc`, skipping lines that don't parse
+pub fn load(doc: &Path) -> Vec<Mark> {
+    let Ok(text) = fs::read_to_string(sidecar(doc)) else {
+        return Vec::new();
+    };
+    text.lines().filter_map(parse).collect()
+}
+
+fn parse(line: &str) -> Option<Mark> {
+    let (kind, rest) = line.trim().split_once(' ')?;
+    let mut words = rest.split_whitespace();
+    let mut point = || Some((words.next()?.parse().ok()?, words.next()?.parse().ok()?));
+    match kind {
+        "arrow" => Some(Mark::Arrow {
+            from: point()?,
+            to: point()?,
+        }),
+        "note" => {
+            let at = point()?;
+            let text = words.collect::<Vec<_>>().join(" ");
+            (!text.is_empty()).then_some(Mark::Note { at, text })
+        }
+        "freehand" => {
+            let points: Vec<_> = std::iter::from_fn(point).collect();
+            (points.len() > 1).then_some(Mark::Freehand { points })
+        }
+        _ => None,
+    }
+}
+
+/// Keep `marks` beside `doc`, removing the file once there are none,
+/// reporting failures without stopping
+pub fn save(doc: &Path, marks: &[Mark]) {
+    let path = sidecar(doc);
+    let result = if marks.is_empty() {
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    } else {
+        let mut text = String::new();
+        for mark in marks {
+            let _ = match mark {
+                Mark::Arrow { from, to } => {
+                    writeln!(text, "arrow {} {} {} {}", from.0, from.1, to.0, to.1)
+                }
+                Mark::Note { at, text: note } => writeln!(text, "note {} {} {note}", at.0, at.1),
+                Mark::Freehand { points } => {
+                    let points: Vec<String> =
+                        points.iter().map(|(x, y)| format!("{x} {y}")).collect();
+                    writeln!(text, "freehand {}", points.join(" "))
+                }
+            };
+        }
+        fs::write(&path, text)
+    };
+    if let Err(e) = result {
+        eprintln!("saving annotations to {}: {e}", path.display());
+    }
+}
@@ -0,0 +1,48 @@
+//! Opening link targets with the desktop's default handler
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// `href` as something to open: URLs as they are, anything else as a path
+/// relative to the directory of `doc`, without its fragment
+pub fn resolve(href: &str, doc: &Path) -> String {
+    let scheme = href.split_once(':').map(|(scheme, _)| scheme);
+    // One letter is a Windows drive, not a scheme.
+    let is_url = scheme.is_some_and(|s| {
+        s.len() > 1
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+    });
+    if is_url {
+        return href.to_string();
+    }
+    let path = href.split('#').next().unwrap_or(href);
+    let dir = doc.parent().unwrap_or(Path::new("."));
+    dir.join(path).to_string_lossy().into_owned()
+}
+
+/// Open `target` without waiting for the handler to finish
+pub fn open(target: &str) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = Command::new("cmd");
+        // The empty argument is `start`'s window title.
+        command.args(["/C", "start", ""]);
+        command
+    };
+    #[cfg(not(any(target_os = "macos", windows)))]
+    let mut command = Command::new("xdg-open");
+
+    let mut child = command
+        .arg(target)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("opening {target}: {e}"))?;
+    // Reap it once it exits.
+    thread::spawn(move || child.wait());
+    Ok(())
+}
@@ -0,0 +1,94 @@
+//! Documents switched away from, kept parsed and rendered so switching back
+//! to them is instant. They aren't watched while cached, so each is re-read
+//! on its way back and replaced if it changed meanwhile.
+
+use std::collections::VecDeque;
+use std::path::Path;
+
+use crate::input::Input;
+
+/// Documents kept at most; the least recently shown go first
+const CAPACITY: usize = 8;
+
+#[derive(Default)]
+pub struct Cache {
+    /// Most recently cached first
+    inputs: VecDeque<Input>,
+}
+
+impl Cache {
+    pub fn new() -> Self {
+        Self {
+            inputs: VecDeque::new(),
+        }
+    }
+
+    /// Keep `input` if it has a document worth keeping
+    pub fn put(&mut self, mut input: Input) {
+        if input.tree.is_none() || input.source.watched().is_none() {
+            return;
+        }
+        // A load under way won't be delivered to it, and it mustn't wait
+        // for one when it comes back.
+        input.loading = None;
+        input.due = None;
+        self.take(input.source.path());
+        self.inputs.push_front(input);
+        self.inputs.truncate(CAPACITY);
+    }
+
+    /// The cached document read from `path`, taken out of the cache
+    pub fn take(&mut self, path: &Path) -> Option<Input> {
+        let i = self
+            .inputs
+            .iter()
+            .position(|input| input.source.watched() == Some(path))?;
+        self.inputs.remove(i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use resvg::usvg;
+
+    use super::*;
+    use crate::input::Source;
+
+    fn path(n: usize) -> PathBuf {
+        PathBuf::from(format!("/docs/{n}.svg"))
+    }
+
+    fn loaded(n: usize) -> Input {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="1" height="1"/>"#;
+        let mut input = Input::new(Source::File(path(n)));
+        input.tree = Some(usvg::Tree::from_str(svg, &usvg::Options::default()).unwrap());
+        input
+    }
+
+    #[test]
+    fn keeps_only_loaded_documents() {
+        let mut cache = Cache::new();
+        cache.put(Input::new(Source::File(path(0))));
+        assert!(cache.take(&path(0)).is_none());
+        cache.put(loaded(0));
+        assert!(cache.take(&path(0)).is_some());
+        assert!(cache.take(&path(0)).is_none());
+    }
+
+    #[test]
+    fn evicts_the_least_recently_cached() {
+        let mut cache = Cache::new();
+        for n in 0..CAPACITY {
+            cache.put(loaded(n));
+        }
+        // Cached again, so it is now the most recent
+        cache.put(loaded(0));
+        cache.put(loaded(CAPACITY));
+        assert!(cache.take(&path(1)).is_none());
+        for n in [0, 2, CAPACITY] {
+            assert!(cache.take(&path(n)).is_some(), "{n}");
+        }
+    }
+}
@@ -0,0 +1,473 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{ArgGroup, Parser, Subcommand};
+use resvg::tiny_skia::{Color, Rect};
+
+use svgtail::access::Contrast;
+use svgtail::color;
+use svgtail::command::parse_positive;
+use svgtail::crop;
+use svgtail::dither::{self, Method, Palette};
+use svgtail::export::Export;
+use svgtail::generate::Generator;
+use svgtail::highlight::{self, Rule};
+use svgtail::loupe;
+use svgtail::render::Edge;
+use svgtail::state::{Fit, Horizontal, Vertical};
+use svgtail::template;
+use svgtail::units::Unit;
+#[cfg(target_os = "linux")]
+use svgtail::v4l2;
+use svgtail::watch::{Pattern, Trigger};
+
+/// A minimal SVG viewer
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about,
+    subcommand_negates_reqs = true,
+    args_conflicts_with_subcommands = true,
+    group(
+        ArgGroup::new("generator")
+            .args(["dot", "typst", "mermaid"])
+            .conflicts_with_all(["paths", "glob", "git_ref"])
+    ),
+    group(
+        ArgGroup::new("exports")
+            .args(["output", "export_on_reload"])
+            .multiple(true)
+    )
+)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// SVG file to display (waits for it to be created if missing), or `-`
+    /// for documents piped in one after another. A directory, or a quoted
+    /// pattern like `'out/*.svg'`, follows the newest SVG in it as --glob does.
+    /// Given several, Tab and the number keys switch between them, and
+    /// backtick flips between them keeping the view.
+    #[arg(value_name = "PATH", num_args = 1..)]
+    #[cfg_attr(
+        not(feature = "data"),
+        arg(required_unless_present_any = ["glob", "generator"])
+    )]
+    #[cfg_attr(
+        feature = "data",
+        arg(required_unless_present_any = ["glob", "generator", "template"])
+    )]
+    pub paths: Vec<PathBuf>,
+
+    /// Watch files matching this pattern, e.g. `'out/**/*.svg'`, switching to
+    /// whichever was last written. Starts from the newest match if no PATH is given.
+    #[arg(long, value_name = "PATTERN", value_parser = Pattern::new)]
+    pub glob: Option<Pattern>,
+
+    /// Show the file as of this git revision; backtick toggles to the working copy
+    #[arg(long, value_name = "REV")]
+    pub git_ref: Option<String>,
+
+    /// Show this Graphviz file as laid out by `dot`, re-running it when the
+    /// file changes
+    #[arg(long, value_name = "FILE")]
+    pub dot: Option<PathBuf>,
+
+    /// Graphviz layout engine for `--dot`, such as `neato`, `fdp` or `circo`
+    #[arg(long, value_name = "ENGINE", requires = "dot")]
+    pub dot_layout: Option<String>,
+
+    /// Show the first page of this Typst document, re-compiling it when it
+    /// changes
+    #[arg(long, value_name = "FILE")]
+    pub typst: Option<PathBuf>,
+
+    /// Show this Mermaid diagram as drawn by `mmdc`, re-drawing it when it
+    /// changes
+    #[arg(long, value_name = "FILE")]
+    pub mermaid: Option<PathBuf>,
+
+    /// Show what this command makes of PATH, re-running it when PATH
+    /// changes, e.g. `--cmd 'plantuml -tsvg -pipe'`. `{input}` stands for
+    /// PATH, which is otherwise piped to it, and `{output}` for a file it
+    /// writes the SVG to, which is otherwise read from its standard output.
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        value_parser = Generator::command,
+        conflicts_with_all = ["glob", "git_ref", "generator"]
+    )]
+    pub cmd: Option<Generator>,
+
+    /// Show this Jinja template rendered with `--data`, reloading when either
+    /// changes
+    #[cfg(feature = "data")]
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "data",
+        conflicts_with_all = ["paths", "glob", "git_ref", "generator", "untrusted"]
+    )]
+    pub template: Option<PathBuf>,
+
+    /// JSON or CSV file to render `--template` with
+    #[cfg(feature = "data")]
+    #[arg(long, value_name = "FILE", requires = "template")]
+    pub data: Option<PathBuf>,
+
+    /// Parse the inputs and exit without displaying them (status 2 if missing, 3 if invalid)
+    #[arg(long)]
+    pub check: bool,
+
+    /// Run without a window, saving the document to `--output` or
+    /// `--export-on-reload` each time it changes
+    #[arg(long, requires = "exports", conflicts_with = "check")]
+    pub headless: bool,
+
+    /// PNG file `--headless` keeps up to date
+    #[arg(long, value_name = "FILE", requires = "headless")]
+    pub output: Option<PathBuf>,
+
+    /// Save a PNG each time the document loads, e.g. `'out/{stem}-{n}.png@2x'`.
+    /// `{stem}` and `{name}` are the document's file name without and with
+    /// its extension and `{n}` counts loads; `@2x`, `@800w`, `@600h` or
+    /// `@800x600` sizes it. Repeat for more than one.
+    #[arg(long, value_name = "PATH[@SIZE]", value_parser = Export::parse)]
+    pub export_on_reload: Vec<Export>,
+
+    /// Export only this part of the page, in user units from its top-left
+    /// corner as the crop tool measures
+    #[arg(long, value_name = "X,Y,W,H", requires = "exports", value_parser = crop::parse_rect)]
+    pub viewbox: Option<Rect>,
+
+    /// Pixels per user unit of exports not given a size
+    #[arg(long, value_name = "N", default_value_t = 1.0, requires = "exports", value_parser = parse_positive)]
+    pub scale: f32,
+
+    /// Exit with status 3 as soon as an input fails to parse
+    #[arg(long)]
+    pub fail_on_error: bool,
+
+    /// Show documents from strangers: images only from `data:` URLs, no
+    /// DTDs, limits on elements, filters and drawing time, and no links
+    /// followed, programs run or hooks loaded
+    #[arg(long, conflicts_with_all = ["generator", "cmd", "git_ref"])]
+    pub untrusted: bool,
+
+    /// Also reload when this file, or anything under this directory, changes
+    /// (repeatable)
+    #[arg(long, value_name = "PATH")]
+    pub also_watch: Vec<PathBuf>,
+
+    /// Replace `{{KEY}}` in the inputs with VALUE before parsing (repeatable)
+    #[arg(long, value_name = "KEY=VALUE", value_parser = template::parse_define)]
+    pub define: Vec<(String, String)>,
+
+    /// Present at most this many frames per second
+    #[arg(long, value_name = "N", default_value_t = 60, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_fps: u32,
+
+    /// Check a focused window for input this often while nothing is being
+    /// drawn, rather than once a frame; file changes are picked up at once
+    /// either way
+    #[arg(long, value_name = "MS", value_parser = parse_millis)]
+    pub poll_interval: Option<Duration>,
+
+    /// Check an unfocused or hidden window for input this often [default: 250]
+    #[arg(long, value_name = "MS", value_parser = parse_millis)]
+    pub idle_poll: Option<Duration>,
+
+    /// Go easy on the battery: check an unfocused or hidden window for input
+    /// only every 3 seconds, unless `--idle-poll` says otherwise
+    #[arg(long)]
+    pub power_save: bool,
+
+    /// Scale the view with the window when it is resized, even after panning
+    /// or zooming
+    #[arg(long)]
+    pub refit_on_resize: bool,
+
+    /// Outline elements by id, as `id~=REGEX:COLOR` for those whose whole id
+    /// matches or `id=ID:COLOR` for one; `:tint` after the colour shades
+    /// them instead (repeatable)
+    #[arg(long, value_name = "RULE", value_parser = highlight::parse)]
+    pub highlight: Vec<Rule>,
+
+    /// On reload, pan and zoom to the part of the document that changed;
+    /// Backspace goes back to the view from before
+    #[arg(long)]
+    pub zoom_to_changes: bool,
+
+    /// On reload, keep this side of the document, or its middle, where it is
+    /// in the window as the document's width changes, at the same scale,
+    /// rather than fitting it again
+    #[arg(long, value_name = "SIDE")]
+    pub anchor_x: Option<Horizontal>,
+
+    /// Likewise for the top, middle or bottom as its height changes
+    #[arg(long, value_name = "SIDE")]
+    pub anchor_y: Option<Vertical>,
+
+    /// On reload, keep the element with this id where it is in the window, at
+    /// the same scale, however the layout around it shifts; without it in
+    /// both versions, the view is fitted or anchored as usual
+    #[arg(long, value_name = "NODE")]
+    pub anchor_id: Option<String>,
+
+    /// On reload, keep the view as it is, panned and zoomed or fitted, unless
+    /// the document's width or height changes by more than a tenth
+    #[arg(long)]
+    pub keep_view: bool,
+
+    /// Keep the bottom edge in place as the document grows: `--anchor-y bottom`
+    #[arg(long, conflicts_with = "anchor_y")]
+    pub follow_bottom: bool,
+
+    /// Keep the right edge in place: `--anchor-x right`
+    #[arg(long, conflicts_with = "anchor_x")]
+    pub follow_right: bool,
+
+    /// Reload each file at most this many times per second
+    #[arg(long, value_name = "N", value_parser = parse_hz)]
+    pub max_reload_hz: Option<f64>,
+
+    /// Which file events reload: any but opening a file, a written file
+    /// being closed, or a file being created
+    #[arg(long, value_name = "EVENT", default_value = "any")]
+    pub reload_on: Trigger,
+
+    /// Colour around the document
+    #[arg(long, visible_alias = "bg", value_name = "COLOR", default_value = "#333333", value_parser = color::parse)]
+    pub letterbox_color: Color,
+
+    /// Colour beneath the document, within its bounds
+    #[arg(long, value_name = "COLOR", value_parser = color::parse)]
+    pub page_color: Option<Color>,
+
+    /// The window's size when it opens
+    #[arg(long, value_name = "WxH", default_value = "800x600", value_parser = parse_size)]
+    pub size: (usize, usize),
+
+    /// How the document is fitted to the window: all of it in view
+    /// (`contain`), across its width or down its height, or `none` for one
+    /// screen pixel per unit
+    #[arg(long, value_name = "MODE", default_value = "contain")]
+    pub fit: Fit,
+
+    /// Open at this magnification (2 for 200%, two screen pixels per unit)
+    /// rather than fitted
+    #[arg(long, value_name = "X", value_parser = parse_positive)]
+    pub zoom: Option<f32>,
+
+    /// The window's title, in place of `svgtail`
+    #[arg(long, value_name = "TEXT")]
+    pub title: Option<String>,
+
+    /// Mark the document's bounds with an outline or a drop shadow
+    #[arg(long, value_name = "STYLE", default_value = "none")]
+    pub page_edge: Edge,
+
+    /// Show a checkerboard through transparent parts of the document (toggle with `t`)
+    #[arg(long)]
+    pub checkerboard: bool,
+
+    /// The checkerboard's two colours
+    #[arg(long, value_name = "A,B", default_value = "#ffffff,#cccccc", value_parser = parse_color_pair)]
+    pub checker_colors: [Color; 2],
+
+    /// Side of a checkerboard square in screen pixels
+    #[arg(long, value_name = "PX", default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..))]
+    pub checker_size: u32,
+
+    /// PNG image drawn beneath the document, stretched to its size
+    #[arg(long, value_name = "IMAGE")]
+    pub backdrop: Option<PathBuf>,
+
+    /// User units per inch, for measurements in millimetres and inches; also
+    /// converts absolute units (`mm`, `in`, `pt`) in documents (default 96)
+    #[arg(long, value_name = "N", value_parser = parse_positive)]
+    pub dpi: Option<f32>,
+
+    /// Also load the fonts in this directory, such as a project's own, on top
+    /// of the system's (repeatable)
+    #[arg(long, value_name = "DIR")]
+    pub font_dir: Vec<PathBuf>,
+
+    /// Unit of the rulers, guides, measurements and crop readouts; physical
+    /// units assume 96 user units per inch unless `--dpi` is given
+    #[arg(long, value_name = "UNIT", default_value = "px")]
+    pub units: Unit,
+
+    /// Lock the crop tool (`x`) to this aspect ratio, e.g. `16:9`
+    #[arg(long, value_name = "W:H", value_parser = crop::parse_aspect)]
+    pub crop_aspect: Option<f32>,
+
+    /// Draw the review marks (`d`) into screenshots too, not only the window
+    #[arg(long)]
+    pub bake_annotations: bool,
+
+    /// Reduce the window to these colours: `gray16` (2 to 256 greys) or a
+    /// list like `#000,#fff,#f00`
+    #[arg(long, value_name = "PALETTE", value_parser = dither::parse_palette)]
+    pub palette: Option<Palette>,
+
+    /// How `--palette` approximates other colours
+    #[arg(
+        long,
+        value_name = "METHOD",
+        default_value = "floyd-steinberg",
+        requires = "palette"
+    )]
+    pub dither: Method,
+
+    /// Draw the window in strongly contrasting greys: dark ink on white, or
+    /// light ink on black with `--high-contrast=dark`
+    #[arg(
+        long,
+        value_name = "STYLE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "light"
+    )]
+    pub high_contrast: Option<Contrast>,
+
+    /// Ring the mouse cursor with a large crosshair
+    #[arg(long)]
+    pub large_cursor: bool,
+
+    /// For walking through a document on a call: a bright halo around the
+    /// cursor, a fading trail behind it, and `z` held to zoom in on what it
+    /// points at
+    #[arg(long)]
+    pub presenter: bool,
+
+    /// Start with the loupe (`m`) on; it follows the cursor, or the middle of
+    /// the view when it is moved with the keyboard
+    #[arg(long)]
+    pub loupe: bool,
+
+    /// Magnification the loupe starts at
+    #[arg(long, value_name = "X", default_value_t = loupe::ZOOM, value_parser = parse_positive)]
+    pub loupe_zoom: f32,
+
+    /// Radius of the loupe in pixels
+    #[arg(long, value_name = "PX", default_value_t = loupe::RADIUS, value_parser = parse_positive)]
+    pub loupe_radius: f32,
+
+    /// Mirror finished frames to this framebuffer, e.g. an e-paper panel's
+    /// `/dev/fb1`, writing only what changed
+    #[cfg(target_os = "linux")]
+    #[arg(long, value_name = "DEVICE")]
+    pub eink: Option<PathBuf>,
+
+    /// Partial updates of `--eink` between writes of the whole screen
+    #[cfg(target_os = "linux")]
+    #[arg(long, value_name = "N", default_value_t = 10, requires = "eink")]
+    pub eink_full_refresh: u32,
+
+    /// Write finished frames to this v4l2loopback device, e.g. `/dev/video10`,
+    /// for streaming software to capture like a webcam
+    #[cfg(target_os = "linux")]
+    #[arg(long, value_name = "DEVICE")]
+    pub v4l2: Option<PathBuf>,
+
+    /// Pixel format written to `--v4l2`
+    #[cfg(target_os = "linux")]
+    #[arg(long, value_name = "FORMAT", default_value = "yuyv", requires = "v4l2")]
+    pub v4l2_format: v4l2::PixelFormat,
+
+    /// Serve finished frames to other processes through this shared memory
+    /// file, e.g. `/dev/shm/svgtail`, announcing each on `FILE.sock`
+    #[cfg(unix)]
+    #[arg(long, value_name = "FILE")]
+    pub shm: Option<PathBuf>,
+
+    /// ICC profile of the display; defaults to the one the desktop publishes on X11
+    #[arg(long, value_name = "FILE")]
+    pub icc: Option<PathBuf>,
+
+    /// Exit when a watched file is deleted
+    #[arg(long)]
+    pub quit_on_delete: bool,
+
+    /// Exit after this many seconds without file events or input
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    pub idle_exit: Option<Duration>,
+
+    /// Open the file in an already running instance if there is one
+    #[cfg(unix)]
+    #[arg(long)]
+    pub single_instance: bool,
+
+    /// Lua script defining `on_load`, `on_reload` and/or `on_key` hooks
+    #[cfg(feature = "lua")]
+    #[arg(long, value_name = "FILE", conflicts_with = "untrusted")]
+    pub script: Option<PathBuf>,
+
+    /// WASM plugin receiving each rendered frame (repeatable)
+    #[cfg(feature = "wasm")]
+    #[arg(long = "plugin", value_name = "FILE", conflicts_with = "untrusted")]
+    pub plugins: Vec<PathBuf>,
+
+    /// Present frames through OpenGL, scaled on the GPU and synced to the display
+    #[cfg(feature = "gl")]
+    #[arg(long)]
+    pub gl: bool,
+
+    /// Serve a JSON-RPC control API on this address, e.g. `127.0.0.1:7878`
+    #[cfg(feature = "rpc")]
+    #[arg(long, value_name = "ADDR")]
+    pub rpc: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Send a command to the running instance, e.g. `svgtail ctl zoom 2`
+    #[cfg(unix)]
+    Ctl {
+        /// Any stdin command (`reload`, `open FILE`, `zoom 2`, `screenshot FILE`, ...) or `state`
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+}
+
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    s.parse::<f64>()
+        .ok()
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .filter(|d| !d.is_zero())
+        .ok_or_else(|| format!("expected a number of seconds above 0, got {s}"))
+}
+
+fn parse_millis(s: &str) -> Result<Duration, String> {
+    match s.parse::<u64>() {
+        Ok(ms) if ms > 0 => Ok(Duration::from_millis(ms)),
+        _ => Err(format!(
+            "expected a whole number of milliseconds above 0, got {s}"
+        )),
+    }
+}
+
+fn parse_color_pair(s: &str) -> Result<[Color; 2], String> {
+    match s.split_once(',') {
+        Some((a, b)) => Ok([color::parse(a)?, color::parse(b)?]),
+        None => Err(format!("expected two comma-separated colours, got {s}")),
+    }
+}
+
+fn parse_size(s: &str) -> Result<(usize, usize), String> {
+    let size = s
+        .split_once('x')
+        .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)));
+    match size {
+        Some((w, h)) if w > 0 && h > 0 => Ok((w, h)),
+        _ => Err(format!("expected a size like 1280x720, got {s}")),
+    }
+}
+
+fn parse_hz(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(hz) if hz > 0.0 && Duration::try_from_secs_f64(1.0 / hz).is_ok() => Ok(hz),
+        _ => Err(format!("expected a positive rate, got {s}")),
+    }
+}
@@ -0,0 +1,205 @@
+//! The clipboard and primary selection under Wayland, where X11 clipboards
+//! don't reach. This speaks wlr-data-control, as wl-clipboard does, which
+//! sway, Hyprland and KDE offer to clients without the keyboard focus, so
+//! neither needs the serial of a key press minifb never hands over.
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
+
+use wayland_client::protocol::wl_seat::WlSeat;
+use wayland_client::{Display, EventQueue, GlobalManager, Main};
+use wayland_protocols::wlr::unstable::data_control::v1::client::{
+    zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+    zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+    zwlr_data_control_offer_v1::{self, ZwlrDataControlOfferV1},
+    zwlr_data_control_source_v1::{self, ZwlrDataControlSourceV1},
+};
+
+/// What text is offered as, and accepted as, from most to least preferred
+pub const TEXT: &[&str] = &[
+    "text/plain;charset=utf-8",
+    "UTF8_STRING",
+    "text/plain",
+    "STRING",
+    "TEXT",
+];
+
+/// Which of the two selections to use
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Selection {
+    /// What copy and paste use
+    Clipboard,
+    /// What selecting text sets, and a middle click pastes
+    Primary,
+}
+
+/// A connection to the compositor, bound to its data control and the first seat
+struct Session {
+    display: Display,
+    queue: EventQueue,
+    device: Main<ZwlrDataControlDeviceV1>,
+    manager: Main<ZwlrDataControlManagerV1>,
+}
+
+impl Session {
+    fn connect(selection: Selection) -> Result<Self, String> {
+        if std::env::var_os("WAYLAND_DISPLAY").is_none() {
+            return Err("not a Wayland session".into());
+        }
+        let display = Display::connect_to_env().map_err(|e| e.to_string())?;
+        let mut queue = display.create_event_queue();
+        let attached = (*display).clone().attach(queue.token());
+        let globals = GlobalManager::new(&attached);
+        queue
+            .sync_roundtrip(&mut (), |_, _, _| {})
+            .map_err(|e| e.to_string())?;
+        let seat = globals
+            .instantiate_exact::<WlSeat>(1)
+            .map_err(|_| "no seat")?;
+        // The primary selection came in version 2
+        let manager = globals
+            .instantiate_range::<ZwlrDataControlManagerV1>(1, 2)
+            .map_err(|_| "the compositor doesn't offer wlr-data-control")?;
+        if selection == Selection::Primary && manager.as_ref().version() < 2 {
+            return Err("the compositor has no primary selection".into());
+        }
+        let device = manager.get_data_device(&seat);
+        Ok(Self {
+            display,
+            queue,
+            device,
+            manager,
+        })
+    }
+
+    fn roundtrip(&mut self) -> Result<(), String> {
+        self.queue
+            .sync_roundtrip(&mut (), |_, _, _| {})
+            .map(drop)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Offer `data` as each of `mimes` on `selection`, until something else
+/// takes it. A thread keeps its own connection to hand it out, so this
+/// returns once the selection is set.
+pub fn copy(selection: Selection, mimes: &[&str], data: Vec<u8>) -> Result<(), String> {
+    let mimes: Vec<String> = mimes.iter().map(|m| m.to_string()).collect();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut session = match Session::connect(selection) {
+            Ok(session) => session,
+            Err(e) => return drop(tx.send(Err(e))),
+        };
+        let source: Main<ZwlrDataControlSourceV1> = session.manager.create_data_source();
+        for mime in mimes {
+            source.offer(mime);
+        }
+        let done = Rc::new(RefCell::new(false));
+        let cancelled = done.clone();
+        source.quick_assign(move |source, event, _| match event {
+            zwlr_data_control_source_v1::Event::Send { fd, .. } => {
+                // Written out in full here; the reader is another
+                // program, and only this thread waits on it.
+                let mut file = unsafe { File::from_raw_fd(fd) };
+                let _ = file.write_all(&data);
+            }
+            zwlr_data_control_source_v1::Event::Cancelled => {
+                source.destroy();
+                *cancelled.borrow_mut() = true;
+            }
+            _ => {}
+        });
+        match selection {
+            Selection::Clipboard => session.device.set_selection(Some(&source)),
+            Selection::Primary => session.device.set_primary_selection(Some(&source)),
+        }
+        if let Err(e) = session.roundtrip() {
+            return drop(tx.send(Err(e)));
+        }
+        let _ = tx.send(Ok(()));
+        while !*done.borrow() {
+            if session.queue.dispatch(&mut (), |_, _, _| {}).is_err() {
+                break;
+            }
+        }
+        let _ = session.display.flush();
+    });
+    rx.recv()
+        .unwrap_or_else(|_| Err("clipboard thread failed".into()))
+}
+
+/// What is on `selection`, as the first of `accept` it is offered as,
+/// with that type
+pub fn paste(selection: Selection, accept: &[&str]) -> Result<(String, Vec<u8>), String> {
+    let mut session = Session::connect(selection)?;
+    // Every offer announces its types before it is made the selection
+    let types: Rc<RefCell<Vec<(u32, String)>>> = Rc::default();
+    let current: Rc<RefCell<Option<ZwlrDataControlOfferV1>>> = Rc::default();
+    let (offered, chosen) = (types.clone(), current.clone());
+    session.device.quick_assign(move |_, event, _| match event {
+        zwlr_data_control_device_v1::Event::DataOffer { id } => {
+            let offered = offered.clone();
+            id.quick_assign(move |offer, event, _| {
+                if let zwlr_data_control_offer_v1::Event::Offer { mime_type } = event {
+                    offered.borrow_mut().push((offer.as_ref().id(), mime_type));
+                }
+            });
+        }
+        zwlr_data_control_device_v1::Event::Selection { id }
+            if selection == Selection::Clipboard =>
+        {
+            *chosen.borrow_mut() = id;
+        }
+        zwlr_data_control_device_v1::Event::PrimarySelection { id }
+            if selection == Selection::Primary =>
+        {
+            *chosen.borrow_mut() = id;
+        }
+        _ => {}
+    });
+    session.roundtrip()?;
+    session.roundtrip()?;
+    let offer = current
+        .borrow_mut()
+        .take()
+        .ok_or("the selection is empty")?;
+    let id = offer.as_ref().id();
+    let mime = accept
+        .iter()
+        .find(|mime| types.borrow().iter().any(|(i, m)| *i == id && m == *mime))
+        .ok_or("nothing on the selection that can be pasted")?
+        .to_string();
+    let (mut reader, writer) = std::io::pipe().map_err(|e| e.to_string())?;
+    offer.receive(mime.clone(), writer.as_raw_fd());
+    offer.destroy();
+    session.display.flush().map_err(|e| e.to_string())?;
+    // The sender has its own copy of the write end now, so reading ends
+    // once it closes that
+    drop(writer);
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).map_err(|e| e.to_string())?;
+    Ok((mime, data))
+}
+
+/// Put an SVG pasted from `selection`, either as `image/svg+xml` or as text
+/// that looks like markup, in a file of its own to open, returning its path
+pub fn paste_svg(selection: Selection) -> Result<PathBuf, String> {
+    let accept: Vec<&str> = std::iter::once("image/svg+xml")
+        .chain(TEXT.iter().copied())
+        .collect();
+    let (mime, data) = paste(selection, &accept)?;
+    if mime != "image/svg+xml" && !String::from_utf8_lossy(&data).trim_start().starts_with('<') {
+        return Err("the selection isn't an SVG".into());
+    }
+    // One per process, so pasting again replaces what was pasted before
+    let path = std::env::temp_dir().join(format!("svgtail-paste-{}.svg", std::process::id()));
+    std::fs::write(&path, data).map_err(|e| format!("{}: {e}", path.display()))?;
+    Ok(path)
+}
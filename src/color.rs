@@ -0,0 +1,27 @@
+use resvg::tiny_skia::{Color, ColorU8};
+
+/// Parse `#rgb`, `#rrggbb` or `#rrggbbaa` (the `#` is optional)
+pub fn parse(s: &str) -> Result<Color, String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    let channel = |i: usize, len: usize| {
+        let v = u8::from_str_radix(hex.get(i * len..(i + 1) * len)?, 16).ok()?;
+        Some(if len == 1 { v * 17 } else { v })
+    };
+
+    let rgba = match hex.len() {
+        3 => (channel(0, 1), channel(1, 1), channel(2, 1), Some(0xFF)),
+        6 => (channel(0, 2), channel(1, 2), channel(2, 2), Some(0xFF)),
+        8 => (channel(0, 2), channel(1, 2), channel(2, 2), channel(3, 2)),
+        _ => (None, None, None, None),
+    };
+    match rgba {
+        (Some(r), Some(g), Some(b), Some(a)) => Ok(Color::from_rgba8(r, g, b, a)),
+        _ => Err(format!("invalid color '{s}'")),
+    }
+}
+
+/// Pack a color into minifb's `0RGB` format, ignoring alpha
+pub fn to_rgb(color: Color) -> u32 {
+    let c: ColorU8 = color.to_color_u8();
+    ((c.red() as u32) << 16) | ((c.green() as u32) << 8) | c.blue() as u32
+}
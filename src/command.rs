@@ -0,0 +1,129 @@
+//! Plain-text control commands, one per line:
+//!
+//! ```text
+//! reload
+//! open path/to/other.svg
+//! open-tab path/to/another.svg
+//! zoom 2
+//! pan 10 -20
+//! reset
+//! background #ffffff
+//! focus node1
+//! screenshot out.png
+//! export-review review.png
+//! crop 10 10 320 200
+//! crop-aspect 16:9
+//! export-crop out.svg
+//! clear
+//! highlight id~=edge_.*:red
+//! unhighlight
+//! hide .label
+//! solo #cluster_0
+//! show-all
+//! quit
+//! ```
+
+use std::io::{self, BufRead, IsTerminal};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::thread;
+
+use crate::action::Action;
+use crate::color;
+use crate::crop;
+use crate::state;
+use crate::viewer::ActionSender;
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.trim();
+        let (cmd, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        let num = |s: &str| parse_finite(s).map_err(|e| format!("{cmd}: {e}"));
+        let arg = || match rest {
+            "" => Err(format!("{cmd}: missing argument")),
+            s => Ok(s),
+        };
+
+        match cmd {
+            "reload" => Ok(Action::Reload),
+            "reset" => Ok(Action::Reset),
+            "quit" | "exit" => Ok(Action::Quit),
+            "clear" => Ok(Action::ClearOverlays),
+            "highlight" => Ok(Action::Highlight(arg()?.parse()?)),
+            "unhighlight" => Ok(Action::ClearHighlights),
+            "hide" => Ok(Action::Hide(arg()?.parse()?)),
+            "solo" => Ok(Action::Solo(arg()?.parse()?)),
+            "show-all" => Ok(Action::ShowAll),
+            "open" => Ok(Action::Open(PathBuf::from(arg()?))),
+            "open-tab" => Ok(Action::OpenTab(PathBuf::from(arg()?))),
+            "zoom" => {
+                let zoom = state::valid_zoom(num(arg()?)?).map_err(|e| format!("{cmd}: {e}"))?;
+                Ok(Action::SetZoom(zoom))
+            }
+            "pan" => match rest.split_whitespace().collect::<Vec<_>>()[..] {
+                [x, y] => {
+                    let (x, y) =
+                        state::valid_pan(num(x)?, num(y)?).map_err(|e| format!("{cmd}: {e}"))?;
+                    Ok(Action::SetPan(x, y))
+                }
+                _ => Err("pan: expected two numbers".to_string()),
+            },
+            "background" | "bg" => Ok(Action::SetBackground(color::parse(arg()?)?)),
+            "focus" => Ok(Action::Focus(arg()?.to_string())),
+            "screenshot" => Ok(Action::Screenshot(PathBuf::from(arg()?), None)),
+            "export-review" => Ok(Action::ExportReview(PathBuf::from(arg()?), None)),
+            "crop" => match rest.split_whitespace().collect::<Vec<_>>()[..] {
+                [x, y, w, h] => Ok(Action::Crop(num(x)?, num(y)?, num(w)?, num(h)?)),
+                _ => Err("crop: expected x, y, width and height".to_string()),
+            },
+            "crop-aspect" => match arg()? {
+                "off" => Ok(Action::CropAspect(None)),
+                ratio => Ok(Action::CropAspect(Some(crop::parse_aspect(ratio)?))),
+            },
+            "export-crop" => Ok(Action::ExportCrop(PathBuf::from(arg()?), None)),
+            _ => Err(format!("unknown command '{cmd}'")),
+        }
+    }
+}
+
+/// `s` as a number greater than zero, as zoom factors and sizes must be
+pub fn parse_positive(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(n) if n > 0.0 && n.is_finite() => Ok(n),
+        _ => Err(format!("expected a positive number, got {s}")),
+    }
+}
+
+/// `s` as a number that is neither infinite nor NaN
+pub fn parse_finite(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(n) if n.is_finite() => Ok(n),
+        _ => Err(format!("expected a number, got {s}")),
+    }
+}
+
+/// If stdin is not a terminal, read commands from it on a background thread
+pub fn spawn_stdin_reader(tx: ActionSender) {
+    if io::stdin().is_terminal() {
+        return;
+    }
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match line.parse() {
+                Ok(action) => {
+                    if tx.send(action).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => eprintln!("{e}"),
+            }
+        }
+    });
+}
@@ -0,0 +1,93 @@
+//! Ways of showing two inputs at once
+
+use resvg::tiny_skia::{Pixmap, PixmapPaint, Transform};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Show one input at a time, flipping with backtick
+    Flip,
+    SideBySide,
+    /// Per-channel absolute difference; identical pixels are black
+    Difference,
+    /// The other input at 50% opacity over the shown one
+    Overlay,
+    /// The shown input left of a draggable split, the other one right of it
+    Wipe,
+}
+
+impl Mode {
+    pub fn next(self) -> Self {
+        match self {
+            Mode::Flip => Mode::SideBySide,
+            Mode::SideBySide => Mode::Difference,
+            Mode::Difference => Mode::Overlay,
+            Mode::Overlay => Mode::Wipe,
+            Mode::Wipe => Mode::Flip,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Flip => "flip",
+            Mode::SideBySide => "side by side",
+            Mode::Difference => "difference",
+            Mode::Overlay => "overlay",
+            Mode::Wipe => "wipe",
+        }
+    }
+}
+
+/// Combine `b` into `a` (both the same size) according to `mode`.
+/// `split` is the wipe position as a fraction of the width.
+pub fn blend(mode: Mode, a: &mut Pixmap, b: &Pixmap, split: f32) {
+    match mode {
+        Mode::Flip | Mode::SideBySide => {}
+        Mode::Difference => {
+            for (pa, pb) in a
+                .data_mut()
+                .chunks_exact_mut(4)
+                .zip(b.data().chunks_exact(4))
+            {
+                for c in 0..3 {
+                    pa[c] = pa[c].abs_diff(pb[c]);
+                }
+                pa[3] = 0xFF;
+            }
+        }
+        Mode::Overlay => {
+            let paint = PixmapPaint {
+                opacity: 0.5,
+                ..Default::default()
+            };
+            a.draw_pixmap(0, 0, b.as_ref(), &paint, Transform::identity(), None);
+        }
+        Mode::Wipe => {
+            let width = a.width() as usize;
+            let x = ((split.clamp(0.0, 1.0) * width as f32) as usize).min(width);
+            let stride = width * 4;
+            let (da, db) = (a.data_mut(), b.data());
+            for (ra, rb) in da.chunks_exact_mut(stride).zip(db.chunks_exact(stride)) {
+                ra[x * 4..].copy_from_slice(&rb[x * 4..]);
+                if x < width {
+                    ra[x * 4..x * 4 + 4].fill(0xFF);
+                }
+            }
+        }
+    }
+}
+
+/// Place `a` and `b` next to each other
+pub fn side_by_side(a: &Pixmap, b: &Pixmap) -> Option<Pixmap> {
+    let mut out = Pixmap::new(a.width() + b.width(), a.height().max(b.height()))?;
+    let paint = PixmapPaint::default();
+    out.draw_pixmap(0, 0, a.as_ref(), &paint, Transform::identity(), None);
+    out.draw_pixmap(
+        a.width() as i32,
+        0,
+        b.as_ref(),
+        &paint,
+        Transform::identity(),
+        None,
+    );
+    Some(out)
+}
@@ -0,0 +1,155 @@
+//! Documents split across files. usvg leaves out a `<use>` of an element in
+//! another file, as in `<use href="parts.svg#arrow"/>`, so the element is
+//! copied in; and it looks for images relative to where svgtail was
+//! started, so `<image href="plot.svg"/>` is pointed at the file beside the
+//! document instead. The files drawn from are watched along with it.
+
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use resvg::usvg::roxmltree;
+
+const XLINK: &str = "http://www.w3.org/1999/xlink";
+
+/// How many files deep `<use>` is followed, so files using each other
+/// don't go round for ever
+const MAX_DEPTH: usize = 8;
+
+/// `text`, the source of `doc` in directory `dir`, with the elements it
+/// uses from other files copied in and its images pointed at files beside
+/// it, or `None` if it does neither; and the files it draws from
+pub fn compose(
+    text: &str,
+    doc: &roxmltree::Document,
+    dir: &Path,
+) -> (Option<String>, Vec<PathBuf>) {
+    let mut composer = Composer::default();
+    let composed = composer.compose(text, doc, dir, 0);
+    (composed, composer.deps)
+}
+
+#[derive(Default)]
+struct Composer {
+    deps: Vec<PathBuf>,
+    /// Elements copied in so far, numbering their ids apart
+    copied: usize,
+}
+
+impl Composer {
+    fn compose(
+        &mut self,
+        text: &str,
+        doc: &roxmltree::Document,
+        dir: &Path,
+        depth: usize,
+    ) -> Option<String> {
+        let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+        let mut copies = String::new();
+        for node in doc.descendants().filter(|n| n.is_element()) {
+            let Some(href) = node
+                .attributes()
+                .find(|a| a.name() == "href" && a.namespace().is_none_or(|ns| ns == XLINK))
+            else {
+                continue;
+            };
+            let value = href.value();
+            if value.starts_with('#') || value.starts_with("data:") || value.contains("://") {
+                continue;
+            }
+            match node.tag_name().name() {
+                "use" => {
+                    let Some((file, id)) = value.split_once('#') else {
+                        continue;
+                    };
+                    let Some((copy, id)) = self.copy(&dir.join(file), id, depth) else {
+                        continue;
+                    };
+                    copies += &copy;
+                    edits.push((href.range_value(), format!("#{id}")));
+                }
+                "image" => {
+                    let path = dir.join(value);
+                    if !path.is_file() {
+                        continue;
+                    }
+                    edits.push((href.range_value(), escape(&path.to_string_lossy())));
+                    self.depend(path);
+                }
+                _ => {}
+            }
+        }
+        if edits.is_empty() {
+            return None;
+        }
+        // Copies go just before the root's end tag
+        let root = doc.root_element().range();
+        let end = text[root.clone()].rfind("</")? + root.start;
+        edits.push((end..end, copies));
+        edits.sort_by_key(|(range, _)| range.start);
+        let mut composed = String::with_capacity(text.len());
+        let mut at = 0;
+        for (range, replacement) in edits {
+            composed += &text[at..range.start];
+            composed += &replacement;
+            at = range.end;
+        }
+        composed += &text[at..];
+        Some(composed)
+    }
+
+    /// The element `id` in the file at `path`, under an id of its own in a
+    /// `<defs>` declaring the namespaces it was written with, and that id
+    fn copy(&mut self, path: &Path, id: &str, depth: usize) -> Option<(String, String)> {
+        if depth >= MAX_DEPTH {
+            return None;
+        }
+        let text = fs::read_to_string(path).ok()?;
+        self.depend(path.to_path_buf());
+        let doc = roxmltree::Document::parse(&text).ok()?;
+        let node = doc
+            .descendants()
+            .find(|n| n.is_element() && n.attribute("id") == Some(id))?;
+        let own_id = node
+            .attributes()
+            .find(|a| a.name() == "id" && a.namespace().is_none())?
+            .range_value();
+        self.copied += 1;
+        let new_id = format!("svgtail-use-{}", self.copied);
+        let namespaces: String = node
+            .namespaces()
+            .filter(|ns| ns.name() != Some("xml"))
+            .map(|ns| match ns.name() {
+                Some(name) => format!(r#" xmlns:{name}="{}""#, escape(ns.uri())),
+                None => format!(r#" xmlns="{}""#, escape(ns.uri())),
+            })
+            .collect();
+        let range = node.range();
+        let copy = format!(
+            "<defs{namespaces}>{}{new_id}{}</defs>",
+            &text[range.start..own_id.start],
+            &text[own_id.end..range.end]
+        );
+        // What it uses from further files, relative to its own
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let nested = roxmltree::Document::parse(&copy)
+            .ok()
+            .and_then(|doc| self.compose(&copy, &doc, dir, depth + 1));
+        Some((nested.unwrap_or(copy), new_id))
+    }
+
+    fn depend(&mut self, path: PathBuf) {
+        if !self.deps.contains(&path) {
+            self.deps.push(path);
+        }
+    }
+}
+
+/// `value` fit to go between the quotes of an attribute, either kind
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
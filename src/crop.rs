@@ -0,0 +1,272 @@
+//! Cropping the document to a rectangle placed by dragging, nudged with the
+//! arrow keys or given numerically, and exporting it as PNG or SVG
+
+use std::path::{Path, PathBuf};
+
+use resvg::tiny_skia::{self, Color, FillRule, IntRect, Paint, PathBuilder, Pixmap, Rect};
+use resvg::usvg;
+
+use crate::overlay;
+use crate::render::{self, Canvas};
+use crate::units::Units;
+
+pub struct Crop {
+    /// The crop in page coordinates, once placed
+    pub rect: Option<Rect>,
+    /// Where a drag placing the crop started
+    anchor: Option<(f32, f32)>,
+    /// Width over height, if locked
+    pub aspect: Option<f32>,
+}
+
+impl Crop {
+    pub fn new(aspect: Option<f32>) -> Self {
+        Self {
+            rect: None,
+            anchor: None,
+            aspect,
+        }
+    }
+
+    /// Start placing the crop at `at`, snapped to whole units
+    pub fn press(&mut self, at: (f32, f32)) {
+        self.anchor = Some((at.0.round(), at.1.round()));
+    }
+
+    /// Stretch the crop from where it was pressed to `at`
+    pub fn drag(&mut self, at: (f32, f32)) {
+        let Some(anchor) = self.anchor else {
+            return;
+        };
+        let (mut dx, mut dy) = (at.0.round() - anchor.0, at.1.round() - anchor.1);
+        if let Some(aspect) = self.aspect {
+            dy = (dx.abs() / aspect).round().copysign(dy);
+            if dy == 0.0 {
+                dx = 0.0;
+            }
+        }
+        let (x, y) = (anchor.0.min(anchor.0 + dx), anchor.1.min(anchor.1 + dy));
+        if let Some(rect) = Rect::from_xywh(x, y, dx.abs(), dy.abs()) {
+            self.rect = Some(rect);
+        }
+    }
+
+    pub fn release(&mut self) {
+        self.anchor = None;
+    }
+
+    pub fn dragging(&self) -> bool {
+        self.anchor.is_some()
+    }
+
+    /// Set the crop, giving precedence to its width if the aspect is locked
+    pub fn set(&mut self, rect: Rect) {
+        let rect = match self.aspect {
+            Some(aspect) => {
+                Rect::from_xywh(rect.x(), rect.y(), rect.width(), rect.width() / aspect)
+            }
+            None => Some(rect),
+        };
+        if rect.is_some() {
+            self.rect = rect;
+        }
+    }
+
+    /// Move the crop by `(dx, dy)` units, or with `resize` move its
+    /// bottom-right corner instead
+    pub fn nudge(&mut self, dx: f32, dy: f32, resize: bool) {
+        let Some(rect) = self.rect else {
+            return;
+        };
+        let moved = if resize {
+            let (mut w, mut h) = (rect.width() + dx, rect.height() + dy);
+            if let Some(aspect) = self.aspect {
+                // Either key resizes both ways, keeping the aspect.
+                if dx != 0.0 {
+                    h = w / aspect;
+                } else {
+                    w = h * aspect;
+                }
+            }
+            Rect::from_xywh(rect.x(), rect.y(), w.max(1.0), h.max(1.0))
+        } else {
+            Rect::from_xywh(rect.x() + dx, rect.y() + dy, rect.width(), rect.height())
+        };
+        if moved.is_some() {
+            self.rect = moved;
+        }
+    }
+
+    /// Shade everything outside the crop and outline it, with its position
+    /// and size in `units` above it
+    pub fn draw(
+        &self,
+        pixmap: &mut Pixmap,
+        transform: tiny_skia::Transform,
+        units: &Units,
+        opts: &usvg::Options,
+    ) {
+        let Some(rect) = self.rect else {
+            return;
+        };
+        let Some(screen) = rect.transform(transform) else {
+            return;
+        };
+        let whole = Rect::from_xywh(0.0, 0.0, pixmap.width() as f32, pixmap.height() as f32);
+        let mut pb = PathBuilder::new();
+        pb.push_rect(whole.unwrap_or(screen));
+        pb.push_rect(screen);
+        if let Some(path) = pb.finish() {
+            let mut paint = Paint::default();
+            paint.set_color_rgba8(0, 0, 0, 0x99);
+            let identity = tiny_skia::Transform::identity();
+            pixmap.fill_path(&path, &paint, FillRule::EvenOdd, identity, None);
+        }
+        let outline = overlay::Shape::Rect {
+            x: rect.x(),
+            y: rect.y(),
+            w: rect.width(),
+            h: rect.height(),
+            color: Color::WHITE,
+        };
+        overlay::draw(pixmap, &[outline], transform, opts);
+        let lock = match self.aspect {
+            Some(aspect) => format!(", {aspect:.3}:1 locked"),
+            None => String::new(),
+        };
+        let label = format!("{}{lock}", describe(rect, units, transform.sx));
+        let y = (screen.top() - 24.0).max(0.0);
+        crate::guides::readout(pixmap, (screen.left(), y), &label, opts);
+    }
+}
+
+/// Position and size of `rect`, to what `scale` screen pixels per user unit
+/// can resolve
+fn describe(rect: Rect, units: &Units, scale: f32) -> String {
+    format!(
+        "{}  {}",
+        units.point((rect.x(), rect.y()), scale),
+        units.size_of((rect.width(), rect.height()), scale)
+    )
+}
+
+/// Where the crop of `doc` is saved by default, as `<name>-crop.<ext>`
+pub fn default_path(doc: &Path, ext: &str) -> PathBuf {
+    let stem = doc.file_stem().unwrap_or_default().to_string_lossy();
+    doc.with_file_name(format!("{stem}-crop.{ext}"))
+}
+
+/// Rasterize the part of `tree` inside `rect` (page coordinates) at `scale`
+/// pixels per unit
+pub fn to_pixmap(tree: &usvg::Tree, rect: Rect, scale: f32, canvas: &Canvas) -> Option<Pixmap> {
+    let (w, h) = (
+        (rect.width() * scale).round() as u32,
+        (rect.height() * scale).round() as u32,
+    );
+    let area = IntRect::from_xywh(0, 0, w, h)?;
+    let transform =
+        tiny_skia::Transform::from_scale(scale, scale).pre_translate(-rect.x(), -rect.y());
+    Some(render::render(tree, area, transform, canvas))
+}
+
+/// `tree` as a standalone SVG showing only `rect` (page coordinates)
+pub fn to_svg(tree: &usvg::Tree, rect: Rect) -> String {
+    let inner = tree.to_string(&usvg::WriteOptions::default());
+    // The whole document as written spans the page, so an outer viewBox in
+    // page coordinates picks out the crop.
+    format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="{x} {y} {w} {h}">{inner}</svg>"#,
+        x = rect.x(),
+        y = rect.y(),
+        w = rect.width(),
+        h = rect.height(),
+    )
+}
+
+/// Parse an aspect ratio as `W:H` or a single number
+pub fn parse_aspect(s: &str) -> Result<f32, String> {
+    let ratio = match s.split_once(':') {
+        Some((w, h)) => w
+            .trim()
+            .parse::<f32>()
+            .ok()
+            .zip(h.trim().parse::<f32>().ok())
+            .map(|(w, h)| w / h),
+        None => s.trim().parse::<f32>().ok(),
+    };
+    match ratio {
+        Some(r) if r > 0.0 && r.is_finite() => Ok(r),
+        _ => Err(format!("expected an aspect ratio like 16:9, got {s}")),
+    }
+}
+
+/// Parse a rectangle in page coordinates as `x,y,w,h`
+pub fn parse_rect(s: &str) -> Result<Rect, String> {
+    let numbers: Vec<f32> = s
+        .split(',')
+        .map(|n| n.trim().parse::<f32>().ok().filter(|n| n.is_finite()))
+        .collect::<Option<_>>()
+        .unwrap_or_default();
+    match numbers[..] {
+        [x, y, w, h] if w > 0.0 && h > 0.0 => {
+            Rect::from_xywh(x, y, w, h).ok_or_else(|| format!("{s} is out of range"))
+        }
+        [_, _, _, _] => Err(format!("{s} has no area")),
+        _ => Err(format!("expected x,y,width,height, got {s}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn xywh(rect: Option<Rect>) -> Option<(f32, f32, f32, f32)> {
+        rect.map(|r| (r.x(), r.y(), r.width(), r.height()))
+    }
+
+    #[test]
+    fn parses_aspect_ratios() {
+        assert_eq!(parse_aspect("16:9"), Ok(16.0 / 9.0));
+        assert_eq!(parse_aspect(" 4 : 3 "), Ok(4.0 / 3.0));
+        assert_eq!(parse_aspect("1.5"), Ok(1.5));
+        for s in ["0:1", "1:0", "-1", "wide", "16:", "nan", "inf"] {
+            assert!(parse_aspect(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn parses_rectangles_with_area() {
+        assert_eq!(
+            xywh(parse_rect("1, 2, 3, 4").ok()),
+            Some((1.0, 2.0, 3.0, 4.0))
+        );
+        for s in ["1,2,0,4", "1,2,3", "1,2,3,4,5", "1,2,nan,4", "a,b,c,d"] {
+            assert!(parse_rect(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn keeps_a_locked_aspect_when_dragged_set_or_resized() {
+        let mut crop = Crop::new(Some(2.0));
+        crop.press((10.2, 10.0));
+        crop.drag((50.0, 0.0));
+        assert_eq!(xywh(crop.rect), Some((10.0, -10.0, 40.0, 20.0)));
+        crop.release();
+        assert!(!crop.dragging());
+
+        crop.set(Rect::from_xywh(0.0, 0.0, 30.0, 100.0).unwrap());
+        assert_eq!(xywh(crop.rect), Some((0.0, 0.0, 30.0, 15.0)));
+        crop.nudge(0.0, 5.0, true);
+        assert_eq!(xywh(crop.rect), Some((0.0, 0.0, 40.0, 20.0)));
+        crop.nudge(-1.0, 2.0, false);
+        assert_eq!(xywh(crop.rect), Some((-1.0, 2.0, 40.0, 20.0)));
+    }
+
+    #[test]
+    fn saves_beside_the_document() {
+        assert_eq!(
+            default_path(Path::new("/docs/plot.svg"), "png"),
+            PathBuf::from("/docs/plot-crop.png")
+        );
+    }
+}
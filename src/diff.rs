@@ -0,0 +1,225 @@
+//! Finding the part of a document that changed between two loads, so a
+//! reload only re-rasterizes that region of the cached frame
+
+use std::collections::HashSet;
+use std::fmt::{self, Write};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use resvg::{tiny_skia::Rect, usvg};
+
+/// A node painted as a unit, in paint order
+#[derive(Clone, Copy, Debug)]
+pub struct Leaf {
+    /// Hash of everything that affects how the node is painted
+    hash: u64,
+    /// What the node can paint, in document coordinates
+    bounds: Rect,
+}
+
+/// The leaves of `tree` in paint order. Groups are flattened unless they
+/// composite their children as a layer (opacity, masks, filters, ...), in
+/// which case the whole group is one leaf.
+pub fn leaves(tree: &usvg::Tree) -> Vec<Leaf> {
+    let mut leaves = Vec::new();
+    collect(tree.root(), &mut leaves);
+    leaves
+}
+
+fn collect(group: &usvg::Group, leaves: &mut Vec<Leaf>) {
+    for node in group.children() {
+        match node {
+            usvg::Node::Group(g) if !g.should_isolate() => collect(g, leaves),
+            usvg::Node::Group(g) => leaves.push(leaf(node, g.abs_layer_bounding_box().to_rect())),
+            _ => leaves.push(leaf(node, node.abs_stroke_bounding_box())),
+        }
+    }
+}
+
+fn leaf(node: &usvg::Node, bounds: Rect) -> Leaf {
+    let mut hasher = DefaultHasher::new();
+    // Debug output covers geometry, paint and absolute bounds, so a node
+    // moved by a parent's transform hashes differently too.
+    let _ = write!(HashWriter(&mut hasher), "{node:?}");
+    if let usvg::Node::Image(image) = node {
+        // Raster data is elided from Debug output
+        match image.kind() {
+            usvg::ImageKind::JPEG(data)
+            | usvg::ImageKind::PNG(data)
+            | usvg::ImageKind::GIF(data)
+            | usvg::ImageKind::WEBP(data) => data.hash(&mut hasher),
+            usvg::ImageKind::SVG(tree) => {
+                let _ = write!(HashWriter(&mut hasher), "{tree:?}");
+            }
+        }
+    }
+    Leaf {
+        hash: hasher.finish(),
+        bounds,
+    }
+}
+
+struct HashWriter<'a>(&'a mut DefaultHasher);
+
+impl Write for HashWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// What a reload changed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Damage {
+    None,
+    /// Only pixels inside this rectangle (document coordinates) changed
+    Region(Rect),
+    /// Anything may have changed
+    Full,
+}
+
+impl Damage {
+    /// Damage covering both `self` and `other`
+    pub fn join(self, other: Damage) -> Damage {
+        match (self, other) {
+            (Damage::None, d) | (d, Damage::None) => d,
+            (Damage::Region(a), Damage::Region(b)) => {
+                union(Some(a), b).map_or(Damage::Full, Damage::Region)
+            }
+            _ => Damage::Full,
+        }
+    }
+}
+
+/// The region that differs between documents with leaves `old` and `new`
+pub fn damage(old: &[Leaf], new: &[Leaf]) -> Damage {
+    // Strip what is unchanged at either end of the paint order.
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(a, b)| a.hash == b.hash)
+        .count();
+    let (old, new) = (&old[prefix..], &new[prefix..]);
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(a, b)| a.hash == b.hash)
+        .count();
+    let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+    if old.is_empty() && new.is_empty() {
+        return Damage::None;
+    }
+
+    // Leaves present on both sides only need repainting if they changed
+    // order; otherwise just the added and removed leaves are damage.
+    let in_old: HashSet<u64> = old.iter().map(|l| l.hash).collect();
+    let in_new: HashSet<u64> = new.iter().map(|l| l.hash).collect();
+    let kept = |leaves: &[Leaf], other: &HashSet<u64>| -> Vec<u64> {
+        leaves
+            .iter()
+            .map(|l| l.hash)
+            .filter(|h| other.contains(h))
+            .collect()
+    };
+    let changed: Vec<&Leaf> = if kept(old, &in_new) == kept(new, &in_old) {
+        old.iter()
+            .filter(|l| !in_new.contains(&l.hash))
+            .chain(new.iter().filter(|l| !in_old.contains(&l.hash)))
+            .collect()
+    } else {
+        old.iter().chain(new).collect()
+    };
+    changed
+        .iter()
+        .try_fold(None, |acc, l| union(acc, l.bounds).map(Some))
+        .map_or(Damage::Full, |r| r.map_or(Damage::None, Damage::Region))
+}
+
+fn union(a: Option<Rect>, b: Rect) -> Option<Rect> {
+    match a {
+        None => Some(b),
+        Some(a) => Rect::from_ltrb(
+            a.left().min(b.left()),
+            a.top().min(b.top()),
+            a.right().max(b.right()),
+            a.bottom().max(b.bottom()),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(hash: u64, x: f32, y: f32, w: f32, h: f32) -> Leaf {
+        Leaf {
+            hash,
+            bounds: Rect::from_xywh(x, y, w, h).unwrap(),
+        }
+    }
+
+    fn region(x: f32, y: f32, w: f32, h: f32) -> Damage {
+        Damage::Region(Rect::from_xywh(x, y, w, h).unwrap())
+    }
+
+    #[test]
+    fn nothing_changed_is_no_damage() {
+        let leaves = [leaf(1, 0.0, 0.0, 10.0, 10.0), leaf(2, 5.0, 5.0, 10.0, 10.0)];
+        assert_eq!(damage(&leaves, &leaves), Damage::None);
+        assert_eq!(damage(&[], &[]), Damage::None);
+    }
+
+    #[test]
+    fn a_changed_leaf_damages_where_it_was_and_is() {
+        let a = leaf(1, 0.0, 0.0, 10.0, 10.0);
+        let c = leaf(3, 80.0, 80.0, 10.0, 10.0);
+        let old = [a, leaf(2, 20.0, 20.0, 10.0, 10.0), c];
+        let new = [a, leaf(4, 30.0, 20.0, 10.0, 10.0), c];
+        assert_eq!(damage(&old, &new), region(20.0, 20.0, 20.0, 10.0));
+    }
+
+    #[test]
+    fn added_and_removed_leaves_damage_only_themselves() {
+        let a = leaf(1, 0.0, 0.0, 10.0, 10.0);
+        let b = leaf(2, 50.0, 50.0, 10.0, 10.0);
+        let added = leaf(3, 20.0, 0.0, 5.0, 5.0);
+        assert_eq!(damage(&[a, b], &[a, added, b]), region(20.0, 0.0, 5.0, 5.0));
+        assert_eq!(damage(&[a, added, b], &[a, b]), region(20.0, 0.0, 5.0, 5.0));
+    }
+
+    #[test]
+    fn reordered_leaves_damage_everything_between() {
+        let a = leaf(1, 0.0, 0.0, 10.0, 10.0);
+        let b = leaf(2, 50.0, 50.0, 10.0, 10.0);
+        assert_eq!(damage(&[a, b], &[b, a]), region(0.0, 0.0, 60.0, 60.0));
+    }
+
+    #[test]
+    fn joins_damage() {
+        let a = region(0.0, 0.0, 10.0, 10.0);
+        let b = region(20.0, 0.0, 10.0, 10.0);
+        assert_eq!(Damage::None.join(a), a);
+        assert_eq!(a.join(Damage::None), a);
+        assert_eq!(a.join(b), region(0.0, 0.0, 30.0, 10.0));
+        assert_eq!(a.join(Damage::Full), Damage::Full);
+        assert_eq!(Damage::Full.join(Damage::None), Damage::Full);
+    }
+
+    #[test]
+    fn finds_the_recoloured_element_in_a_document() {
+        let doc = |fill: &str| {
+            let svg = format!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+                    <rect width="10" height="10"/>
+                    <rect x="40" y="50" width="20" height="10" fill="{fill}"/>
+                </svg>"#
+            );
+            leaves(&usvg::Tree::from_str(&svg, &usvg::Options::default()).unwrap())
+        };
+        assert_eq!(damage(&doc("red"), &doc("red")), Damage::None);
+        assert_eq!(
+            damage(&doc("red"), &doc("blue")),
+            region(40.0, 50.0, 20.0, 10.0)
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! Reducing the window to a few levels of grey or a fixed palette, dithered,
+//! to preview or drive e-ink and other low-colour displays
+
+use resvg::tiny_skia::{ColorU8, Pixmap};
+
+use crate::color;
+
+/// How colours between those of the palette are approximated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Method {
+    /// The nearest colour, leaving flat bands
+    None,
+    /// An 8×8 Bayer pattern, stable from frame to frame
+    Ordered,
+    /// Error diffusion, finer but shimmering as the view moves
+    #[default]
+    FloydSteinberg,
+}
+
+/// The colours the output is reduced to
+#[derive(Clone, Debug, PartialEq)]
+pub enum Palette {
+    /// Evenly spaced greys from black to white
+    Gray(u32),
+    Colors(Vec<[f32; 3]>),
+}
+
+impl Palette {
+    /// The palette colour closest to `c`
+    fn nearest(&self, c: [f32; 3]) -> [f32; 3] {
+        match self {
+            Palette::Gray(levels) => {
+                let steps = (levels - 1) as f32;
+                let luma = 0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2];
+                let v = ((luma / 255.0 * steps).round().clamp(0.0, steps)) * 255.0 / steps;
+                [v; 3]
+            }
+            Palette::Colors(colors) => {
+                let distance =
+                    |p: &[f32; 3]| (0..3).map(|i| (p[i] - c[i]) * (p[i] - c[i])).sum::<f32>();
+                *colors
+                    .iter()
+                    .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+                    .expect("palettes are never empty")
+            }
+        }
+    }
+
+    /// Roughly how far apart neighbouring colours are, per channel
+    fn spacing(&self) -> f32 {
+        match self {
+            Palette::Gray(levels) => 255.0 / (levels - 1) as f32,
+            Palette::Colors(colors) => 255.0 / (colors.len() as f32).cbrt(),
+        }
+    }
+}
+
+/// Parse `grayN` (2 to 256 levels) or comma-separated colours
+pub fn parse_palette(s: &str) -> Result<Palette, String> {
+    if let Some(levels) = s.strip_prefix("gray").or_else(|| s.strip_prefix("grey")) {
+        return match levels.parse::<u32>() {
+            Ok(n @ 2..=256) => Ok(Palette::Gray(n)),
+            _ => Err(format!("expected gray2 to gray256, got {s}")),
+        };
+    }
+    let colors = s
+        .split(',')
+        .map(|c| {
+            let c = color::parse(c.trim())?.to_color_u8();
+            Ok([c.red() as f32, c.green() as f32, c.blue() as f32])
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    match colors.len() {
+        0 | 1 => Err(format!("a palette needs at least two colours, got {s}")),
+        _ => Ok(Palette::Colors(colors)),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Dither {
+    pub method: Method,
+    pub palette: Palette,
+}
+
+/// 8×8 Bayer thresholds
+const BAYER: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+impl Dither {
+    /// Reduce a minifb `0RGB` buffer `width` pixels wide
+    pub fn apply(&self, buffer: &mut [u32], width: usize) {
+        let mut pixels: Vec<[f32; 3]> = buffer
+            .iter()
+            .map(|&px| {
+                let [_, r, g, b] = px.to_be_bytes();
+                [r as f32, g as f32, b as f32]
+            })
+            .collect();
+        self.reduce(&mut pixels, width);
+        for (px, c) in buffer.iter_mut().zip(&pixels) {
+            let [r, g, b] = c.map(|c| c.round() as u8);
+            *px = u32::from_be_bytes([0, r, g, b]);
+        }
+    }
+
+    /// Reduce the colours of `pixmap`, for screenshots to match the window
+    pub fn apply_pixmap(&self, pixmap: &mut Pixmap) {
+        let width = pixmap.width() as usize;
+        let colors: Vec<ColorU8> = pixmap.pixels().iter().map(|px| px.demultiply()).collect();
+        let mut pixels: Vec<[f32; 3]> = colors
+            .iter()
+            .map(|c| [c.red() as f32, c.green() as f32, c.blue() as f32])
+            .collect();
+        self.reduce(&mut pixels, width);
+        for ((px, c), old) in pixmap.pixels_mut().iter_mut().zip(&pixels).zip(&colors) {
+            let [r, g, b] = c.map(|c| c.round() as u8);
+            *px = ColorU8::from_rgba(r, g, b, old.alpha()).premultiply();
+        }
+    }
+
+    /// Replace each of `pixels`, in rows `width` long, with a palette colour
+    fn reduce(&self, pixels: &mut [[f32; 3]], width: usize) {
+        match self.method {
+            Method::None => {
+                for px in pixels.iter_mut() {
+                    *px = self.palette.nearest(*px);
+                }
+            }
+            Method::Ordered => {
+                let spacing = self.palette.spacing();
+                for (i, px) in pixels.iter_mut().enumerate() {
+                    let (x, y) = (i % width, i / width);
+                    let offset = ((BAYER[y % 8][x % 8] as f32 + 0.5) / 64.0 - 0.5) * spacing;
+                    *px = self.palette.nearest(px.map(|c| c + offset));
+                }
+            }
+            Method::FloydSteinberg => {
+                for i in 0..pixels.len() {
+                    let old = pixels[i];
+                    let new = self.palette.nearest(old);
+                    pixels[i] = new;
+                    let error = [old[0] - new[0], old[1] - new[1], old[2] - new[2]];
+                    let x = i % width;
+                    let mut spread = |j: usize, weight: f32| {
+                        if let Some(px) = pixels.get_mut(j) {
+                            for c in 0..3 {
+                                px[c] += error[c] * weight;
+                            }
+                        }
+                    };
+                    if x + 1 < width {
+                        spread(i + 1, 7.0 / 16.0);
+                        spread(i + width + 1, 1.0 / 16.0);
+                    }
+                    if x > 0 {
+                        spread(i + width - 1, 3.0 / 16.0);
+                    }
+                    spread(i + width, 5.0 / 16.0);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_grey_levels_in_range() {
+        assert_eq!(parse_palette("gray2"), Ok(Palette::Gray(2)));
+        assert_eq!(parse_palette("grey256"), Ok(Palette::Gray(256)));
+        for s in ["gray1", "gray257", "gray", "grayish"] {
+            assert!(parse_palette(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn parses_at_least_two_colours() {
+        assert_eq!(
+            parse_palette("#000, fff,#ff0000"),
+            Ok(Palette::Colors(vec![
+                [0.0, 0.0, 0.0],
+                [255.0, 255.0, 255.0],
+                [255.0, 0.0, 0.0],
+            ]))
+        );
+        assert!(parse_palette("#000").is_err());
+        assert!(parse_palette("#000,nope").is_err());
+    }
+
+    #[test]
+    fn maps_to_the_nearest_palette_colour() {
+        assert_eq!(Palette::Gray(2).nearest([100.0; 3]), [0.0; 3]);
+        assert_eq!(Palette::Gray(3).nearest([120.0; 3]), [127.5; 3]);
+        let colors = Palette::Colors(vec![[255.0, 0.0, 0.0], [0.0, 0.0, 255.0]]);
+        assert_eq!(colors.nearest([200.0, 50.0, 60.0]), [255.0, 0.0, 0.0]);
+    }
+}
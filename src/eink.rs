@@ -0,0 +1,157 @@
+//! Mirroring finished frames to a Linux framebuffer, such as an e-paper
+//! panel's. Only the rectangle that changed since the last frame is written,
+//! which drivers refresh as a partial update, with the whole screen written
+//! every so often to clear ghosting.
+
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+use crate::sink::Sink;
+
+pub struct Eink {
+    file: File,
+    /// Visible width and height in pixels
+    size: (usize, usize),
+    /// Bytes per row
+    stride: usize,
+    bits_per_pixel: u32,
+    /// The frame last written, and its width
+    last: Vec<u32>,
+    last_width: usize,
+    /// Partial updates since the screen was last written whole
+    partial: u32,
+    /// Partial updates allowed between full ones
+    full_every: u32,
+}
+
+impl Eink {
+    /// Open framebuffer device `path` (such as `/dev/fb1`), reading its
+    /// geometry from sysfs
+    pub fn open(path: &Path, full_every: u32) -> io::Result<Self> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| io::Error::other("not a framebuffer device"))?;
+        let sys = Path::new("/sys/class/graphics").join(name);
+        let read = |attr: &str| {
+            fs::read_to_string(sys.join(attr))
+                .map(|s| s.trim().to_string())
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", sys.join(attr).display())))
+        };
+        let number = |s: &str| {
+            s.parse::<usize>()
+                .map_err(|_| io::Error::other(format!("unexpected framebuffer attribute {s}")))
+        };
+        let size = read("virtual_size")?;
+        let (w, h) = size
+            .split_once(',')
+            .ok_or_else(|| io::Error::other(format!("unexpected framebuffer size {size}")))?;
+        let bits_per_pixel = number(&read("bits_per_pixel")?)? as u32;
+        if ![8, 16, 24, 32].contains(&bits_per_pixel) {
+            return Err(io::Error::other(format!(
+                "{bits_per_pixel} bits per pixel isn't supported"
+            )));
+        }
+        Ok(Self {
+            file: OpenOptions::new().write(true).open(path)?,
+            size: (number(w)?, number(h)?),
+            stride: number(&read("stride")?)?,
+            bits_per_pixel,
+            last: Vec::new(),
+            last_width: 0,
+            partial: 0,
+            full_every,
+        })
+    }
+
+    /// The framebuffer's width and height, which the window starts at
+    pub fn size(&self) -> (usize, usize) {
+        self.size
+    }
+}
+
+impl Sink for Eink {
+    fn name(&self) -> &str {
+        "eink"
+    }
+
+    /// Write what changed in `buffer`
+    fn push(&mut self, buffer: &[u32], width: usize) -> io::Result<()> {
+        let height = buffer.len() / width.max(1);
+        let full = self.last_width != width
+            || self.last.len() != buffer.len()
+            || self.partial >= self.full_every;
+        let rect = match full {
+            true => Some((0, 0, width, height)),
+            false => changed(&self.last, buffer, width),
+        };
+        let Some((x, y, w, h)) = rect else {
+            return Ok(());
+        };
+        // Only the part of the frame the screen has room for
+        let (w, h) = (
+            w.min(self.size.0.saturating_sub(x)),
+            h.min(self.size.1.saturating_sub(y)),
+        );
+        let bytes = (self.bits_per_pixel / 8) as usize;
+        let mut row = Vec::with_capacity(w * bytes);
+        for y in y..y + h {
+            row.clear();
+            for &px in &buffer[y * width + x..y * width + x + w] {
+                encode(px, self.bits_per_pixel, &mut row);
+            }
+            let offset = (y * self.stride + x * bytes) as u64;
+            self.file.write_all_at(&row, offset)?;
+        }
+        self.partial = match full {
+            true => 0,
+            false => self.partial + 1,
+        };
+        self.last.clear();
+        self.last.extend_from_slice(buffer);
+        self.last_width = width;
+        Ok(())
+    }
+}
+
+/// The smallest rectangle, as `x, y, w, h`, holding every pixel that differs
+/// between two frames `width` pixels wide, or `None` if they are the same
+fn changed(old: &[u32], new: &[u32], width: usize) -> Option<(usize, usize, usize, usize)> {
+    let mut rows = old
+        .chunks_exact(width)
+        .zip(new.chunks_exact(width))
+        .enumerate();
+    let differ = |(_, (a, b)): &(usize, (&[u32], &[u32]))| a != b;
+    let top = rows.find(differ)?.0;
+    let bottom = rows.rev().find(differ).map_or(top, |(y, _)| y);
+    let (mut left, mut right) = (width, 0);
+    for y in top..=bottom {
+        let (a, b) = (&old[y * width..][..width], &new[y * width..][..width]);
+        if let Some(x) = a.iter().zip(b).position(|(a, b)| a != b) {
+            left = left.min(x);
+        }
+        if let Some(x) = a.iter().zip(b).rposition(|(a, b)| a != b) {
+            right = right.max(x);
+        }
+    }
+    Some((left, top, right + 1 - left, bottom + 1 - top))
+}
+
+/// Append `0RGB` pixel `px` in the framebuffer's format: greys for 8 bits
+/// per pixel, RGB565 for 16 and little-endian BGR(X) otherwise
+fn encode(px: u32, bits_per_pixel: u32, out: &mut Vec<u8>) {
+    let [_, r, g, b] = px.to_be_bytes();
+    match bits_per_pixel {
+        8 => {
+            let luma = (2126 * r as u32 + 7152 * g as u32 + 722 * b as u32) / 10000;
+            out.push(luma as u8);
+        }
+        16 => {
+            let rgb565 = ((r as u16 >> 3) << 11) | ((g as u16 >> 2) << 5) | (b as u16 >> 3);
+            out.extend_from_slice(&rgb565.to_le_bytes());
+        }
+        24 => out.extend_from_slice(&[b, g, r]),
+        _ => out.extend_from_slice(&[b, g, r, 0]),
+    }
+}
@@ -0,0 +1,43 @@
+//! Process exit codes, so scripts can tell failures apart
+
+use std::error::Error;
+use std::fmt;
+use std::process::ExitCode;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Code {
+    /// Anything not covered below: bad arguments, window or watcher errors
+    Error = 1,
+    /// An input file does not exist and never appeared
+    Missing = 2,
+    /// An input failed to parse (with `--check` or `--fail-on-error`)
+    Invalid = 3,
+}
+
+/// An error together with the exit code it should produce
+#[derive(Debug)]
+pub struct Failure {
+    pub code: Code,
+    pub message: String,
+}
+
+impl Failure {
+    pub fn new(code: Code, message: impl fmt::Display) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl<E: Into<Box<dyn Error>>> From<E> for Failure {
+    fn from(e: E) -> Self {
+        Self::new(Code::Error, e.into())
+    }
+}
+
+impl From<Code> for ExitCode {
+    fn from(code: Code) -> Self {
+        ExitCode::from(code as u8)
+    }
+}
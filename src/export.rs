@@ -0,0 +1,256 @@
+//! Writing the document out as PNG each time it loads, so `--headless` can
+//! keep rendered copies of sources up to date without a window and
+//! `--export-on-reload` can keep them alongside one
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use resvg::tiny_skia::{Color, Pixmap, Rect};
+use resvg::usvg;
+
+use crate::crop;
+use crate::dither::Dither;
+use crate::icc::Profile;
+use crate::render::{Canvas, Edge};
+
+/// How big an exported image is
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Size {
+    /// Pixels per user unit, `@2x`
+    Scale(f32),
+    /// Scaled to this many pixels wide, `@800w`
+    Width(u32),
+    /// Scaled to this many pixels high, `@600h`
+    Height(u32),
+    /// Scaled to fit within a box, `@800x600`
+    Fit(u32, u32),
+}
+
+impl Size {
+    /// Pixels per user unit for an area `width` × `height` units
+    fn scale(self, width: f32, height: f32) -> f32 {
+        match self {
+            Size::Scale(scale) => scale,
+            Size::Width(w) => w as f32 / width,
+            Size::Height(h) => h as f32 / height,
+            Size::Fit(w, h) => (w as f32 / width).min(h as f32 / height),
+        }
+    }
+}
+
+/// An image written each time the document loads
+#[derive(Clone, Debug, PartialEq)]
+pub struct Export {
+    /// Where to write it, with `{stem}`, `{name}` and `{n}` filled in unless
+    /// taken as it is
+    path: String,
+    literal: bool,
+    /// How big it is, if not at `--scale`
+    size: Option<Size>,
+}
+
+impl Export {
+    /// `path` itself, as for `--output`
+    pub fn exact(path: &Path) -> Self {
+        Self {
+            path: path.to_string_lossy().into_owned(),
+            literal: true,
+            size: None,
+        }
+    }
+
+    /// Parse an `--export-on-reload` argument: a path in which `{stem}` and
+    /// `{name}` stand for the document's file name without and with its
+    /// extension and `{n}` for the count of loads, optionally followed by a
+    /// size such as `@2x`, `@800w`, `@600h` or `@800x600`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (path, size) = match s.rsplit_once('@') {
+            Some((path, size)) if !size.contains(['/', '\\']) => (path, Some(parse_size(size)?)),
+            _ => (s, None),
+        };
+        let mut rest = path;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed {{ in '{path}'"))?;
+            match &rest[start + 1..start + end] {
+                "stem" | "name" | "n" => {}
+                other => {
+                    return Err(format!(
+                        "'{{{other}}}' isn't known: use {{stem}}, {{name}} or {{n}}"
+                    ));
+                }
+            }
+            rest = &rest[start + end + 1..];
+        }
+        if path.is_empty() {
+            return Err("expected a path to export to".to_string());
+        }
+        Ok(Self {
+            path: path.to_string(),
+            literal: false,
+            size,
+        })
+    }
+
+    /// Where the `n`th load of `doc` is written
+    pub fn path(&self, doc: &Path, n: u64) -> PathBuf {
+        if self.literal {
+            return PathBuf::from(&self.path);
+        }
+        let stem = doc.file_stem().unwrap_or_default().to_string_lossy();
+        let name = doc.file_name().unwrap_or_default().to_string_lossy();
+        PathBuf::from(
+            self.path
+                .replace("{stem}", &stem)
+                .replace("{name}", &name)
+                .replace("{n}", &n.to_string()),
+        )
+    }
+
+    /// `area` of `tree`'s page, or all of it, at this export's size or else
+    /// `scale` pixels per unit, over what of `canvas` belongs in a picture of
+    /// the document: the page colour and backdrop, but not the window's
+    /// letterbox, grid or checkerboard
+    pub fn render(
+        &self,
+        tree: &usvg::Tree,
+        canvas: &Canvas,
+        area: Option<Rect>,
+        scale: f32,
+    ) -> Option<Pixmap> {
+        let size = tree.size();
+        let area = match area {
+            Some(area) => area,
+            None => Rect::from_xywh(0.0, 0.0, size.width(), size.height())?,
+        };
+        let canvas = Canvas {
+            background: Color::TRANSPARENT,
+            edge: Edge::None,
+            checkerboard: None,
+            world_grid: false,
+            ..canvas.clone()
+        };
+        let scale = self
+            .size
+            .map_or(scale, |size| size.scale(area.width(), area.height()));
+        crop::to_pixmap(tree, area, scale, &canvas)
+    }
+}
+
+fn parse_size(s: &str) -> Result<Size, String> {
+    let number = |n: &str| n.parse::<u32>().ok().filter(|&n| n > 0);
+    let size = if let Some(scale) = s.strip_suffix('x') {
+        scale
+            .parse::<f32>()
+            .ok()
+            .filter(|s| *s > 0.0 && s.is_finite())
+            .map(Size::Scale)
+    } else if let Some(w) = s.strip_suffix('w') {
+        number(w).map(Size::Width)
+    } else if let Some(h) = s.strip_suffix('h') {
+        number(h).map(Size::Height)
+    } else {
+        s.split_once('x')
+            .and_then(|(w, h)| Some(Size::Fit(number(w)?, number(h)?)))
+    };
+    size.ok_or_else(|| format!("expected a size like @2x, @800w, @600h or @800x600, got @{s}"))
+}
+
+/// Save `pixmap` to `path` as the window's screenshots are saved, through
+/// `dither` and `profile` if given, making the directory it goes in if
+/// need be. It is written beside `path` and renamed over it, so anything
+/// reading `path` sees the old image or the new one and never half of one.
+pub fn save(
+    mut pixmap: Pixmap,
+    path: &Path,
+    dither: Option<&Dither>,
+    profile: Option<&Profile>,
+) -> Result<(), String> {
+    if let Some(dither) = dither {
+        dither.apply_pixmap(&mut pixmap);
+    }
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let partial = partial_path(path);
+    let saved = match profile {
+        Some(profile) => profile.save_png(&pixmap, &partial),
+        None => pixmap.save_png(&partial).map_err(|e| e.to_string()),
+    };
+    let result = saved.and_then(|()| fs::rename(&partial, path).map_err(|e| e.to_string()));
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result
+}
+
+/// Where `path` is written before being renamed into place, hidden beside it
+fn partial_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}.{}.part", std::process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_paths_and_sizes() {
+        let export = Export::parse("out/{stem}-{n}.png@2x").unwrap();
+        assert_eq!(export.size, Some(Size::Scale(2.0)));
+        assert_eq!(
+            export.path(Path::new("/docs/plot.svg"), 3),
+            PathBuf::from("out/plot-3.png")
+        );
+        let sizes = [
+            ("a.png@800w", Size::Width(800)),
+            ("a.png@600h", Size::Height(600)),
+            ("a.png@800x600", Size::Fit(800, 600)),
+        ];
+        for (s, size) in sizes {
+            assert_eq!(Export::parse(s).unwrap().size, Some(size), "{s}");
+        }
+        // An `@` in a directory isn't a size.
+        let export = Export::parse("v@2/{name}.png").unwrap();
+        assert_eq!(export.size, None);
+        assert_eq!(
+            export.path(Path::new("plot.svg"), 1),
+            PathBuf::from("v@2/plot.svg.png")
+        );
+    }
+
+    #[test]
+    fn refuses_what_it_cant_fill_in_or_size() {
+        for s in [
+            "{stem",
+            "{date}.png",
+            "",
+            "@2x",
+            "a.png@0x",
+            "a.png@-1x",
+            "a.png@0w",
+            "a.png@x600",
+            "a.png@big",
+        ] {
+            assert!(Export::parse(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn takes_exact_paths_as_they_are() {
+        let export = Export::exact(Path::new("out/{stem}.png"));
+        assert_eq!(
+            export.path(Path::new("plot.svg"), 2),
+            PathBuf::from("out/{stem}.png")
+        );
+    }
+
+    #[test]
+    fn scales_to_the_size_asked_for() {
+        assert_eq!(Size::Width(200).scale(100.0, 50.0), 2.0);
+        assert_eq!(Size::Height(200).scale(100.0, 50.0), 4.0);
+        assert_eq!(Size::Fit(200, 200).scale(100.0, 50.0), 2.0);
+        assert_eq!(Size::Scale(3.0).scale(100.0, 50.0), 3.0);
+    }
+}
@@ -0,0 +1,240 @@
+//! A C interface to loading, watching and rendering documents, for
+//! applications that want svgtail's live reloading without running the
+//! viewer. `include/svgtail.h` declares it.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_int, c_void};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, mpsc};
+use std::thread;
+use std::time::Duration;
+
+use notify_debouncer_full::{
+    DebounceEventResult, Debouncer, RecommendedCache, new_debouncer,
+    notify::{RecommendedWatcher, RecursiveMode},
+};
+use resvg::tiny_skia::Pixmap;
+use resvg::usvg;
+
+use crate::input::{self, Input, Source};
+use crate::render;
+use crate::select::Shown;
+use crate::template::Defines;
+use crate::watch::{self, Trigger};
+
+/// Called from a background thread with the watched path and, if the
+/// changed document doesn't load, why; `NULL` if it does
+pub type Callback = extern "C" fn(user: *mut c_void, path: *const c_char, error: *const c_char);
+
+/// A file being watched, from [`svgtail_watch`]
+pub struct Watch {
+    _debouncer: Debouncer<RecommendedWatcher, RecommendedCache>,
+    /// Taken on unwatching, so no callback can follow
+    user: Arc<Mutex<Option<User>>>,
+}
+
+/// Where the caller's `user` pointer is passed through to the callback
+struct User(*mut c_void);
+
+// SAFETY: the caller promises, as with any C callback API, that `user` may
+// be used from the callback's thread.
+unsafe impl Send for User {}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Options shared by every call, with system fonts loaded once
+fn options() -> &'static usvg::Options<'static> {
+    static OPTIONS: OnceLock<usvg::Options<'static>> = OnceLock::new();
+    OPTIONS.get_or_init(|| {
+        #[cfg_attr(not(feature = "text"), allow(unused_mut))]
+        let mut opts = usvg::Options::default();
+        #[cfg(feature = "text")]
+        opts.fontdb_mut().load_system_fonts();
+        opts
+    })
+}
+
+fn set_error(e: impl ToString) {
+    let message = CString::new(e.to_string().replace('\0', " ")).expect("no NUL bytes");
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// # Safety
+/// `path` must be a NUL-terminated string.
+unsafe fn path_arg(path: *const c_char) -> Result<PathBuf, String> {
+    if path.is_null() {
+        return Err("path is NULL".into());
+    }
+    let path = unsafe { CStr::from_ptr(path) }
+        .to_str()
+        .map_err(|_| "path is not UTF-8")?;
+    std::path::absolute(path).map_err(|e| format!("{path}: {e}"))
+}
+
+/// Render the document at `path` into `out`, `width * height * 4` bytes of
+/// RGBA, scaled to fit and centred with transparency around it. Returns 0,
+/// or -1 with the reason in [`svgtail_last_error`].
+///
+/// # Safety
+/// `path` must be a NUL-terminated string and `out` must point to
+/// `width * height * 4` writable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn svgtail_render(
+    path: *const c_char,
+    width: u32,
+    height: u32,
+    out: *mut u8,
+) -> c_int {
+    let result = unsafe { path_arg(path) }.and_then(|path| render(&path, width, height));
+    match result {
+        Ok(pixmap) if !out.is_null() => {
+            let out = unsafe { std::slice::from_raw_parts_mut(out, pixmap.data().len()) };
+            render::to_rgba(&pixmap, out);
+            0
+        }
+        Ok(_) => {
+            set_error("out is NULL");
+            -1
+        }
+        Err(e) => {
+            set_error(e);
+            -1
+        }
+    }
+}
+
+fn render(path: &Path, width: u32, height: u32) -> Result<Pixmap, String> {
+    let tree = Input::new(Source::File(path.to_path_buf()))
+        .load(options(), &Defines::default(), None)
+        .map_err(|e| format!("{}: {e}", path.display()))?;
+    render::fitted(&tree, width, height).ok_or_else(|| "width and height must be non-zero".into())
+}
+
+/// Why the last call on this thread failed, or `NULL`. The string stays
+/// valid until the next failing call on the thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn svgtail_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |e| e.as_ptr())
+    })
+}
+
+/// Call `callback` whenever the document at `path` changes, as the viewer
+/// would reload it: atomic saves and writes through a symlink are seen, and
+/// writes that leave the bytes as they were are ignored. Returns `NULL` on
+/// failure, with the reason in [`svgtail_last_error`].
+///
+/// # Safety
+/// `path` must be a NUL-terminated string, and `user` usable from the
+/// callback's thread until [`svgtail_unwatch`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn svgtail_watch(
+    path: *const c_char,
+    callback: Callback,
+    user: *mut c_void,
+) -> *mut Watch {
+    let result = unsafe { path_arg(path) }.and_then(|path| watch(path, callback, User(user)));
+    match result {
+        Ok(watch) => Box::into_raw(Box::new(watch)),
+        Err(e) => {
+            set_error(e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+fn watch(path: PathBuf, callback: Callback, user: User) -> Result<Watch, String> {
+    let (tx, rx) = mpsc::channel::<DebounceEventResult>();
+    let mut debouncer = new_debouncer(Duration::from_millis(200), None, move |res| {
+        let _ = tx.send(res);
+    })
+    .map_err(|e| e.to_string())?;
+    debouncer
+        .watch(&path, RecursiveMode::NonRecursive)
+        .map_err(|e| format!("{}: {e}", path.display()))?;
+    if let Some(target) = watch::link_target(&path) {
+        // As in the viewer: the target's writes and the link being repointed
+        let dir = path.parent().unwrap_or(Path::new("/"));
+        for watched in [target.as_path(), dir] {
+            debouncer
+                .watch(watched, RecursiveMode::NonRecursive)
+                .map_err(|e| format!("{}: {e}", watched.display()))?;
+        }
+    }
+
+    let source = Source::File(path.clone());
+    let mut hash = input::load_changed(
+        &source,
+        None,
+        options(),
+        &Defines::default(),
+        &Shown::default(),
+        None,
+        None,
+    )
+    .ok()
+    .flatten()
+    .map(|parsed| parsed.hash);
+    let c_path = CString::new(path.to_string_lossy().into_owned()).map_err(|e| e.to_string())?;
+    let user = Arc::new(Mutex::new(Some(user)));
+    let shared = Arc::clone(&user);
+    thread::spawn(move || {
+        // Ends when the debouncer, and with it the sender, is dropped.
+        for result in rx {
+            // Other files in a symlink's directory are sorted out by the
+            // hash of what the path reads as.
+            let relevant = match &result {
+                Ok(events) => events.iter().any(|e| Trigger::Any.fires(&e.kind)),
+                Err(_) => true,
+            };
+            if !relevant {
+                continue;
+            }
+            let error = match input::load_changed(
+                &source,
+                hash,
+                options(),
+                &Defines::default(),
+                &Shown::default(),
+                None,
+                None,
+            ) {
+                Ok(None) => continue,
+                Ok(Some(parsed)) => {
+                    hash = Some(parsed.hash);
+                    None
+                }
+                Err(e) => {
+                    hash = None;
+                    CString::new(e.replace('\0', " ")).ok()
+                }
+            };
+            let error = error.as_ref().map_or(std::ptr::null(), |e| e.as_ptr());
+            match &*shared.lock().unwrap() {
+                Some(user) => callback(user.0, c_path.as_ptr(), error),
+                None => break,
+            }
+        }
+    });
+    Ok(Watch {
+        _debouncer: debouncer,
+        user,
+    })
+}
+
+/// Stop watching, waiting for a callback under way to return; none follow.
+///
+/// # Safety
+/// `watch` must come from [`svgtail_watch`] and not have been unwatched, and
+/// this must not be called from the callback.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn svgtail_unwatch(watch: *mut Watch) {
+    if !watch.is_null() {
+        let watch = unsafe { Box::from_raw(watch) };
+        watch.user.lock().unwrap().take();
+    }
+}
@@ -0,0 +1,67 @@
+//! The fonts text is set in: the system's, from the directories each
+//! platform keeps them in, any given with `--font-dir`, and generic
+//! families (`serif`, `sans-serif`, `monospace`) mapped to faces the
+//! platform actually ships, rather than to the Windows core fonts usvg
+//! assumes, so a document looks alike on each teammate's machine.
+
+use std::path::PathBuf;
+
+use resvg::usvg::fontdb::Database;
+
+/// Faces to try for `serif`, `sans-serif` and `monospace`, in order
+#[cfg(target_os = "windows")]
+const GENERIC: [&[&str]; 3] = [
+    &["Times New Roman"],
+    &["Arial", "Segoe UI"],
+    &["Consolas", "Courier New"],
+];
+#[cfg(target_os = "macos")]
+const GENERIC: [&[&str]; 3] = [
+    &["Times", "Times New Roman"],
+    &["Helvetica", "Arial"],
+    &["Menlo", "Courier"],
+];
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const GENERIC: [&[&str]; 3] = [
+    &[
+        "DejaVu Serif",
+        "Liberation Serif",
+        "Noto Serif",
+        "Times New Roman",
+    ],
+    &["DejaVu Sans", "Liberation Sans", "Noto Sans", "Arial"],
+    &[
+        "DejaVu Sans Mono",
+        "Liberation Mono",
+        "Noto Sans Mono",
+        "Courier New",
+    ],
+];
+
+/// Load the system's fonts and those in `dirs` into `db`, and point the
+/// generic families at the first of the platform's usual faces it has
+pub fn load(db: &mut Database, dirs: &[PathBuf]) {
+    db.load_system_fonts();
+    for dir in dirs {
+        db.load_fonts_dir(dir);
+    }
+    let installed = |names: &[&str]| {
+        names
+            .iter()
+            .find(|name| {
+                db.faces()
+                    .any(|f| f.families.iter().any(|(n, _)| n == *name))
+            })
+            .map(|name| name.to_string())
+    };
+    let [serif, sans_serif, monospace] = GENERIC.map(installed);
+    if let Some(family) = serif {
+        db.set_serif_family(family);
+    }
+    if let Some(family) = sans_serif {
+        db.set_sans_serif_family(family);
+    }
+    if let Some(family) = monospace {
+        db.set_monospace_family(family);
+    }
+}
@@ -0,0 +1,151 @@
+//! Programs that turn a watched source file into SVG, such as Graphviz's
+//! `dot`, Typst, the Mermaid CLI or any given with `--cmd`, run afresh on
+//! every change
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A command line run to produce SVG, in which `{input}` stands for the
+/// watched file and `{output}`, if present, for a file the program writes
+/// the SVG to. Without `{output}` the SVG is read from its standard output,
+/// and without `{input}` the file is given on its standard input.
+#[derive(Clone, Debug)]
+pub struct Generator {
+    program: String,
+    args: Vec<String>,
+}
+
+impl Generator {
+    /// The program and arguments in `line`, split at spaces except within
+    /// single or double quotes
+    pub fn command(line: &str) -> Result<Self, String> {
+        let mut words = Vec::new();
+        let mut word: Option<String> = None;
+        let mut quote = None;
+        for c in line.chars() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), c) => word.get_or_insert_default().push(c),
+                (None, '\'' | '"') => {
+                    quote = Some(c);
+                    word.get_or_insert_default();
+                }
+                (None, c) if c.is_whitespace() => words.extend(word.take()),
+                (None, c) => word.get_or_insert_default().push(c),
+            }
+        }
+        if let Some(q) = quote {
+            return Err(format!("unclosed {q} in '{line}'"));
+        }
+        words.extend(word);
+        let mut words = words.into_iter();
+        let program = words.next().ok_or("the command is empty")?;
+        Ok(Self {
+            program,
+            args: words.collect(),
+        })
+    }
+
+    /// Graphviz, laying graphs out with `layout` (such as `neato`) rather
+    /// than `dot` if given
+    pub fn dot(layout: Option<&str>) -> Self {
+        let mut args = vec!["-Tsvg".to_string()];
+        if let Some(layout) = layout {
+            args.push(format!("-K{layout}"));
+        }
+        args.push("{input}".to_string());
+        Self {
+            program: "dot".to_string(),
+            args,
+        }
+    }
+
+    /// Typst, rendering the document's first page
+    pub fn typst() -> Self {
+        Self {
+            program: "typst".to_string(),
+            args: [
+                "compile", "--format", "svg", "--pages", "1", "{input}", "{output}",
+            ]
+            .map(String::from)
+            .to_vec(),
+        }
+    }
+
+    /// The Mermaid CLI, `mmdc`
+    pub fn mermaid() -> Self {
+        Self {
+            program: "mmdc".to_string(),
+            args: ["--quiet", "-i", "{input}", "-o", "{output}"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+
+    /// Run the program on `input`, returning the SVG it produced or, if it
+    /// failed, what it said on standard error
+    pub fn run(&self, input: &Path) -> Result<Vec<u8>, String> {
+        let output = self
+            .args
+            .iter()
+            .any(|arg| arg.contains("{output}"))
+            .then(output_path);
+        let fill = |arg: &String| {
+            let arg = arg.replace("{input}", &input.to_string_lossy());
+            match &output {
+                Some(output) => arg.replace("{output}", &output.to_string_lossy()),
+                None => arg,
+            }
+        };
+        let stdin = match self.args.iter().any(|arg| arg.contains("{input}")) {
+            true => Stdio::null(),
+            false => fs::File::open(input)
+                .map_err(|e| format!("{}: {e}", input.display()))?
+                .into(),
+        };
+        let mut command = Command::new(&self.program);
+        command
+            .args(self.args.iter().map(fill))
+            .stdin(stdin)
+            .stdout(match output {
+                Some(_) => Stdio::null(),
+                None => Stdio::piped(),
+            });
+        // Relative paths in the source resolve as they would from a shell
+        // in its directory.
+        if let Some(dir) = input.parent() {
+            command.current_dir(dir);
+        }
+        let result = command.output().map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => format!("{} isn't installed, or not on PATH", self.program),
+            _ => format!("{}: {e}", self.program),
+        });
+        let svg = result.and_then(|result| {
+            if !result.status.success() {
+                let stderr = String::from_utf8_lossy(&result.stderr);
+                return Err(match stderr.trim() {
+                    "" => format!("{} {}", self.program, result.status),
+                    stderr => stderr.to_string(),
+                });
+            }
+            match &output {
+                Some(output) => fs::read(output).map_err(|e| format!("{}: {e}", output.display())),
+                None => Ok(result.stdout),
+            }
+        });
+        if let Some(output) = &output {
+            let _ = fs::remove_file(output);
+        }
+        svg
+    }
+}
+
+/// A fresh file for a program to write its SVG to
+fn output_path() -> PathBuf {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("svgtail-{}-{n}.svg", std::process::id()))
+}
@@ -0,0 +1,48 @@
+//! Reading files as of a git revision, via plumbing commands
+
+use std::path::Path;
+use std::process::Command;
+
+fn git(dir: &Path, args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| format!("git: {e}"))?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+fn split(path: &Path) -> Result<(&Path, String), String> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("{} is not a file", path.display()))?;
+    Ok((dir, name.to_string_lossy().into_owned()))
+}
+
+/// Check that `rev` names a commit in the repository containing `path`
+pub fn verify(path: &Path, rev: &str) -> Result<(), String> {
+    let (dir, _) = split(path)?;
+    git(
+        dir,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{rev}^{{commit}}"),
+        ],
+    )
+    .map(|_| ())
+    .map_err(|_| format!("not a git revision: {rev}"))
+}
+
+/// Contents of `path` at revision `rev`
+pub fn show(path: &Path, rev: &str) -> Result<Vec<u8>, String> {
+    let (dir, name) = split(path)?;
+    git(dir, &["cat-file", "blob", &format!("{rev}:./{name}")])
+}
@@ -0,0 +1,223 @@
+//! Presenting frames through OpenGL instead of minifb's software blit: each
+//! frame is uploaded as a texture and drawn over the window, so the GPU does
+//! any scaling to the window's size and buffer swaps can wait for vsync.
+
+use std::num::NonZeroU32;
+
+use glow::HasContext;
+use glutin::config::ConfigTemplateBuilder;
+use glutin::context::{
+    ContextApi, ContextAttributesBuilder, NotCurrentGlContext, PossiblyCurrentContext, Version,
+};
+use glutin::display::{Display, DisplayApiPreference, GlDisplay};
+use glutin::surface::{GlSurface, Surface, SurfaceAttributesBuilder, SwapInterval, WindowSurface};
+use minifb::Window;
+use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+
+/// Positions a unit square over the part of the window the frame covers
+const VERTEX: &str = "
+attribute vec2 corner;
+uniform vec2 extent;
+varying vec2 uv;
+void main() {
+    uv = vec2(corner.x, 1.0 - corner.y);
+    gl_Position = vec4((corner * 2.0 - 1.0) * extent, 0.0, 1.0);
+}
+";
+
+/// Frames are uploaded as minifb's `0RGB` words, which in little-endian
+/// bytes read as BGR.
+const FRAGMENT: &str = "
+varying vec2 uv;
+uniform sampler2D frame;
+void main() {
+    gl_FragColor = vec4(texture2D(frame, uv).bgr, 1.0);
+}
+";
+
+pub struct Gl {
+    gl: glow::Context,
+    surface: Surface<WindowSurface>,
+    context: PossiblyCurrentContext,
+    program: glow::Program,
+    quad: glow::Buffer,
+    texture: glow::Texture,
+    /// What the surface was last sized to
+    size: (u32, u32),
+}
+
+impl Gl {
+    /// Take over presenting to `window`, with buffer swaps waiting for
+    /// vertical sync if `vsync`
+    pub fn new(window: &Window, vsync: bool) -> Result<Self, String> {
+        let display = window.display_handle().map_err(|e| e.to_string())?;
+        let handle = window.window_handle().map_err(|e| e.to_string())?;
+        let (display, handle) = (display.as_raw(), handle.as_raw());
+
+        #[cfg(target_os = "macos")]
+        let preference = DisplayApiPreference::Cgl;
+        #[cfg(not(target_os = "macos"))]
+        let preference = DisplayApiPreference::Egl;
+        let display = unsafe { Display::new(display, preference) }.map_err(|e| e.to_string())?;
+        let template = ConfigTemplateBuilder::new()
+            .compatible_with_native_window(handle)
+            .build();
+        let config = unsafe { display.find_configs(template) }
+            .map_err(|e| e.to_string())?
+            .next()
+            .ok_or("no OpenGL configuration fits the window")?;
+        // OpenGL ES 2 is enough and the most widely available, but take
+        // whatever the platform offers otherwise.
+        let gles = ContextAttributesBuilder::new()
+            .with_context_api(ContextApi::Gles(Some(Version::new(2, 0))))
+            .build(Some(handle));
+        let context = unsafe { display.create_context(&config, &gles) }
+            .or_else(|_| unsafe {
+                display.create_context(
+                    &config,
+                    &ContextAttributesBuilder::new().build(Some(handle)),
+                )
+            })
+            .map_err(|e| e.to_string())?;
+
+        let (w, h) = window.get_size();
+        let size = (w.max(1) as u32, h.max(1) as u32);
+        let attrs = SurfaceAttributesBuilder::<WindowSurface>::new().build(
+            handle,
+            non_zero(size.0),
+            non_zero(size.1),
+        );
+        let surface =
+            unsafe { display.create_window_surface(&config, &attrs) }.map_err(|e| e.to_string())?;
+        let context = context.make_current(&surface).map_err(|e| e.to_string())?;
+        let interval = match vsync {
+            true => SwapInterval::Wait(non_zero(1)),
+            false => SwapInterval::DontWait,
+        };
+        if let Err(e) = surface.set_swap_interval(&context, interval) {
+            eprintln!("gl: can't set the swap interval: {e}");
+        }
+
+        let gl = unsafe {
+            glow::Context::from_loader_function_cstr(|name| display.get_proc_address(name))
+        };
+        let (program, quad, texture) = unsafe { setup(&gl)? };
+        Ok(Self {
+            gl,
+            surface,
+            context,
+            program,
+            quad,
+            texture,
+            size,
+        })
+    }
+
+    /// Show a `size` frame of `0RGB` words in a `window` sized window, scaled
+    /// to fit it and centred over `background`
+    pub fn present(
+        &mut self,
+        buffer: &[u32],
+        size: (usize, usize),
+        window: (usize, usize),
+        background: u32,
+    ) -> Result<(), String> {
+        let window = (window.0.max(1) as u32, window.1.max(1) as u32);
+        if window != self.size {
+            self.surface
+                .resize(&self.context, non_zero(window.0), non_zero(window.1));
+            self.size = window;
+        }
+        // Letterboxed rather than stretched, as a fitted view would be.
+        let (fw, fh) = (size.0 as f32, size.1 as f32);
+        let (ww, wh) = (window.0 as f32, window.1 as f32);
+        let scale = (ww / fw).min(wh / fh);
+        let bytes =
+            unsafe { std::slice::from_raw_parts(buffer.as_ptr().cast::<u8>(), buffer.len() * 4) };
+        let [_, r, g, b] = background.to_be_bytes();
+        let gl = &self.gl;
+        unsafe {
+            gl.viewport(0, 0, window.0 as i32, window.1 as i32);
+            gl.clear_color(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0);
+            gl.clear(glow::COLOR_BUFFER_BIT);
+            gl.use_program(Some(self.program));
+            gl.bind_texture(glow::TEXTURE_2D, Some(self.texture));
+            gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::RGBA as i32,
+                size.0 as i32,
+                size.1 as i32,
+                0,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelUnpackData::Slice(Some(bytes)),
+            );
+            let extent = gl.get_uniform_location(self.program, "extent");
+            gl.uniform_2_f32(extent.as_ref(), fw * scale / ww, fh * scale / wh);
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.quad));
+            gl.enable_vertex_attrib_array(0);
+            gl.vertex_attrib_pointer_f32(0, 2, glow::FLOAT, false, 0, 0);
+            gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
+        }
+        self.surface
+            .swap_buffers(&self.context)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Compile the program and create the quad and texture it draws with
+unsafe fn setup(
+    gl: &glow::Context,
+) -> Result<(glow::Program, glow::Buffer, glow::Texture), String> {
+    // GLSL ES and desktop GLSL 1.20 differ only in their headers here.
+    let header = match gl.version().is_embedded {
+        true => "#version 100\nprecision mediump float;\n",
+        false => "#version 120\n",
+    };
+    unsafe {
+        let program = gl.create_program()?;
+        for (kind, source) in [
+            (glow::VERTEX_SHADER, VERTEX),
+            (glow::FRAGMENT_SHADER, FRAGMENT),
+        ] {
+            let shader = gl.create_shader(kind)?;
+            gl.shader_source(shader, &format!("{header}{source}"));
+            gl.compile_shader(shader);
+            if !gl.get_shader_compile_status(shader) {
+                return Err(gl.get_shader_info_log(shader));
+            }
+            gl.attach_shader(program, shader);
+            gl.delete_shader(shader);
+        }
+        gl.bind_attrib_location(program, 0, "corner");
+        gl.link_program(program);
+        if !gl.get_program_link_status(program) {
+            return Err(gl.get_program_info_log(program));
+        }
+
+        let quad = gl.create_buffer()?;
+        let corners: [f32; 8] = [0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+        let bytes: Vec<u8> = corners.iter().flat_map(|c| c.to_ne_bytes()).collect();
+        gl.bind_buffer(glow::ARRAY_BUFFER, Some(quad));
+        gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, &bytes, glow::STATIC_DRAW);
+
+        let texture = gl.create_texture()?;
+        gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+        // Frames of any size, smoothly scaled; ES 2 needs clamping for sizes
+        // that aren't powers of two.
+        for (param, value) in [
+            (glow::TEXTURE_MIN_FILTER, glow::LINEAR),
+            (glow::TEXTURE_MAG_FILTER, glow::LINEAR),
+            (glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE),
+            (glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE),
+        ] {
+            gl.tex_parameter_i32(glow::TEXTURE_2D, param, value as i32);
+        }
+        Ok((program, quad, texture))
+    }
+}
+
+fn non_zero(n: u32) -> NonZeroU32 {
+    NonZeroU32::new(n.max(1)).expect("not zero")
+}
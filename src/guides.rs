@@ -0,0 +1,283 @@
+//! Rulers along the top and left of the view, and guide lines dragged out of
+//! them. Guides are positioned in document units and remembered per file
+//! under `$XDG_STATE_HOME/svgtail/guides`.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use resvg::tiny_skia::{Color, Paint, Pixmap, Rect, Transform};
+use resvg::usvg;
+
+use crate::units::Units;
+
+/// Thickness of the rulers in screen pixels
+pub const RULER: f32 = 20.0;
+/// Screen pixels between major ruler ticks, at least
+const MAJOR_SPACING: f32 = 60.0;
+/// How close in screen pixels the cursor must be to pick up a guide
+const GRAB_DISTANCE: f32 = 4.0;
+const FONT_SIZE: f32 = 10.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+    /// A line of constant y, dragged from the top ruler
+    Horizontal,
+    /// A line of constant x, dragged from the left ruler
+    Vertical,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Guide {
+    pub axis: Axis,
+    /// Position in document units
+    pub pos: f32,
+}
+
+impl Guide {
+    /// Where the guide is on screen, along its axis
+    fn screen(&self, transform: Transform) -> f32 {
+        match self.axis {
+            Axis::Horizontal => transform.ty + self.pos * transform.sy,
+            Axis::Vertical => transform.tx + self.pos * transform.sx,
+        }
+    }
+}
+
+/// The guide within grabbing distance of screen point `at`, if any
+pub fn grab(guides: &[Guide], transform: Transform, at: (f32, f32)) -> Option<usize> {
+    guides.iter().position(|g| {
+        let cursor = match g.axis {
+            Axis::Horizontal => at.1,
+            Axis::Vertical => at.0,
+        };
+        (g.screen(transform) - cursor).abs() <= GRAB_DISTANCE
+    })
+}
+
+/// Move `guide` to screen point `at`, snapped to the minor ticks of rulers
+/// in `units`
+pub fn drag(guide: &mut Guide, transform: Transform, units: &Units, at: (f32, f32)) {
+    let transform = units.scaled(transform);
+    let (screen, offset, scale) = match guide.axis {
+        Axis::Horizontal => (at.1, transform.ty, transform.sy),
+        Axis::Vertical => (at.0, transform.tx, transform.sx),
+    };
+    let minor = ticks(scale).1;
+    guide.pos = ((screen - offset) / scale / minor).round() * minor * units.size();
+}
+
+/// Whether screen point `at` is over the ruler that `axis` guides come from
+pub fn over_ruler(axis: Axis, at: (f32, f32)) -> bool {
+    match axis {
+        Axis::Horizontal => at.1 < RULER,
+        Axis::Vertical => at.0 < RULER,
+    }
+}
+
+/// Major and minor tick spacing in document units, for `scale` screen
+/// pixels per unit
+pub fn ticks(scale: f32) -> (f32, f32) {
+    let target = MAJOR_SPACING / scale;
+    let magnitude = 10f32.powf(target.log10().floor());
+    match target / magnitude {
+        m if m <= 1.0 => (magnitude, magnitude / 10.0),
+        m if m <= 2.0 => (2.0 * magnitude, magnitude / 2.0),
+        m if m <= 5.0 => (5.0 * magnitude, magnitude),
+        _ => (10.0 * magnitude, magnitude),
+    }
+}
+
+/// As many decimals as the minor tick spacing at `scale` needs
+pub fn decimals(scale: f32) -> usize {
+    (-ticks(scale).1.log10().floor()).max(0.0) as usize
+}
+
+/// Draw `guides` across the top-left `width` x `height` of `pixmap`
+pub fn draw(pixmap: &mut Pixmap, guides: &[Guide], transform: Transform, width: u32, height: u32) {
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(0x00, 0xBF, 0xFF, 0xFF);
+    paint.anti_alias = false;
+    for guide in guides {
+        let at = guide.screen(transform).round();
+        let rect = match guide.axis {
+            Axis::Horizontal => Rect::from_xywh(0.0, at, width as f32, 1.0),
+            Axis::Vertical => Rect::from_xywh(at, 0.0, 1.0, height as f32),
+        };
+        if let Some(rect) = rect {
+            pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+        }
+    }
+}
+
+/// Draw rulers along the top and left of a `width` x `height` viewport,
+/// marked and labelled in `units`
+pub fn draw_rulers(
+    pixmap: &mut Pixmap,
+    transform: Transform,
+    units: &Units,
+    width: u32,
+    height: u32,
+    opts: &usvg::Options,
+) {
+    let transform = units.scaled(transform);
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(0x22, 0x22, 0x22, 0xE0);
+    let strips = [
+        Rect::from_xywh(0.0, 0.0, width as f32, RULER),
+        Rect::from_xywh(0.0, RULER, RULER, height as f32 - RULER),
+    ];
+    for strip in strips.into_iter().flatten() {
+        pixmap.fill_rect(strip, &paint, Transform::identity(), None);
+    }
+
+    paint.set_color_rgba8(0xAA, 0xAA, 0xAA, 0xFF);
+    paint.anti_alias = false;
+    let mut labels = String::new();
+    // The top ruler measures x, the left one y.
+    let rulers = [
+        (true, transform.tx, transform.sx, width),
+        (false, transform.ty, transform.sy, height),
+    ];
+    for (top, offset, scale, len) in rulers {
+        let (major, minor) = ticks(scale);
+        let first = ((RULER - offset) / scale / minor).ceil() as i64;
+        let last = ((len as f32 - offset) / scale / minor).floor() as i64;
+        let per_major = (major / minor).round() as i64;
+        for n in first..=last {
+            let at = (offset + n as f32 * minor * scale).round();
+            let is_major = n % per_major == 0;
+            let tick = if is_major { RULER } else { RULER / 4.0 };
+            let rect = if top {
+                Rect::from_xywh(at, RULER - tick, 1.0, tick)
+            } else {
+                Rect::from_xywh(RULER - tick, at, tick, 1.0)
+            };
+            if let Some(rect) = rect {
+                pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+            }
+            if is_major {
+                let text = units.number(n as f32 * minor, decimals(scale));
+                let x = at + 3.0;
+                let y = at - 3.0;
+                let _ = if top {
+                    write!(labels, r#"<text x="{x}" y="{FONT_SIZE}">{text}</text>"#)
+                } else {
+                    // Reading bottom to top, to fit the ruler
+                    write!(
+                        labels,
+                        r#"<text transform="translate({FONT_SIZE} {y}) rotate(-90)">{text}</text>"#
+                    )
+                };
+            }
+        }
+    }
+    // One tree for all labels is much cheaper than one per label.
+    let svg = format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}">
+            <g font-family="sans-serif" font-size="{FONT_SIZE}" fill="#DDDDDD">{labels}</g>
+        </svg>"##
+    );
+    if let Ok(tree) = usvg::Tree::from_str(&svg, opts) {
+        resvg::render(&tree, Transform::identity(), &mut pixmap.as_mut());
+    }
+}
+
+/// Show `text` at screen point `at`, on a dark backing for legibility
+pub fn readout(pixmap: &mut Pixmap, at: (f32, f32), text: &str, opts: &usvg::Options) {
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(0x22, 0x22, 0x22, 0xE0);
+    let w = text.len() as f32 * 8.0 + 8.0;
+    if let Some(rect) = Rect::from_xywh(at.0, at.1, w, 20.0) {
+        pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+    }
+    crate::overlay::text(pixmap, (at.0 + 4.0, at.1 + 1.0), text, Color::WHITE, opts);
+}
+
+/// Where the guides for `doc` are kept
+fn store(doc: &Path) -> Option<PathBuf> {
+    let state = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/state")))?;
+    // FNV-1a, as std's hashers may change between releases
+    let hash = doc
+        .as_os_str()
+        .as_encoded_bytes()
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325u64, |h, &b| {
+            (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+    Some(state.join("svgtail/guides").join(format!("{hash:016x}")))
+}
+
+/// The guides saved for `doc`
+pub fn load(doc: &Path) -> Vec<Guide> {
+    let Some(text) = store(doc).and_then(|path| fs::read_to_string(path).ok()) else {
+        return Vec::new();
+    };
+    text.lines()
+        .filter_map(|line| {
+            let (axis, pos) = line.split_once(' ')?;
+            let axis = match axis {
+                "h" => Axis::Horizontal,
+                "v" => Axis::Vertical,
+                _ => return None,
+            };
+            Some(Guide {
+                axis,
+                pos: pos.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Remember `guides` for `doc`, reporting failures without stopping
+pub fn save(doc: &Path, guides: &[Guide]) {
+    let Some(path) = store(doc) else {
+        return;
+    };
+    let result = if guides.is_empty() {
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    } else {
+        // The path is only a hash, so record which file the guides belong to.
+        let mut text = format!("# {}\n", doc.display());
+        for guide in guides {
+            let axis = match guide.axis {
+                Axis::Horizontal => 'h',
+                Axis::Vertical => 'v',
+            };
+            let _ = writeln!(text, "{axis} {}", guide.pos);
+        }
+        path.parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(&path, text))
+    };
+    if let Err(e) = result {
+        eprintln!("saving guides to {}: {e}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_step_by_one_two_and_five() {
+        // Major ticks at least 60 pixels apart
+        assert_eq!(ticks(1.0), (100.0, 10.0));
+        assert_eq!(ticks(2.0), (50.0, 10.0));
+        assert_eq!(ticks(30.0), (2.0, 0.5));
+        assert_eq!(ticks(60.0), (1.0, 0.1));
+        assert_eq!(ticks(0.5), (200.0, 50.0));
+    }
+
+    #[test]
+    fn decimals_resolve_the_minor_ticks() {
+        assert_eq!(decimals(1.0), 0);
+        assert_eq!(decimals(60.0), 1);
+        assert_eq!(decimals(600.0), 2);
+    }
+}
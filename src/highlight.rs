@@ -0,0 +1,146 @@
+//! Emphasising elements by their id, on top of the normal render and again
+//! in each version of the document as it reloads. A rule such as
+//! `id~=edge_.*:red` outlines in red every element whose whole id matches
+//! the regular expression `edge_.*`, and `id=n1:#00f` the one with id `n1`.
+//! Ending it in `:tint` shades the elements instead.
+
+use std::str::FromStr;
+
+use regex_automata::meta::Regex;
+use resvg::tiny_skia::{Color, Paint, Pixmap, Rect, Transform};
+use resvg::usvg;
+
+use crate::overlay::{self, Shape};
+
+/// Opacity of the shading over tinted elements
+const TINT_ALPHA: f32 = 0.35;
+
+#[derive(Clone, Debug)]
+pub struct Rule {
+    ids: Ids,
+    color: Color,
+    style: Style,
+}
+
+#[derive(Clone, Debug)]
+enum Ids {
+    Exactly(String),
+    Matching(Regex),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Style {
+    Outline,
+    Tint,
+}
+
+impl Rule {
+    fn matches(&self, id: &str) -> bool {
+        match &self.ids {
+            Ids::Exactly(want) => id == want,
+            Ids::Matching(regex) => regex.is_match(id),
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    /// `id~=REGEX:COLOR` or `id=ID:COLOR`, either ending in `:tint` or
+    /// `:outline`, the default
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rest, style) = match s.rsplit_once(':') {
+            Some((rest, "tint")) => (rest, Style::Tint),
+            Some((rest, "outline")) => (rest, Style::Outline),
+            _ => (s, Style::Outline),
+        };
+        let (selector, color) = rest
+            .rsplit_once(':')
+            .ok_or_else(|| format!("'{s}': expected SELECTOR:COLOR"))?;
+        let color = svgtypes::Color::from_str(color)
+            .map_err(|_| format!("'{s}': invalid color '{color}'"))?;
+        let color = Color::from_rgba8(color.red, color.green, color.blue, color.alpha);
+        let ids = match selector.split_once('=') {
+            Some(("id~", pattern)) => Regex::new(&format!("^(?:{pattern})$"))
+                .map(Ids::Matching)
+                .map_err(|e| format!("'{s}': {e}"))?,
+            Some(("id", id)) => Ids::Exactly(id.to_string()),
+            _ => return Err(format!("'{s}': expected id=ID or id~=REGEX")),
+        };
+        Ok(Self { ids, color, style })
+    }
+}
+
+/// Parse a rule given on the command line
+pub fn parse(s: &str) -> Result<Rule, String> {
+    s.parse()
+}
+
+/// The rules in force and what they pick out of the shown document
+#[derive(Default)]
+pub struct Highlights {
+    pub rules: Vec<Rule>,
+    /// The bounds of each element matched, in document coordinates, with
+    /// the rule matching it
+    found: Vec<(Rect, usize)>,
+}
+
+impl Highlights {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self {
+            rules,
+            found: Vec::new(),
+        }
+    }
+
+    /// Find what the rules match in `tree`, a new document or the same
+    /// under new rules
+    pub fn find(&mut self, tree: &usvg::Tree) {
+        self.found.clear();
+        if !self.rules.is_empty() {
+            collect(tree.root(), &self.rules, &mut self.found);
+        }
+    }
+
+    /// Shade or outline what was found, mapping document coordinates
+    /// through `transform`
+    pub fn draw(&self, pixmap: &mut Pixmap, transform: Transform, opts: &usvg::Options) {
+        let mut outlines = Vec::new();
+        for &(rect, rule) in &self.found {
+            let Rule { color, style, .. } = self.rules[rule];
+            match style {
+                Style::Outline => outlines.push(Shape::Rect {
+                    x: rect.x(),
+                    y: rect.y(),
+                    w: rect.width(),
+                    h: rect.height(),
+                    color,
+                }),
+                Style::Tint => {
+                    let mut paint = Paint::default();
+                    let mut color = color;
+                    color.apply_opacity(TINT_ALPHA);
+                    paint.set_color(color);
+                    pixmap.fill_rect(rect, &paint, transform, None);
+                }
+            }
+        }
+        overlay::draw(pixmap, &outlines, transform, opts);
+    }
+}
+
+/// The elements under `group` that any of `rules` matches, with the first
+/// rule that does
+fn collect(group: &usvg::Group, rules: &[Rule], found: &mut Vec<(Rect, usize)>) {
+    for node in group.children() {
+        let id = node.id();
+        if !id.is_empty()
+            && let Some(rule) = rules.iter().position(|r| r.matches(id))
+        {
+            found.push((node.abs_stroke_bounding_box(), rule));
+        }
+        if let usvg::Node::Group(g) = node {
+            collect(g, rules, found);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::path::Path;
+
+use resvg::{
+    tiny_skia::{Pixmap, Transform},
+    usvg,
+};
+
+use crate::action::Action;
+
+/// Summary of a loaded document passed to hooks
+pub struct DocInfo<'a> {
+    pub path: &'a Path,
+    pub width: f32,
+    pub height: f32,
+    pub has_text: bool,
+}
+
+impl<'a> DocInfo<'a> {
+    pub fn new(path: &'a Path, tree: &usvg::Tree) -> Self {
+        Self {
+            path,
+            width: tree.size().width(),
+            height: tree.size().height(),
+            has_text: tree.has_text_nodes(),
+        }
+    }
+}
+
+/// Extension point for user code reacting to viewer events
+pub trait Hooks {
+    /// Called the first time a document is successfully loaded
+    fn on_load(&mut self, _info: &DocInfo) {}
+
+    /// Called after every subsequent successful reload
+    fn on_reload(&mut self, _info: &DocInfo) {}
+
+    /// Called once per key press (without repeat), with minifb's key name
+    fn on_key(&mut self, _key: &str) {}
+
+    /// Called with each rendered frame (premultiplied RGBA) before it is presented.
+    /// `transform` maps document coordinates to frame pixels.
+    fn on_frame(&mut self, _pixmap: &mut Pixmap, _transform: Transform) {}
+
+    /// Drain the actions requested since the last call
+    fn take_actions(&mut self) -> Vec<Action> {
+        Vec::new()
+    }
+}
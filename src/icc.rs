@@ -0,0 +1,146 @@
+//! Colour management. Documents are rendered in sRGB; with a display profile
+//! the window contents are converted into the monitor's colour space, and
+//! screenshots are converted the same way and tagged with the profile.
+
+use std::fmt;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use qcms::{DataType, Intent, Transform};
+use rayon::prelude::*;
+use resvg::tiny_skia::Pixmap;
+
+/// Pixels converted per parallel job
+const JOB: usize = 1 << 16;
+
+/// An RGB output profile, with transforms from sRGB into it
+pub struct Profile {
+    /// The profile as given, embedded in exported PNGs
+    data: Vec<u8>,
+    /// For minifb's `0RGB` buffer, whose little-endian bytes are BGRA
+    bgra: Transform,
+    /// For unpremultiplied RGBA pixels being exported
+    rgba: Transform,
+}
+
+impl Profile {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        Self::new(std::fs::read(path).map_err(|e| e.to_string())?)
+    }
+
+    pub fn new(data: Vec<u8>) -> Result<Self, String> {
+        let mut output =
+            qcms::Profile::new_from_slice(&data, false).ok_or("not a valid ICC profile")?;
+        output.precache_output_transform();
+        let srgb = qcms::Profile::new_sRGB();
+        let transform = |ty| {
+            Transform::new(&srgb, &output, ty, Intent::default())
+                .ok_or("only RGB display profiles are supported")
+        };
+        Ok(Self {
+            bgra: transform(DataType::BGRA8)?,
+            rgba: transform(DataType::RGBA8)?,
+            data,
+        })
+    }
+
+    /// The profile the desktop publishes for the default screen, if any
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "redox"))))]
+    pub fn display() -> Option<Self> {
+        use std::{ffi::c_int, ptr, slice};
+        use x11_dl::xlib;
+
+        /// Longest profile read from the root window, in bytes
+        const MAX_LEN: usize = 64 << 20;
+
+        let xlib = xlib::Xlib::open().ok()?;
+        let data = unsafe {
+            let display = (xlib.XOpenDisplay)(ptr::null());
+            if display.is_null() {
+                return None;
+            }
+            // Set by colord and most colour managers, per the X color
+            // management spec ("_ICC_PROFILE_n" for further screens).
+            let atom = (xlib.XInternAtom)(display, c"_ICC_PROFILE".as_ptr(), xlib::True);
+            let mut data = None;
+            if atom != 0 {
+                let (mut ty, mut format, mut len, mut after) = (0, 0, 0, 0);
+                let mut prop = ptr::null_mut();
+                let status = (xlib.XGetWindowProperty)(
+                    display,
+                    (xlib.XDefaultRootWindow)(display),
+                    atom,
+                    0,
+                    (MAX_LEN / 4) as _,
+                    xlib::False,
+                    xlib::AnyPropertyType as _,
+                    &mut ty,
+                    &mut format,
+                    &mut len,
+                    &mut after,
+                    &mut prop,
+                );
+                if status == xlib::Success as c_int && !prop.is_null() {
+                    if format == 8 && len > 0 {
+                        data = Some(slice::from_raw_parts(prop, len as usize).to_vec());
+                    }
+                    (xlib.XFree)(prop.cast());
+                }
+            }
+            (xlib.XCloseDisplay)(display);
+            data?
+        };
+        Self::new(data)
+            .map_err(|e| eprintln!("ignoring display colour profile: {e}"))
+            .ok()
+    }
+
+    #[cfg(not(all(unix, not(any(target_os = "macos", target_os = "redox")))))]
+    pub fn display() -> Option<Self> {
+        None
+    }
+
+    /// Convert a minifb `0RGB` buffer from sRGB into this profile
+    pub fn apply(&self, buffer: &mut [u32]) {
+        buffer.par_chunks_mut(JOB).for_each(|pixels| {
+            let mut bytes: Vec<u8> = pixels.iter().flat_map(|px| px.to_le_bytes()).collect();
+            self.bgra.apply(&mut bytes);
+            for (px, b) in pixels.iter_mut().zip(bytes.chunks_exact(4)) {
+                *px = u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+            }
+        });
+    }
+
+    /// Save `pixmap` as a PNG in this colour space, with the profile embedded
+    pub fn save_png(&self, pixmap: &Pixmap, path: &Path) -> Result<(), String> {
+        let mut data: Vec<u8> = pixmap
+            .pixels()
+            .iter()
+            .flat_map(|px| {
+                let c = px.demultiply();
+                [c.red(), c.green(), c.blue(), c.alpha()]
+            })
+            .collect();
+        self.rgba.apply(&mut data);
+
+        let mut info = png::Info::with_size(pixmap.width(), pixmap.height());
+        info.color_type = png::ColorType::Rgba;
+        info.bit_depth = png::BitDepth::Eight;
+        info.icc_profile = Some(self.data.as_slice().into());
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        let mut writer = png::Encoder::with_info(BufWriter::new(file), info)
+            .and_then(|encoder| encoder.write_header())
+            .map_err(|e| e.to_string())?;
+        writer.write_image_data(&data).map_err(|e| e.to_string())?;
+        writer.finish().map_err(|e| e.to_string())
+    }
+}
+
+impl fmt::Debug for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Profile")
+            .field("len", &self.data.len())
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,474 @@
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
+
+use resvg::tiny_skia::Rect;
+use resvg::usvg::{self, roxmltree};
+
+use crate::annotate::{self, Mark};
+use crate::compose;
+use crate::diff::{self, Damage, Leaf};
+use crate::generate::Generator;
+use crate::git;
+use crate::guides::{self, Guide};
+use crate::meta::{self, Meta};
+use crate::render::Frame;
+use crate::resource::{self, ResourceLoader};
+use crate::select::Shown;
+use crate::state::State;
+use crate::stream::Stream;
+#[cfg(feature = "data")]
+use crate::template;
+use crate::template::Defines;
+use crate::untrusted::Limits;
+
+/// Where a document's bytes come from
+#[derive(Clone, Debug)]
+pub enum Source {
+    File(PathBuf),
+    /// A file as of a git revision
+    Git {
+        path: PathBuf,
+        rev: String,
+    },
+    /// SVG produced from a file by a program
+    Generated {
+        path: PathBuf,
+        generator: Generator,
+    },
+    /// A template filled in with a data file's contents
+    #[cfg(feature = "data")]
+    Template {
+        path: PathBuf,
+        data: PathBuf,
+    },
+    /// The latest of the documents piped to standard input
+    Stdin(Arc<Stream>),
+}
+
+impl Source {
+    pub fn path(&self) -> &Path {
+        match self {
+            Source::File(path) | Source::Git { path, .. } | Source::Generated { path, .. } => path,
+            #[cfg(feature = "data")]
+            Source::Template { path, .. } => path,
+            Source::Stdin(_) => Path::new("-"),
+        }
+    }
+
+    /// The file to watch for changes, if any
+    pub fn watched(&self) -> Option<&Path> {
+        match self {
+            Source::File(path) | Source::Generated { path, .. } => Some(path),
+            Source::Git { .. } | Source::Stdin(_) => None,
+            #[cfg(feature = "data")]
+            Source::Template { path, .. } => Some(path),
+        }
+    }
+
+    /// Short description for the window title
+    pub fn label(&self) -> String {
+        let name = self
+            .path()
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        match self {
+            Source::File(_) | Source::Generated { .. } => name.into_owned(),
+            Source::Git { rev, .. } => format!("{name} @ {rev}"),
+            #[cfg(feature = "data")]
+            Source::Template { data, .. } => {
+                let data = data.file_name().unwrap_or_default().to_string_lossy();
+                format!("{name} + {data}")
+            }
+            Source::Stdin(_) => "stdin".to_string(),
+        }
+    }
+
+    pub fn read(&self) -> Result<Vec<u8>, String> {
+        match self {
+            Source::File(path) => match read_file(path) {
+                // Saves that truncate first leave the file empty for a moment.
+                Ok(data) if data.is_empty() => Err("the file is empty".to_string()),
+                Ok(data) => Ok(data),
+                Err(e) => Err(read_error(e)),
+            },
+            Source::Git { path, rev } => git::show(path, rev),
+            Source::Generated { path, generator } => generator.run(path),
+            #[cfg(feature = "data")]
+            Source::Template { path, data } => template::render(path, data),
+            Source::Stdin(stream) => match stream.latest() {
+                Some(data) => Ok(data.to_vec()),
+                None => Err("waiting for a document on standard input".to_string()),
+            },
+        }
+    }
+
+    /// Whether a program or template produces the document, so a failure is
+    /// worth showing rather than likely a file read half-written
+    pub fn generated(&self) -> bool {
+        match self {
+            Source::File(_) | Source::Git { .. } => false,
+            Source::Generated { .. } | Source::Stdin(_) => true,
+            #[cfg(feature = "data")]
+            Source::Template { .. } => true,
+        }
+    }
+
+    /// Size of the source in bytes, if cheaply known
+    pub fn size(&self) -> Option<u64> {
+        match self {
+            Source::File(path) => fs::metadata(path).ok().map(|m| m.len()),
+            Source::Git { .. } | Source::Generated { .. } | Source::Stdin(_) => None,
+            #[cfg(feature = "data")]
+            Source::Template { .. } => None,
+        }
+    }
+}
+
+/// How much of a file is read at a time
+const CHUNK: u64 = 1 << 20;
+
+/// Why a file couldn't be read, saying so plainly when another program
+/// holds it locked, as Windows apps do while they save
+fn read_error(e: std::io::Error) -> String {
+    // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+    if cfg!(windows) && matches!(e.raw_os_error(), Some(32 | 33)) {
+        return "the file is locked by the program writing it".to_string();
+    }
+    e.to_string()
+}
+
+/// The file at `path`, read a chunk at a time straight into a buffer sized
+/// for it up front, so a large one is neither copied nor grown as it is read.
+/// Reading a copy rather than mapping the file keeps a writer truncating it
+/// meanwhile from faulting the read.
+fn read_file(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut data = Vec::with_capacity(usize::try_from(len).unwrap_or(0));
+    while (&mut file).take(CHUNK).read_to_end(&mut data)? > 0 {}
+    Ok(data)
+}
+
+/// `data` as text: UTF-8, with or without a byte order mark, or UTF-16 as
+/// some Windows tools write it, told by its byte order mark or by the
+/// `<` it starts with, or Latin-1 where the XML declaration says so
+fn decode(data: &[u8]) -> Result<Cow<'_, str>, String> {
+    let utf16 = |data: &[u8], unit: fn([u8; 2]) -> u16| {
+        let units = data.chunks_exact(2).map(|c| unit([c[0], c[1]]));
+        char::decode_utf16(units)
+            .collect::<Result<String, _>>()
+            .map(Cow::Owned)
+            .map_err(|_| "the document isn't valid UTF-16".to_string())
+    };
+    match data {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => std::str::from_utf8(rest)
+            .map(Cow::Borrowed)
+            .map_err(|_| usvg::Error::NotAnUtf8Str.to_string()),
+        [0xFF, 0xFE, 0, 0, ..] | [0, 0, 0xFE, 0xFF, ..] => Err(unsupported("UTF-32")),
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        [b'<', 0, ..] => utf16(data, u16::from_le_bytes),
+        [0, b'<', ..] => utf16(data, u16::from_be_bytes),
+        _ => match std::str::from_utf8(data) {
+            Ok(text) => Ok(Cow::Borrowed(text)),
+            Err(_) => match declared_encoding(data) {
+                Some(name) if LATIN_1.contains(&name.to_ascii_lowercase().as_str()) => {
+                    Ok(Cow::Owned(data.iter().map(|&b| char::from(b)).collect()))
+                }
+                Some(name) => Err(unsupported(&name)),
+                None => Err(usvg::Error::NotAnUtf8Str.to_string()),
+            },
+        },
+    }
+}
+
+fn unsupported(encoding: &str) -> String {
+    format!("the document is encoded as {encoding}, which can't be read; save it as UTF-8")
+}
+
+/// Names of the encoding whose bytes are the first 256 code points
+const LATIN_1: &[&str] = &["iso-8859-1", "latin1", "l1", "iso_8859-1"];
+
+/// The encoding the XML declaration at the start of `data` names, if any
+fn declared_encoding(data: &[u8]) -> Option<String> {
+    let head = data.strip_prefix(b"<?xml")?;
+    let end = head.iter().position(|&b| b == b'>')?;
+    let head = std::str::from_utf8(&head[..end]).ok()?;
+    let (_, rest) = head.split_once("encoding")?;
+    let rest = rest.trim_start().strip_prefix('=')?.trim_start();
+    let quote = rest.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+    let name = rest[1..].split(quote).next()?;
+    Some(name.to_string())
+}
+
+/// Parse SVG or gzipped SVG `data` as [`usvg::Tree::from_data`] does, also
+/// returning where the root `viewBox` lands on the page (the document's
+/// width/height, in its own units), the metadata usvg leaves out and the
+/// files in `dir`, where it lives, it draws from.
+/// Stylesheets it links to are read through `resources`, if given,
+/// elements not `shown` are styled away, and documents past `limits`, if
+/// given, are refused.
+fn parse(
+    data: &[u8],
+    dir: &Path,
+    opts: &usvg::Options,
+    shown: &Shown,
+    resources: Option<&dyn ResourceLoader>,
+    limits: Option<&Limits>,
+) -> Result<(usvg::Tree, Option<Rect>, Meta, Vec<PathBuf>), String> {
+    let unzipped;
+    let data = if data.starts_with(&[0x1f, 0x8b]) {
+        unzipped = usvg::decompress_svgz(data).map_err(|e| e.to_string())?;
+        &unzipped[..]
+    } else {
+        data
+    };
+    let decoded = decode(data)?;
+    let text = &*decoded;
+    let xml_opts = match limits {
+        Some(limits) => limits.xml_options(),
+        None => roxmltree::ParsingOptions {
+            allow_dtd: true,
+            ..Default::default()
+        },
+    };
+    let parse_xml = |text| {
+        roxmltree::Document::parse_with_options(text, xml_opts).map_err(|e| {
+            limits
+                .and_then(|limits| limits.explain(&e))
+                .unwrap_or_else(|| usvg::Error::ParsingFailed(e).to_string())
+        })
+    };
+    let (composed, styled, filtered, tagged);
+    let mut doc = parse_xml(text)?;
+    let mut text = text;
+    let mut deps = Vec::new();
+    // Other files are only read for documents trusted with the filesystem
+    if resources.is_none() && limits.is_none() {
+        let (with_parts, files) = compose::compose(text, &doc, dir);
+        deps = files;
+        if let Some(with_parts) = with_parts {
+            composed = with_parts;
+            text = &composed;
+            doc = parse_xml(text)?;
+        }
+    }
+    if let Some(resources) = resources
+        && let Some(with_css) = resource::inline_stylesheets(text, &doc, resources)
+    {
+        styled = with_css;
+        text = &styled;
+        doc = parse_xml(text)?;
+    }
+    if let Some(css) = shown.css()
+        && let Some(with_css) = resource::with_style(text, &doc, &css)
+    {
+        filtered = with_css;
+        text = &filtered;
+        doc = parse_xml(text)?;
+    }
+    if let Some(text) = meta::tag(text, &doc) {
+        tagged = text;
+        doc = parse_xml(&tagged)?;
+    }
+    if let Some(limits) = limits {
+        limits.check(&doc)?;
+    }
+    let meta = Meta::collect(&doc);
+    let tree = usvg::Tree::from_xmltree(&doc, opts).map_err(|e| e.to_string())?;
+    let root = doc.root_element();
+    let view_box = root
+        .attribute("viewBox")
+        .and_then(|v| v.parse::<svgtypes::ViewBox>().ok())
+        .and_then(|vb| {
+            let aspect = root
+                .attribute("preserveAspectRatio")
+                .and_then(|v| v.parse::<svgtypes::AspectRatio>().ok())
+                .unwrap_or_default();
+            place_view_box(vb, aspect, tree.size())
+        });
+    Ok((tree, view_box, meta, deps))
+}
+
+/// Where `view_box` is drawn within a page of `size`
+fn place_view_box(
+    view_box: svgtypes::ViewBox,
+    aspect: svgtypes::AspectRatio,
+    size: usvg::Size,
+) -> Option<Rect> {
+    use svgtypes::Align;
+
+    let (w, h) = (size.width(), size.height());
+    let (sx, sy) = (w / view_box.w as f32, h / view_box.h as f32);
+    let scale = match aspect.align {
+        Align::None => return Rect::from_xywh(0.0, 0.0, w, h),
+        _ if aspect.slice => sx.max(sy),
+        _ => sx.min(sy),
+    };
+    let (vw, vh) = (view_box.w as f32 * scale, view_box.h as f32 * scale);
+    let (x, y) = match aspect.align {
+        Align::None | Align::XMinYMin => (0.0, 0.0),
+        Align::XMidYMin => ((w - vw) / 2.0, 0.0),
+        Align::XMaxYMin => (w - vw, 0.0),
+        Align::XMinYMid => (0.0, (h - vh) / 2.0),
+        Align::XMidYMid => ((w - vw) / 2.0, (h - vh) / 2.0),
+        Align::XMaxYMid => (w - vw, (h - vh) / 2.0),
+        Align::XMinYMax => (0.0, h - vh),
+        Align::XMidYMax => ((w - vw) / 2.0, h - vh),
+        Align::XMaxYMax => (w - vw, h - vh),
+    };
+    Rect::from_xywh(x, y, vw, vh)
+}
+
+/// A freshly parsed document
+pub struct Parsed {
+    pub tree: usvg::Tree,
+    /// The root `viewBox` in page coordinates, if it has one
+    pub view_box: Option<Rect>,
+    pub meta: Meta,
+    /// Hash of the source bytes, and of what was shown of them
+    pub hash: u64,
+    pub leaves: Vec<Leaf>,
+    /// Other files the document draws from
+    pub deps: Vec<PathBuf>,
+}
+
+/// Read and parse `source`, showing what `shown` says and keeping within
+/// `limits`, or `None` if its bytes and `shown` hash to `unchanged`
+pub fn load_changed(
+    source: &Source,
+    unchanged: Option<u64>,
+    opts: &usvg::Options,
+    defines: &Defines,
+    shown: &Shown,
+    resources: Option<&dyn ResourceLoader>,
+    limits: Option<&Limits>,
+) -> Result<Option<Parsed>, String> {
+    let data = defines.apply(source.read()?)?;
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    shown.hash(&mut hasher);
+    let hash = hasher.finish();
+    if unchanged == Some(hash) {
+        return Ok(None);
+    }
+    let dir = source.path().parent().unwrap_or(Path::new(""));
+    let (tree, view_box, meta, deps) = parse(&data, dir, opts, shown, resources, limits)?;
+    let leaves = diff::leaves(&tree);
+    Ok(Some(Parsed {
+        tree,
+        view_box,
+        meta,
+        hash,
+        leaves,
+        deps,
+    }))
+}
+
+/// A background load in progress
+pub struct Loading {
+    /// Identifies the load, so results for replaced inputs can be dropped
+    pub ticket: u64,
+    pub started: Instant,
+    pub size: Option<u64>,
+}
+
+/// A render of an untrusted document on another thread
+pub struct Drawing {
+    /// Identifies the render, so results for replaced inputs can be dropped
+    pub ticket: u64,
+    pub started: Instant,
+    /// Set to skip the render if it hasn't started
+    pub cancel: Arc<AtomicBool>,
+}
+
+/// One of the documents the viewer can flip between
+pub struct Input {
+    pub source: Source,
+    pub tree: Option<usvg::Tree>,
+    /// The root `viewBox` of `tree` in page coordinates
+    pub view_box: Option<Rect>,
+    /// Titles and descriptions of elements in `tree`
+    pub meta: Meta,
+    /// When the source was last read
+    pub last_read: Option<Instant>,
+    /// When the source should next be reloaded
+    pub due: Option<Instant>,
+    /// Retries made since the last successful load or file event
+    pub retries: u32,
+    /// Hash of the bytes `tree` was parsed from, and of `shown`
+    pub hash: Option<u64>,
+    /// Why the source didn't load the last time it was read, if it didn't
+    pub error: Option<String>,
+    pub loading: Option<Loading>,
+    /// Paint-order fingerprint of `tree`
+    pub leaves: Vec<Leaf>,
+    /// The last rendering of `tree`
+    pub frame: Option<Frame>,
+    /// The rendering of `tree` under way, with `--untrusted`
+    pub drawing: Option<Drawing>,
+    /// What changed in `tree` since `frame` was drawn
+    pub damage: Damage,
+    /// How long the last full render took or, before there has been one, how
+    /// long parsing did, as a guess
+    pub render_time: Option<Duration>,
+    /// Guide lines saved for this file
+    pub guides: Vec<Guide>,
+    /// Review marks saved beside this file
+    pub marks: Vec<Mark>,
+    /// Where the file led when it was last watched, if it is a symlink
+    pub link: Option<PathBuf>,
+    /// Which of its elements are shown, kept as it reloads
+    pub shown: Shown,
+    /// Other files `tree` draws from, watched along with the source
+    pub deps: Vec<PathBuf>,
+    /// The view it was left at, for when it is switched back to; `None`
+    /// while it is shown, as the viewer's own view is then its view
+    pub view: Option<State>,
+}
+
+impl Input {
+    pub fn new(source: Source) -> Self {
+        Self {
+            guides: guides::load(source.path()),
+            marks: annotate::load(source.path()),
+            source,
+            tree: None,
+            view_box: None,
+            meta: Meta::default(),
+            last_read: None,
+            due: None,
+            retries: 0,
+            hash: None,
+            error: None,
+            loading: None,
+            leaves: Vec::new(),
+            frame: None,
+            drawing: None,
+            damage: Damage::None,
+            render_time: None,
+            link: None,
+            shown: Shown::default(),
+            deps: Vec::new(),
+            view: None,
+        }
+    }
+
+    pub fn load(
+        &self,
+        opts: &usvg::Options,
+        defines: &Defines,
+        limits: Option<&Limits>,
+    ) -> Result<usvg::Tree, String> {
+        let data = defines.apply(self.source.read()?)?;
+        let dir = self.source.path().parent().unwrap_or(Path::new(""));
+        parse(&data, dir, opts, &self.shown, None, limits).map(|(tree, ..)| tree)
+    }
+}
@@ -0,0 +1,218 @@
+//! Local control socket used by `--single-instance` and `svgtail ctl`.
+//!
+//! It speaks the same line-based commands as stdin, plus `state`. Each command line gets a
+//! single `ok [output]` or `error: ...` reply line. The first running instance owns the socket.
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use crate::action::Action;
+use crate::viewer::ActionSender;
+
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Per-user socket location: in `$XDG_RUNTIME_DIR`, or else in a directory
+/// of our own under the shared temporary directory, so no one else can
+/// stand in for the running instance
+pub fn socket_path() -> io::Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        return Ok(PathBuf::from(dir).join("svgtail.sock"));
+    }
+    // SAFETY: getuid has no preconditions and cannot fail.
+    let uid = unsafe { libc::getuid() };
+    let dir = std::env::temp_dir().join(format!("svgtail-{uid}"));
+    match fs::DirBuilder::new().mode(0o700).create(&dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e),
+    }
+    // Made by someone else first, or opened up since
+    let meta = fs::symlink_metadata(&dir)?;
+    if !meta.is_dir() || meta.uid() != uid || meta.mode() & 0o077 != 0 {
+        return Err(io::Error::other(format!(
+            "{} isn't a directory only you can use",
+            dir.display()
+        )));
+    }
+    Ok(dir.join("svgtail.sock"))
+}
+
+/// Removes the socket file when dropped
+pub struct Listening(PathBuf);
+
+impl Drop for Listening {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Send `line` to a running instance, returning its reply,
+/// or `None` if no instance is listening
+pub fn send(line: &str) -> io::Result<Option<String>> {
+    let mut stream = match UnixStream::connect(socket_path()?) {
+        Ok(stream) => stream,
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+            ) =>
+        {
+            return Ok(None);
+        }
+        Err(e) => return Err(e),
+    };
+    writeln!(stream, "{line}")?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(Some(reply.trim_end().to_string()))
+}
+
+/// Ask a running instance to open each of `paths` beside what it shows,
+/// returning `false` if there is none. One that goes away after taking
+/// some is an error, as opening them all here would show those twice.
+pub fn forward(paths: &[PathBuf]) -> io::Result<bool> {
+    for (i, path) in paths.iter().enumerate() {
+        match send(&format!("open-tab {}", path.display()))? {
+            None if i == 0 => return Ok(false),
+            None => {
+                return Err(io::Error::other(format!(
+                    "the running instance exited after opening {i} of {} files",
+                    paths.len()
+                )));
+            }
+            Some(reply) if reply == "ok" => {}
+            Some(reply) => return Err(io::Error::other(reply)),
+        }
+    }
+    Ok(true)
+}
+
+/// Accept commands on the socket in the background,
+/// unless another instance is already listening on it
+pub fn listen(tx: ActionSender) -> io::Result<Option<Listening>> {
+    let path = socket_path()?;
+    if UnixStream::connect(&path).is_ok() {
+        return Ok(None);
+    }
+    // Whoever created the socket file is gone, otherwise we would have connected to it.
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let _ = handle_connection(stream, tx);
+            });
+        }
+    });
+    Ok(Some(Listening(path)))
+}
+
+fn handle_connection(stream: UnixStream, tx: ActionSender) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match execute(&line, &tx) {
+            Ok(None) => "ok".to_string(),
+            Ok(Some(output)) => format!("ok {output}"),
+            Err(e) => format!("error: {e}"),
+        };
+        writeln!(writer, "{reply}")?;
+    }
+    Ok(())
+}
+
+fn execute(line: &str, tx: &ActionSender) -> Result<Option<String>, String> {
+    let gone = |_| "viewer has exited".to_string();
+    let timeout = |_| "viewer did not respond".to_string();
+
+    if line.trim() == "state" {
+        let (reply, rx) = mpsc::channel();
+        tx.send(Action::GetState(reply)).map_err(gone)?;
+        let s = rx.recv_timeout(REPLY_TIMEOUT).map_err(timeout)?;
+        let doc = s
+            .doc_size
+            .map_or("none".to_string(), |(w, h)| format!("{w}x{h}"));
+        return Ok(Some(format!(
+            "path={} doc={doc} window={}x{} zoom={} pan={},{} fit_scale={} auto_fit={}",
+            s.path.display(),
+            s.window_size.0,
+            s.window_size.1,
+            s.zoom,
+            s.pan.0,
+            s.pan.1,
+            s.fit_scale,
+            s.auto_fit,
+        )));
+    }
+
+    match line.parse()? {
+        Action::Screenshot(path, None) => {
+            let (reply, rx) = mpsc::channel();
+            tx.send(Action::Screenshot(path, Some(reply)))
+                .map_err(gone)?;
+            rx.recv_timeout(REPLY_TIMEOUT).map_err(timeout)??;
+        }
+        Action::ExportReview(path, None) => {
+            let (reply, rx) = mpsc::channel();
+            tx.send(Action::ExportReview(path, Some(reply)))
+                .map_err(gone)?;
+            rx.recv_timeout(REPLY_TIMEOUT).map_err(timeout)??;
+        }
+        Action::ExportCrop(path, None) => {
+            let (reply, rx) = mpsc::channel();
+            tx.send(Action::ExportCrop(path, Some(reply)))
+                .map_err(gone)?;
+            rx.recv_timeout(REPLY_TIMEOUT).map_err(timeout)??;
+        }
+        action => tx.send(action).map_err(gone)?,
+    }
+    Ok(None)
+}
+
+/// Run `svgtail ctl <words>` against the running instance
+pub fn ctl(words: &[String]) -> ! {
+    let line = match words {
+        // Paths are relative to the caller, not the viewer.
+        [cmd, path @ ..]
+            if matches!(
+                cmd.as_str(),
+                "open" | "open-tab" | "screenshot" | "export-review" | "export-crop"
+            ) && !path.is_empty() =>
+        {
+            match std::path::absolute(path.join(" ")) {
+                Ok(path) => format!("{cmd} {}", path.display()),
+                Err(e) => fail(&e.to_string()),
+            }
+        }
+        _ => words.join(" "),
+    };
+    match send(&line) {
+        Ok(None) => fail("no running svgtail instance"),
+        Ok(Some(reply)) => match reply.strip_prefix("ok") {
+            Some(output) => {
+                let output = output.trim_start();
+                if !output.is_empty() {
+                    println!("{output}");
+                }
+                std::process::exit(0)
+            }
+            None => fail(reply.strip_prefix("error: ").unwrap_or(&reply)),
+        },
+        Err(e) => fail(&e.to_string()),
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("svgtail ctl: {message}");
+    std::process::exit(1)
+}
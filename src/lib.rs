@@ -0,0 +1,79 @@
+//! The viewer behind the `svgtail` binary: loading and watching documents,
+//! rendering them and the window that shows them
+
+#[cfg(all(
+    feature = "a11y",
+    unix,
+    not(any(target_os = "macos", target_os = "redox"))
+))]
+pub mod a11y;
+pub mod access;
+pub mod action;
+pub mod annotate;
+pub mod browser;
+pub mod cache;
+#[cfg(all(unix, not(any(target_os = "macos", target_os = "redox"))))]
+pub mod clipboard;
+pub mod color;
+pub mod command;
+pub mod compare;
+pub mod compose;
+pub mod crop;
+pub mod diff;
+pub mod dither;
+#[cfg(target_os = "linux")]
+pub mod eink;
+pub mod exit;
+pub mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "text")]
+pub mod fonts;
+pub mod generate;
+pub mod git;
+#[cfg(feature = "gl")]
+pub mod gl;
+pub mod guides;
+pub mod highlight;
+pub mod hooks;
+pub mod icc;
+pub mod input;
+#[cfg(unix)]
+pub mod ipc;
+pub mod loupe;
+pub mod measure;
+pub mod meta;
+pub mod minimap;
+#[cfg(all(unix, not(any(target_os = "macos", target_os = "redox"))))]
+pub mod monitor;
+pub mod overlay;
+#[cfg(feature = "wasm")]
+pub mod plugin;
+pub mod presenter;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod render;
+pub mod resource;
+pub mod review;
+#[cfg(feature = "rpc")]
+pub mod rpc;
+#[cfg(feature = "lua")]
+pub mod script;
+pub mod search;
+pub mod select;
+#[cfg(unix)]
+pub mod shm;
+pub mod sink;
+pub mod state;
+pub mod stream;
+pub mod template;
+pub mod units;
+pub mod untrusted;
+#[cfg(target_os = "linux")]
+pub mod v4l2;
+pub mod viewer;
+#[cfg(all(unix, not(any(target_os = "macos", target_os = "redox"))))]
+pub mod visibility;
+pub mod watch;
+pub mod watcher;
+pub mod window;
@@ -0,0 +1,98 @@
+//! A magnifier following the cursor or keyboard, showing the document freshly
+//! rasterized at a higher zoom rather than enlarged pixels
+
+use resvg::tiny_skia::{
+    Color, FillRule, IntRect, Mask, Paint, PathBuilder, Pixmap, PixmapPaint, Stroke, Transform,
+};
+use resvg::usvg;
+
+use crate::render::{self, Canvas};
+
+/// Radius of the loupe in screen pixels, unless chosen on the command line
+pub const RADIUS: f32 = 90.0;
+/// Magnification the loupe starts at, unless chosen on the command line
+pub const ZOOM: f32 = 4.0;
+const MIN_ZOOM: f32 = 2.0;
+const MAX_ZOOM: f32 = 16.0;
+/// Zoom factor per scroll wheel notch
+const ZOOM_STEP: f32 = 1.25;
+
+pub struct Loupe {
+    /// Magnification relative to the view
+    pub zoom: f32,
+    /// Radius in screen pixels
+    radius: f32,
+}
+
+impl Loupe {
+    pub fn new(zoom: f32, radius: f32) -> Self {
+        Self {
+            zoom: zoom.clamp(MIN_ZOOM, MAX_ZOOM),
+            radius,
+        }
+    }
+
+    /// Zoom in for positive `delta`, out for negative, returning `true` if it changed
+    pub fn scroll(&mut self, delta: f32) -> bool {
+        let zoom = (self.zoom * ZOOM_STEP.powf(delta.signum())).clamp(MIN_ZOOM, MAX_ZOOM);
+        let changed = zoom != self.zoom;
+        self.zoom = zoom;
+        changed
+    }
+
+    /// Draw the loupe over `pixmap`, centred on `centre`. `transform` maps
+    /// `tree` into a viewport whose left edge is at `left` in `pixmap`.
+    pub fn draw(
+        &self,
+        pixmap: &mut Pixmap,
+        tree: &usvg::Tree,
+        transform: Transform,
+        canvas: &Canvas,
+        centre: (f32, f32),
+        left: f32,
+    ) {
+        let (x, y) = (centre.0 - left, centre.1);
+        let diameter = (2.0 * self.radius).ceil() as u32;
+        let Some(area) = IntRect::from_xywh(
+            (x - self.radius).floor() as i32,
+            (y - self.radius).floor() as i32,
+            diameter,
+            diameter,
+        ) else {
+            return;
+        };
+        // Scale the view about the centre.
+        let magnified = transform
+            .post_translate(-x, -y)
+            .post_scale(self.zoom, self.zoom)
+            .post_translate(x, y);
+        let mut patch = render::render(tree, area, magnified, canvas);
+
+        let (cx, cy) = (x - area.x() as f32, y - area.y() as f32);
+        let Some(circle) = PathBuilder::from_circle(cx, cy, self.radius) else {
+            return;
+        };
+        let mut mask = Mask::new(diameter, diameter).expect("loupe size is non-zero");
+        mask.fill_path(&circle, FillRule::Winding, true, Transform::identity());
+        patch.apply_mask(&mask);
+        // A dark ring inside a light one shows against any background.
+        for (width, color) in [(4.0, Color::WHITE), (2.0, Color::from_rgba8(0, 0, 0, 0xAA))] {
+            let mut paint = Paint::default();
+            paint.set_color(color);
+            let stroke = Stroke {
+                width,
+                ..Default::default()
+            };
+            patch.stroke_path(&circle, &paint, &stroke, Transform::identity(), None);
+        }
+
+        pixmap.draw_pixmap(
+            area.x() + left as i32,
+            area.y(),
+            patch.as_ref(),
+            &PixmapPaint::default(),
+            Transform::identity(),
+            None,
+        );
+    }
+}
@@ -1,311 +1,393 @@
-use std::env;
-use std::fs;
-use std::path::PathBuf;
-use std::sync::mpsc;
+mod cli;
+
+use std::io;
+use std::process::ExitCode;
+use std::sync::Arc;
 use std::time::Duration;
 
-use minifb::{Key, Window, WindowOptions};
-use notify_debouncer_full::{
-    DebounceEventResult, new_debouncer,
-    notify::{
-        RecursiveMode,
-        event::{AccessKind, AccessMode, EventKind},
-    },
-};
+use clap::Parser;
 use resvg::{tiny_skia, usvg};
 
-fn load_svg(path: &PathBuf, opts: &usvg::Options) -> Option<usvg::Tree> {
-    let data = fs::read(path).ok()?;
-    usvg::Tree::from_data(&data, opts).ok()
+#[cfg(all(
+    feature = "a11y",
+    unix,
+    not(any(target_os = "macos", target_os = "redox"))
+))]
+use svgtail::a11y;
+use svgtail::action::Action;
+use svgtail::dither::Dither;
+#[cfg(target_os = "linux")]
+use svgtail::eink;
+use svgtail::exit::{Code, Failure};
+use svgtail::export::Export;
+use svgtail::generate::Generator;
+use svgtail::input::{Input, Source};
+#[cfg(unix)]
+use svgtail::ipc;
+#[cfg(feature = "wasm")]
+use svgtail::plugin;
+use svgtail::render::{Canvas, Checkerboard};
+#[cfg(feature = "rpc")]
+use svgtail::rpc;
+#[cfg(feature = "lua")]
+use svgtail::script;
+#[cfg(unix)]
+use svgtail::shm;
+use svgtail::state::{Anchor, Horizontal, Vertical};
+use svgtail::stream::Stream;
+use svgtail::template::Defines;
+use svgtail::units::Units;
+use svgtail::untrusted::{self, Limits};
+#[cfg(target_os = "linux")]
+use svgtail::v4l2;
+use svgtail::viewer::{self, Config, Viewer};
+use svgtail::watch::{Pattern, wait_for_creation};
+use svgtail::{command, git, icc};
+
+use crate::cli::{Args, Command};
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => {
+            eprintln!("svgtail: {}", failure.message);
+            failure.code.into()
+        }
+    }
 }
 
-fn render(
-    tree: &usvg::Tree,
-    width: u32,
-    height: u32,
-    pan: (f32, f32),
-    zoom: f32,
-    fit_scale: f32,
-) -> Vec<u32> {
-    let mut pixmap = tiny_skia::Pixmap::new(width, height).unwrap();
-    pixmap.fill(tiny_skia::Color::from_rgba8(0x33, 0x33, 0x33, 0xFF));
-
-    let svg_size = tree.size();
-    let eff_scale = fit_scale * zoom;
-    let offset_x = (width as f32 - svg_size.width() * eff_scale) / 2.0 + pan.0;
-    let offset_y = (height as f32 - svg_size.height() * eff_scale) / 2.0 + pan.1;
-
-    let transform =
-        tiny_skia::Transform::from_translate(offset_x, offset_y).pre_scale(eff_scale, eff_scale);
-    resvg::render(tree, transform, &mut pixmap.as_mut());
-
-    pixmap
-        .data()
-        .chunks_exact(4)
-        .map(|px| {
-            let (r, g, b, a) = (px[0] as u32, px[1] as u32, px[2] as u32, px[3] as u32);
-            if a == 0 {
-                0x00333333
-            } else {
-                let r = (r * 255 / a).min(255);
-                let g = (g * 255 / a).min(255);
-                let b = (b * 255 / a).min(255);
-                (r << 16) | (g << 8) | b
-            }
-        })
-        .collect()
+/// Parse every input once, without opening a window
+fn check(
+    sources: &[Source],
+    opts: &usvg::Options,
+    defines: &Defines,
+    limits: Option<&Limits>,
+) -> Result<(), Failure> {
+    for source in sources {
+        let path = source.path();
+        if source.watched().is_some() && !path.exists() {
+            return Err(Failure::new(
+                Code::Missing,
+                format!("{}: no such file", path.display()),
+            ));
+        }
+        Input::new(source.clone())
+            .load(opts, defines, limits)
+            .map_err(|e| Failure::new(Code::Invalid, format!("{}: {e}", path.display())))?;
+    }
+    Ok(())
 }
 
-fn should_reload(kind: &EventKind) -> bool {
-    match kind {
-        EventKind::Access(AccessKind::Open(AccessMode::Any)) => false,
-        _ => true,
+fn run(mut args: Args) -> Result<(), Failure> {
+    match &args.command {
+        #[cfg(unix)]
+        Some(Command::Ctl { command }) => ipc::ctl(command),
+        None => {}
+    }
+    // `-` stands for documents piped in, one after another
+    let stdin = args.paths.iter().any(|p| p.as_os_str() == "-");
+    // Generators and --template already conflict with paths in clap.
+    if stdin
+        && (args.paths.len() > 1
+            || args.glob.is_some()
+            || args.git_ref.is_some()
+            || args.cmd.is_some()
+            || args.check)
+    {
+        return Err(
+            "`-` reads documents from standard input, so takes no other paths, --glob, --git-ref, --cmd or --check"
+                .into(),
+        );
+    }
+    // A directory, or a pattern the shell left alone, follows the newest SVG
+    // in it as `--glob` does
+    if let [path] = args.paths.as_slice()
+        && (path.is_dir() || Pattern::is_pattern(path))
+    {
+        if args.glob.is_some() {
+            return Err(format!("{}: give either a directory or --glob", path.display()).into());
+        }
+        let pattern = if path.is_dir() {
+            Pattern::within(path)?
+        } else {
+            Pattern::new(&path.to_string_lossy())?
+        };
+        args.glob = Some(pattern);
+        args.paths.clear();
+    }
+    let stream = Arc::new(Stream::default());
+    let mut paths = args
+        .paths
+        .iter()
+        .filter(|p| p.as_os_str() != "-")
+        .map(std::path::absolute)
+        .collect::<Result<Vec<_>, _>>()?;
+    if paths.is_empty()
+        && let Some(pattern) = &args.glob
+    {
+        paths.push(pattern.wait().map_err(|e| Failure::new(Code::Missing, e))?);
+    }
+    let also_watch = args
+        .also_watch
+        .iter()
+        .map(std::path::absolute)
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some(missing) = also_watch.iter().find(|p| !p.exists()) {
+        return Err(format!(
+            "--also-watch {}: no such file or directory",
+            missing.display()
+        )
+        .into());
     }
-}
 
-/// Wait for the watched path to be created before trying to render
-fn wait_for_creation(path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-    if path.exists() {
+    #[cfg(unix)]
+    if args.single_instance
+        && !args.check
+        && !args.headless
+        && args.cmd.is_none()
+        && !paths.is_empty()
+        && ipc::forward(&paths)?
+    {
         return Ok(());
     }
-    eprintln!(
-        "file '{}' does not exist, waiting for it to be created...",
-        path.display()
-    );
-
-    let parent = path
-        .parent()
-        .filter(|p| !p.as_os_str().is_empty())
-        .unwrap_or_else(|| std::path::Path::new("."))
-        .to_path_buf();
-
-    let (tx, rx) = mpsc::channel::<DebounceEventResult>();
-    let mut debouncer = new_debouncer(Duration::from_millis(200), None, move |res| {
-        let _ = tx.send(res);
-    })?;
 
-    debouncer.watch(&parent, RecursiveMode::NonRecursive)?;
-
-    loop {
-        match rx.recv() {
-            Ok(Ok(events)) => {
-                if events.iter().any(|e| e.paths.iter().any(|p| p == path)) && path.exists() {
-                    return Ok(());
-                }
+    let sources = match &args.git_ref {
+        _ if stdin => vec![Source::Stdin(Arc::clone(&stream))],
+        Some(rev) => {
+            if paths.len() > 1 {
+                return Err("--git-ref compares a single file against the working copy".into());
             }
-            Ok(Err(_)) => {
-                if path.exists() {
-                    return Ok(());
+            git::verify(&paths[0], rev)?;
+            vec![
+                Source::Git {
+                    path: paths[0].clone(),
+                    rev: rev.clone(),
+                },
+                Source::File(paths[0].clone()),
+            ]
+        }
+        None => {
+            if !args.check {
+                for path in &paths {
+                    wait_for_creation(path).map_err(|e| Failure::new(Code::Missing, e))?;
                 }
             }
-            Err(e) => return Err(e.into()),
+            match &args.cmd {
+                Some(generator) => paths
+                    .into_iter()
+                    .map(|path| Source::Generated {
+                        path,
+                        generator: generator.clone(),
+                    })
+                    .collect(),
+                None => paths.into_iter().map(Source::File).collect(),
+            }
         }
-    }
-}
-
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: svgtail <file.svg>");
-        std::process::exit(1);
-    }
-
-    let svg_path = std::path::absolute(&args[1])?;
-    wait_for_creation(&svg_path)?;
-
-    let mut svg_opts = usvg::Options::default();
-    svg_opts.fontdb_mut().load_system_fonts();
-
-    event_loop(svg_path, svg_opts)
-}
-
-fn event_loop(
-    svg_path: PathBuf,
-    svg_opts: usvg::Options,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut tree = load_svg(&svg_path, &svg_opts);
-    let mut width: usize = 800;
-    let mut height: usize = 600;
-
-    let mut window = Window::new(
-        "svgtail",
-        width,
-        height,
-        WindowOptions {
-            resize: true,
-            ..Default::default()
-        },
-    )
-    .map_err(|e| format!("{e:?}"))?;
-
-    window.set_target_fps(60);
-
-    let (tx, rx) = mpsc::channel::<DebounceEventResult>();
-    let _debouncer = {
-        let tx = tx.clone();
-        let mut debouncer = new_debouncer(Duration::from_millis(200), None, move |res| {
-            let _ = tx.send(res);
-        })?;
-        debouncer.watch(&svg_path, RecursiveMode::NonRecursive)?;
-        debouncer
     };
-
-    let mut state = State::new();
-
-    let mut dirty = true;
-    let mut buffer: Vec<u32> = vec![0; width * height];
-
-    while window.is_open() && !window.is_key_down(Key::Escape) {
-        // 1) Drain watcher queue; reload at most once per iteration.
-        let mut reload = false;
-        while let Ok(res) = rx.try_recv() {
-            match res {
-                Ok(events) => {
-                    for e in events {
-                        if e.paths.iter().any(|p| p == &svg_path) && should_reload(&e.kind) {
-                            reload = true;
-                        }
-                    }
-                }
-                Err(_) => reload = true,
+    let generated = match (&args.dot, &args.typst, &args.mermaid) {
+        (Some(path), ..) => Some((path, Generator::dot(args.dot_layout.as_deref()))),
+        (_, Some(path), _) => Some((path, Generator::typst())),
+        (.., Some(path)) => Some((path, Generator::mermaid())),
+        _ => None,
+    };
+    let sources = match generated {
+        Some((path, generator)) => {
+            let path = std::path::absolute(path)?;
+            if !args.check {
+                wait_for_creation(&path).map_err(|e| Failure::new(Code::Missing, e))?;
             }
+            vec![Source::Generated { path, generator }]
         }
-        if reload {
-            if let Some(new_tree) = load_svg(&svg_path, &svg_opts) {
-                tree = Some(new_tree);
-                state.reset();
-                dirty = true;
+        None => sources,
+    };
+    #[cfg(feature = "data")]
+    let (sources, also_watch) = match (&args.template, &args.data) {
+        (Some(template), Some(data)) => {
+            let (path, data) = (std::path::absolute(template)?, std::path::absolute(data)?);
+            if !args.check {
+                for path in [&path, &data] {
+                    wait_for_creation(path).map_err(|e| Failure::new(Code::Missing, e))?;
+                }
             }
+            // The template is watched as the input, and the data like
+            // `--also-watch`.
+            let also_watch = also_watch.into_iter().chain([data.clone()]).collect();
+            (vec![Source::Template { path, data }], also_watch)
         }
+        _ => (sources, also_watch),
+    };
 
-        // 2) Resize
-        let (new_w, new_h) = window.get_size();
-        if new_w != width || new_h != height {
-            width = new_w.max(1);
-            height = new_h.max(1);
-            buffer.resize(width * height, 0);
-            dirty = true;
-        }
+    let mut svg_opts = usvg::Options::default();
+    #[cfg(feature = "text")]
+    svgtail::fonts::load(svg_opts.fontdb_mut(), &args.font_dir);
+    if let Some(dpi) = args.dpi {
+        svg_opts.dpi = dpi;
+    }
+    let limits = args.untrusted.then(Limits::default);
+    if limits.is_some() {
+        untrusted::sandbox(&mut svg_opts);
+    }
 
-        // 3) Fit scale only when needed
-        if dirty {
-            if let Some(ref t) = tree {
-                state.update_fit_scale(t, width, height);
-            }
-        }
+    let defines = Defines::new(args.define.iter().cloned());
+    if args.check {
+        return check(&sources, &svg_opts, &defines, limits.as_ref());
+    }
 
-        // 4) Input
-        if state.handle_input(&mut window) {
-            dirty = true;
+    let profile = match &args.icc {
+        Some(path) => {
+            Some(icc::Profile::load(path).map_err(|e| format!("{}: {e}", path.display()))?)
         }
+        // Without a window there is no display to match.
+        None if args.headless => None,
+        None => icc::Profile::display(),
+    };
 
-        // 5) Present if dirty
-        if dirty {
-            if let Some(ref t) = tree {
-                buffer = render(
-                    t,
-                    width as u32,
-                    height as u32,
-                    state.pan,
-                    state.zoom,
-                    state.fit_scale,
-                );
-            } else {
-                buffer.fill(0x00333333);
-            }
-            dirty = false;
-
-            window
-                .update_with_buffer(&buffer, width, height)
-                .map_err(|e| format!("{e:?}"))?;
-        } else {
-            // Pump window events once (non-blocking)
-            window.update();
-        }
-    }
+    let backdrop = match &args.backdrop {
+        Some(path) => Some(Arc::new(
+            tiny_skia::Pixmap::load_png(path).map_err(|e| format!("{}: {e}", path.display()))?,
+        )),
+        None => None,
+    };
 
-    Ok(())
-}
+    let checkerboard = Checkerboard {
+        colors: args.checker_colors,
+        size: args.checker_size,
+    };
+    let config = Config {
+        title: args.title,
+        window_size: args.size,
+        fit: args.fit,
+        zoom: args.zoom,
+        max_fps: args.max_fps,
+        poll_interval: args.poll_interval,
+        idle_poll: args.idle_poll.unwrap_or(match args.power_save {
+            true => viewer::POWER_SAVE_POLL,
+            false => viewer::IDLE_POLL,
+        }),
+        quit_on_delete: args.quit_on_delete,
+        idle_exit: args.idle_exit,
+        reload_interval: args
+            .max_reload_hz
+            .map(|hz| Duration::from_secs_f64(1.0 / hz)),
+        reload_on: args.reload_on,
+        fail_on_error: args.fail_on_error,
+        limits,
+        refit_on_resize: args.refit_on_resize,
+        highlights: args.highlight,
+        zoom_to_changes: args.zoom_to_changes,
+        anchor: Anchor {
+            x: args
+                .anchor_x
+                .or(args.follow_right.then_some(Horizontal::Right)),
+            y: args
+                .anchor_y
+                .or(args.follow_bottom.then_some(Vertical::Bottom)),
+        },
+        anchor_id: args.anchor_id,
+        keep_view: args.keep_view,
+        also_watch,
+        glob: args.glob.clone(),
+        defines: Arc::new(defines),
+        exports: args
+            .output
+            .iter()
+            .map(|path| Export::exact(path))
+            .chain(args.export_on_reload.iter().cloned())
+            .collect(),
+        export_area: args.viewbox,
+        export_scale: args.scale,
+        units: Units::new(args.units, args.dpi),
+        crop_aspect: args.crop_aspect,
+        bake_annotations: args.bake_annotations,
+        #[cfg(feature = "gl")]
+        gl: args.gl,
+        profile: profile.map(Arc::new),
+        dither: args.palette.clone().map(|palette| Dither {
+            method: args.dither,
+            palette,
+        }),
+        contrast: args.high_contrast,
+        large_cursor: args.large_cursor,
+        presenter: args.presenter,
+        loupe: args.loupe,
+        loupe_zoom: args.loupe_zoom,
+        loupe_radius: args.loupe_radius,
+        canvas: Canvas {
+            background: args.letterbox_color,
+            page: args.page_color,
+            edge: args.page_edge,
+            checkerboard: args.checkerboard.then_some(checkerboard),
+            backdrop,
+            world_grid: true,
+        },
+        checkerboard,
+    };
+    let mut viewer = Viewer::new(sources, svg_opts, config);
 
-struct State {
-    pan: (f32, f32),
-    zoom: f32,
-    auto_fit: bool,
-    fit_scale: f32,
-    was_active: bool,
-}
+    #[cfg(target_os = "linux")]
+    if let Some(path) = &args.eink {
+        let eink = eink::Eink::open(path, args.eink_full_refresh)
+            .map_err(|e| format!("--eink {}: {e}", path.display()))?;
+        viewer.mirror_to(eink);
+    }
 
-impl State {
-    fn new() -> Self {
-        Self {
-            pan: (0.0, 0.0),
-            zoom: 1.0,
-            auto_fit: true,
-            fit_scale: 1.0,
-            was_active: false,
-        }
+    #[cfg(target_os = "linux")]
+    if let Some(path) = &args.v4l2 {
+        let v4l2 = v4l2::V4l2::open(path, args.v4l2_format)
+            .map_err(|e| format!("--v4l2 {}: {e}", path.display()))?;
+        viewer.add_sink(Box::new(v4l2));
     }
 
-    fn reset(&mut self) {
-        self.pan = (0.0, 0.0);
-        self.zoom = 1.0;
-        self.auto_fit = true;
+    #[cfg(unix)]
+    if let Some(path) = &args.shm {
+        let shm = shm::Shm::create(path).map_err(|e| format!("--shm {}: {e}", path.display()))?;
+        viewer.add_sink(Box::new(shm));
     }
 
-    fn update_fit_scale(&mut self, tree: &usvg::Tree, width: usize, height: usize) {
-        if self.auto_fit {
-            let svg_size = tree.size();
-            self.fit_scale =
-                (width as f32 / svg_size.width()).min(height as f32 / svg_size.height());
-        }
+    #[cfg(feature = "lua")]
+    if let Some(path) = &args.script {
+        let script = script::Script::load(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        viewer.add_hooks(Box::new(script));
     }
 
-    fn handle_input(&mut self, window: &mut Window) -> bool {
-        let mut changed = false;
+    #[cfg(feature = "wasm")]
+    for path in &args.plugins {
+        let plugin = plugin::Plugin::load(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        viewer.add_hooks(Box::new(plugin));
+    }
 
-        let active = window.is_active();
-        if active && !self.was_active {
-            changed = true;
-        }
-        self.was_active = active;
+    if stdin {
+        let sender = viewer.sender();
+        stream.read_from(io::stdin(), move || sender.send(Action::Reload).is_ok());
+    } else {
+        command::spawn_stdin_reader(viewer.sender());
+    }
 
-        let pan_speed = 10.0;
+    #[cfg(unix)]
+    let _listening = ipc::listen(viewer.sender()).unwrap_or_else(|e| {
+        // Only `svgtail ctl` and `--single-instance` need it, which the
+        // viewer does without.
+        eprintln!("svgtail: not listening for control commands: {e}");
+        None
+    });
+
+    #[cfg(all(
+        feature = "a11y",
+        unix,
+        not(any(target_os = "macos", target_os = "redox"))
+    ))]
+    if !args.headless {
+        a11y::spawn(viewer.sender(), Units::new(args.units, args.dpi));
+    }
 
-        if window.is_key_down(Key::K) {
-            self.pan.1 += pan_speed;
-            self.auto_fit = false;
-            changed = true;
-        }
-        if window.is_key_down(Key::J) {
-            self.pan.1 -= pan_speed;
-            self.auto_fit = false;
-            changed = true;
-        }
-        if window.is_key_down(Key::H) {
-            self.pan.0 += pan_speed;
-            self.auto_fit = false;
-            changed = true;
-        }
-        if window.is_key_down(Key::L) {
-            self.pan.0 -= pan_speed;
-            self.auto_fit = false;
-            changed = true;
-        }
-        if window.is_key_down(Key::Equal) || window.is_key_down(Key::NumPadPlus) {
-            self.zoom *= 1.1;
-            self.auto_fit = false;
-            changed = true;
-        }
-        if window.is_key_down(Key::Minus) || window.is_key_down(Key::NumPadMinus) {
-            self.zoom /= 1.1;
-            self.auto_fit = false;
-            changed = true;
-        }
-        if window.is_key_down(Key::R) {
-            self.reset();
-            changed = true;
-        }
+    #[cfg(feature = "rpc")]
+    if let Some(addr) = &args.rpc {
+        let addr = rpc::serve(addr, viewer.sender()).map_err(|e| format!("--rpc {addr}: {e}"))?;
+        eprintln!("listening for JSON-RPC on {addr}");
+    }
 
-        changed
+    match args.headless {
+        true => viewer.run_headless(),
+        false => viewer.run(),
     }
 }
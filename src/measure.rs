@@ -0,0 +1,105 @@
+//! Measuring parts of the document: a rubber-band rectangle reporting its
+//! size in document units, and physical units given the document's DPI, and
+//! angles between three clicked points
+
+use resvg::tiny_skia::Color;
+
+use crate::overlay::Shape;
+use crate::units::Units;
+
+/// What measurements are drawn in
+fn color() -> Color {
+    Color::from_rgba8(0xFF, 0xD7, 0x00, 0xFF)
+}
+
+/// A rectangle being dragged out, in page coordinates
+#[derive(Clone, Copy, Debug)]
+pub struct Selection {
+    pub start: (f32, f32),
+    pub end: (f32, f32),
+}
+
+impl Selection {
+    pub fn new(at: (f32, f32)) -> Self {
+        Self { start: at, end: at }
+    }
+
+    fn size(&self) -> (f32, f32) {
+        (
+            (self.end.0 - self.start.0).abs(),
+            (self.end.1 - self.start.1).abs(),
+        )
+    }
+
+    pub fn shape(&self) -> Shape {
+        let (w, h) = self.size();
+        Shape::Rect {
+            x: self.start.0.min(self.end.0),
+            y: self.start.1.min(self.end.1),
+            w,
+            h,
+            color: color(),
+        }
+    }
+
+    /// The size as text in `units`, with as many decimals as `scale` screen
+    /// pixels per user unit can resolve, plus millimetres and inches if the
+    /// readout is in user units and their size is known
+    pub fn readout(&self, scale: f32, units: &Units) -> String {
+        let size = units.size_of(self.size(), scale);
+        match units.physical(self.size()) {
+            Some(physical) => format!("{size}  ({physical})"),
+            None => size,
+        }
+    }
+}
+
+/// An angle measured by clicking a point on one arm, the vertex, then a
+/// point on the other arm, all in page coordinates
+#[derive(Clone, Debug, Default)]
+pub struct Angle {
+    points: Vec<(f32, f32)>,
+}
+
+impl Angle {
+    /// Place the next point, starting over once all three are placed
+    pub fn click(&mut self, at: (f32, f32)) {
+        if self.points.len() == 3 {
+            self.points.clear();
+        }
+        self.points.push(at);
+    }
+
+    /// The points so far, followed by `cursor` while more are to be placed
+    fn with_cursor(&self, cursor: Option<(f32, f32)>) -> Vec<(f32, f32)> {
+        let mut points = self.points.clone();
+        if points.len() < 3 {
+            points.extend(cursor.filter(|_| !points.is_empty()));
+        }
+        points
+    }
+
+    /// The arms, drawn to the cursor while it is being placed
+    pub fn shapes(&self, cursor: Option<(f32, f32)>) -> Vec<Shape> {
+        self.with_cursor(cursor)
+            .windows(2)
+            .map(|arm| Shape::Line {
+                from: arm[0],
+                to: arm[1],
+                color: color(),
+            })
+            .collect()
+    }
+
+    /// The vertex and the angle there in degrees, once it can be measured
+    pub fn degrees(&self, cursor: Option<(f32, f32)>) -> Option<((f32, f32), f32)> {
+        let [a, b, c] = self.with_cursor(cursor)[..] else {
+            return None;
+        };
+        let (ux, uy) = (a.0 - b.0, a.1 - b.1);
+        let (vx, vy) = (c.0 - b.0, c.1 - b.1);
+        // Between 0 and 180, whichever way round the arms were placed
+        let angle = (ux * vy - uy * vx).abs().atan2(ux * vx + uy * vy);
+        Some((b, angle.to_degrees()))
+    }
+}
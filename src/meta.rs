@@ -0,0 +1,152 @@
+//! Metadata usvg drops while building its tree: the `<title>` and `<desc>`
+//! of elements and the targets of links, looked up again by hit-testing
+//! the tree by element id.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use resvg::usvg::{self, roxmltree};
+
+/// Given to elements that need one to be found in the tree but have none
+const ID_PREFIX: &str = "svgtail-meta-";
+
+#[derive(Clone, Debug, Default)]
+pub struct Meta {
+    /// Tooltip text by element id
+    tips: HashMap<String, String>,
+    /// `href` of `<a>` elements by id
+    links: HashMap<String, String>,
+}
+
+impl Meta {
+    /// Collect metadata from `doc`, whose elements should already have ids
+    /// where [`tag`] would give them one
+    pub fn collect(doc: &roxmltree::Document) -> Self {
+        let mut meta = Self::default();
+        for node in noted(doc) {
+            let Some(id) = node.attribute("id") else {
+                continue;
+            };
+            if let Some(tip) = tip(node) {
+                meta.tips.insert(id.to_string(), tip);
+            }
+            if let Some(href) = href(node) {
+                meta.links.insert(id.to_string(), href.to_string());
+            }
+        }
+        meta
+    }
+
+    /// The tooltip of the innermost described element at `point`, in page
+    /// coordinates
+    pub fn tip_at(&self, tree: &usvg::Tree, point: (f32, f32)) -> Option<&str> {
+        innermost(&self.tips, tree, point)
+    }
+
+    /// The target of the innermost link at `point`, in page coordinates
+    pub fn link_at(&self, tree: &usvg::Tree, point: (f32, f32)) -> Option<&str> {
+        innermost(&self.links, tree, point)
+    }
+}
+
+/// The value in `by_id` for the smallest element at `point` with an entry
+fn innermost<'a>(
+    by_id: &'a HashMap<String, String>,
+    tree: &usvg::Tree,
+    point: (f32, f32),
+) -> Option<&'a str> {
+    if by_id.is_empty() {
+        return None;
+    }
+    let mut best: Option<(f32, &str)> = None;
+    visit(tree.root(), &mut |node| {
+        let Some(value) = by_id.get(node.id()) else {
+            return;
+        };
+        let bbox = node.abs_bounding_box();
+        let inside = (bbox.left()..=bbox.right()).contains(&point.0)
+            && (bbox.top()..=bbox.bottom()).contains(&point.1);
+        // Of nested or overlapping elements, the smallest is most specific.
+        let area = bbox.width() * bbox.height();
+        if inside && best.is_none_or(|(a, _)| area <= a) {
+            best = Some((area, value));
+        }
+    });
+    best.map(|(_, value)| value)
+}
+
+/// `text` with an id added to each element with metadata lacking one, or
+/// `None` if none do. usvg only keeps ids, so this is what lets them be
+/// found again in the tree.
+pub fn tag(text: &str, doc: &roxmltree::Document) -> Option<String> {
+    let mut at: Vec<usize> = noted(doc)
+        .filter(|node| node.attribute("id").is_none_or(str::is_empty))
+        .map(|node| {
+            // Just after the tag name
+            let start = node.range().start + 1;
+            let name = text[start..]
+                .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+                .unwrap_or(0);
+            start + name
+        })
+        .collect();
+    if at.is_empty() {
+        return None;
+    }
+    at.sort_unstable();
+    let mut tagged = String::with_capacity(text.len() + at.len() * 24);
+    let mut from = 0;
+    for (n, &pos) in at.iter().enumerate() {
+        tagged.push_str(&text[from..pos]);
+        let _ = write!(tagged, r#" id="{ID_PREFIX}{n}""#);
+        from = pos;
+    }
+    tagged.push_str(&text[from..]);
+    Some(tagged)
+}
+
+/// Links, and elements other than the root with a `<title>` or `<desc>`
+fn noted<'a>(doc: &'a roxmltree::Document) -> impl Iterator<Item = roxmltree::Node<'a, 'a>> + 'a {
+    let root = doc.root_element();
+    doc.descendants().filter(move |node| {
+        node.is_element() && *node != root && (tip(*node).is_some() || href(*node).is_some())
+    })
+}
+
+/// Where `node` links to, if it is an `<a>`
+fn href<'a>(node: roxmltree::Node<'a, '_>) -> Option<&'a str> {
+    const XLINK: &str = "http://www.w3.org/1999/xlink";
+    if !node.has_tag_name("a") {
+        return None;
+    }
+    node.attribute("href")
+        .or_else(|| node.attribute((XLINK, "href")))
+        .filter(|href| !href.is_empty())
+}
+
+/// The title then description of `node`, each with whitespace collapsed
+fn tip(node: roxmltree::Node) -> Option<String> {
+    let child = |name| {
+        let child = node.children().find(|c| c.has_tag_name(name))?;
+        let text = child
+            .descendants()
+            .filter(|n| n.is_text())
+            .filter_map(|n| n.text())
+            .collect::<String>();
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        (!text.is_empty()).then_some(text)
+    };
+    match (child("title"), child("desc")) {
+        (Some(title), Some(desc)) => Some(format!("{title}\n{desc}")),
+        (title, desc) => title.or(desc),
+    }
+}
+
+fn visit<'a>(group: &'a usvg::Group, f: &mut impl FnMut(&'a usvg::Node)) {
+    for node in group.children() {
+        f(node);
+        if let usvg::Node::Group(group) = node {
+            visit(group, f);
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! A thumbnail of the whole document in the corner of the view, outlining
+//! the part that is on screen. Clicking or dragging in it moves the view.
+
+use resvg::tiny_skia::{
+    Color, IntRect, Paint, PathBuilder, Pixmap, PixmapPaint, Rect, Stroke, Transform,
+};
+use resvg::usvg;
+
+use crate::render::{self, Canvas};
+
+/// Longest side of the minimap in screen pixels
+const MAX_SIDE: f32 = 200.0;
+/// Largest fraction of the viewport the minimap may cover, each way
+const MAX_FRACTION: f32 = 0.3;
+/// Gap between the minimap and the viewport's bottom-right corner
+const MARGIN: f32 = 12.0;
+
+#[derive(Default)]
+pub struct Minimap {
+    /// The last thumbnail, with the document hash and canvas it shows
+    thumb: Option<(Option<u64>, Canvas, Pixmap)>,
+    /// Whether a drag started in the minimap is under way
+    pub dragging: bool,
+}
+
+impl Minimap {
+    pub fn new() -> Self {
+        Self {
+            thumb: None,
+            dragging: false,
+        }
+    }
+
+    /// Draw the minimap in the corner of a `viewport` at the top left of
+    /// `pixmap`, which `transform` maps `tree` into.
+    /// `hash` identifies the document, so its thumbnail is only redrawn
+    /// when it changes.
+    pub fn draw(
+        &mut self,
+        pixmap: &mut Pixmap,
+        tree: &usvg::Tree,
+        hash: Option<u64>,
+        transform: Transform,
+        viewport: (f32, f32),
+        canvas: &Canvas,
+    ) {
+        let Some((rect, scale)) = place(tree.size(), viewport) else {
+            return;
+        };
+        let (w, h) = (rect.width().round() as u32, rect.height().round() as u32);
+        let stale = match &self.thumb {
+            Some((old, old_canvas, thumb)) => {
+                *old != hash || old_canvas != canvas || (thumb.width(), thumb.height()) != (w, h)
+            }
+            None => true,
+        };
+        if stale {
+            let Some(area) = IntRect::from_xywh(0, 0, w, h) else {
+                return;
+            };
+            let thumb = render::render(tree, area, Transform::from_scale(scale, scale), canvas);
+            self.thumb = Some((hash, canvas.clone(), thumb));
+        }
+        let Some((_, _, thumb)) = &self.thumb else {
+            return;
+        };
+        pixmap.draw_pixmap(
+            rect.x() as i32,
+            rect.y() as i32,
+            thumb.as_ref(),
+            &PixmapPaint::default(),
+            Transform::identity(),
+            None,
+        );
+        outline(pixmap, rect, 1.0, Color::from_rgba8(0x80, 0x80, 0x80, 0xFF));
+
+        // The viewport in document coordinates, then in the thumbnail's
+        let Some(seen) = transform
+            .invert()
+            .and_then(|inv| Rect::from_xywh(0.0, 0.0, viewport.0, viewport.1)?.transform(inv))
+            .and_then(|r| r.transform(to_screen(rect, scale)))
+        else {
+            return;
+        };
+        let clipped = Rect::from_ltrb(
+            seen.left().max(rect.left()),
+            seen.top().max(rect.top()),
+            seen.right().min(rect.right()),
+            seen.bottom().min(rect.bottom()),
+        );
+        if let Some(seen) = clipped {
+            // A light line on a dark one shows against any document.
+            outline(pixmap, seen, 3.0, Color::from_rgba8(0, 0, 0, 0xAA));
+            outline(pixmap, seen, 1.0, Color::from_rgba8(0x00, 0xBF, 0xFF, 0xFF));
+        }
+    }
+}
+
+/// Whether screen point `at` is over the minimap
+pub fn contains(size: usvg::Size, viewport: (f32, f32), at: (f32, f32)) -> bool {
+    place(size, viewport).is_some_and(|(rect, _)| {
+        (rect.left()..=rect.right()).contains(&at.0) && (rect.top()..=rect.bottom()).contains(&at.1)
+    })
+}
+
+/// The document point under screen point `at` in the minimap, clamped to
+/// the document
+pub fn point(size: usvg::Size, viewport: (f32, f32), at: (f32, f32)) -> Option<(f32, f32)> {
+    let (rect, scale) = place(size, viewport)?;
+    Some((
+        ((at.0 - rect.x()) / scale).clamp(0.0, size.width()),
+        ((at.1 - rect.y()) / scale).clamp(0.0, size.height()),
+    ))
+}
+
+/// Where the minimap goes in `viewport`, and its scale from document units
+fn place(size: usvg::Size, viewport: (f32, f32)) -> Option<(Rect, f32)> {
+    let max_w = MAX_SIDE.min(viewport.0 * MAX_FRACTION);
+    let max_h = MAX_SIDE.min(viewport.1 * MAX_FRACTION);
+    let scale = (max_w / size.width()).min(max_h / size.height());
+    let (w, h) = (
+        (size.width() * scale).round().max(1.0),
+        (size.height() * scale).round().max(1.0),
+    );
+    let rect = Rect::from_xywh(viewport.0 - MARGIN - w, viewport.1 - MARGIN - h, w, h)?;
+    Some((rect, scale))
+}
+
+fn to_screen(rect: Rect, scale: f32) -> Transform {
+    Transform::from_translate(rect.x(), rect.y()).pre_scale(scale, scale)
+}
+
+fn outline(pixmap: &mut Pixmap, rect: Rect, width: f32, color: Color) {
+    let Some(rect) = rect.outset(0.5, 0.5) else {
+        return;
+    };
+    let mut paint = Paint::default();
+    paint.set_color(color);
+    let path = PathBuilder::from_rect(rect);
+    let stroke = Stroke {
+        width,
+        ..Default::default()
+    };
+    pixmap.stroke_path(&path, &paint, &stroke, Transform::identity(), None);
+}
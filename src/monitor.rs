@@ -0,0 +1,111 @@
+//! Which monitor the window is on and how dense its pixels are, so a view
+//! moved to another monitor can keep the document the same physical size.
+//! minifb doesn't know about monitors, so on X11 they are listed through
+//! XRandR on a second connection.
+
+use std::ptr;
+use std::time::{Duration, Instant};
+
+use x11_dl::{xlib, xrandr};
+
+/// How often to check which monitor the window is on
+const POLL: Duration = Duration::from_millis(500);
+
+/// Pixels per inch a scale of 1 stands for
+const BASE_DPI: f32 = 96.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Monitor {
+    pub name: xlib::Atom,
+    /// Pixel density relative to 96 DPI, to the nearest quarter
+    pub scale: f32,
+}
+
+pub struct Monitors {
+    xlib: xlib::Xlib,
+    xrandr: xrandr::Xrandr,
+    display: *mut xlib::Display,
+    current: Option<Monitor>,
+    checked: Instant,
+}
+
+impl Monitors {
+    /// Start tracking monitors, or `None` if not on X11 or without XRandR 1.5
+    pub fn track() -> Option<Self> {
+        // As in `visibility`, a Wayland window can't be looked up in X.
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            return None;
+        }
+        let xlib = xlib::Xlib::open().ok()?;
+        let xrandr = xrandr::Xrandr::open().ok()?;
+        let display = unsafe { (xlib.XOpenDisplay)(ptr::null()) };
+        if display.is_null() {
+            return None;
+        }
+        Some(Self {
+            xlib,
+            xrandr,
+            display,
+            current: None,
+            checked: Instant::now() - POLL,
+        })
+    }
+
+    /// The monitors the window has moved from and to since last asked. The
+    /// first answer only records where it started.
+    pub fn moved(&mut self, window: &minifb::Window) -> Option<(Monitor, Monitor)> {
+        if self.checked.elapsed() < POLL {
+            return None;
+        }
+        self.checked = Instant::now();
+        let (x, y) = window.get_position();
+        let (w, h) = window.get_size();
+        let centre = (x + w as isize / 2, y + h as isize / 2);
+        let now = self.monitor_at(centre)?;
+        match self.current.replace(now) {
+            Some(before) if before.name != now.name => Some((before, now)),
+            _ => None,
+        }
+    }
+
+    /// The monitor containing root window point `at`
+    fn monitor_at(&self, at: (isize, isize)) -> Option<Monitor> {
+        unsafe {
+            let root = (self.xlib.XDefaultRootWindow)(self.display);
+            let mut count = 0;
+            let list = (self.xrandr.XRRGetMonitors)(self.display, root, xlib::True, &mut count);
+            if list.is_null() {
+                return None;
+            }
+            let monitors = std::slice::from_raw_parts(list, count.max(0) as usize);
+            let found = monitors
+                .iter()
+                .find(|m| {
+                    (m.x as isize..(m.x + m.width) as isize).contains(&at.0)
+                        && (m.y as isize..(m.y + m.height) as isize).contains(&at.1)
+                })
+                .map(|m| Monitor {
+                    name: m.name,
+                    scale: scale(m.width, m.mwidth),
+                });
+            (self.xrandr.XRRFreeMonitors)(list);
+            found
+        }
+    }
+}
+
+impl Drop for Monitors {
+    fn drop(&mut self) {
+        unsafe { (self.xlib.XCloseDisplay)(self.display) };
+    }
+}
+
+/// The scale of a monitor `pixels` wide and `mm` millimetres wide. Monitors
+/// not reporting their size count as 96 DPI.
+fn scale(pixels: i32, mm: i32) -> f32 {
+    if mm <= 0 {
+        return 1.0;
+    }
+    let dpi = pixels as f32 / (mm as f32 / 25.4);
+    ((dpi / BASE_DPI) * 4.0).round().max(1.0) / 4.0
+}
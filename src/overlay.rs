@@ -0,0 +1,294 @@
+use resvg::{
+    tiny_skia::{self, Color, Paint, PathBuilder, Pixmap, Stroke, Transform},
+    usvg,
+};
+
+/// A simple shape drawn on top of the rendered document.
+/// Coordinates are in SVG user units; strokes and text are sized in screen pixels.
+#[derive(Clone, Debug)]
+pub enum Shape {
+    Rect {
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        color: Color,
+    },
+    Line {
+        from: (f32, f32),
+        to: (f32, f32),
+        color: Color,
+    },
+    Text {
+        at: (f32, f32),
+        text: String,
+        color: Color,
+    },
+}
+
+const STROKE_WIDTH: f32 = 2.0;
+const FONT_SIZE: f32 = 14.0;
+/// Advance of a monospace character and height of a line at `FONT_SIZE`
+const CHAR_WIDTH: f32 = 8.4;
+const LINE_HEIGHT: f32 = 18.0;
+/// Screen pixels per document pixel from which the pixel grid is shown
+const GRID_MIN_SCALE: f32 = 8.0;
+/// Distance of the way-home arrow's tip from the window edge
+const ARROW_MARGIN: f32 = 12.0;
+const ARROW_SIZE: f32 = 24.0;
+
+/// Draw `shapes` onto `pixmap`, mapping document coordinates through `transform`
+pub fn draw(pixmap: &mut Pixmap, shapes: &[Shape], transform: Transform, opts: &usvg::Options) {
+    for shape in shapes {
+        match shape {
+            Shape::Rect { x, y, w, h, color } => {
+                if let Some(rect) = tiny_skia::Rect::from_xywh(*x, *y, *w, *h) {
+                    stroke(pixmap, PathBuilder::from_rect(rect), transform, *color);
+                }
+            }
+            Shape::Line { from, to, color } => {
+                let mut pb = PathBuilder::new();
+                pb.move_to(from.0, from.1);
+                pb.line_to(to.0, to.1);
+                if let Some(path) = pb.finish() {
+                    stroke(pixmap, path, transform, *color);
+                }
+            }
+            Shape::Text { at, text: s, color } => {
+                let mut p = tiny_skia::Point::from_xy(at.0, at.1);
+                transform.map_point(&mut p);
+                text(pixmap, (p.x, p.y), s, *color, opts);
+            }
+        }
+    }
+}
+
+/// Draw hairlines on the boundaries between document pixels (whole user
+/// units, as rasterized at 100%) across the top-left `width` pixels of
+/// `pixmap`, once zoomed in far enough for them to be told apart
+pub fn pixel_grid(pixmap: &mut Pixmap, transform: Transform, width: u32) {
+    let (scale_x, scale_y) = (transform.sx, transform.sy);
+    if scale_x < GRID_MIN_SCALE || scale_y < GRID_MIN_SCALE {
+        return;
+    }
+    let height = pixmap.height();
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(0x80, 0x80, 0x80, 0x80);
+    // Crisp one-pixel lines, not antialiased across two
+    paint.anti_alias = false;
+    let lines = |offset: f32, scale: f32, len: u32| {
+        let first = (-offset / scale).ceil() as i64;
+        let last = ((len as f32 - offset) / scale).floor() as i64;
+        (first..=last).map(move |n| (offset + n as f32 * scale).round())
+    };
+    for x in lines(transform.tx, scale_x, width) {
+        if let Some(rect) = tiny_skia::Rect::from_xywh(x, 0.0, 1.0, height as f32) {
+            pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+        }
+    }
+    for y in lines(transform.ty, scale_y, height) {
+        if let Some(rect) = tiny_skia::Rect::from_xywh(0.0, y, width as f32, 1.0) {
+            pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+        }
+    }
+}
+
+/// Point from the window edge towards `target`, a screen point outside the
+/// `size` viewport, with a hint of the key that returns there
+pub fn way_home(pixmap: &mut Pixmap, target: (f32, f32), size: (f32, f32), opts: &usvg::Options) {
+    let centre = (size.0 / 2.0, size.1 / 2.0);
+    let (dx, dy) = (target.0 - centre.0, target.1 - centre.1);
+    let len = dx.hypot(dy);
+    if len == 0.0 {
+        return;
+    }
+    let (ux, uy) = (dx / len, dy / len);
+    // Where the line to the target leaves the window, pulled in by the margin
+    let reach = |half: f32, d: f32| match d {
+        0.0 => f32::INFINITY,
+        d => (half - ARROW_MARGIN) / d.abs(),
+    };
+    let t = reach(centre.0, ux).min(reach(centre.1, uy)).max(0.0);
+    let tip = (centre.0 + ux * t, centre.1 + uy * t);
+
+    let (back, side) = (ARROW_SIZE, ARROW_SIZE / 3.0);
+    let mut pb = PathBuilder::new();
+    pb.move_to(tip.0, tip.1);
+    pb.line_to(tip.0 - ux * back - uy * side, tip.1 - uy * back + ux * side);
+    pb.line_to(tip.0 - ux * back + uy * side, tip.1 - uy * back - ux * side);
+    pb.close();
+    let Some(arrow) = pb.finish() else {
+        return;
+    };
+    let mut paint = Paint::default();
+    paint.set_color(Color::WHITE);
+    paint.anti_alias = true;
+    pixmap.fill_path(
+        &arrow,
+        &paint,
+        tiny_skia::FillRule::Winding,
+        Transform::identity(),
+        None,
+    );
+    stroke(
+        pixmap,
+        arrow,
+        Transform::identity(),
+        Color::from_rgba8(0, 0, 0, 0xAA),
+    );
+
+    // Along the bottom, clear of wherever the arrow is
+    let hint = "Home: back to the document";
+    let x = ((size.0 - hint.len() as f32 * 8.0) / 2.0).max(8.0);
+    let y = size.1 - 2.0 * FONT_SIZE;
+    text(pixmap, (x, y), hint, Color::WHITE, opts);
+}
+
+/// Show possibly multi-line `tip` beside the cursor at `at`, on a dark
+/// backing and kept within the window
+pub fn tooltip(pixmap: &mut Pixmap, at: (f32, f32), tip: &str, opts: &usvg::Options) {
+    let lines: Vec<&str> = tip.lines().collect();
+    let longest = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let (w, h) = (longest as f32 * 8.0 + 12.0, lines.len() as f32 * 20.0 + 6.0);
+    let (max_x, max_y) = (pixmap.width() as f32 - w, pixmap.height() as f32 - h);
+    // Below and right of the pointer, or flipped to stay on screen
+    let x = match at.0 + 16.0 {
+        x if x <= max_x => x,
+        _ => (at.0 - 8.0 - w).max(0.0),
+    };
+    let y = match at.1 + 20.0 {
+        y if y <= max_y => y,
+        _ => (at.1 - 8.0 - h).max(0.0),
+    };
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(0x22, 0x22, 0x22, 0xE8);
+    if let Some(rect) = tiny_skia::Rect::from_xywh(x, y, w, h) {
+        pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+    }
+    for (i, line) in lines.iter().enumerate() {
+        text(
+            pixmap,
+            (x + 6.0, y + 3.0 + i as f32 * 20.0),
+            line,
+            Color::WHITE,
+            opts,
+        );
+    }
+}
+
+fn stroke(pixmap: &mut Pixmap, path: tiny_skia::Path, transform: Transform, color: Color) {
+    // Transform the geometry rather than the stroke, so line widths stay constant on screen.
+    let Some(path) = path.transform(transform) else {
+        return;
+    };
+    let mut paint = Paint::default();
+    paint.set_color(color);
+    paint.anti_alias = true;
+    let stroke = Stroke {
+        width: STROKE_WIDTH,
+        ..Default::default()
+    };
+    pixmap.stroke_path(&path, &paint, &stroke, Transform::identity(), None);
+}
+
+/// Show `message`, such as a generator's error output, in a pane across the
+/// bottom of the window headed by `title`, cut short to fit at most half of it
+pub fn error_pane(pixmap: &mut Pixmap, title: &str, message: &str, opts: &usvg::Options) {
+    let (width, height) = (pixmap.width() as f32, pixmap.height() as f32);
+    let columns = ((width - 16.0) / CHAR_WIDTH).max(1.0) as usize;
+    let rows = ((height / 2.0 - 12.0) / LINE_HEIGHT).max(2.0) as usize;
+    // Long lines wrap rather than running off the side.
+    let mut lines: Vec<String> = message
+        .lines()
+        .flat_map(|line| {
+            let chars: Vec<char> = printable(&line.replace('\t', "    ")).collect();
+            let wrapped: Vec<String> = chars
+                .chunks(columns)
+                .map(|chunk| chunk.iter().collect())
+                .collect();
+            match wrapped.is_empty() {
+                true => vec![String::new()],
+                false => wrapped,
+            }
+        })
+        .collect();
+    if lines.len() > rows - 1 {
+        lines.truncate(rows - 2);
+        lines.push("…".to_string());
+    }
+    let h = (lines.len() + 1) as f32 * LINE_HEIGHT + 12.0;
+    let top = height - h;
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(0x40, 0x10, 0x10, 0xF0);
+    if let Some(rect) = tiny_skia::Rect::from_xywh(0.0, top, width, h) {
+        pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+    }
+    let heading = Color::from_rgba8(0xFF, 0x99, 0x99, 0xFF);
+    text(pixmap, (8.0, top + 6.0), title, heading, opts);
+    for (i, line) in lines.iter().enumerate() {
+        let at = (8.0, top + 6.0 + (i + 1) as f32 * LINE_HEIGHT);
+        draw_text(pixmap, at, line, Color::WHITE, "monospace", opts);
+    }
+}
+
+/// `line` without terminal escape sequences, such as colour codes, or other
+/// control characters, which can't be drawn
+fn printable(line: &str) -> impl Iterator<Item = char> + '_ {
+    let mut chars = line.chars();
+    std::iter::from_fn(move || {
+        loop {
+            match chars.next()? {
+                '\x1b' => {
+                    // `ESC [`, parameters, then a final letter; otherwise
+                    // a single character follows the escape.
+                    if chars.next()? == '[' {
+                        chars.find(|c| ('@'..='~').contains(c))?;
+                    }
+                }
+                c if c.is_control() => {}
+                c => return Some(c),
+            }
+        }
+    })
+}
+
+/// Draw a line of text with its top-left corner at screen position `at`
+pub fn text(pixmap: &mut Pixmap, at: (f32, f32), text: &str, color: Color, opts: &usvg::Options) {
+    draw_text(pixmap, at, text, color, "sans-serif", opts);
+}
+
+fn draw_text(
+    pixmap: &mut Pixmap,
+    at: (f32, f32),
+    text: &str,
+    color: Color,
+    family: &str,
+    opts: &usvg::Options,
+) {
+    let c = color.to_color_u8();
+    let svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="1" height="1">
+            <text y="{FONT_SIZE}" font-family="{family}" font-size="{FONT_SIZE}"
+                  xml:space="preserve"
+                  fill="rgb({},{},{})" fill-opacity="{}">{}</text>
+        </svg>"#,
+        c.red(),
+        c.green(),
+        c.blue(),
+        color.alpha(),
+        escape(text),
+    );
+    if let Ok(tree) = usvg::Tree::from_str(&svg, opts) {
+        resvg::render(
+            &tree,
+            Transform::from_translate(at.0, at.1),
+            &mut pixmap.as_mut(),
+        );
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
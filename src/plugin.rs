@@ -0,0 +1,250 @@
+//! WASM plugins, enabled with the `wasm` feature.
+//!
+//! A plugin is a core WebAssembly module exporting `memory` and
+//! `alloc(len: u32) -> u32`, plus any of these optional hooks:
+//!
+//! - `on_load(info_ptr: u32, info_len: u32)` / `on_reload(info_ptr, info_len)`: called with a
+//!   UTF-8 JSON object `{"path", "width", "height", "has_text"}` describing the document
+//! - `on_frame(ptr: u32, width: u32, height: u32, scale: f32, tx: f32, ty: f32)`: called with
+//!   the rendered frame as premultiplied RGBA8 rows, which the plugin may modify in place.
+//!   A document point `(x, y)` appears on screen at `(x * scale + tx, y * scale + ty)`.
+//!
+//! Buffers passed to the plugin are obtained from `alloc`, and must stay valid until the next
+//! call to `alloc`; the host reuses the frame buffer while it is large enough.
+//!
+//! Plugins may import these functions from the `svgtail` module (colors are `0xRRGGBBAA`,
+//! coordinates are in SVG user units):
+//!
+//! - `draw_rect(x: f32, y: f32, w: f32, h: f32, color: u32)`
+//! - `draw_line(x1: f32, y1: f32, x2: f32, y2: f32, color: u32)`
+//! - `draw_text(x: f32, y: f32, ptr: u32, len: u32, color: u32)`
+//! - `clear_overlays()`
+//! - `set_zoom(zoom: f32)`, `set_pan(x: f32, y: f32)`
+
+use std::path::Path;
+
+use resvg::tiny_skia::{Color, Pixmap, Transform};
+use wasmtime::{Caller, Engine, Extern, Instance, Linker, Memory, Module, Store, TypedFunc};
+
+use crate::action::Action;
+use crate::hooks::{DocInfo, Hooks};
+use crate::overlay::Shape;
+
+type FrameFn = TypedFunc<(u32, u32, u32, f32, f32, f32), ()>;
+type InfoFn = TypedFunc<(u32, u32), ()>;
+
+pub struct Plugin {
+    name: String,
+    store: Store<Vec<Action>>,
+    memory: Memory,
+    alloc: TypedFunc<u32, u32>,
+    on_load: Option<InfoFn>,
+    on_reload: Option<InfoFn>,
+    on_frame: Option<FrameFn>,
+    /// Guest frame buffer as (pointer, capacity)
+    frame_buf: Option<(u32, usize)>,
+}
+
+impl Plugin {
+    pub fn load(path: &Path) -> wasmtime::Result<Self> {
+        let engine = Engine::default();
+        let module = Module::from_file(&engine, path)?;
+        let mut linker = Linker::new(&engine);
+        add_imports(&mut linker)?;
+
+        let mut store = Store::new(&engine, Vec::new());
+        let instance = linker.instantiate(&mut store, &module)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| wasmtime::format_err!("plugin does not export `memory`"))?;
+        let alloc = instance.get_typed_func(&mut store, "alloc")?;
+        let on_load = optional(&instance, &mut store, "on_load")?;
+        let on_reload = optional(&instance, &mut store, "on_reload")?;
+        let on_frame = optional(&instance, &mut store, "on_frame")?;
+
+        Ok(Self {
+            name: path.display().to_string(),
+            store,
+            memory,
+            alloc,
+            on_load,
+            on_reload,
+            on_frame,
+            frame_buf: None,
+        })
+    }
+
+    fn call_info(&mut self, f: Option<InfoFn>, info: &DocInfo) -> wasmtime::Result<()> {
+        let Some(f) = f else { return Ok(()) };
+        let json = info_json(info);
+        let ptr = self.alloc.call(&mut self.store, json.len() as u32)?;
+        self.memory
+            .write(&mut self.store, ptr as usize, json.as_bytes())?;
+        f.call(&mut self.store, (ptr, json.len() as u32))
+    }
+
+    fn call_frame(&mut self, pixmap: &mut Pixmap, transform: Transform) -> wasmtime::Result<()> {
+        let Some(f) = self.on_frame.clone() else {
+            return Ok(());
+        };
+        let len = pixmap.data().len();
+        let ptr = match self.frame_buf {
+            Some((ptr, cap)) if cap >= len => ptr,
+            _ => {
+                let ptr = self.alloc.call(&mut self.store, len as u32)?;
+                self.frame_buf = Some((ptr, len));
+                ptr
+            }
+        };
+        self.memory
+            .write(&mut self.store, ptr as usize, pixmap.data())?;
+        f.call(
+            &mut self.store,
+            (
+                ptr,
+                pixmap.width(),
+                pixmap.height(),
+                transform.sx,
+                transform.tx,
+                transform.ty,
+            ),
+        )?;
+        self.memory
+            .read(&self.store, ptr as usize, pixmap.data_mut())?;
+        Ok(())
+    }
+
+    fn report(&self, hook: &str, result: wasmtime::Result<()>) {
+        if let Err(e) = result {
+            eprintln!("plugin {} failed in {hook}: {e}", self.name);
+        }
+    }
+}
+
+impl Hooks for Plugin {
+    fn on_load(&mut self, info: &DocInfo) {
+        let result = self.call_info(self.on_load.clone(), info);
+        self.report("on_load", result);
+    }
+
+    fn on_reload(&mut self, info: &DocInfo) {
+        let result = self.call_info(self.on_reload.clone(), info);
+        self.report("on_reload", result);
+    }
+
+    fn on_frame(&mut self, pixmap: &mut Pixmap, transform: Transform) {
+        let result = self.call_frame(pixmap, transform);
+        self.report("on_frame", result);
+    }
+
+    fn take_actions(&mut self) -> Vec<Action> {
+        std::mem::take(self.store.data_mut())
+    }
+}
+
+fn optional<P, R>(
+    instance: &Instance,
+    store: &mut Store<Vec<Action>>,
+    name: &str,
+) -> wasmtime::Result<Option<TypedFunc<P, R>>>
+where
+    P: wasmtime::WasmParams,
+    R: wasmtime::WasmResults,
+{
+    match instance.get_func(&mut *store, name) {
+        Some(f) => Ok(Some(f.typed(&*store)?)),
+        None => Ok(None),
+    }
+}
+
+fn add_imports(linker: &mut Linker<Vec<Action>>) -> wasmtime::Result<()> {
+    linker.func_wrap(
+        "svgtail",
+        "draw_rect",
+        |mut caller: Caller<'_, Vec<Action>>, x: f32, y: f32, w: f32, h: f32, color: u32| {
+            let color = rgba(color);
+            caller
+                .data_mut()
+                .push(Action::Draw(Shape::Rect { x, y, w, h, color }));
+        },
+    )?;
+    linker.func_wrap(
+        "svgtail",
+        "draw_line",
+        |mut caller: Caller<'_, Vec<Action>>, x1: f32, y1: f32, x2: f32, y2: f32, color: u32| {
+            let line = Shape::Line {
+                from: (x1, y1),
+                to: (x2, y2),
+                color: rgba(color),
+            };
+            caller.data_mut().push(Action::Draw(line));
+        },
+    )?;
+    linker.func_wrap(
+        "svgtail",
+        "draw_text",
+        |mut caller: Caller<'_, Vec<Action>>,
+         x: f32,
+         y: f32,
+         ptr: u32,
+         len: u32,
+         color: u32|
+         -> wasmtime::Result<()> {
+            let Some(Extern::Memory(memory)) = caller.get_export("memory") else {
+                wasmtime::bail!("plugin does not export `memory`");
+            };
+            let mut bytes = vec![0; len as usize];
+            memory.read(&caller, ptr as usize, &mut bytes)?;
+            let text = Shape::Text {
+                at: (x, y),
+                text: String::from_utf8_lossy(&bytes).into_owned(),
+                color: rgba(color),
+            };
+            caller.data_mut().push(Action::Draw(text));
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "svgtail",
+        "clear_overlays",
+        |mut caller: Caller<'_, Vec<Action>>| caller.data_mut().push(Action::ClearOverlays),
+    )?;
+    linker.func_wrap(
+        "svgtail",
+        "set_zoom",
+        |mut caller: Caller<'_, Vec<Action>>, zoom: f32| {
+            caller.data_mut().push(Action::SetZoom(zoom))
+        },
+    )?;
+    linker.func_wrap(
+        "svgtail",
+        "set_pan",
+        |mut caller: Caller<'_, Vec<Action>>, x: f32, y: f32| {
+            caller.data_mut().push(Action::SetPan(x, y))
+        },
+    )?;
+    Ok(())
+}
+
+fn rgba(c: u32) -> Color {
+    let [r, g, b, a] = c.to_be_bytes();
+    Color::from_rgba8(r, g, b, a)
+}
+
+fn info_json(info: &DocInfo) -> String {
+    let path: String = info
+        .path
+        .display()
+        .to_string()
+        .chars()
+        .flat_map(|c| match c {
+            '"' | '\\' => vec!['\\', c],
+            c if c.is_control() => format!("\\u{:04x}", c as u32).chars().collect(),
+            c => vec![c],
+        })
+        .collect();
+    format!(
+        r#"{{"path":"{path}","width":{},"height":{},"has_text":{}}}"#,
+        info.width, info.height, info.has_text
+    )
+}
@@ -0,0 +1,104 @@
+//! Pointing things out on a call: a bright halo around the cursor that
+//! viewers can follow even through video compression, a laser-pointer
+//! trail behind it that fades within a second, and a key held to zoom in on
+//! what it points at
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use resvg::tiny_skia::{Color, FillRule, LineCap, Paint, PathBuilder, Pixmap, Stroke, Transform};
+
+use crate::state::State;
+
+/// How long a point of the trail lasts
+const TRAIL_LIFE: Duration = Duration::from_millis(700);
+/// Radius of the halo, and width of the trail where it is newest, in
+/// screen pixels
+const HALO_RADIUS: f32 = 28.0;
+const TRAIL_WIDTH: f32 = 6.0;
+/// How far holding the zoom key zooms in
+pub const PEEK_ZOOM: f32 = 3.0;
+
+fn laser() -> Color {
+    Color::from_rgba8(0xFF, 0x20, 0x20, 0xFF)
+}
+
+#[derive(Default)]
+pub struct Presenter {
+    /// Where the cursor has been recently, oldest first
+    trail: VecDeque<((f32, f32), Instant)>,
+    cursor: Option<(f32, f32)>,
+    /// The view from before zooming in, while the zoom key is held
+    pub peek: Option<State>,
+}
+
+impl Presenter {
+    /// Follow the cursor, now at `at`, forgetting the trail older than it
+    /// lasts. Returns `true` while there is a trail to fade, or the cursor
+    /// moved, so there is something new to draw.
+    pub fn track(&mut self, at: Option<(f32, f32)>, now: Instant) -> bool {
+        let faded = self.trail.len();
+        while self
+            .trail
+            .front()
+            .is_some_and(|(_, when)| now - *when > TRAIL_LIFE)
+        {
+            self.trail.pop_front();
+        }
+        let moved = at != std::mem::replace(&mut self.cursor, at);
+        if let (true, Some(at)) = (moved, at) {
+            self.trail.push_back((at, now));
+        }
+        // A lone point draws nothing, so once the cursor stops there is
+        // only the rest of the trail to fade.
+        moved || faded != self.trail.len() || self.trail.len() > 1
+    }
+
+    /// Draw the trail, fading and thinning with age, and the halo around
+    /// the cursor at `at`
+    pub fn draw(&self, pixmap: &mut Pixmap, at: Option<(f32, f32)>, now: Instant) {
+        let life = TRAIL_LIFE.as_secs_f32();
+        let mut paint = Paint::default();
+        let points: Vec<_> = self.trail.iter().collect();
+        for pair in points.windows(2) {
+            let ((from, _), (to, when)) = (pair[0], pair[1]);
+            let fresh = 1.0 - ((now - *when).as_secs_f32() / life).min(1.0);
+            let mut pb = PathBuilder::new();
+            pb.move_to(from.0, from.1);
+            pb.line_to(to.0, to.1);
+            let Some(path) = pb.finish() else {
+                continue;
+            };
+            let mut color = laser();
+            color.apply_opacity(fresh);
+            paint.set_color(color);
+            let stroke = Stroke {
+                width: TRAIL_WIDTH * fresh.max(0.3),
+                line_cap: LineCap::Round,
+                ..Default::default()
+            };
+            pixmap.stroke_path(&path, &paint, &stroke, Transform::identity(), None);
+        }
+
+        let Some((x, y)) = at else {
+            return;
+        };
+        let Some(halo) = PathBuilder::from_circle(x, y, HALO_RADIUS) else {
+            return;
+        };
+        paint.set_color_rgba8(0xFF, 0xEB, 0x3B, 0x60);
+        pixmap.fill_path(
+            &halo,
+            &paint,
+            FillRule::Winding,
+            Transform::identity(),
+            None,
+        );
+        paint.set_color_rgba8(0xFF, 0xC1, 0x07, 0xE0);
+        let ring = Stroke {
+            width: 3.0,
+            ..Default::default()
+        };
+        pixmap.stroke_path(&halo, &paint, &ring, Transform::identity(), None);
+    }
+}
@@ -0,0 +1,259 @@
+//! Python bindings, built as the `svgtail` extension module with maturin, for
+//! previewing documents from notebooks and scripts:
+//!
+//! ```python
+//! viewer = svgtail.Viewer.open("plot.svg")
+//! viewer.on_reload(lambda path, error: show(viewer.render_to_numpy(800)))
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, mpsc};
+use std::thread;
+use std::time::Duration;
+
+use notify_debouncer_full::{
+    DebounceEventResult, Debouncer, RecommendedCache, new_debouncer,
+    notify::{RecommendedWatcher, RecursiveMode},
+};
+use numpy::ndarray::Array3;
+use numpy::{IntoPyArray, PyArray3};
+use pyo3::exceptions::{PyOSError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyType;
+use resvg::usvg;
+
+use crate::input::{self, Source};
+use crate::render;
+use crate::select::Shown;
+use crate::template::Defines;
+use crate::watch::{self, Trigger};
+
+/// Options shared by every viewer, with system fonts loaded once
+fn options() -> &'static usvg::Options<'static> {
+    static OPTIONS: OnceLock<usvg::Options<'static>> = OnceLock::new();
+    OPTIONS.get_or_init(|| {
+        #[cfg_attr(not(feature = "text"), allow(unused_mut))]
+        let mut opts = usvg::Options::default();
+        #[cfg(feature = "text")]
+        opts.fontdb_mut().load_system_fonts();
+        opts
+    })
+}
+
+/// What the watched path last loaded as
+struct Document {
+    /// The last version that loaded, kept while later saves don't
+    tree: usvg::Tree,
+    hash: Option<u64>,
+    /// Why the latest save didn't load, if it didn't
+    error: Option<String>,
+}
+
+/// State shared with the watching thread
+struct Shared {
+    document: Mutex<Document>,
+    callbacks: Mutex<Vec<Py<PyAny>>>,
+}
+
+/// A document kept up to date as it changes on disk
+#[pyclass(module = "svgtail")]
+pub struct Viewer {
+    path: PathBuf,
+    shared: Arc<Shared>,
+    /// Dropped on closing, which ends the watching thread
+    debouncer: Mutex<Option<Debouncer<RecommendedWatcher, RecommendedCache>>>,
+}
+
+#[pymethods]
+impl Viewer {
+    /// Load the document at `path` and watch it for changes as the viewer
+    /// would. Raises `ValueError` if it doesn't load.
+    #[classmethod]
+    fn open(_cls: &Bound<'_, PyType>, py: Python<'_>, path: PathBuf) -> PyResult<Self> {
+        let path = std::path::absolute(&path)
+            .map_err(|e| PyOSError::new_err(format!("{}: {e}", path.display())))?;
+        let source = Source::File(path.clone());
+        let parsed = py
+            .detach(|| {
+                input::load_changed(
+                    &source,
+                    None,
+                    options(),
+                    &Defines::default(),
+                    &Shown::default(),
+                    None,
+                    None,
+                )
+            })
+            .map_err(|e| PyValueError::new_err(format!("{}: {e}", path.display())))?
+            .expect("nothing to compare against");
+        let shared = Arc::new(Shared {
+            document: Mutex::new(Document {
+                tree: parsed.tree,
+                hash: Some(parsed.hash),
+                error: None,
+            }),
+            callbacks: Mutex::new(Vec::new()),
+        });
+        let debouncer = watch_path(&path, Arc::clone(&shared)).map_err(PyOSError::new_err)?;
+        Ok(Self {
+            path,
+            shared,
+            debouncer: Mutex::new(Some(debouncer)),
+        })
+    }
+
+    /// Call `callback(path, error)` from a background thread each time the
+    /// document changes, with `error` saying why it no longer loads, or
+    /// `None`. Returns `callback`, so this can decorate a function.
+    fn on_reload(&self, py: Python<'_>, callback: Py<PyAny>) -> Py<PyAny> {
+        let mut callbacks = self.shared.callbacks.lock().unwrap();
+        callbacks.push(callback.clone_ref(py));
+        callback
+    }
+
+    /// Render the last version that loaded into a `height` × `width` × 4
+    /// array of RGBA bytes, scaled to fit and centred with transparency
+    /// around it. Either side left out follows the document's aspect ratio;
+    /// with neither, the document is drawn at its own size.
+    #[pyo3(signature = (width=None, height=None))]
+    fn render_to_numpy<'py>(
+        &self,
+        py: Python<'py>,
+        width: Option<u32>,
+        height: Option<u32>,
+    ) -> PyResult<Bound<'py, PyArray3<u8>>> {
+        let pixels = py.detach(|| {
+            let document = self.shared.document.lock().unwrap();
+            let size = document.tree.size();
+            let aspect = size.width() / size.height();
+            let (w, h) = match (width, height) {
+                (Some(w), Some(h)) => (w, h),
+                (Some(w), None) => (w, (w as f32 / aspect).round() as u32),
+                (None, Some(h)) => ((h as f32 * aspect).round() as u32, h),
+                (None, None) => (size.width().ceil() as u32, size.height().ceil() as u32),
+            };
+            let pixmap =
+                render::fitted(&document.tree, w, h).ok_or("width and height must be non-zero")?;
+            let mut rgba = vec![0; pixmap.data().len()];
+            render::to_rgba(&pixmap, &mut rgba);
+            Ok::<_, &str>(Array3::from_shape_vec((h as usize, w as usize, 4), rgba).unwrap())
+        });
+        Ok(pixels.map_err(PyValueError::new_err)?.into_pyarray(py))
+    }
+
+    /// The watched path
+    #[getter]
+    fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    /// Width and height of the last version that loaded, in user units
+    #[getter]
+    fn size(&self) -> (f32, f32) {
+        let size = self.shared.document.lock().unwrap().tree.size();
+        (size.width(), size.height())
+    }
+
+    /// Why the latest save didn't load, or `None` if it did
+    #[getter]
+    fn error(&self) -> Option<String> {
+        self.shared.document.lock().unwrap().error.clone()
+    }
+
+    /// Stop watching and drop the callbacks.
+    fn close(&self, py: Python<'_>) {
+        let debouncer = self.debouncer.lock().unwrap().take();
+        // The watcher waits for its thread, which may be waiting on the GIL.
+        py.detach(|| drop(debouncer));
+        self.shared.callbacks.lock().unwrap().clear();
+    }
+
+    fn __repr__(&self) -> String {
+        format!("svgtail.Viewer.open({:?})", self.path)
+    }
+}
+
+/// Watch `path` as `svgtail_watch` does, updating `shared` and calling its
+/// callbacks on each change
+fn watch_path(
+    path: &Path,
+    shared: Arc<Shared>,
+) -> Result<Debouncer<RecommendedWatcher, RecommendedCache>, String> {
+    let (tx, rx) = mpsc::channel::<DebounceEventResult>();
+    let mut debouncer = new_debouncer(Duration::from_millis(200), None, move |res| {
+        let _ = tx.send(res);
+    })
+    .map_err(|e| e.to_string())?;
+    debouncer
+        .watch(path, RecursiveMode::NonRecursive)
+        .map_err(|e| format!("{}: {e}", path.display()))?;
+    if let Some(target) = watch::link_target(path) {
+        let dir = path.parent().unwrap_or(Path::new("/"));
+        for watched in [target.as_path(), dir] {
+            debouncer
+                .watch(watched, RecursiveMode::NonRecursive)
+                .map_err(|e| format!("{}: {e}", watched.display()))?;
+        }
+    }
+
+    let source = Source::File(path.to_path_buf());
+    let path = path.to_path_buf();
+    thread::spawn(move || {
+        for result in rx {
+            let relevant = match &result {
+                Ok(events) => events.iter().any(|e| Trigger::Any.fires(&e.kind)),
+                Err(_) => true,
+            };
+            if !relevant {
+                continue;
+            }
+            let hash = shared.document.lock().unwrap().hash;
+            let error = match input::load_changed(
+                &source,
+                hash,
+                options(),
+                &Defines::default(),
+                &Shown::default(),
+                None,
+                None,
+            ) {
+                Ok(None) => continue,
+                Ok(Some(parsed)) => {
+                    let mut document = shared.document.lock().unwrap();
+                    document.tree = parsed.tree;
+                    document.hash = Some(parsed.hash);
+                    document.error = None;
+                    None
+                }
+                Err(e) => {
+                    let mut document = shared.document.lock().unwrap();
+                    document.hash = None;
+                    document.error = Some(e.clone());
+                    Some(e)
+                }
+            };
+            Python::attach(|py| {
+                // Copied out, so a callback can add another
+                let callbacks: Vec<_> = shared
+                    .callbacks
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|c| c.clone_ref(py))
+                    .collect();
+                for callback in callbacks {
+                    if let Err(e) = callback.call1(py, (&path, error.as_deref())) {
+                        e.write_unraisable(py, Some(callback.bind(py)));
+                    }
+                }
+            });
+        }
+    });
+    Ok(debouncer)
+}
+
+#[pymodule]
+fn svgtail(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Viewer>()
+}
@@ -0,0 +1,544 @@
+use std::sync::Arc;
+
+use rayon::prelude::*;
+use resvg::{tiny_skia, usvg};
+
+use crate::color;
+
+/// Bands thinner than this aren't worth a thread of their own
+const MIN_BAND_ROWS: u32 = 64;
+
+/// How many times smaller than the window a coarse frame is rendered
+const COARSE: u32 = 4;
+
+/// Offset of the page's drop shadow in window pixels, down and right
+const SHADOW_OFFSET: f32 = 3.0;
+/// Width of the shadow's soft edge in window pixels
+const SHADOW_BLUR: u32 = 6;
+
+/// What the document is drawn over
+#[derive(Clone, Debug)]
+pub struct Canvas {
+    /// Fills the window around the page
+    pub background: tiny_skia::Color,
+    /// Fills the page, the area the document covers
+    pub page: Option<tiny_skia::Color>,
+    /// How the page stands out from the background
+    pub edge: Edge,
+    /// Shown through transparent parts of the document
+    pub checkerboard: Option<Checkerboard>,
+    /// Stretched to the document's size, beneath it
+    pub backdrop: Option<Arc<tiny_skia::Pixmap>>,
+    /// Lines at round document coordinates around the page, so panning
+    /// off it keeps a sense of place
+    pub world_grid: bool,
+}
+
+impl PartialEq for Canvas {
+    fn eq(&self, other: &Self) -> bool {
+        self.background == other.background
+            && self.page == other.page
+            && self.edge == other.edge
+            && self.checkerboard == other.checkerboard
+            && self.world_grid == other.world_grid
+            && match (&self.backdrop, &other.backdrop) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                (a, b) => a.is_none() && b.is_none(),
+            }
+    }
+}
+
+/// How the page's boundary is marked
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Edge {
+    #[default]
+    None,
+    /// A one-pixel line just outside the page
+    Outline,
+    /// A soft shadow below and right of the page
+    Shadow,
+}
+
+/// Two-colour squares, fixed to the window rather than the document
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Checkerboard {
+    pub colors: [tiny_skia::Color; 2],
+    /// Side of a square in window pixels
+    pub size: u32,
+}
+
+impl Checkerboard {
+    /// Fill the part of `pixmap` inside `rect` (window pixels) with squares.
+    /// `pixmap` covers the window from `origin`.
+    fn draw(&self, pixmap: &mut tiny_skia::PixmapMut, origin: (i32, i32), rect: tiny_skia::Rect) {
+        let colors = self.colors.map(|c| c.premultiply().to_color_u8());
+        let size = self.size as i32;
+        let width = pixmap.width() as i32;
+        let span = |lo: f32, hi: f32, origin: i32, len: i32| {
+            let lo = (lo.round() as i32 - origin).clamp(0, len);
+            let hi = (hi.round() as i32 - origin).clamp(0, len);
+            lo as usize..hi as usize
+        };
+        let cols = span(rect.left(), rect.right(), origin.0, width);
+        let rows = span(rect.top(), rect.bottom(), origin.1, pixmap.height() as i32);
+        for (row, pixels) in pixmap
+            .pixels_mut()
+            .chunks_exact_mut(width as usize)
+            .enumerate()
+            .take(rows.end)
+            .skip(rows.start)
+        {
+            let y = (origin.1 + row as i32).div_euclid(size);
+            for (col, px) in pixels
+                .iter_mut()
+                .enumerate()
+                .take(cols.end)
+                .skip(cols.start)
+            {
+                let x = (origin.0 + col as i32).div_euclid(size);
+                *px = colors[(x + y).rem_euclid(2) as usize];
+            }
+        }
+    }
+}
+
+/// Rasterize `tree` over `canvas` into a fresh pixmap covering `area` of the
+/// window; `transform` maps the document to window pixels.
+/// The pixmap is split into horizontal bands rendered in parallel, unless
+/// the document has filters: resvg clips filter regions to a few times the
+/// size of what it draws into, so a blur or shadow would be cut off at the
+/// edges of a band.
+pub fn render(
+    tree: &usvg::Tree,
+    area: tiny_skia::IntRect,
+    transform: tiny_skia::Transform,
+    canvas: &Canvas,
+) -> tiny_skia::Pixmap {
+    let (width, height) = (area.width(), area.height());
+    let mut pixmap = tiny_skia::Pixmap::new(width, height).unwrap();
+    pixmap.fill(canvas.background);
+    let page = tiny_skia::Rect::from_xywh(0.0, 0.0, tree.size().width(), tree.size().height())
+        .and_then(|r| r.transform(transform));
+    let threads = rayon::current_num_threads() as u32;
+    let rows = match tree.filters() {
+        [] => height.div_ceil(threads).max(MIN_BAND_ROWS),
+        _ => height,
+    };
+    let stride = width as usize * 4;
+    pixmap
+        .data_mut()
+        .par_chunks_mut(rows as usize * stride)
+        .enumerate()
+        .for_each(|(i, band)| {
+            let band_rows = (band.len() / stride) as u32;
+            let mut band = tiny_skia::PixmapMut::from_bytes(band, width, band_rows).unwrap();
+            let origin = (area.x(), area.y() + (i as u32 * rows) as i32);
+            if canvas.world_grid {
+                draw_world_grid(&mut band, canvas, origin, transform, page);
+            }
+            if let Some(page) = page {
+                draw_page(&mut band, canvas, origin, page);
+                if let Some(checkerboard) = &canvas.checkerboard {
+                    checkerboard.draw(&mut band, origin, page);
+                }
+            }
+            let transform = transform.post_translate(-origin.0 as f32, -origin.1 as f32);
+            if let Some(backdrop) = &canvas.backdrop {
+                draw_backdrop(&mut band, backdrop, tree.size(), transform);
+            }
+            resvg::render(tree, transform, &mut band);
+        });
+    pixmap
+}
+
+/// Rasterize `tree` scaled to fit a transparent `width` × `height` pixmap
+/// and centred in it, as embedders want a document; `None` if either is zero
+pub fn fitted(tree: &usvg::Tree, width: u32, height: u32) -> Option<tiny_skia::Pixmap> {
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)?;
+    let size = tree.size();
+    let scale = (width as f32 / size.width()).min(height as f32 / size.height());
+    let (x, y) = (
+        (width as f32 - size.width() * scale) / 2.0,
+        (height as f32 - size.height() * scale) / 2.0,
+    );
+    let transform = tiny_skia::Transform::from_scale(scale, scale).post_translate(x, y);
+    resvg::render(tree, transform, &mut pixmap.as_mut());
+    Some(pixmap)
+}
+
+/// Write `pixmap` to `out` as straight (not premultiplied) RGBA bytes
+pub fn to_rgba(pixmap: &tiny_skia::Pixmap, out: &mut [u8]) {
+    for (px, rgba) in pixmap.pixels().iter().zip(out.chunks_exact_mut(4)) {
+        let c = px.demultiply();
+        rgba.copy_from_slice(&[c.red(), c.green(), c.blue(), c.alpha()]);
+    }
+}
+
+/// Rasterize `tree` repeated in a 3×3 grid around where `transform` puts
+/// it. Every copy is rendered at the same whole number of pixels and clipped
+/// to it, so seams only show if the artwork has them.
+pub fn render_tiled(
+    tree: &usvg::Tree,
+    width: u32,
+    height: u32,
+    transform: tiny_skia::Transform,
+    canvas: &Canvas,
+) -> tiny_skia::Pixmap {
+    let size = tree.size();
+    let tile_w = (size.width() * transform.sx).round().max(1.0);
+    let tile_h = (size.height() * transform.sy).round().max(1.0);
+    let tile = tiny_skia::Transform::from_scale(tile_w / size.width(), tile_h / size.height());
+    let (left, top) = (transform.tx.round(), transform.ty.round());
+
+    let mut pixmap = tiny_skia::Pixmap::new(width, height).unwrap();
+    pixmap.fill(canvas.background);
+    let bounds = tiny_skia::IntRect::from_xywh(0, 0, width, height).unwrap();
+    let paint = tiny_skia::PixmapPaint {
+        blend_mode: tiny_skia::BlendMode::Source,
+        ..Default::default()
+    };
+    for row in -1..=1 {
+        for col in -1..=1 {
+            let (x, y) = (left + col as f32 * tile_w, top + row as f32 * tile_h);
+            let Some(area) =
+                tiny_skia::IntRect::from_xywh(x as i32, y as i32, tile_w as u32, tile_h as u32)
+                    .and_then(|r| r.intersect(&bounds))
+            else {
+                continue;
+            };
+            let patch = render(tree, area, tile.post_translate(x, y), canvas);
+            pixmap.draw_pixmap(
+                area.x(),
+                area.y(),
+                patch.as_ref(),
+                &paint,
+                tiny_skia::Transform::identity(),
+                None,
+            );
+        }
+    }
+    pixmap
+}
+
+/// Draw the page's edge and fill; `page` is in window pixels and
+/// `pixmap` covers the window from `origin`
+fn draw_page(
+    pixmap: &mut tiny_skia::PixmapMut,
+    canvas: &Canvas,
+    origin: (i32, i32),
+    page: tiny_skia::Rect,
+) {
+    let to_pixmap = tiny_skia::Transform::from_translate(-origin.0 as f32, -origin.1 as f32);
+    let mut paint = tiny_skia::Paint::default();
+    match canvas.edge {
+        Edge::None => {}
+        Edge::Outline => {
+            // Mid grey shows against both light and dark backgrounds.
+            paint.set_color_rgba8(0x80, 0x80, 0x80, 0xFF);
+            if let Some(rect) = page.outset(0.5, 0.5) {
+                let path = tiny_skia::PathBuilder::from_rect(rect);
+                let stroke = tiny_skia::Stroke::default();
+                pixmap.stroke_path(&path, &paint, &stroke, to_pixmap, None);
+            }
+        }
+        Edge::Shadow => {
+            // Nested translucent rectangles darken towards the middle.
+            paint.set_color_rgba8(0, 0, 0, 0x14);
+            for i in 0..SHADOW_BLUR {
+                let d = i as f32;
+                if let Some(rect) = tiny_skia::Rect::from_ltrb(
+                    page.left() + SHADOW_OFFSET - d,
+                    page.top() + SHADOW_OFFSET - d,
+                    page.right() + SHADOW_OFFSET + d,
+                    page.bottom() + SHADOW_OFFSET + d,
+                ) {
+                    pixmap.fill_rect(rect, &paint, to_pixmap, None);
+                }
+            }
+        }
+    }
+    if let Some(color) = canvas.page {
+        paint.set_color(color);
+        pixmap.fill_rect(page, &paint, to_pixmap, None);
+    }
+}
+
+/// Draw grid lines at round document coordinates, leaving `page` clear.
+/// `pixmap` covers the window from `origin`.
+fn draw_world_grid(
+    pixmap: &mut tiny_skia::PixmapMut,
+    canvas: &Canvas,
+    origin: (i32, i32),
+    transform: tiny_skia::Transform,
+    page: Option<tiny_skia::Rect>,
+) {
+    let (width, height) = (pixmap.width() as f32, pixmap.height() as f32);
+    let mut paint = tiny_skia::Paint {
+        anti_alias: false,
+        ..Default::default()
+    };
+    let axes = [
+        (true, transform.tx - origin.0 as f32, transform.sx, width),
+        (false, transform.ty - origin.1 as f32, transform.sy, height),
+    ];
+    for (vertical, offset, scale, len) in axes {
+        let (major, minor) = crate::guides::ticks(scale);
+        let first = (-offset / scale / minor).ceil() as i64;
+        let last = ((len - offset) / scale / minor).floor() as i64;
+        let per_major = (major / minor).round() as i64;
+        for n in first..=last {
+            // Mid grey, faint enough not to compete with the document
+            let alpha = if n % per_major == 0 { 0x40 } else { 0x18 };
+            paint.set_color_rgba8(0x80, 0x80, 0x80, alpha);
+            let at = (offset + n as f32 * minor * scale).round();
+            let rect = if vertical {
+                tiny_skia::Rect::from_xywh(at, 0.0, 1.0, height)
+            } else {
+                tiny_skia::Rect::from_xywh(0.0, at, width, 1.0)
+            };
+            if let Some(rect) = rect {
+                pixmap.fill_rect(rect, &paint, tiny_skia::Transform::identity(), None);
+            }
+        }
+    }
+    // The grid is for finding your way around the page, not for showing
+    // through transparent parts of it.
+    if let Some(page) = page {
+        paint.set_color(canvas.background);
+        paint.blend_mode = tiny_skia::BlendMode::Source;
+        let to_pixmap = tiny_skia::Transform::from_translate(-origin.0 as f32, -origin.1 as f32);
+        pixmap.fill_rect(page, &paint, to_pixmap, None);
+    }
+}
+
+fn draw_backdrop(
+    pixmap: &mut tiny_skia::PixmapMut,
+    backdrop: &tiny_skia::Pixmap,
+    size: usvg::Size,
+    transform: tiny_skia::Transform,
+) {
+    let paint = tiny_skia::PixmapPaint {
+        quality: tiny_skia::FilterQuality::Bicubic,
+        ..Default::default()
+    };
+    let scale = transform.pre_scale(
+        size.width() / backdrop.width() as f32,
+        size.height() / backdrop.height() as f32,
+    );
+    pixmap.draw_pixmap(0, 0, backdrop.as_ref(), &paint, scale, None);
+}
+
+/// A rasterized document, kept so a reload only redraws what changed
+pub struct Frame {
+    pub pixmap: tiny_skia::Pixmap,
+    transform: tiny_skia::Transform,
+    canvas: Canvas,
+    /// Rendered at low resolution, to be replaced by a full render
+    pub coarse: bool,
+    /// Repeated in a grid, by [`render_tiled`]
+    pub tiled: bool,
+}
+
+impl Frame {
+    pub fn new(
+        tree: &usvg::Tree,
+        width: u32,
+        height: u32,
+        transform: tiny_skia::Transform,
+        canvas: &Canvas,
+    ) -> Self {
+        let area = tiny_skia::IntRect::from_xywh(0, 0, width, height).unwrap();
+        Self {
+            pixmap: render(tree, area, transform, canvas),
+            transform,
+            canvas: canvas.clone(),
+            coarse: false,
+            tiled: false,
+        }
+    }
+
+    /// [`Frame::new`], tiled as [`render_tiled`] does
+    pub fn tiled(
+        tree: &usvg::Tree,
+        width: u32,
+        height: u32,
+        transform: tiny_skia::Transform,
+        canvas: &Canvas,
+    ) -> Self {
+        Self {
+            pixmap: render_tiled(tree, width, height, transform, canvas),
+            transform,
+            canvas: canvas.clone(),
+            coarse: false,
+            tiled: true,
+        }
+    }
+
+    /// A quick, blurry stand-in for [`Frame::new`], rendered at a fraction
+    /// of the resolution and scaled up, to show while a slow document
+    /// renders in full
+    pub fn coarse(
+        tree: &usvg::Tree,
+        width: u32,
+        height: u32,
+        transform: tiny_skia::Transform,
+        canvas: &Canvas,
+    ) -> Self {
+        let shrink = 1.0 / COARSE as f32;
+        let small = Self::new(
+            tree,
+            width.div_ceil(COARSE),
+            height.div_ceil(COARSE),
+            transform.post_scale(shrink, shrink),
+            canvas,
+        );
+        Self {
+            pixmap: small.rescaled(width, height, transform),
+            transform,
+            canvas: canvas.clone(),
+            coarse: true,
+            tiled: false,
+        }
+    }
+
+    /// Whether the frame shows the document as it would be rendered with these parameters
+    pub fn matches(
+        &self,
+        width: u32,
+        height: u32,
+        transform: tiny_skia::Transform,
+        canvas: &Canvas,
+    ) -> bool {
+        self.pixmap.width() == width
+            && self.pixmap.height() == height
+            && self.transform == transform
+            && self.canvas == *canvas
+    }
+
+    /// Where the frame shows the document
+    pub fn transform(&self) -> tiny_skia::Transform {
+        self.transform
+    }
+
+    /// The frame resampled to where `transform` would put the document in a
+    /// `width` × `height` window, to stand in for a render while the window
+    /// is being resized. Cheap, but blurry when enlarged.
+    pub fn rescaled(
+        &self,
+        width: u32,
+        height: u32,
+        transform: tiny_skia::Transform,
+    ) -> tiny_skia::Pixmap {
+        let mut pixmap = tiny_skia::Pixmap::new(width, height).unwrap();
+        pixmap.fill(self.canvas.background);
+        if let Some(inverse) = self.transform.invert() {
+            let paint = tiny_skia::PixmapPaint {
+                quality: tiny_skia::FilterQuality::Bilinear,
+                ..Default::default()
+            };
+            let transform = transform.pre_concat(inverse);
+            pixmap.draw_pixmap(0, 0, self.pixmap.as_ref(), &paint, transform, None);
+        }
+        pixmap
+    }
+
+    /// Redraw the part of the frame covering `rect`, in document coordinates.
+    /// Antialiased edges may come out a shade different from a full render,
+    /// as they do when the window is resized.
+    pub fn repaint(&mut self, tree: &usvg::Tree, rect: tiny_skia::Rect) {
+        let bounds = tiny_skia::IntRect::from_xywh(0, 0, self.pixmap.width(), self.pixmap.height());
+        // Pad for antialiasing at the edges.
+        let Some(area) = rect
+            .transform(self.transform)
+            .and_then(|r| r.round_out())
+            .and_then(|r| r.make_outset(2, 2))
+            .zip(bounds)
+            .and_then(|(r, bounds)| r.intersect(&bounds))
+        else {
+            return;
+        };
+        let patch = render(tree, area, self.transform, &self.canvas);
+        let paint = tiny_skia::PixmapPaint {
+            blend_mode: tiny_skia::BlendMode::Source,
+            ..Default::default()
+        };
+        self.pixmap.draw_pixmap(
+            area.x(),
+            area.y(),
+            patch.as_ref(),
+            &paint,
+            tiny_skia::Transform::identity(),
+            None,
+        );
+    }
+}
+
+/// Pixels converted together, one to a lane: each step below runs across
+/// all of them at once, which compiles to SIMD on any target with it
+const LANES: usize = 8;
+/// Pixels per parallel job
+const JOB: usize = 1 << 16;
+
+/// Convert a (premultiplied) pixmap into minifb's `0RGB` buffer format, reusing `buffer`.
+/// Overlays and post-processing hooks draw on the pixmap with tiny-skia
+/// after it is composited, so this stays a pass of its own, run last.
+pub fn to_buffer(pixmap: &tiny_skia::Pixmap, background: tiny_skia::Color, buffer: &mut Vec<u32>) {
+    let bg = color::to_rgb(background);
+    buffer.resize(pixmap.pixels().len(), 0);
+    buffer
+        .par_chunks_mut(JOB)
+        .zip(pixmap.data().par_chunks(JOB * 4))
+        .for_each(|(out, data)| {
+            let mut blocks = out.chunks_exact_mut(LANES);
+            let mut src = data.chunks_exact(LANES * 4);
+            for (out, data) in (&mut blocks).zip(&mut src) {
+                let out: &mut [u32; LANES] = out.try_into().unwrap();
+                convert_lanes(data.try_into().unwrap(), out, bg);
+            }
+            let rest = src.remainder().chunks_exact(4);
+            for (o, px) in blocks.into_remainder().iter_mut().zip(rest) {
+                *o = unpremultiply(px, bg);
+            }
+        });
+}
+
+/// Convert `LANES` pixels of RGBA bytes. A block that is entirely opaque
+/// (the usual case, as frames start from an opaque background) needs no
+/// unpremultiply, only its bytes moved.
+fn convert_lanes(data: &[u8; LANES * 4], out: &mut [u32; LANES], bg: u32) {
+    let px: [u32; LANES] = std::array::from_fn(|i| {
+        u32::from_le_bytes([
+            data[4 * i],
+            data[4 * i + 1],
+            data[4 * i + 2],
+            data[4 * i + 3],
+        ])
+    });
+    let alphas = px.iter().fold(u32::MAX, |acc, p| acc & p) >> 24;
+    if alphas == 0xFF {
+        // Little-endian `ABGR` to `0RGB`: red and blue swap, green stays.
+        let red = px.map(|p| (p & 0xFF) << 16);
+        let green = px.map(|p| p & 0xFF00);
+        let blue = px.map(|p| (p >> 16) & 0xFF);
+        for i in 0..LANES {
+            out[i] = red[i] | green[i] | blue[i];
+        }
+    } else {
+        for (o, px) in out.iter_mut().zip(data.chunks_exact(4)) {
+            *o = unpremultiply(px, bg);
+        }
+    }
+}
+
+fn unpremultiply(px: &[u8], bg: u32) -> u32 {
+    let (r, g, b, a) = (px[0] as u32, px[1] as u32, px[2] as u32, px[3] as u32);
+    match (r * 255).checked_div(a) {
+        None => bg,
+        Some(r) => {
+            let r = r.min(255);
+            let g = (g * 255 / a).min(255);
+            let b = (b * 255 / a).min(255);
+            (r << 16) | (g << 8) | b
+        }
+    }
+}
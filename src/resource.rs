@@ -0,0 +1,155 @@
+//! Resolving what documents refer to, such as images, fonts and
+//! stylesheets, from wherever an embedder keeps them: a tar archive, a
+//! database or a server needing credentials rather than the filesystem
+
+use std::sync::Arc;
+
+use resvg::usvg::{self, ImageHrefResolver, roxmltree};
+
+/// Supplies the resources documents refer to. Anything it has no answer for
+/// is looked for as it would be without one: images on the filesystem and
+/// fonts among those loaded.
+pub trait ResourceLoader: Send + Sync {
+    /// The bytes behind `href` as written in the document, such as
+    /// `images/logo.png`, from an `<image>` or an `<?xml-stylesheet?>`
+    fn load(&self, href: &str) -> Option<Vec<u8>>;
+
+    /// A font file, such as TrueType or OpenType, for the family `name`,
+    /// asked for when no font loaded already has it. Its first face is used
+    /// if it names its family otherwise.
+    fn font(&self, _name: &str) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+/// Have `opts` ask `loader` for images and fonts. Stylesheets are read
+/// before parsing, by [`inline_stylesheets`].
+pub fn install(opts: &mut usvg::Options<'static>, loader: Arc<dyn ResourceLoader>) {
+    let images = Arc::clone(&loader);
+    let from_file = ImageHrefResolver::default_string_resolver();
+    let from_data = ImageHrefResolver::default_data_resolver();
+    opts.image_href_resolver.resolve_string = Box::new(move |href, opts| match images.load(href) {
+        Some(data) => from_data(mime(&data), Arc::new(data), opts),
+        None => from_file(href, opts),
+    });
+
+    #[cfg(feature = "text")]
+    {
+        use usvg::{FontFamily, FontResolver, fontdb};
+
+        let loaded = FontResolver::default_font_selector();
+        opts.font_resolver.select_font = Box::new(move |font, db| {
+            let has = |db: &fontdb::Database, id: fontdb::ID, name: &str| {
+                db.face(id).is_some_and(|face| {
+                    face.families
+                        .iter()
+                        .any(|(family, _)| family.eq_ignore_ascii_case(name))
+                })
+            };
+            // The first family that is loaded or can be is the one used;
+            // otherwise usvg's selector would settle for a serif fallback.
+            for family in font.families() {
+                let FontFamily::Named(name) = family else {
+                    break;
+                };
+                if db.faces().any(|face| has(db, face.id, name)) {
+                    break;
+                }
+                if let Some(data) = loader.font(name) {
+                    let source = fontdb::Source::Binary(Arc::new(data));
+                    let ids = Arc::make_mut(db).load_font_source(source);
+                    // A font that goes by another name can't be queried by this one.
+                    if !ids.iter().any(|&id| has(db, id, name)) {
+                        return ids.first().copied();
+                    }
+                    break;
+                }
+            }
+            loaded(font, db)
+        });
+    }
+}
+
+/// The type of image `data` holds, going by its first bytes, taking
+/// anything unrecognised to be SVG
+fn mime(data: &[u8]) -> &'static str {
+    match data {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [0xFF, 0xD8, 0xFF, ..] => "image/jpeg",
+        [b'G', b'I', b'F', b'8', ..] => "image/gif",
+        [
+            b'R',
+            b'I',
+            b'F',
+            b'F',
+            _,
+            _,
+            _,
+            _,
+            b'W',
+            b'E',
+            b'B',
+            b'P',
+            ..,
+        ] => "image/webp",
+        _ => "image/svg+xml",
+    }
+}
+
+/// `text`, the source of `doc`, with the CSS of each `<?xml-stylesheet?>`
+/// that `loader` can supply copied into a `<style>` element, or `None` if
+/// there is none to copy. usvg reads only stylesheets within the document.
+pub fn inline_stylesheets(
+    text: &str,
+    doc: &roxmltree::Document,
+    loader: &dyn ResourceLoader,
+) -> Option<String> {
+    let css: Vec<String> = doc
+        .root()
+        .children()
+        .filter_map(|node| node.pi())
+        .filter(|pi| pi.target == "xml-stylesheet")
+        .filter_map(|pi| {
+            let value = pi.value.unwrap_or_default();
+            let css = pseudo_attribute(value, "type").is_none_or(|t| t == "text/css");
+            let href = pseudo_attribute(value, "href").filter(|_| css)?;
+            let data = loader.load(href)?;
+            Some(String::from_utf8_lossy(&data).into_owned())
+        })
+        .collect();
+    if css.is_empty() {
+        return None;
+    }
+    with_style(text, doc, &css.join("\n"))
+}
+
+/// `text`, the source of `doc`, with `css` in a `<style>` element at the end
+/// of the root, or `None` if the root closes itself and so has nothing to
+/// style
+pub fn with_style(text: &str, doc: &roxmltree::Document, css: &str) -> Option<String> {
+    // Before the root's end tag
+    let root = doc.root_element().range();
+    let end = text[root.clone()].rfind("</")? + root.start;
+    let css = css.replace("]]>", "]]]]><![CDATA[>");
+    Some(format!(
+        r#"{}<style xmlns="http://www.w3.org/2000/svg"><![CDATA[{css}]]></style>{}"#,
+        &text[..end],
+        &text[end..]
+    ))
+}
+
+/// The value of pseudo-attribute `name` in a processing instruction's
+/// `value`, such as `href` in `href="a.css" type="text/css"`
+fn pseudo_attribute<'a>(value: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = value;
+    while let Some((key, after)) = rest.split_once('=') {
+        let after = after.trim_start();
+        let quote = after.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+        let (found, after) = after[1..].split_once(quote)?;
+        if key.trim() == name {
+            return Some(found);
+        }
+        rest = after;
+    }
+    None
+}
@@ -0,0 +1,72 @@
+//! Review images: the view as shown, review marks and all, above a caption
+//! saying what it shows, ready to paste into a ticket
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use resvg::tiny_skia::{Color, IntSize, Pixmap, PixmapPaint, Transform};
+use resvg::usvg;
+
+use crate::overlay;
+
+/// Height of each line of the caption, and the margin around them
+const LINE_HEIGHT: u32 = 20;
+const MARGIN: u32 = 8;
+
+/// Where the review image for `doc` goes by default: `NAME-review.png`
+/// beside it
+pub fn default_path(doc: &Path) -> PathBuf {
+    let stem = doc.file_stem().unwrap_or_default().to_string_lossy();
+    doc.with_file_name(format!("{stem}-review.png"))
+}
+
+/// `view` with `lines` of caption in a band beneath it
+pub fn with_caption(view: &Pixmap, lines: &[String], opts: &usvg::Options) -> Option<Pixmap> {
+    let band = lines.len() as u32 * LINE_HEIGHT + 2 * MARGIN;
+    let size = IntSize::from_wh(view.width(), view.height() + band)?;
+    let mut pixmap = Pixmap::new(size.width(), size.height())?;
+    pixmap.fill(Color::from_rgba8(0x22, 0x22, 0x22, 0xFF));
+    pixmap.draw_pixmap(
+        0,
+        0,
+        view.as_ref(),
+        &PixmapPaint::default(),
+        Transform::identity(),
+        None,
+    );
+    for (i, line) in lines.iter().enumerate() {
+        let y = view.height() + MARGIN + i as u32 * LINE_HEIGHT;
+        overlay::text(
+            &mut pixmap,
+            (MARGIN as f32, y as f32),
+            line,
+            Color::WHITE,
+            opts,
+        );
+    }
+    Some(pixmap)
+}
+
+/// `time` as `YYYY-MM-DD HH:MM:SS UTC`
+pub fn utc(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+    // Days since 1970-01-01 to a civil date, after Howard Hinnant's
+    // `civil_from_days`, with years starting in March
+    let days = days as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
@@ -0,0 +1,272 @@
+//! JSON-RPC 2.0 control server (`--rpc ADDR`), enabled with the `rpc` feature.
+//!
+//! Requests and responses are newline-delimited JSON over TCP. Methods:
+//!
+//! - `open {"path"}`, `reload`
+//! - `setView {"zoom"?, "pan"?: [x, y], "fit"?: bool}`
+//! - `highlight {"rule"}`: outline or tint elements by id, as `--highlight`
+//!   does; `clearHighlights` removes every rule
+//! - `hide {"selector"}`, `solo {"selector"}`: leave out the elements matching
+//!   `#id`, `.class` or a tag name, or everything else; `showAll` undoes both
+//! - `screenshot {"path"}`: save the current view as a PNG
+//! - `exportReview {"path"}`: the same with the review marks drawn in and a
+//!   caption naming the file, time and zoom
+//! - `getState`: path, document and window size, zoom, pan and fit state
+//! - `subscribe`: receive `event` notifications (`loaded`, `reloaded`, `loadFailed`,
+//!   `viewChanged`) on this connection
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{Value, json};
+
+use crate::action::{Action, Event, Snapshot};
+use crate::state;
+use crate::viewer::ActionSender;
+
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+struct RpcError(i64, String);
+
+type Writer = Arc<Mutex<TcpStream>>;
+
+/// Start accepting connections on `addr` in the background
+pub fn serve(addr: &str, tx: ActionSender) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let local = listener.local_addr()?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                if let Err(e) = handle_connection(stream, tx) {
+                    eprintln!("rpc: {e}");
+                }
+            });
+        }
+    });
+    Ok(local)
+}
+
+fn handle_connection(stream: TcpStream, tx: ActionSender) -> io::Result<()> {
+    let writer: Writer = Arc::new(Mutex::new(stream.try_clone()?));
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(Value::Array(batch)) if !batch.is_empty() => {
+                let replies: Vec<Value> = batch
+                    .iter()
+                    .filter_map(|req| handle_request(req, &tx, &writer))
+                    .collect();
+                (!replies.is_empty()).then_some(Value::Array(replies))
+            }
+            Ok(req) => handle_request(&req, &tx, &writer),
+            Err(e) => Some(error(Value::Null, RpcError(PARSE_ERROR, e.to_string()))),
+        };
+        if let Some(response) = response {
+            send_line(&writer, &response)?;
+        }
+    }
+    Ok(())
+}
+
+/// Handle a single request, returning `None` for notifications
+fn handle_request(req: &Value, tx: &ActionSender, writer: &Writer) -> Option<Value> {
+    let id = req.get("id").cloned();
+    let method = req.get("method").and_then(Value::as_str);
+    let result = match (req.get("jsonrpc").and_then(Value::as_str), method) {
+        (Some("2.0"), Some(method)) => {
+            let params = req.get("params").cloned().unwrap_or(Value::Null);
+            call(method, &params, tx, writer)
+        }
+        _ => Err(RpcError(INVALID_REQUEST, "invalid request".into())),
+    };
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => error(id, e),
+    })
+}
+
+fn call(
+    method: &str,
+    params: &Value,
+    tx: &ActionSender,
+    writer: &Writer,
+) -> Result<Value, RpcError> {
+    let send = |action: Action| {
+        tx.send(action)
+            .map_err(|_| RpcError(SERVER_ERROR, "viewer has exited".into()))
+    };
+    let path = || {
+        params
+            .get("path")
+            .and_then(Value::as_str)
+            .map(PathBuf::from)
+            .ok_or_else(|| RpcError(INVALID_PARAMS, "expected a \"path\" string".into()))
+    };
+
+    match method {
+        "open" => send(Action::Open(path()?))?,
+        "reload" => send(Action::Reload)?,
+        "setView" => {
+            // Checked whole before any of it is applied
+            let zoom = params
+                .get("zoom")
+                .map(|zoom| {
+                    let zoom = zoom.as_f64().ok_or_else(|| {
+                        RpcError(INVALID_PARAMS, "zoom must be a positive number".into())
+                    })?;
+                    state::valid_zoom(zoom as f32).map_err(|e| RpcError(INVALID_PARAMS, e))
+                })
+                .transpose()?;
+            let pan = params
+                .get("pan")
+                .map(|pan| {
+                    let (x, y) = pan
+                        .as_array()
+                        .and_then(|p| {
+                            Some((p.first()?.as_f64()? as f32, p.get(1)?.as_f64()? as f32))
+                        })
+                        .ok_or_else(|| RpcError(INVALID_PARAMS, "pan must be [x, y]".into()))?;
+                    state::valid_pan(x, y).map_err(|e| RpcError(INVALID_PARAMS, e))
+                })
+                .transpose()?;
+            if params.get("fit").and_then(Value::as_bool) == Some(true) {
+                send(Action::Reset)?;
+            }
+            if let Some(zoom) = zoom {
+                send(Action::SetZoom(zoom))?;
+            }
+            if let Some((x, y)) = pan {
+                send(Action::SetPan(x, y))?;
+            }
+        }
+        "highlight" => {
+            let rule = params
+                .get("rule")
+                .and_then(Value::as_str)
+                .ok_or_else(|| RpcError(INVALID_PARAMS, "expected a \"rule\" string".into()))?
+                .parse()
+                .map_err(|e| RpcError(INVALID_PARAMS, e))?;
+            send(Action::Highlight(rule))?;
+        }
+        "clearHighlights" => send(Action::ClearHighlights)?,
+        "hide" | "solo" => {
+            let selector = params
+                .get("selector")
+                .and_then(Value::as_str)
+                .ok_or_else(|| RpcError(INVALID_PARAMS, "expected a \"selector\" string".into()))?
+                .parse()
+                .map_err(|e| RpcError(INVALID_PARAMS, e))?;
+            send(match method {
+                "hide" => Action::Hide(selector),
+                _ => Action::Solo(selector),
+            })?;
+        }
+        "showAll" => send(Action::ShowAll)?,
+        "screenshot" => {
+            let (reply, rx) = mpsc::channel();
+            send(Action::Screenshot(path()?, Some(reply)))?;
+            wait(rx)?.map_err(|e| RpcError(SERVER_ERROR, e))?;
+        }
+        "exportReview" => {
+            let (reply, rx) = mpsc::channel();
+            send(Action::ExportReview(path()?, Some(reply)))?;
+            wait(rx)?.map_err(|e| RpcError(SERVER_ERROR, e))?;
+        }
+        "getState" => {
+            let (reply, rx) = mpsc::channel();
+            send(Action::GetState(reply))?;
+            return Ok(snapshot_json(&wait(rx)?));
+        }
+        "subscribe" => {
+            let (events, rx) = mpsc::channel();
+            send(Action::Subscribe(events))?;
+            let writer = writer.clone();
+            thread::spawn(move || {
+                for event in rx {
+                    let notification = json!({
+                        "jsonrpc": "2.0",
+                        "method": "event",
+                        "params": event_json(&event),
+                    });
+                    if send_line(&writer, &notification).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        _ => {
+            return Err(RpcError(
+                METHOD_NOT_FOUND,
+                format!("unknown method '{method}'"),
+            ));
+        }
+    }
+    Ok(Value::Bool(true))
+}
+
+fn wait<T>(rx: mpsc::Receiver<T>) -> Result<T, RpcError> {
+    rx.recv_timeout(REPLY_TIMEOUT)
+        .map_err(|_| RpcError(SERVER_ERROR, "viewer did not respond".into()))
+}
+
+fn error(id: Value, RpcError(code, message): RpcError) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn send_line(writer: &Writer, value: &Value) -> io::Result<()> {
+    let mut stream = writer.lock().unwrap();
+    writeln!(stream, "{value}")?;
+    stream.flush()
+}
+
+fn snapshot_json(s: &Snapshot) -> Value {
+    json!({
+        "path": s.path.display().to_string(),
+        "docSize": s.doc_size.map(|(w, h)| [w, h]),
+        "windowSize": [s.window_size.0, s.window_size.1],
+        "zoom": s.zoom,
+        "pan": [s.pan.0, s.pan.1],
+        "fitScale": s.fit_scale,
+        "autoFit": s.auto_fit,
+    })
+}
+
+fn event_json(event: &Event) -> Value {
+    match event {
+        Event::Loaded(path) => json!({ "type": "loaded", "path": path.display().to_string() }),
+        Event::Reloaded(path) => {
+            json!({ "type": "reloaded", "path": path.display().to_string() })
+        }
+        Event::LoadFailed(path, message) => json!({
+            "type": "loadFailed",
+            "path": path.display().to_string(),
+            "message": message,
+        }),
+        Event::ViewChanged { zoom, pan } => {
+            json!({ "type": "viewChanged", "zoom": zoom, "pan": [pan.0, pan.1] })
+        }
+        Event::Key(key) => json!({ "type": "key", "key": key }),
+        Event::Click { button, at, point } => json!({
+            "type": "click",
+            "button": format!("{button:?}").to_lowercase(),
+            "at": [at.0, at.1],
+            "point": point.map(|(x, y)| [x, y]),
+        }),
+    }
+}
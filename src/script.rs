@@ -0,0 +1,169 @@
+//! Lua scripting hooks, enabled with the `lua` feature.
+//!
+//! A script may define any of the global functions `on_load(info)`, `on_reload(info)` and
+//! `on_key(key)`, and drive the viewer through the `svgtail` table:
+//!
+//! ```lua
+//! function on_reload(info)
+//!     svgtail.clear_overlays()
+//!     svgtail.draw_text(0, 0, info.path, "#ff0000")
+//!     svgtail.focus("node1")
+//! end
+//! ```
+
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+use mlua::{Function, Lua, Table};
+
+use crate::action::Action;
+use crate::color;
+use crate::hooks::{DocInfo, Hooks};
+use crate::overlay::Shape;
+
+pub struct Script {
+    lua: Lua,
+    actions: Rc<RefCell<Vec<Action>>>,
+}
+
+impl Script {
+    pub fn load(path: &Path) -> mlua::Result<Self> {
+        let lua = Lua::new();
+        let actions = Rc::new(RefCell::new(Vec::new()));
+        lua.globals().set("svgtail", api(&lua, &actions)?)?;
+
+        let source = std::fs::read_to_string(path)?;
+        lua.load(source)
+            .set_name(path.display().to_string())
+            .exec()?;
+
+        Ok(Self { lua, actions })
+    }
+
+    fn call<A: mlua::IntoLuaMulti>(&self, name: &str, args: A) {
+        let result = self
+            .lua
+            .globals()
+            .get::<Option<Function>>(name)
+            .and_then(|f| f.map_or(Ok(()), |f| f.call::<()>(args)));
+        if let Err(e) = result {
+            eprintln!("script error in {name}: {e}");
+        }
+    }
+
+    fn info_table(&self, info: &DocInfo) -> mlua::Result<Table> {
+        let t = self.lua.create_table()?;
+        t.set("path", info.path.display().to_string())?;
+        t.set("width", info.width)?;
+        t.set("height", info.height)?;
+        t.set("has_text", info.has_text)?;
+        Ok(t)
+    }
+}
+
+impl Hooks for Script {
+    fn on_load(&mut self, info: &DocInfo) {
+        match self.info_table(info) {
+            Ok(t) => self.call("on_load", t),
+            Err(e) => eprintln!("script error: {e}"),
+        }
+    }
+
+    fn on_reload(&mut self, info: &DocInfo) {
+        match self.info_table(info) {
+            Ok(t) => self.call("on_reload", t),
+            Err(e) => eprintln!("script error: {e}"),
+        }
+    }
+
+    fn on_key(&mut self, key: &str) {
+        self.call("on_key", key);
+    }
+
+    fn take_actions(&mut self) -> Vec<Action> {
+        std::mem::take(&mut self.actions.borrow_mut())
+    }
+}
+
+/// Build the `svgtail` table; every function queues an [`Action`]
+fn api(lua: &Lua, actions: &Rc<RefCell<Vec<Action>>>) -> mlua::Result<Table> {
+    let t = lua.create_table()?;
+
+    let push = |action: Action, actions: &Rc<RefCell<Vec<Action>>>| {
+        actions.borrow_mut().push(action);
+        Ok(())
+    };
+    let parse_color = |s: String| color::parse(&s).map_err(mlua::Error::runtime);
+
+    let a = actions.clone();
+    t.set(
+        "set_zoom",
+        lua.create_function(move |_, zoom: f32| push(Action::SetZoom(zoom), &a))?,
+    )?;
+
+    let a = actions.clone();
+    t.set(
+        "set_pan",
+        lua.create_function(move |_, (x, y): (f32, f32)| push(Action::SetPan(x, y), &a))?,
+    )?;
+
+    let a = actions.clone();
+    t.set(
+        "set_background",
+        lua.create_function(move |_, c: String| push(Action::SetBackground(parse_color(c)?), &a))?,
+    )?;
+
+    let a = actions.clone();
+    t.set(
+        "focus",
+        lua.create_function(move |_, id: String| push(Action::Focus(id), &a))?,
+    )?;
+
+    let a = actions.clone();
+    t.set(
+        "draw_rect",
+        lua.create_function(move |_, (x, y, w, h, c): (f32, f32, f32, f32, String)| {
+            let color = parse_color(c)?;
+            push(Action::Draw(Shape::Rect { x, y, w, h, color }), &a)
+        })?,
+    )?;
+
+    let a = actions.clone();
+    t.set(
+        "draw_line",
+        lua.create_function(
+            move |_, (x1, y1, x2, y2, c): (f32, f32, f32, f32, String)| {
+                let color = parse_color(c)?;
+                let line = Shape::Line {
+                    from: (x1, y1),
+                    to: (x2, y2),
+                    color,
+                };
+                push(Action::Draw(line), &a)
+            },
+        )?,
+    )?;
+
+    let a = actions.clone();
+    t.set(
+        "draw_text",
+        lua.create_function(move |_, (x, y, text, c): (f32, f32, String, String)| {
+            let color = parse_color(c)?;
+            let text = Shape::Text {
+                at: (x, y),
+                text,
+                color,
+            };
+            push(Action::Draw(text), &a)
+        })?,
+    )?;
+
+    let a = actions.clone();
+    t.set(
+        "clear_overlays",
+        lua.create_function(move |_, ()| push(Action::ClearOverlays, &a))?,
+    )?;
+
+    Ok(t)
+}
@@ -0,0 +1,195 @@
+//! Finding text in the document: `/` opens a bar for a query, matched
+//! case-insensitively against the content of each text element as it is
+//! typed, with every match outlined and the view centred on one at a time
+
+use resvg::tiny_skia::{Color, Paint, Pixmap, Rect, Transform};
+use resvg::usvg;
+
+use crate::overlay::{self, Shape};
+use crate::window::Key;
+
+/// Height of the bar the query is typed into, along the window's bottom
+const BAR_HEIGHT: f32 = 26.0;
+
+pub struct Search {
+    pub query: String,
+    /// Where the matching text elements are, in document coordinates and
+    /// document order
+    matches: Vec<Rect>,
+    current: usize,
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Self {
+            query: String::new(),
+            matches: Vec::new(),
+            current: 0,
+        }
+    }
+
+    /// Look for the query in `tree` afresh, staying at the same place among
+    /// the matches where there are still enough
+    pub fn find(&mut self, tree: &usvg::Tree) {
+        let query = self.query.to_lowercase();
+        self.matches.clear();
+        if !query.is_empty() {
+            collect(tree.root(), &query, &mut self.matches);
+        }
+        self.current = self.current.min(self.matches.len().saturating_sub(1));
+    }
+
+    /// The match shown, if any
+    pub fn current(&self) -> Option<Rect> {
+        self.matches.get(self.current).copied()
+    }
+
+    /// Move to the next match, or the previous one, wrapping around
+    pub fn step(&mut self, forward: bool) -> Option<Rect> {
+        let n = self.matches.len();
+        if n == 0 {
+            return None;
+        }
+        self.current = match forward {
+            true => (self.current + 1) % n,
+            false => (self.current + n - 1) % n,
+        };
+        self.current()
+    }
+
+    /// Start again from the first match, as the query changes
+    pub fn restart(&mut self) {
+        self.current = 0;
+    }
+
+    /// Outline the matches, the current one brighter, mapping document
+    /// coordinates through `transform`, and draw the bar
+    pub fn draw(&self, pixmap: &mut Pixmap, transform: Transform, opts: &usvg::Options) {
+        let others = Color::from_rgba8(0xFF, 0xDD, 0x00, 0xFF);
+        let current = Color::from_rgba8(0xFF, 0x55, 0x00, 0xFF);
+        let shapes: Vec<Shape> = self
+            .matches
+            .iter()
+            .enumerate()
+            .map(|(i, rect)| Shape::Rect {
+                x: rect.x(),
+                y: rect.y(),
+                w: rect.width(),
+                h: rect.height(),
+                color: if i == self.current { current } else { others },
+            })
+            .collect();
+        overlay::draw(pixmap, &shapes, transform, opts);
+
+        let (width, height) = (pixmap.width() as f32, pixmap.height() as f32);
+        let top = height - BAR_HEIGHT;
+        let mut paint = Paint::default();
+        paint.set_color_rgba8(0x22, 0x22, 0x22, 0xE8);
+        if let Some(rect) = Rect::from_xywh(0.0, top, width, BAR_HEIGHT) {
+            pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+        }
+        let found = match (self.query.is_empty(), self.matches.len()) {
+            (true, _) => String::new(),
+            (false, 0) => "no matches".to_string(),
+            (false, n) => format!("{} of {n}", self.current + 1),
+        };
+        let line = format!("/{}▏", self.query);
+        overlay::text(pixmap, (8.0, top + 4.0), &line, Color::WHITE, opts);
+        let x = width - 8.0 - found.chars().count() as f32 * 8.0;
+        let grey = Color::from_rgba8(0xAA, 0xAA, 0xAA, 0xFF);
+        overlay::text(pixmap, (x, top + 4.0), &found, grey, opts);
+    }
+}
+
+/// The bounds of the text elements under `group` whose content contains
+/// `query`, given in lower case
+fn collect(group: &usvg::Group, query: &str, matches: &mut Vec<Rect>) {
+    for node in group.children() {
+        match node {
+            usvg::Node::Group(g) => collect(g, query, matches),
+            usvg::Node::Text(text) => {
+                let content: String = text.chunks().iter().map(|c| c.text()).collect();
+                if content.to_lowercase().contains(query) {
+                    matches.push(text.abs_bounding_box());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The character `key` types on a US layout, with `shift` held or not
+pub fn typed(key: Key, shift: bool) -> Option<char> {
+    let (plain, shifted) = match key {
+        Key::A => ('a', 'A'),
+        Key::B => ('b', 'B'),
+        Key::C => ('c', 'C'),
+        Key::D => ('d', 'D'),
+        Key::E => ('e', 'E'),
+        Key::F => ('f', 'F'),
+        Key::G => ('g', 'G'),
+        Key::H => ('h', 'H'),
+        Key::I => ('i', 'I'),
+        Key::J => ('j', 'J'),
+        Key::K => ('k', 'K'),
+        Key::L => ('l', 'L'),
+        Key::M => ('m', 'M'),
+        Key::N => ('n', 'N'),
+        Key::O => ('o', 'O'),
+        Key::P => ('p', 'P'),
+        Key::Q => ('q', 'Q'),
+        Key::R => ('r', 'R'),
+        Key::S => ('s', 'S'),
+        Key::T => ('t', 'T'),
+        Key::U => ('u', 'U'),
+        Key::V => ('v', 'V'),
+        Key::W => ('w', 'W'),
+        Key::X => ('x', 'X'),
+        Key::Y => ('y', 'Y'),
+        Key::Z => ('z', 'Z'),
+        Key::Key0 => ('0', ')'),
+        Key::Key1 => ('1', '!'),
+        Key::Key2 => ('2', '@'),
+        Key::Key3 => ('3', '#'),
+        Key::Key4 => ('4', '$'),
+        Key::Key5 => ('5', '%'),
+        Key::Key6 => ('6', '^'),
+        Key::Key7 => ('7', '&'),
+        Key::Key8 => ('8', '*'),
+        Key::Key9 => ('9', '('),
+        Key::NumPad0 => ('0', '0'),
+        Key::NumPad1 => ('1', '1'),
+        Key::NumPad2 => ('2', '2'),
+        Key::NumPad3 => ('3', '3'),
+        Key::NumPad4 => ('4', '4'),
+        Key::NumPad5 => ('5', '5'),
+        Key::NumPad6 => ('6', '6'),
+        Key::NumPad7 => ('7', '7'),
+        Key::NumPad8 => ('8', '8'),
+        Key::NumPad9 => ('9', '9'),
+        Key::Space => (' ', ' '),
+        Key::Minus => ('-', '_'),
+        Key::NumPadMinus => ('-', '-'),
+        Key::Equal => ('=', '+'),
+        Key::NumPadPlus => ('+', '+'),
+        Key::Period => ('.', '>'),
+        Key::NumPadDot => ('.', '.'),
+        Key::Comma => (',', '<'),
+        Key::Slash => ('/', '?'),
+        Key::NumPadSlash => ('/', '/'),
+        Key::Semicolon => (';', ':'),
+        Key::Apostrophe => ('\'', '"'),
+        Key::LeftBracket => ('[', '{'),
+        Key::RightBracket => (']', '}'),
+        Key::Backslash => ('\\', '|'),
+        Key::Backquote => ('`', '~'),
+        _ => return None,
+    };
+    Some(if shift { shifted } else { plain })
+}
@@ -0,0 +1,87 @@
+//! Hiding elements, or showing only some of them, by a simple selector: an
+//! id (`#legend`), a class (`.edge`) or a tag name (`text`). What is left
+//! out is styled away as the document is parsed, so it stays out through
+//! reloads, and the file itself is left alone.
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Selector {
+    Id(String),
+    Class(String),
+    Tag(String),
+}
+
+impl FromStr for Selector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (make, name): (fn(String) -> Selector, &str) = match s.trim() {
+            s if s.starts_with('#') => (Selector::Id, &s[1..]),
+            s if s.starts_with('.') => (Selector::Class, &s[1..]),
+            s => (Selector::Tag, s),
+        };
+        // Anything else could carry CSS of its own into the stylesheet.
+        let plain = |c: char| c.is_alphanumeric() || matches!(c, '-' | '_');
+        if name.is_empty() || !name.chars().all(plain) {
+            return Err(format!("'{s}': expected #id, .class or a tag name"));
+        }
+        Ok(make(name.to_string()))
+    }
+}
+
+impl fmt::Display for Selector {
+    /// As CSS
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Selector::Id(id) => write!(f, "#{id}"),
+            Selector::Class(class) => write!(f, ".{class}"),
+            Selector::Tag(tag) => write!(f, "{tag}"),
+        }
+    }
+}
+
+/// Which elements of a document are shown
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Shown {
+    /// Left out, even if soloed
+    pub hidden: Vec<Selector>,
+    /// If any, only these are shown, with what they contain
+    pub solo: Vec<Selector>,
+}
+
+impl Shown {
+    /// A stylesheet leaving out what isn't shown, taking precedence over
+    /// the document's own styles, or `None` if everything is
+    pub fn css(&self) -> Option<String> {
+        let mut css = String::new();
+        let list = |selectors: &[Selector], descendants: bool| {
+            selectors
+                .iter()
+                .flat_map(|s| {
+                    let all = descendants.then(|| format!("{s} *"));
+                    std::iter::once(s.to_string()).chain(all)
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        if !self.hidden.is_empty() {
+            css += &format!(
+                "{} {{ display: none !important }}\n",
+                list(&self.hidden, false)
+            );
+        }
+        if !self.solo.is_empty() {
+            // Visibility is inherited, but unlike display can be given back.
+            // usvg applies `*` before anything more specific, and of two
+            // important declarations keeps the first, so only these are.
+            css += "* { visibility: hidden }\n";
+            css += &format!(
+                "{} {{ visibility: visible !important }}\n",
+                list(&self.solo, true)
+            );
+        }
+        (!css.is_empty()).then_some(css)
+    }
+}
@@ -0,0 +1,170 @@
+//! Serving finished frames to other processes, such as an OBS plugin or a
+//! game engine, through a shared memory file they map and read in place.
+//!
+//! The file starts with a 64-byte header of native-endian fields:
+//!
+//! | offset | type     | field                                               |
+//! |--------|----------|-----------------------------------------------------|
+//! | 0      | `[u8;8]` | magic, `SVGTSHM\0`                                  |
+//! | 8      | `u32`    | version, 1                                          |
+//! | 12     | `u32`    | header size, 64                                     |
+//! | 16     | `u64`    | sequence, odd while the fields below are updated    |
+//! | 24     | `u64`    | frame number, counting from 1                       |
+//! | 32     | `u32`    | width                                               |
+//! | 36     | `u32`    | height                                              |
+//! | 40     | `u32`    | stride, bytes per row                               |
+//! | 44     | `u32`    | slot holding the frame, 0 or 1                      |
+//! | 48     | `u64`    | slot size; slot `i` starts at `64 + i * slot size`  |
+//!
+//! Pixels are native-endian `u32`s of `0x00RRGGBB`, which is `BGRX` bytes on
+//! little-endian machines. Each frame is written to the slot not holding the
+//! last one, so a reader has a whole frame's time to use it. Readers should
+//! read the fields until the sequence is even and unchanged either side, and
+//! map the file again when the slot size grows.
+//!
+//! Clients connected to the socket beside it, the file's path with `.sock`
+//! appended, are sent each new frame number as eight native-endian bytes.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering, fence};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use memmap2::MmapMut;
+
+use crate::sink::Sink;
+
+const MAGIC: &[u8; 8] = b"SVGTSHM\0";
+const VERSION: u32 = 1;
+const HEADER: usize = 64;
+
+pub struct Shm {
+    path: PathBuf,
+    file: File,
+    map: MmapMut,
+    slot_size: usize,
+    frame: u64,
+    /// Clients waiting to hear of new frames
+    clients: Arc<Mutex<Vec<UnixStream>>>,
+    socket: PathBuf,
+}
+
+impl Shm {
+    /// Create the file at `path`, such as `/dev/shm/svgtail`, and its socket
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(HEADER as u64)?;
+        // SAFETY: the file is ours; readers only read it.
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+        map[..8].copy_from_slice(MAGIC);
+        map[8..12].copy_from_slice(&VERSION.to_ne_bytes());
+        map[12..16].copy_from_slice(&(HEADER as u32).to_ne_bytes());
+
+        let mut socket = path.as_os_str().to_owned();
+        socket.push(".sock");
+        let socket = PathBuf::from(socket);
+        // Left behind by an instance that didn't exit cleanly
+        let _ = fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket)?;
+        let clients = Arc::new(Mutex::new(Vec::new()));
+        let accepted = Arc::clone(&clients);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // A client that stops reading misses frames rather than
+                // holding up the viewer.
+                if stream.set_nonblocking(true).is_ok() {
+                    accepted.lock().unwrap().push(stream);
+                }
+            }
+        });
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            map,
+            slot_size: 0,
+            frame: 0,
+            clients,
+            socket,
+        })
+    }
+
+    fn u32_at(&self, offset: usize) -> &AtomicU32 {
+        // SAFETY: the offset is within the header, which is page aligned.
+        unsafe { &*(self.map.as_ptr().add(offset) as *const AtomicU32) }
+    }
+
+    fn u64_at(&self, offset: usize) -> &AtomicU64 {
+        // SAFETY: as for `u32_at`
+        unsafe { &*(self.map.as_ptr().add(offset) as *const AtomicU64) }
+    }
+
+    /// Tell connected clients of the latest frame, dropping those gone
+    fn notify(&self) {
+        let message = self.frame.to_ne_bytes();
+        self.clients
+            .lock()
+            .unwrap()
+            .retain_mut(|client| match client.write(&message) {
+                Ok(_) => true,
+                Err(e) => e.kind() == io::ErrorKind::WouldBlock,
+            });
+    }
+}
+
+impl Sink for Shm {
+    fn name(&self) -> &str {
+        "shm"
+    }
+
+    fn push(&mut self, frame: &[u32], width: usize) -> io::Result<()> {
+        let bytes = frame.len() * 4;
+        if bytes > self.slot_size {
+            self.file.set_len((HEADER + 2 * bytes) as u64)?;
+            // SAFETY: as when created
+            self.map = unsafe { MmapMut::map_mut(&self.file)? };
+            self.slot_size = bytes;
+        }
+        let slot = match self.frame {
+            0 => 0,
+            _ => 1 - self.u32_at(44).load(Ordering::Relaxed),
+        };
+        let start = HEADER + slot as usize * self.slot_size;
+        for (px, out) in frame
+            .iter()
+            .zip(self.map[start..start + bytes].chunks_exact_mut(4))
+        {
+            out.copy_from_slice(&px.to_ne_bytes());
+        }
+
+        self.frame += 1;
+        let sequence = self.u64_at(16).load(Ordering::Relaxed);
+        self.u64_at(16).store(sequence + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        self.u64_at(24).store(self.frame, Ordering::Relaxed);
+        self.u32_at(32).store(width as u32, Ordering::Relaxed);
+        self.u32_at(36)
+            .store((frame.len() / width.max(1)) as u32, Ordering::Relaxed);
+        self.u32_at(40).store(width as u32 * 4, Ordering::Relaxed);
+        self.u32_at(44).store(slot, Ordering::Relaxed);
+        self.u64_at(48)
+            .store(self.slot_size as u64, Ordering::Relaxed);
+        self.u64_at(16).store(sequence + 2, Ordering::Release);
+        self.notify();
+        Ok(())
+    }
+}
+
+impl Drop for Shm {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+        let _ = fs::remove_file(&self.socket);
+    }
+}
@@ -0,0 +1,14 @@
+//! Places finished frames are sent to besides the window, such as an e-paper
+//! panel or another process
+
+use std::io;
+
+/// Receives each finished frame, once it is final: coarse frames and frames
+/// shown while the window is being resized are not sent
+pub trait Sink {
+    /// How the sink is referred to in errors, such as its option's name
+    fn name(&self) -> &str;
+
+    /// Take `frame`, `width` pixels wide in minifb's `0RGB` format
+    fn push(&mut self, frame: &[u32], width: usize) -> io::Result<()>;
+}
@@ -0,0 +1,440 @@
+use std::time::Duration;
+
+use resvg::{tiny_skia, usvg};
+
+use crate::window::{Key, Window};
+
+/// Pixels per second panned while a pan key is held (10 per frame at 60 fps)
+const PAN_SPEED: f32 = 600.0;
+/// Zoom factor per second while a zoom key is held (1.1 per frame at 60 fps)
+const ZOOM_SPEED: f32 = 304.5;
+
+#[derive(Clone, PartialEq)]
+pub struct State {
+    pub pan: (f32, f32),
+    pub zoom: f32,
+    pub auto_fit: bool,
+    pub fit_scale: f32,
+    /// How the document is fitted to the window
+    pub fit: Fit,
+    /// The last manual view given up for fitting, to return to
+    manual: Option<Manual>,
+}
+
+/// How a fitted document fills the window, from `--fit`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Fit {
+    /// All of it in view
+    #[default]
+    Contain,
+    /// Its width across the window
+    Width,
+    /// Its height down the window
+    Height,
+    /// One screen pixel per user unit, in the middle
+    #[value(name = "none")]
+    Unscaled,
+}
+
+/// Which part of the document stays where it is in the window as its size
+/// changes between reloads, along each axis. Along one without an anchor,
+/// the middle does, unless neither has one: then the document is fitted
+/// again.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Anchor {
+    pub x: Option<Horizontal>,
+    pub y: Option<Vertical>,
+}
+
+impl Anchor {
+    pub fn any(self) -> bool {
+        self.x.is_some() || self.y.is_some()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Horizontal {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Vertical {
+    Top,
+    Center,
+    Bottom,
+}
+
+impl Horizontal {
+    /// How far to pan, in halves of the growth, to hold this still
+    fn sign(self) -> f32 {
+        match self {
+            Horizontal::Left => -1.0,
+            Horizontal::Center => 0.0,
+            Horizontal::Right => 1.0,
+        }
+    }
+}
+
+impl Vertical {
+    fn sign(self) -> f32 {
+        match self {
+            Vertical::Top => -1.0,
+            Vertical::Center => 0.0,
+            Vertical::Bottom => 1.0,
+        }
+    }
+}
+
+/// A manual view, as pan, zoom and the fit scale the zoom was relative to
+#[derive(Clone, Copy, PartialEq)]
+struct Manual {
+    pan: (f32, f32),
+    zoom: f32,
+    fit_scale: f32,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self {
+            pan: (0.0, 0.0),
+            zoom: 1.0,
+            auto_fit: true,
+            fit_scale: 1.0,
+            fit: Fit::default(),
+            manual: None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        if !self.auto_fit {
+            self.remember_manual();
+        }
+        self.fit_again();
+    }
+
+    /// Switch between fitting the document to the window and a manual view:
+    /// the one last left for fitting if there is one, or else the fitted
+    /// view, kept as the window changes
+    pub fn toggle_fit(&mut self) {
+        if !self.auto_fit {
+            self.remember_manual();
+            self.fit_again();
+            return;
+        }
+        if let Some(manual) = self.manual.take() {
+            self.pan = manual.pan;
+            self.zoom = manual.zoom;
+            self.fit_scale = manual.fit_scale;
+        }
+        self.auto_fit = false;
+    }
+
+    /// Zoom by hand, refusing factors the view cannot be drawn at
+    pub fn set_zoom(&mut self, zoom: f32) -> Result<(), String> {
+        self.zoom = valid_zoom(zoom)?;
+        self.auto_fit = false;
+        Ok(())
+    }
+
+    /// Pan by hand, refusing offsets that are not numbers
+    pub fn set_pan(&mut self, x: f32, y: f32) -> Result<(), String> {
+        self.pan = valid_pan(x, y)?;
+        self.auto_fit = false;
+        Ok(())
+    }
+
+    /// Keep the manual view to come back to with [`Self::toggle_fit`]
+    fn remember_manual(&mut self) {
+        self.manual = Some(Manual {
+            pan: self.pan,
+            zoom: self.zoom,
+            fit_scale: self.fit_scale,
+        });
+    }
+
+    /// Fit the document to the window, centred
+    fn fit_again(&mut self) {
+        self.pan = (0.0, 0.0);
+        self.zoom = 1.0;
+        self.auto_fit = true;
+    }
+
+    pub fn update_fit_scale(&mut self, tree: &usvg::Tree, width: usize, height: usize) {
+        if self.auto_fit {
+            self.fit_scale = fit(self.fit, tree.size(), width, height);
+        }
+    }
+
+    /// Fit to a new window size even when not fitting, scaling the view with
+    /// the window so the same part of the document stays in view
+    pub fn refit(&mut self, tree: &usvg::Tree, width: usize, height: usize) {
+        let old = self.fit_scale;
+        self.fit_scale = fit(self.fit, tree.size(), width, height);
+        let ratio = self.fit_scale / old;
+        self.pan = (self.pan.0 * ratio, self.pan.1 * ratio);
+    }
+
+    /// Keep the view at its scale as the document goes from `old` to `new`
+    /// in size, holding the parts `anchor` names still in the window. Leaves
+    /// a view of a document the same size as it was, fitted or not.
+    pub fn anchor(
+        &mut self,
+        anchor: Anchor,
+        old: usvg::Size,
+        new: usvg::Size,
+        width: usize,
+        height: usize,
+    ) {
+        if old == new {
+            return;
+        }
+        let scale = self.fit_scale * self.zoom;
+        // An edge at `size * scale / 2 + pan` from the middle stays put.
+        let x = anchor.x.map_or(0.0, Horizontal::sign);
+        let y = anchor.y.map_or(0.0, Vertical::sign);
+        self.pan.0 -= x * (new.width() - old.width()) * scale / 2.0;
+        self.pan.1 -= y * (new.height() - old.height()) * scale / 2.0;
+        self.fit_scale = fit(self.fit, new, width, height);
+        self.zoom = scale / self.fit_scale;
+        self.auto_fit = false;
+    }
+
+    /// Keep the view at its scale as an element moves from `old` to `new`
+    /// (in document coordinates) and the document from `old_size` to
+    /// `new_size`, holding the element's middle where it was in the window.
+    /// Leaves the view be if neither moved.
+    pub fn hold(
+        &mut self,
+        (old, old_size): (usvg::Rect, usvg::Size),
+        (new, new_size): (usvg::Rect, usvg::Size),
+        width: usize,
+        height: usize,
+    ) {
+        if old == new && old_size == new_size {
+            return;
+        }
+        let scale = self.fit_scale * self.zoom;
+        let middle = |r: usvg::Rect| (r.x() + r.width() / 2.0, r.y() + r.height() / 2.0);
+        let (old_x, old_y) = middle(old);
+        let (new_x, new_y) = middle(new);
+        // A point at `(p - size / 2) * scale + pan` from the window's middle
+        self.pan.0 += ((new_size.width() - old_size.width()) / 2.0 - (new_x - old_x)) * scale;
+        self.pan.1 += ((new_size.height() - old_size.height()) / 2.0 - (new_y - old_y)) * scale;
+        self.fit_scale = fit(self.fit, new_size, width, height);
+        self.zoom = scale / self.fit_scale;
+        self.auto_fit = false;
+    }
+
+    /// `FIT` while fitting the document to the window, `MANUAL` once panned
+    /// or zoomed
+    pub fn mode_name(&self) -> &'static str {
+        match self.auto_fit {
+            true => "FIT",
+            false => "MANUAL",
+        }
+    }
+
+    /// Map from document coordinates to window pixels
+    pub fn transform(
+        &self,
+        svg_size: usvg::Size,
+        width: usize,
+        height: usize,
+    ) -> tiny_skia::Transform {
+        let eff_scale = self.fit_scale * self.zoom;
+        let offset_x = (width as f32 - svg_size.width() * eff_scale) / 2.0 + self.pan.0;
+        let offset_y = (height as f32 - svg_size.height() * eff_scale) / 2.0 + self.pan.1;
+        tiny_skia::Transform::from_translate(offset_x, offset_y).pre_scale(eff_scale, eff_scale)
+    }
+
+    /// Pan and zoom so that `rect` (in document coordinates) fills most of the window
+    pub fn focus(&mut self, rect: usvg::Rect, svg_size: usvg::Size, width: usize, height: usize) {
+        let eff_scale = 0.9 * (width as f32 / rect.width()).min(height as f32 / rect.height());
+        self.zoom = eff_scale / self.fit_scale;
+        self.pan = (
+            eff_scale * (svg_size.width() / 2.0 - (rect.x() + rect.width() / 2.0)),
+            eff_scale * (svg_size.height() / 2.0 - (rect.y() + rect.height() / 2.0)),
+        );
+        self.auto_fit = false;
+    }
+
+    /// Pan so that `point` (in document coordinates) is in the middle of the window
+    pub fn centre_on(&mut self, point: (f32, f32), svg_size: usvg::Size) {
+        let eff_scale = self.fit_scale * self.zoom;
+        self.pan = (
+            eff_scale * (svg_size.width() / 2.0 - point.0),
+            eff_scale * (svg_size.height() / 2.0 - point.1),
+        );
+        self.auto_fit = false;
+    }
+
+    /// Zoom by `factor` keeping the document point under `about` (in
+    /// viewport pixels) where it is
+    pub fn zoom_about(
+        &mut self,
+        factor: f32,
+        about: (f32, f32),
+        svg_size: usvg::Size,
+        width: usize,
+        height: usize,
+    ) {
+        let before = self.transform(svg_size, width, height);
+        let scale = self.fit_scale * self.zoom;
+        let point = ((about.0 - before.tx) / scale, (about.1 - before.ty) / scale);
+        self.zoom *= factor;
+        let scale = self.fit_scale * self.zoom;
+        // Solve `transform` for the pan putting `point` back under `about`.
+        self.pan = (
+            about.0 - point.0 * scale - (width as f32 - svg_size.width() * scale) / 2.0,
+            about.1 - point.1 * scale - (height as f32 - svg_size.height() * scale) / 2.0,
+        );
+        self.auto_fit = false;
+    }
+
+    /// Apply held pan/zoom keys for a frame lasting `dt`, so movement
+    /// speed doesn't depend on the frame rate. Zooming keeps the point at
+    /// `about` in a `viewport` showing a document of `svg_size` still.
+    pub fn handle_input(
+        &mut self,
+        window: &dyn Window,
+        dt: Duration,
+        svg_size: Option<usvg::Size>,
+        viewport: (usize, usize),
+        about: (f32, f32),
+    ) -> bool {
+        let mut changed = false;
+        let dt = dt.as_secs_f32();
+        let pan_speed = PAN_SPEED * dt;
+        let zoom_step = ZOOM_SPEED.powf(dt);
+
+        if window.is_key_down(Key::K) {
+            self.pan.1 += pan_speed;
+            self.auto_fit = false;
+            changed = true;
+        }
+        if window.is_key_down(Key::J) {
+            self.pan.1 -= pan_speed;
+            self.auto_fit = false;
+            changed = true;
+        }
+        if window.is_key_down(Key::H) {
+            self.pan.0 += pan_speed;
+            self.auto_fit = false;
+            changed = true;
+        }
+        if window.is_key_down(Key::L) {
+            self.pan.0 -= pan_speed;
+            self.auto_fit = false;
+            changed = true;
+        }
+        let mut zoom = |factor: f32| match svg_size {
+            Some(size) => self.zoom_about(factor, about, size, viewport.0, viewport.1),
+            None => {
+                self.zoom *= factor;
+                self.auto_fit = false;
+            }
+        };
+        if window.is_key_down(Key::Equal) || window.is_key_down(Key::NumPadPlus) {
+            zoom(zoom_step);
+            changed = true;
+        }
+        if window.is_key_down(Key::Minus) || window.is_key_down(Key::NumPadMinus) {
+            zoom(1.0 / zoom_step);
+            changed = true;
+        }
+        if window.is_key_down(Key::R) || window.is_key_down(Key::Home) {
+            self.reset();
+            changed = true;
+        }
+
+        changed
+    }
+}
+
+/// The scale fitting a document of `svg_size` to `width` × `height` as
+/// `mode` says
+fn fit(mode: Fit, svg_size: usvg::Size, width: usize, height: usize) -> f32 {
+    let (across, down) = (
+        width as f32 / svg_size.width(),
+        height as f32 / svg_size.height(),
+    );
+    match mode {
+        Fit::Contain => across.min(down),
+        Fit::Width => across,
+        Fit::Height => down,
+        Fit::Unscaled => 1.0,
+    }
+}
+
+/// `zoom` if [`State::set_zoom`] would take it, for callers that want to
+/// refuse a view before sending it on
+pub fn valid_zoom(zoom: f32) -> Result<f32, String> {
+    match zoom > 0.0 && zoom.is_finite() {
+        true => Ok(zoom),
+        false => Err(format!("expected a positive zoom, got {zoom}")),
+    }
+}
+
+/// `(x, y)` if [`State::set_pan`] would take it
+pub fn valid_pan(x: f32, y: f32) -> Result<(f32, f32), String> {
+    match x.is_finite() && y.is_finite() {
+        true => Ok((x, y)),
+        false => Err(format!("expected a finite pan, got {x} {y}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggling_fit_twice_returns_to_the_manual_view() {
+        let mut state = State::new();
+        state.pan = (10.0, -5.0);
+        state.zoom = 3.0;
+        state.auto_fit = false;
+        let manual = state.clone();
+
+        state.toggle_fit();
+        assert!(state.auto_fit);
+        assert_eq!((state.pan, state.zoom), ((0.0, 0.0), 1.0));
+        state.toggle_fit();
+        assert!(state == manual);
+    }
+
+    #[test]
+    fn toggling_off_fit_without_a_manual_view_keeps_the_fitted_one() {
+        let mut state = State::new();
+        state.fit_scale = 2.0;
+        state.toggle_fit();
+        assert!(!state.auto_fit);
+        assert_eq!(
+            (state.pan, state.zoom, state.fit_scale),
+            ((0.0, 0.0), 1.0, 2.0)
+        );
+    }
+
+    #[test]
+    fn refuses_views_that_cannot_be_drawn() {
+        let mut state = State::new();
+        for zoom in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(state.set_zoom(zoom).is_err(), "{zoom}");
+        }
+        assert!(state.set_pan(f32::NAN, 0.0).is_err());
+        assert!(state.set_pan(0.0, f32::NEG_INFINITY).is_err());
+        assert!(state == State::new());
+
+        state.set_zoom(2.0).unwrap();
+        state.set_pan(10.0, -20.0).unwrap();
+        assert!(!state.auto_fit);
+        assert_eq!((state.pan, state.zoom), ((10.0, -20.0), 2.0));
+    }
+}
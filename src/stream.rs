@@ -0,0 +1,135 @@
+//! Documents piped in one after another, as in `my-tool | svgtail -`: each
+//! ends at a NUL or at the `</svg>` closing its root, and replaces the one
+//! before as soon as it is complete, as `tail -f` shows lines.
+
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// How much is read at a time
+const CHUNK: usize = 64 * 1024;
+
+/// What the splitter looks out for, besides NULs
+const MARKUP: [&[u8]; 4] = [b"<!--", b"<![CDATA[", b"</svg", b"<svg"];
+
+/// The newest complete document read so far
+#[derive(Debug, Default)]
+pub struct Stream {
+    latest: Mutex<Option<Arc<[u8]>>>,
+}
+
+impl Stream {
+    pub fn latest(&self) -> Option<Arc<[u8]>> {
+        self.latest.lock().unwrap().clone()
+    }
+
+    /// Read documents from `reader` on a thread of its own until it ends,
+    /// calling `arrived` as each becomes the latest, until it returns
+    /// `false`. Whatever follows the last delimiter counts as a document
+    /// once the reader ends.
+    pub fn read_from(
+        self: &Arc<Self>,
+        mut reader: impl Read + Send + 'static,
+        mut arrived: impl FnMut() -> bool + Send + 'static,
+    ) {
+        let stream = Arc::clone(self);
+        thread::spawn(move || {
+            let mut splitter = Splitter::default();
+            let mut chunk = vec![0; CHUNK];
+            let mut publish = |doc: &[u8]| {
+                if doc.iter().all(u8::is_ascii_whitespace) {
+                    return true;
+                }
+                *stream.latest.lock().unwrap() = Some(doc.into());
+                arrived()
+            };
+            loop {
+                let n = match reader.read(&mut chunk) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => n,
+                };
+                splitter.buf.extend_from_slice(&chunk[..n]);
+                while let Some(doc) = splitter.next() {
+                    if !publish(&doc) {
+                        return;
+                    }
+                }
+            }
+            publish(&splitter.buf);
+        });
+    }
+}
+
+/// Finds where documents end in what has been read, picking up where it
+/// left off as more arrives
+#[derive(Default)]
+struct Splitter {
+    buf: Vec<u8>,
+    /// Where scanning resumes: the start of the first markup not yet seen
+    /// whole
+    pos: usize,
+    /// `<svg>` elements open at `pos`
+    depth: usize,
+}
+
+impl Splitter {
+    /// The first complete document, removed from the buffer
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let end = self.scan()?;
+        let mut doc: Vec<u8> = self.buf.drain(..end).collect();
+        if doc.last() == Some(&0) {
+            doc.pop();
+        }
+        self.pos = 0;
+        self.depth = 0;
+        Some(doc)
+    }
+
+    /// How much of the buffer the first document takes up, delimiter and
+    /// all, if it is all there
+    fn scan(&mut self) -> Option<usize> {
+        while self.pos < self.buf.len() {
+            let rest = &self.buf[self.pos..];
+            let skip_to = |end: &[u8]| find(rest, end).map(|i| i + end.len());
+            let tag = |name: &[u8]| {
+                rest.starts_with(name)
+                    && rest
+                        .get(name.len())
+                        .is_some_and(|&b| b.is_ascii_whitespace() || b == b'>' || b == b'/')
+            };
+            let step = if rest[0] == 0 {
+                return Some(self.pos + 1);
+            } else if rest.starts_with(b"<!--") {
+                skip_to(b"-->")?
+            } else if rest.starts_with(b"<![CDATA[") {
+                skip_to(b"]]>")?
+            } else if tag(b"</svg") {
+                let len = skip_to(b">")?;
+                self.depth = self.depth.saturating_sub(1);
+                if self.depth == 0 {
+                    return Some(self.pos + len);
+                }
+                len
+            } else if tag(b"<svg") {
+                let len = skip_to(b">")?;
+                if rest[len - 2] != b'/' {
+                    self.depth += 1;
+                } else if self.depth == 0 {
+                    return Some(self.pos + len);
+                }
+                len
+            } else if MARKUP.iter().any(|m| m.starts_with(rest)) {
+                // Cut off before it can be told what it is
+                return None;
+            } else {
+                1
+            };
+            self.pos += step;
+        }
+        None
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
@@ -0,0 +1,228 @@
+//! Documents written as templates: `{{key}}` placeholders in the SVG text are
+//! replaced with values given by `--define key=value` before parsing, on
+//! every load, so a template can be viewed without writing it out filled in.
+//! With the `data` feature, `--template` and `--data` instead render a Jinja
+//! template with a JSON or CSV file's contents.
+
+use std::collections::BTreeMap;
+#[cfg(feature = "data")]
+use std::fs;
+#[cfg(feature = "data")]
+use std::path::Path;
+
+#[cfg(feature = "data")]
+use minijinja::value::Serde;
+#[cfg(feature = "data")]
+use minijinja::{AutoEscape, Environment, UndefinedBehavior};
+
+use resvg::usvg;
+
+/// Values for placeholders, from `--define`
+#[derive(Clone, Debug, Default)]
+pub struct Defines(BTreeMap<String, String>);
+
+impl Defines {
+    /// Later definitions of a key replace earlier ones.
+    pub fn new(pairs: impl IntoIterator<Item = (String, String)>) -> Self {
+        Self(pairs.into_iter().collect())
+    }
+
+    /// `data` with its placeholders filled in, or as it is if nothing is
+    /// defined
+    pub fn apply(&self, data: Vec<u8>) -> Result<Vec<u8>, String> {
+        if self.0.is_empty() {
+            return Ok(data);
+        }
+        let unzipped;
+        let text = if data.starts_with(&[0x1f, 0x8b]) {
+            unzipped = usvg::decompress_svgz(&data).map_err(|e| e.to_string())?;
+            &unzipped[..]
+        } else {
+            &data[..]
+        };
+        let text = std::str::from_utf8(text).map_err(|_| usvg::Error::NotAnUtf8Str.to_string())?;
+        self.substitute(text).map(String::into_bytes)
+    }
+
+    /// Replace each `{{key}}` in `text`, allowing spaces inside the braces,
+    /// with the key's value escaped for XML. Braces around anything but a
+    /// key are left alone.
+    fn substitute(&self, text: &str) -> Result<String, String> {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("{{") {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            let key = after.find("}}").map(|end| (after[..end].trim(), end));
+            match key {
+                Some((key, end)) if is_key(key) => {
+                    let value = self
+                        .0
+                        .get(key)
+                        .ok_or_else(|| format!("{{{{{key}}}}} is not given with --define"))?;
+                    escape(value, &mut out);
+                    rest = &after[end + 2..];
+                }
+                _ => {
+                    out.push_str("{{");
+                    rest = after;
+                }
+            }
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+fn is_key(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+fn escape(value: &str, out: &mut String) {
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+}
+
+/// Parse a `--define` argument, `key=value`
+pub fn parse_define(s: &str) -> Result<(String, String), String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected key=value, got '{s}'"))?;
+    if !is_key(key) {
+        return Err(format!(
+            "'{key}' isn't a key: use letters, digits, '_', '-' and '.'"
+        ));
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Render the Jinja template at `path` with the contents of `data`, a JSON
+/// or CSV file. The data is `data` in the template and, if it is a JSON
+/// object, its keys are also variables of their own. Other templates can be
+/// included from beside the template.
+#[cfg(feature = "data")]
+pub fn render(path: &Path, data: &Path) -> Result<Vec<u8>, String> {
+    let value = load_data(data).map_err(|e| format!("{}: {e}", data.display()))?;
+    let source = fs::read_to_string(path).map_err(|e| e.to_string())?;
+
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::SemiStrict);
+    env.set_auto_escape_callback(|_| AutoEscape::Html);
+    if let Some(dir) = path.parent() {
+        env.set_loader(minijinja::path_loader(dir));
+    }
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let template = env
+        .template_from_named_str(&name, &source)
+        .map_err(|e| describe(&e))?;
+    let mut context = match &value {
+        serde_json::Value::Object(fields) => fields.clone(),
+        _ => serde_json::Map::new(),
+    };
+    context.insert("data".to_string(), value);
+    template
+        .render(Serde(&context))
+        .map(String::into_bytes)
+        .map_err(|e| describe(&e))
+}
+
+/// A template error with the errors behind it, such as the one in an
+/// included template
+#[cfg(feature = "data")]
+fn describe(e: &minijinja::Error) -> String {
+    let mut message = e.to_string();
+    let mut cause = std::error::Error::source(e);
+    while let Some(e) = cause {
+        message.push_str(&format!(": {e}"));
+        cause = e.source();
+    }
+    message
+}
+
+/// Read a JSON file, or with a `.csv` extension a CSV file as a list of rows
+/// keyed by the header's column names, with numbers as numbers
+#[cfg(feature = "data")]
+fn load_data(path: &Path) -> Result<serde_json::Value, String> {
+    use serde_json::Value;
+
+    let csv = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+    if !csv {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        return serde_json::from_str(&text).map_err(|e| e.to_string());
+    }
+    let mut reader = csv::Reader::from_path(path).map_err(|e| e.to_string())?;
+    let header = reader.headers().map_err(|e| e.to_string())?.clone();
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| e.to_string())?;
+        let row = header
+            .iter()
+            .zip(record.iter())
+            .map(|(column, field)| {
+                let value = match field.parse::<f64>() {
+                    Ok(n) if n.is_finite() => Value::from(n),
+                    _ => Value::from(field),
+                };
+                (column.to_string(), value)
+            })
+            .collect();
+        rows.push(Value::Object(row));
+    }
+    Ok(Value::Array(rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_definitions() {
+        assert_eq!(
+            parse_define("fill=red"),
+            Ok(("fill".to_string(), "red".to_string()))
+        );
+        assert_eq!(
+            parse_define("a.b-c_1=x=y"),
+            Ok(("a.b-c_1".to_string(), "x=y".to_string()))
+        );
+        assert_eq!(parse_define("empty="), Ok(("empty".into(), String::new())));
+        for s in ["fill", "=red", "two words=x", "{x}=y"] {
+            assert!(parse_define(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn fills_in_keys_escaped_and_leaves_other_braces() {
+        let defines = Defines::new([
+            ("fill".to_string(), "red".to_string()),
+            ("label".to_string(), "<a & \"b\">".to_string()),
+        ]);
+        assert_eq!(
+            defines.substitute(r#"<rect fill="{{ fill }}"/>{{label}}"#),
+            Ok(r#"<rect fill="red"/>&lt;a &amp; &quot;b&quot;&gt;"#.to_string())
+        );
+        assert_eq!(
+            defines.substitute("{{not a key}} {{ }} {{fill"),
+            Ok("{{not a key}} {{ }} {{fill".to_string())
+        );
+        assert!(defines.substitute("{{stroke}}").is_err());
+    }
+
+    #[test]
+    fn leaves_documents_alone_without_definitions() {
+        let data = b"{{anything}}".to_vec();
+        assert_eq!(Defines::default().apply(data.clone()), Ok(data));
+    }
+}
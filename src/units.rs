@@ -0,0 +1,214 @@
+//! Numbers in readouts, formatted in the unit chosen with `--units` and with
+//! the decimal separator of the user's locale. The locale is judged from
+//! `LC_ALL`, `LC_NUMERIC` or `LANG` by its language, which gets the
+//! separator right for the common locales without a locale database.
+
+use resvg::tiny_skia::Transform;
+
+use crate::guides;
+
+const MM_PER_INCH: f32 = 25.4;
+const PT_PER_INCH: f32 = 72.0;
+/// User units per inch unless `--dpi` says otherwise, as in CSS
+const CSS_DPI: f32 = 96.0;
+
+/// Languages writing a decimal comma, the rest writing a point
+const COMMA_LANGUAGES: &[&str] = &[
+    "af", "be", "bg", "bs", "ca", "cs", "da", "de", "el", "es", "et", "eu", "fi", "fr", "gl", "hr",
+    "hu", "id", "is", "it", "ka", "kk", "lt", "lv", "mk", "nb", "nl", "nn", "no", "pl", "pt", "ro",
+    "ru", "sk", "sl", "sq", "sr", "sv", "tr", "uk", "uz", "vi",
+];
+/// Regions writing a decimal point although their language mostly doesn't
+const POINT_LOCALES: &[&str] = &["de_CH", "de_LI", "it_CH", "es_MX", "es_US"];
+
+/// A unit lengths are read out in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Unit {
+    /// Document user units
+    #[default]
+    Px,
+    Mm,
+    In,
+    Pt,
+}
+
+#[derive(Clone, Debug)]
+pub struct Units {
+    unit: Unit,
+    /// User units per inch, if given with `--dpi`
+    dpi: Option<f32>,
+    /// Decimal comma rather than point
+    comma: bool,
+}
+
+impl Units {
+    /// `unit` at `dpi` user units per inch, in the environment's locale
+    pub fn new(unit: Unit, dpi: Option<f32>) -> Self {
+        let locale = ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .into_iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        Self {
+            unit,
+            dpi,
+            comma: decimal_comma(&locale),
+        }
+    }
+
+    /// User units in one of the unit
+    pub fn size(&self) -> f32 {
+        let dpi = self.dpi.unwrap_or(CSS_DPI);
+        match self.unit {
+            Unit::Px => 1.0,
+            Unit::Mm => dpi / MM_PER_INCH,
+            Unit::In => dpi,
+            Unit::Pt => dpi / PT_PER_INCH,
+        }
+    }
+
+    /// `transform`, mapping the unit rather than user units to the screen
+    pub fn scaled(&self, transform: Transform) -> Transform {
+        transform.pre_scale(self.size(), self.size())
+    }
+
+    pub fn suffix(&self) -> &'static str {
+        match self.unit {
+            Unit::Px => "px",
+            Unit::Mm => "mm",
+            Unit::In => "in",
+            Unit::Pt => "pt",
+        }
+    }
+
+    /// `value` with `decimals` decimals and the locale's separator
+    pub fn number(&self, value: f32, decimals: usize) -> String {
+        let s = format!("{value:.decimals$}");
+        match self.comma {
+            true => s.replace('.', ","),
+            false => s,
+        }
+    }
+
+    /// `user` units in the unit, without a suffix, to as many decimals as
+    /// `scale` screen pixels per user unit can resolve
+    pub fn plain(&self, user: f32, scale: f32) -> String {
+        let size = self.size();
+        self.number(user / size, guides::decimals(scale * size))
+    }
+
+    /// `user` units in the unit, with its suffix
+    pub fn length(&self, user: f32, scale: f32) -> String {
+        format!("{} {}", self.plain(user, scale), self.suffix())
+    }
+
+    /// Width by height, with the unit's suffix once
+    pub fn size_of(&self, (w, h): (f32, f32), scale: f32) -> String {
+        format!(
+            "{} × {} {}",
+            self.plain(w, scale),
+            self.plain(h, scale),
+            self.suffix()
+        )
+    }
+
+    /// A coordinate pair, kept from running together under a decimal comma
+    pub fn point(&self, (x, y): (f32, f32), scale: f32) -> String {
+        let separator = if self.comma { "; " } else { ", " };
+        format!(
+            "{}{separator}{}",
+            self.plain(x, scale),
+            self.plain(y, scale)
+        )
+    }
+
+    /// A ratio as a whole percentage, with the space before the sign that
+    /// locales writing a decimal comma mostly use
+    pub fn percent(&self, ratio: f32) -> String {
+        let space = if self.comma { "\u{a0}" } else { "" };
+        format!("{}{space}%", self.number(ratio * 100.0, 0))
+    }
+
+    /// Physical sizes for a size in user units, if `--dpi` gave the scale
+    /// and the readout isn't already physical
+    pub fn physical(&self, (w, h): (f32, f32)) -> Option<String> {
+        let dpi = self.dpi.filter(|_| self.unit == Unit::Px)?;
+        let (w_in, h_in) = (w / dpi, h / dpi);
+        Some(format!(
+            "{} × {} mm, {} × {} in",
+            self.number(w_in * MM_PER_INCH, 2),
+            self.number(h_in * MM_PER_INCH, 2),
+            self.number(w_in, 3),
+            self.number(h_in, 3)
+        ))
+    }
+}
+
+/// Whether `locale`, like `de_DE.UTF-8`, writes a decimal comma
+fn decimal_comma(locale: &str) -> bool {
+    let name = locale.split(['.', '@']).next().unwrap_or_default();
+    let language = name.split('_').next().unwrap_or_default();
+    COMMA_LANGUAGES.contains(&language) && !POINT_LOCALES.contains(&name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn units(unit: Unit, dpi: Option<f32>, comma: bool) -> Units {
+        Units { unit, dpi, comma }
+    }
+
+    #[test]
+    fn judges_the_separator_by_language_and_region() {
+        assert!(decimal_comma("de_DE.UTF-8"));
+        assert!(decimal_comma("fr_FR@euro"));
+        assert!(decimal_comma("pt"));
+        assert!(!decimal_comma("de_CH.UTF-8"));
+        assert!(!decimal_comma("en_GB.UTF-8"));
+        assert!(!decimal_comma("C"));
+        assert!(!decimal_comma(""));
+    }
+
+    #[test]
+    fn converts_at_css_resolution_unless_told_otherwise() {
+        assert_eq!(units(Unit::In, None, false).size(), 96.0);
+        assert_eq!(units(Unit::Pt, None, false).size(), 96.0 / 72.0);
+        assert_eq!(units(Unit::Mm, Some(300.0), false).size(), 300.0 / 25.4);
+        assert_eq!(units(Unit::Px, Some(300.0), false).size(), 1.0);
+    }
+
+    #[test]
+    fn gives_as_many_decimals_as_the_zoom_resolves() {
+        let px = units(Unit::Px, None, false);
+        assert_eq!(px.length(12.34, 1.0), "12 px");
+        assert_eq!(px.length(12.34, 60.0), "12.3 px");
+        let inches = units(Unit::In, None, false);
+        assert_eq!(inches.length(144.0, 1.0), "1.5 in");
+        assert_eq!(inches.size_of((96.0, 48.0), 1.0), "1.0 × 0.5 in");
+    }
+
+    #[test]
+    fn writes_numbers_as_the_locale_does() {
+        let comma = units(Unit::Px, None, true);
+        assert_eq!(comma.point((1.5, 2.0), 60.0), "1,5; 2,0");
+        assert_eq!(comma.percent(1.5), "150\u{a0}%");
+        let point = units(Unit::Px, None, false);
+        assert_eq!(point.point((1.5, 2.0), 60.0), "1.5, 2.0");
+        assert_eq!(point.percent(1.5), "150%");
+    }
+
+    #[test]
+    fn adds_physical_sizes_only_to_user_units_at_a_known_resolution() {
+        let px = units(Unit::Px, Some(96.0), false);
+        assert_eq!(
+            px.physical((96.0, 192.0)).as_deref(),
+            Some("25.40 × 50.80 mm, 1.000 × 2.000 in")
+        );
+        assert_eq!(units(Unit::Px, None, false).physical((96.0, 96.0)), None);
+        assert_eq!(
+            units(Unit::Mm, Some(96.0), false).physical((96.0, 96.0)),
+            None
+        );
+    }
+}
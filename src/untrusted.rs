@@ -0,0 +1,240 @@
+//! Showing documents from people you don't know, with `--untrusted`: they
+//! may read nothing off the disk, nor expand into more than can be drawn.
+//! Elements are counted as `<use>` expands them, and filters by the
+//! primitives each drawing of a filtered element runs, as a few hundred
+//! bytes can nest either into billions. A document too slow to draw anyway
+//! is drawn off the window's thread, and dropped once it runs over time.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock, mpsc};
+use std::thread;
+use std::time::Duration;
+
+use resvg::usvg::{self, ImageHrefResolver, roxmltree};
+
+/// How deep elements may nest, counting through `<use>`
+const MAX_DEPTH: usize = 256;
+
+/// What a document may cost to parse and draw
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Limits {
+    /// Elements, once `<use>` is expanded
+    pub max_nodes: u32,
+    /// Filter primitives run to draw the document once
+    pub max_filter_primitives: u64,
+    /// How long drawing it once may take
+    pub render_timeout: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_nodes: 100_000,
+            max_filter_primitives: 500,
+            render_timeout: Duration::from_secs(2),
+        }
+    }
+}
+
+impl Limits {
+    /// How to parse a document within these limits: without a DTD, whose
+    /// entities expand unseen, and stopping at too many nodes
+    pub fn xml_options(&self) -> roxmltree::ParsingOptions {
+        roxmltree::ParsingOptions {
+            allow_dtd: false,
+            nodes_limit: self.max_nodes,
+        }
+    }
+
+    /// Why `error`, from parsing with [`Self::xml_options`], is one of these
+    /// limits, if it is
+    pub fn explain(&self, error: &roxmltree::Error) -> Option<String> {
+        match error {
+            roxmltree::Error::NodesLimitReached => Some(self.too_many_nodes()),
+            roxmltree::Error::DtdDetected => {
+                Some("the document has a DTD, which --untrusted doesn't allow".into())
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether `doc` keeps within these limits once expanded
+    pub fn check(&self, doc: &roxmltree::Document) -> Result<(), String> {
+        let ids = doc
+            .descendants()
+            .filter(|n| n.is_element())
+            .filter_map(|n| Some((n.attribute("id")?, n)))
+            .collect();
+        let mut count = Count {
+            ids,
+            costs: HashMap::new(),
+            visiting: HashSet::new(),
+        };
+        let cost = count.cost(doc.root_element(), 0)?;
+        if cost.nodes > u64::from(self.max_nodes) {
+            return Err(self.too_many_nodes());
+        }
+        if cost.filters > self.max_filter_primitives {
+            return Err(format!(
+                "the document runs {} filter primitives, more than the {} --untrusted allows",
+                cost.filters, self.max_filter_primitives
+            ));
+        }
+        Ok(())
+    }
+
+    /// Why a document still being drawn after `render_timeout` was given up on
+    pub fn too_slow(&self) -> String {
+        format!(
+            "the document took longer to draw than the {:.1} s --untrusted allows",
+            self.render_timeout.as_secs_f32()
+        )
+    }
+
+    /// Draw with `draw` as [`spawn`] does, waiting for it no longer than
+    /// `render_timeout`
+    pub fn draw<T: Send + 'static>(
+        &self,
+        draw: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<T, String> {
+        let (tx, rx) = mpsc::channel();
+        let cancel = spawn(draw, move |drawn| {
+            let _ = tx.send(drawn);
+        });
+        rx.recv_timeout(self.render_timeout).map_err(|_| {
+            cancel.store(true, Ordering::Relaxed);
+            self.too_slow()
+        })
+    }
+
+    fn too_many_nodes(&self) -> String {
+        format!(
+            "the document has more than the {} elements --untrusted allows",
+            self.max_nodes
+        )
+    }
+}
+
+/// The threads untrusted documents are drawn on, apart from the global pool
+/// the window's own work runs on, so a drawing given up on holds up only
+/// others like it. There are half as many as cores, which also caps how
+/// many drawings, given up on or not, run at once.
+fn pool() -> &'static rayon::ThreadPool {
+    static POOL: OnceLock<rayon::ThreadPool> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let cores = thread::available_parallelism().map_or(1, |n| n.get());
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(cores.div_ceil(2))
+            .thread_name(|i| format!("svgtail-untrusted-{i}"))
+            .build()
+            .expect("the untrusted drawing threads to start")
+    })
+}
+
+/// Draw with `draw` on the untrusted threads, handing what it draws to
+/// `done`. Setting the flag returned skips the drawing if it hasn't started.
+pub fn spawn<T: Send + 'static>(
+    draw: impl FnOnce() -> T + Send + 'static,
+    done: impl FnOnce(T) + Send + 'static,
+) -> Arc<AtomicBool> {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let cancelled = Arc::clone(&cancel);
+    pool().spawn(move || {
+        if !cancelled.load(Ordering::Relaxed) {
+            done(draw());
+        }
+    });
+    cancel
+}
+
+/// Have `opts` load images only from `data:` URLs, and never SVG ones,
+/// which would be parsed outside the limits
+pub fn sandbox(opts: &mut usvg::Options) {
+    let from_data = ImageHrefResolver::default_data_resolver();
+    opts.image_href_resolver.resolve_data = Box::new(move |mime, data, opts| match mime {
+        "image/svg+xml" => None,
+        _ => from_data(mime, data, opts),
+    });
+    opts.image_href_resolver.resolve_string = Box::new(|_, _| None);
+}
+
+/// Elements and filter primitives drawn for an element
+#[derive(Clone, Copy, Default)]
+struct Cost {
+    nodes: u64,
+    filters: u64,
+}
+
+impl Cost {
+    fn add(&mut self, other: Cost) {
+        self.nodes = self.nodes.saturating_add(other.nodes);
+        self.filters = self.filters.saturating_add(other.filters);
+    }
+}
+
+/// Costs of the elements seen so far, so each is counted once however
+/// often it is used
+struct Count<'a, 'input> {
+    ids: HashMap<&'a str, roxmltree::Node<'a, 'input>>,
+    costs: HashMap<roxmltree::NodeId, Cost>,
+    /// Elements being counted, so one that uses itself isn't followed round
+    visiting: HashSet<roxmltree::NodeId>,
+}
+
+impl<'a> Count<'a, '_> {
+    fn cost(&mut self, node: roxmltree::Node<'a, '_>, depth: usize) -> Result<Cost, String> {
+        if let Some(cost) = self.costs.get(&node.id()) {
+            return Ok(*cost);
+        }
+        if depth > MAX_DEPTH {
+            return Err(format!(
+                "the document nests elements more than the {MAX_DEPTH} deep --untrusted allows"
+            ));
+        }
+        if !self.visiting.insert(node.id()) {
+            return Ok(Cost::default());
+        }
+        let mut cost = Cost {
+            nodes: 1,
+            filters: self.filter_primitives(node),
+        };
+        for child in node.children().filter(|n| n.is_element()) {
+            cost.add(self.cost(child, depth + 1)?);
+        }
+        let href = node
+            .attribute(("http://www.w3.org/1999/xlink", "href"))
+            .or_else(|| node.attribute("href"));
+        if node.tag_name().name() == "use"
+            && let Some(target) = href
+                .and_then(|href| href.strip_prefix('#'))
+                .and_then(|id| self.ids.get(id).copied())
+        {
+            cost.add(self.cost(target, depth + 1)?);
+        }
+        self.visiting.remove(&node.id());
+        self.costs.insert(node.id(), cost);
+        Ok(cost)
+    }
+
+    /// Primitives in the filters `node` is drawn through, by its `filter`
+    /// attribute or style
+    fn filter_primitives(&self, node: roxmltree::Node) -> u64 {
+        let values = [node.attribute("filter"), node.attribute("style")];
+        values
+            .into_iter()
+            .flatten()
+            .flat_map(|value| value.split("url(").skip(1))
+            .filter_map(|rest| {
+                let id = rest
+                    .trim_start_matches(['\'', '"', ' '])
+                    .strip_prefix('#')?;
+                let id = id.split([')', '\'', '"', ' ']).next()?;
+                let filter = self.ids.get(id)?;
+                // A style's `url()` may name a clip path or mask instead
+                (filter.tag_name().name() == "filter").then_some(())?;
+                Some(filter.children().filter(|n| n.is_element()).count() as u64)
+            })
+            .sum()
+    }
+}
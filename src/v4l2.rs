@@ -0,0 +1,199 @@
+//! Writing finished frames to a v4l2loopback device, so streaming and video
+//! call software can capture the view like a webcam.
+//!
+//! The device is set to the first frame's size. A later frame of another
+//! size changes it too, unless a reader has it open, in which case the frame
+//! is cropped or padded with black to the size readers expect.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::fd::AsRawFd;
+use std::path::Path;
+
+use crate::sink::Sink;
+
+const fn fourcc(code: &[u8; 4]) -> u32 {
+    u32::from_le_bytes(*code)
+}
+
+const BUF_TYPE_VIDEO_OUTPUT: u32 = 2;
+const FIELD_NONE: u32 = 1;
+const COLORSPACE_SRGB: u32 = 8;
+
+/// `struct v4l2_pix_format`
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct PixFormat {
+    width: u32,
+    height: u32,
+    pixelformat: u32,
+    field: u32,
+    bytesperline: u32,
+    sizeimage: u32,
+    colorspace: u32,
+    private: u32,
+    flags: u32,
+    ycbcr_enc: u32,
+    quantization: u32,
+    xfer_func: u32,
+}
+
+/// The union in `struct v4l2_format`, aligned to hold pointers as the
+/// kernel's is
+#[repr(C)]
+#[derive(Clone, Copy)]
+union FormatData {
+    pix: PixFormat,
+    raw: [u8; 200],
+    _align: [usize; 0],
+}
+
+/// `struct v4l2_format`
+#[repr(C)]
+struct Format {
+    kind: u32,
+    data: FormatData,
+}
+
+/// `VIDIOC_S_FMT`, `_IOWR('V', 5, struct v4l2_format)`
+const VIDIOC_S_FMT: u64 =
+    (3 << 30) | ((size_of::<Format>() as u64) << 16) | ((b'V' as u64) << 8) | 5;
+
+/// How pixels are written to the device
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum PixelFormat {
+    /// 4:2:2 YUV, which nearly everything capturing video accepts
+    #[default]
+    Yuyv,
+    /// Full-colour 32-bit BGRX, for software such as OBS that takes it
+    Bgrx,
+}
+
+impl PixelFormat {
+    fn fourcc(self) -> u32 {
+        match self {
+            PixelFormat::Yuyv => fourcc(b"YUYV"),
+            PixelFormat::Bgrx => fourcc(b"XR24"),
+        }
+    }
+
+    fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Yuyv => 2,
+            PixelFormat::Bgrx => 4,
+        }
+    }
+}
+
+pub struct V4l2 {
+    file: File,
+    format: PixelFormat,
+    /// Width and height the device is set to, once a frame has been written
+    size: Option<(usize, usize)>,
+    /// The frame being encoded, kept to save allocating one each time
+    out: Vec<u8>,
+}
+
+impl V4l2 {
+    /// Open the v4l2loopback device at `path`, such as `/dev/video10`
+    pub fn open(path: &Path, format: PixelFormat) -> io::Result<Self> {
+        Ok(Self {
+            file: OpenOptions::new().write(true).open(path)?,
+            format,
+            size: None,
+            out: Vec::new(),
+        })
+    }
+
+    /// Set the device to `width` × `height` frames
+    fn set_size(&mut self, width: usize, height: usize) -> io::Result<()> {
+        let bytesperline = (width * self.format.bytes_per_pixel()) as u32;
+        let mut format = Format {
+            kind: BUF_TYPE_VIDEO_OUTPUT,
+            data: FormatData { raw: [0; 200] },
+        };
+        format.data.pix = PixFormat {
+            width: width as u32,
+            height: height as u32,
+            pixelformat: self.format.fourcc(),
+            field: FIELD_NONE,
+            bytesperline,
+            sizeimage: bytesperline * height as u32,
+            colorspace: COLORSPACE_SRGB,
+            ..Default::default()
+        };
+        // SAFETY: `format` is a `struct v4l2_format`, which the ioctl takes.
+        let result = unsafe { libc::ioctl(self.file.as_raw_fd(), VIDIOC_S_FMT as _, &mut format) };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.size = Some((width, height));
+        Ok(())
+    }
+}
+
+impl Sink for V4l2 {
+    fn name(&self) -> &str {
+        "v4l2"
+    }
+
+    fn push(&mut self, frame: &[u32], width: usize) -> io::Result<()> {
+        let height = frame.len() / width.max(1);
+        // YUYV pairs pixels, so an odd last column is left off.
+        let wanted = match self.format {
+            PixelFormat::Yuyv => (width & !1, height),
+            PixelFormat::Bgrx => (width, height),
+        };
+        match self.size {
+            Some(size) if size == wanted => {}
+            Some(_) => {
+                // Busy while a reader has it open, so kept as it is
+                let _ = self.set_size(wanted.0, wanted.1);
+            }
+            None => self.set_size(wanted.0, wanted.1)?,
+        }
+        let (out_w, out_h) = self.size.expect("set above");
+
+        self.out.clear();
+        for y in 0..out_h {
+            let row = frame.get(y * width..(y + 1) * width).unwrap_or_default();
+            encode_row(row, out_w, self.format, &mut self.out);
+        }
+        self.file.write_all(&self.out)
+    }
+}
+
+/// Append `row` in `format`, cropped or padded with black to `width`
+fn encode_row(row: &[u32], width: usize, format: PixelFormat, out: &mut Vec<u8>) {
+    let px = |x: usize| row.get(x).copied().unwrap_or(0);
+    match format {
+        PixelFormat::Bgrx => {
+            for x in 0..width {
+                let [_, r, g, b] = px(x).to_be_bytes();
+                out.extend_from_slice(&[b, g, r, 0]);
+            }
+        }
+        PixelFormat::Yuyv => {
+            for x in (0..width).step_by(2) {
+                let (y0, u0, v0) = yuv(px(x));
+                let (y1, u1, v1) = yuv(px(x + 1));
+                out.extend_from_slice(&[
+                    y0,
+                    ((u0 as u16 + u1 as u16) / 2) as u8,
+                    y1,
+                    ((v0 as u16 + v1 as u16) / 2) as u8,
+                ]);
+            }
+        }
+    }
+}
+
+/// `0RGB` pixel `px` in limited-range BT.601 Y'CbCr, as sRGB video is read
+fn yuv(px: u32) -> (u8, u8, u8) {
+    let [_, r, g, b] = px.to_be_bytes();
+    let (r, g, b) = (r as i32, g as i32, b as i32);
+    let y = 16 + ((66 * r + 129 * g + 25 * b + 128) >> 8);
+    let u = 128 + ((-38 * r - 74 * g + 112 * b + 128) >> 8);
+    let v = 128 + ((112 * r - 94 * g - 18 * b + 128) >> 8);
+    (y as u8, u as u8, v as u8)
+}
@@ -0,0 +1,3097 @@
+use std::hash::{BuildHasher, RandomState};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use minifb::WindowOptions;
+use notify_debouncer_full::{DebounceEventResult, notify::RecursiveMode};
+use resvg::{tiny_skia, usvg};
+
+use crate::access::{self, Contrast};
+use crate::action::{Action, Button, Event, Snapshot};
+use crate::annotate::{self, Pen};
+use crate::browser;
+use crate::cache::Cache;
+#[cfg(all(unix, not(any(target_os = "macos", target_os = "redox"))))]
+use crate::clipboard;
+use crate::compare::{self, Mode};
+use crate::crop::{self, Crop};
+use crate::diff::{self, Damage};
+use crate::dither::Dither;
+#[cfg(target_os = "linux")]
+use crate::eink::Eink;
+use crate::exit::{Code, Failure};
+use crate::export::{self, Export};
+use crate::guides::{self, Axis, Guide, RULER};
+use crate::highlight::{Highlights, Rule};
+use crate::hooks::{DocInfo, Hooks};
+use crate::icc::Profile;
+use crate::input::{self, Drawing, Input, Loading, Parsed, Source};
+use crate::loupe::Loupe;
+use crate::measure::{Angle, Selection};
+use crate::minimap::{self, Minimap};
+use crate::overlay::{self, Shape};
+use crate::presenter::{self, Presenter};
+use crate::render::{self, Canvas, Checkerboard, Frame};
+use crate::resource::{self, ResourceLoader};
+use crate::review;
+use crate::search::{self, Search};
+use crate::select::Shown;
+use crate::sink::Sink;
+use crate::state::{Anchor, Fit, State};
+use crate::template::Defines;
+use crate::units::Units;
+use crate::untrusted::{self, Limits};
+use crate::watch::{self, Pattern, Trigger};
+use crate::watcher::{Status, Watcher};
+use crate::window::{Key, KeyRepeat, MouseButton, MouseMode, Window};
+
+/// A function given each finished frame, from [`Viewer::add_post_process`]
+type PostProcess = Box<dyn FnMut(&mut tiny_skia::Pixmap, Option<tiny_skia::Transform>)>;
+
+/// Delay before the first retry of a failed load, doubling on each retry
+const RETRY_BASE: Duration = Duration::from_millis(50);
+/// Retries after a failed load before waiting for the next file event
+const MAX_RETRIES: u32 = 6;
+
+/// How long to wait before retry `n` of a failed load: doubling each time,
+/// give or take half, so the retries don't fall into step with a writer
+/// saving on a timer and find the file locked or empty every time
+fn retry_delay(n: u32) -> Duration {
+    let jitter = RandomState::new().hash_one(n) as f64 / u64::MAX as f64;
+    (RETRY_BASE * 2u32.pow(n)).mul_f64(0.5 + jitter)
+}
+
+/// The view documents open at, as configured
+fn opening_view(config: &Config) -> State {
+    let mut state = State::new();
+    state.fit = config.fit;
+    if let Some(zoom) = config.zoom {
+        // With nothing fitted yet, the fit scale is one pixel per unit.
+        state.zoom = zoom;
+        state.auto_fit = false;
+    }
+    state
+}
+
+/// The keys switching to the first nine inputs
+const NUMBER_KEYS: [Key; 9] = [
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+];
+
+/// Whether a document going from `old` to `new` in size is near enough the
+/// same size for `--keep-view` to keep the view
+fn similar(old: usvg::Size, new: usvg::Size) -> bool {
+    let near = |a: f32, b: f32| (a - b).abs() <= a * KEEP_VIEW_CHANGE;
+    near(old.width(), new.width()) && near(old.height(), new.height())
+}
+
+/// Whether `input`'s load error is shown over the view: always for what a
+/// program or template produces, and for a file once it has been retried
+/// in vain
+fn shows_error(input: &Input) -> bool {
+    input.source.generated() || input.retries >= MAX_RETRIES
+}
+
+/// Loads shorter than this don't show progress
+const HUD_DELAY: Duration = Duration::from_millis(250);
+/// How often to redraw load progress
+const HUD_REFRESH: Duration = Duration::from_millis(100);
+
+/// How often an unfocused or hidden window is polled for input, by default
+/// and with `--power-save`
+pub const IDLE_POLL: Duration = Duration::from_millis(250);
+pub const POWER_SAVE_POLL: Duration = Duration::from_secs(3);
+
+/// How long the cursor must rest before a tooltip shows
+const TOOLTIP_DELAY: Duration = Duration::from_millis(500);
+
+/// Documents taking longer than this to render are first shown coarse
+const PROGRESSIVE_AFTER: Duration = Duration::from_millis(250);
+
+/// How long the window size must hold still before the document is
+/// rendered at it; until then the last frame is rescaled to fit
+const RESIZE_SETTLE: Duration = Duration::from_millis(150);
+
+/// The least of the document's width and height a change is framed in
+const MIN_FRAMED: f32 = 0.1;
+/// The largest change in the document's width or height, as a fraction of
+/// it, across which `--keep-view` keeps the view
+pub const KEEP_VIEW_CHANGE: f32 = 0.1;
+/// Zoom factor per step of the scroll wheel
+const WHEEL_ZOOM: f32 = 1.2;
+
+/// Longest frame time that key-held movement is advanced by
+const MAX_STEP: Duration = Duration::from_millis(100);
+
+/// Viewer behaviour chosen on the command line
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// The window's title, in place of `svgtail`
+    pub title: Option<String>,
+    /// The window's size when it opens
+    pub window_size: (usize, usize),
+    /// How the document is fitted to the window
+    pub fit: Fit,
+    /// Magnification to open at, in screen pixels per user unit, instead of
+    /// fitting
+    pub zoom: Option<f32>,
+    /// Upper bound on frames presented per second
+    pub max_fps: u32,
+    /// How often a focused window is polled for input while there is nothing
+    /// to draw, if not once a frame
+    pub poll_interval: Option<Duration>,
+    /// How often an unfocused or hidden window is
+    pub idle_poll: Duration,
+    /// Exit when a watched file is deleted
+    pub quit_on_delete: bool,
+    /// Exit after this long without file events or input
+    pub idle_exit: Option<Duration>,
+    /// Reload each input at most once per this interval
+    pub reload_interval: Option<Duration>,
+    /// Which file events reload
+    pub reload_on: Trigger,
+    /// Exit with [`Code::Invalid`] as soon as an input fails to load
+    pub fail_on_error: bool,
+    /// What documents may cost to show, for those that can't be trusted;
+    /// links out of them aren't followed either
+    pub limits: Option<Limits>,
+    /// Scale the view with the window when it is resized, even if panned or
+    /// zoomed
+    pub refit_on_resize: bool,
+    /// Elements outlined or tinted by id
+    pub highlights: Vec<Rule>,
+    /// Pan and zoom to what each reload changed
+    pub zoom_to_changes: bool,
+    /// What stays in place across reloads as the document changes size,
+    /// instead of fitting it again
+    pub anchor: Anchor,
+    /// The element whose bounding box stays where it is in the window across
+    /// reloads, as the layout around it shifts
+    pub anchor_id: Option<String>,
+    /// Leave the view as it is across reloads, unless the document's size
+    /// changes by more than [`KEEP_VIEW_CHANGE`]
+    pub keep_view: bool,
+    /// Further files and directories whose changes reload every input
+    pub also_watch: Vec<PathBuf>,
+    /// Files to switch to as they are written
+    pub glob: Option<Pattern>,
+    /// Values filled in for `{{key}}` placeholders in the inputs
+    pub defines: Arc<Defines>,
+    /// Images written each time the shown input loads
+    pub exports: Vec<Export>,
+    /// Part of the page the exports show, in page coordinates, if not all
+    pub export_area: Option<tiny_skia::Rect>,
+    /// Pixels per user unit of exports not given a size
+    pub export_scale: f32,
+    /// How lengths and other numbers are read out
+    pub units: Units,
+    /// Aspect ratio the crop tool starts locked to
+    pub crop_aspect: Option<f32>,
+    /// Draw the review marks into screenshots, as well as the window
+    pub bake_annotations: bool,
+    /// Present frames through OpenGL
+    #[cfg(feature = "gl")]
+    pub gl: bool,
+    /// Colour profile of the display, applied to the window and screenshots
+    pub profile: Option<Arc<Profile>>,
+    /// Palette the window and screenshots are reduced to
+    pub dither: Option<Dither>,
+    /// Draw the window in strongly contrasting greys
+    pub contrast: Option<Contrast>,
+    /// Ring the cursor with a large crosshair
+    pub large_cursor: bool,
+    /// Halo the cursor, trail it like a laser pointer and zoom in while `z`
+    /// is held
+    pub presenter: bool,
+    /// Start with the loupe on
+    pub loupe: bool,
+    /// Magnification and radius the loupe starts with
+    pub loupe_zoom: f32,
+    pub loupe_radius: f32,
+    /// What the document is first drawn over
+    pub canvas: Canvas,
+    /// Squares shown through transparent parts of the document when toggled on
+    pub checkerboard: Checkerboard,
+}
+
+/// Anything that wakes the main loop
+enum Wake {
+    Action(Action),
+    Watch(DebounceEventResult),
+    Watching(Status),
+    Loaded(Box<Loaded>),
+    Rendered(Box<Rendered>),
+}
+
+/// Sends actions to the viewer from other threads, waking it immediately
+#[derive(Clone)]
+pub struct ActionSender(mpsc::Sender<Wake>);
+
+impl ActionSender {
+    /// Fails once the viewer has exited
+    pub fn send(&self, action: Action) -> Result<(), mpsc::SendError<Action>> {
+        self.0
+            .send(Wake::Action(action))
+            .map_err(|mpsc::SendError(wake)| match wake {
+                Wake::Action(action) => mpsc::SendError(action),
+                _ => unreachable!("only actions are sent"),
+            })
+    }
+}
+
+/// A background load that has finished
+struct Loaded {
+    index: usize,
+    ticket: u64,
+    result: Result<Option<Parsed>, String>,
+}
+
+/// A background render that has finished
+struct Rendered {
+    index: usize,
+    ticket: u64,
+    frame: Frame,
+}
+
+/// The frame on screen and the one being drawn off-screen, swapped once the
+/// latter is complete so the window is only ever given whole frames
+struct Buffers {
+    /// The last complete frame, and its width and height
+    front: Vec<u32>,
+    size: (usize, usize),
+    back: Vec<u32>,
+    /// Presents through OpenGL instead of minifb's blit
+    #[cfg(feature = "gl")]
+    gl: Option<crate::gl::Gl>,
+}
+
+impl Buffers {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            front: vec![0; width * height],
+            size: (width, height),
+            back: Vec::new(),
+            #[cfg(feature = "gl")]
+            gl: None,
+        }
+    }
+
+    /// Whether presenting scales the front buffer to the window on the GPU,
+    /// so there is no need to draw a rescaled frame while resizing
+    fn scales(&self) -> bool {
+        #[cfg(feature = "gl")]
+        return self.gl.is_some();
+        #[cfg(not(feature = "gl"))]
+        false
+    }
+
+    /// Bring the back buffer, now holding a `size` frame, to the front
+    fn swap(&mut self, size: (usize, usize)) {
+        std::mem::swap(&mut self.front, &mut self.back);
+        self.size = size;
+    }
+
+    /// Show the front buffer. Until a frame at a new window size is ready it
+    /// is the previous one, stretched by minifb or fitted over `background`
+    /// through OpenGL.
+    #[cfg_attr(not(feature = "gl"), expect(unused_variables))]
+    fn present(&mut self, window: &mut dyn Window, background: u32) -> Result<(), Failure> {
+        #[cfg(feature = "gl")]
+        if let Some(gl) = &mut self.gl {
+            gl.present(&self.front, self.size, window.get_size(), background)?;
+            // Only pump events; minifb mustn't draw over the GL surface.
+            window.update();
+            return Ok(());
+        }
+        window
+            .update_with_buffer(&self.front, self.size.0, self.size.1)
+            .map_err(Failure::from)
+    }
+}
+
+pub struct Viewer {
+    /// Documents to switch between with Tab and the number keys, each
+    /// keeping a view of its own, or to step through with backtick, `n` and
+    /// `p` in the view shown
+    inputs: Vec<Input>,
+    active: usize,
+    /// Documents opened before, to show again without parsing
+    cache: Cache,
+    config: Config,
+    /// How the shown input is combined with the next one
+    compare: Mode,
+    /// Wipe split position as a fraction of the window width
+    split: f32,
+    svg_opts: Arc<usvg::Options<'static>>,
+    state: State,
+    canvas: Canvas,
+    overlays: Vec<Shape>,
+    /// Outline the page and viewBox over the document
+    show_bounds: bool,
+    /// Draw the document's pixel grid when zoomed in far enough
+    show_grid: bool,
+    loupe: Option<Loupe>,
+    /// The view was last moved with the keyboard rather than the cursor,
+    /// so the loupe follows the middle of the view
+    keyboard_focus: bool,
+    /// Thumbnail navigator in the bottom-right corner
+    minimap: Option<Minimap>,
+    /// Repeat the document 3×3 to check that it tiles
+    tile: bool,
+    /// Show rulers and guides
+    show_rulers: bool,
+    /// Index of the shown input's guide being dragged
+    dragging: Option<usize>,
+    /// Mouse position in window pixels, if it is over the window
+    cursor: Option<(f32, f32)>,
+    /// Whether the left button was down last frame
+    mouse_down: bool,
+    /// Whether the middle button was down last frame
+    middle_down: bool,
+    /// Where the pointer was last frame, while dragging the view to pan it
+    panning: Option<(f32, f32)>,
+    /// Which buttons were down when last reported to subscribers
+    buttons_down: [bool; 3],
+    /// Rectangle being dragged out with Shift held, to measure
+    selection: Option<Selection>,
+    /// Angle being measured by clicking, while in angle mode
+    angle: Option<Angle>,
+    /// The crop tool, while it is shown
+    crop: Option<Crop>,
+    /// The text search, while its bar is open
+    search: Option<Search>,
+    /// Elements picked out by id
+    highlights: Highlights,
+    /// Drawing review marks, while on
+    pen: Option<Pen>,
+    /// The cursor's halo and trail, with `--presenter`
+    presenter: Option<Presenter>,
+    /// Whether Escape has been held since it closed the search or a note
+    /// being typed, and so doesn't quit
+    closed_bar: bool,
+    /// When the cursor last moved
+    rested_since: Instant,
+    /// The tooltip under the resting cursor, once looked up
+    tooltip: Option<Option<String>>,
+    hooks: Vec<Box<dyn Hooks>>,
+    /// Where finished frames are sent besides the window
+    sinks: Vec<Box<dyn Sink>>,
+    /// Supplies what documents refer to, in place of the filesystem
+    resources: Option<Arc<dyn ResourceLoader>>,
+    /// Called with each whole frame before it is presented
+    post_processes: Vec<PostProcess>,
+    /// Times the shown input has loaded, numbering exports
+    loads: u64,
+    width: usize,
+    height: usize,
+    /// When the window was last resized, until the size settles
+    resizing: Option<Instant>,
+    /// The view from before a reload, kept for one frame so the new content
+    /// first appears exactly where the old was, before any fit
+    held: Option<tiny_skia::Transform>,
+    /// The view before a reload's changes were zoomed to, and the view
+    /// zoomed to
+    before_changes: Option<(State, State)>,
+    /// Actions, file events and load results, in arrival order
+    queue: (mpsc::Sender<Wake>, mpsc::Receiver<Wake>),
+    /// Actions received but not yet applied
+    actions: Vec<Action>,
+    /// When the last file event, action or input arrived
+    last_activity: Instant,
+    next_ticket: u64,
+    subscribers: Vec<mpsc::Sender<Event>>,
+    /// Zoom and pan last reported to subscribers
+    reported_view: (f32, (f32, f32)),
+    /// Whether the watcher is running, as it last said
+    watching: Status,
+    quit: bool,
+    /// Why the viewer stopped, if it was not asked to
+    failure: Option<Failure>,
+}
+
+impl Viewer {
+    pub fn new(sources: Vec<Source>, svg_opts: usvg::Options<'static>, config: Config) -> Self {
+        assert!(!sources.is_empty(), "viewer needs at least one input");
+        let canvas = config.canvas.clone();
+        let loupe = config
+            .loupe
+            .then(|| Loupe::new(config.loupe_zoom, config.loupe_radius));
+        let highlights = Highlights::new(config.highlights.clone());
+        let presenter = config.presenter.then(Presenter::default);
+        let state = opening_view(&config);
+        let (width, height) = config.window_size;
+        Self {
+            inputs: sources.into_iter().map(Input::new).collect(),
+            active: 0,
+            cache: Cache::new(),
+            config,
+            compare: Mode::Flip,
+            split: 0.5,
+            svg_opts: Arc::new(svg_opts),
+            state,
+            canvas,
+            overlays: Vec::new(),
+            show_bounds: false,
+            show_grid: false,
+            loupe,
+            keyboard_focus: false,
+            minimap: None,
+            tile: false,
+            show_rulers: false,
+            dragging: None,
+            cursor: None,
+            mouse_down: false,
+            middle_down: false,
+            panning: None,
+            buttons_down: [false; 3],
+            selection: None,
+            angle: None,
+            crop: None,
+            search: None,
+            highlights,
+            pen: None,
+            presenter,
+            closed_bar: false,
+            rested_since: Instant::now(),
+            tooltip: None,
+            hooks: Vec::new(),
+            sinks: Vec::new(),
+            resources: None,
+            post_processes: Vec::new(),
+            loads: 0,
+            width,
+            height,
+            resizing: None,
+            held: None,
+            before_changes: None,
+            queue: mpsc::channel(),
+            actions: Vec::new(),
+            last_activity: Instant::now(),
+            next_ticket: 0,
+            subscribers: Vec::new(),
+            reported_view: (1.0, (0.0, 0.0)),
+            watching: Status::Watching,
+            quit: false,
+            failure: None,
+        }
+    }
+
+    pub fn add_hooks(&mut self, hooks: Box<dyn Hooks>) {
+        self.hooks.push(hooks);
+    }
+
+    /// Mirror finished frames to `eink`, starting the window at its size
+    #[cfg(target_os = "linux")]
+    pub fn mirror_to(&mut self, eink: Eink) {
+        (self.width, self.height) = eink.size();
+        self.add_sink(Box::new(eink));
+    }
+
+    pub fn add_sink(&mut self, sink: Box<dyn Sink>) {
+        self.sinks.push(sink);
+    }
+
+    /// Call `f` with each frame once everything the viewer draws is on it,
+    /// just before it is presented and sent to sinks, to draw over it or
+    /// change it. The transform maps the shown document to frame pixels, if
+    /// one is loaded. Unlike [`Hooks::on_frame`], it sees the viewer's own
+    /// overlays and doesn't touch screenshots.
+    pub fn add_post_process(
+        &mut self,
+        f: impl FnMut(&mut tiny_skia::Pixmap, Option<tiny_skia::Transform>) + 'static,
+    ) {
+        self.post_processes.push(Box::new(f));
+    }
+
+    /// Resolve the images, fonts and stylesheets documents refer to through
+    /// `loader`, falling back to the filesystem for any it doesn't have
+    pub fn set_resource_loader(&mut self, loader: Arc<dyn ResourceLoader>) {
+        let opts = Arc::get_mut(&mut self.svg_opts).expect("set before running");
+        resource::install(opts, Arc::clone(&loader));
+        self.resources = Some(loader);
+    }
+
+    /// A handle for sending actions to the viewer from other threads
+    pub fn sender(&self) -> ActionSender {
+        ActionSender(self.queue.0.clone())
+    }
+
+    /// Receive an [`Event`] for every change from here on, as
+    /// [`Action::Subscribe`] does, including the keys and clicks the viewer
+    /// doesn't use itself
+    pub fn subscribe(&mut self) -> mpsc::Receiver<Event> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    fn emit(&mut self, event: Event) {
+        self.subscribers.retain(|s| s.send(event.clone()).is_ok());
+    }
+
+    fn path(&self) -> &Path {
+        self.inputs[self.active].source.path()
+    }
+
+    fn tree(&self) -> Option<&usvg::Tree> {
+        self.inputs[self.active].tree.as_ref()
+    }
+
+    /// The compare mode in effect; only meaningful with several inputs
+    fn mode(&self) -> Mode {
+        if self.inputs.len() > 1 {
+            self.compare
+        } else {
+            Mode::Flip
+        }
+    }
+
+    /// The area the shown input is fitted to: half the window side by side
+    fn viewport(&self) -> (usize, usize) {
+        match self.mode() {
+            Mode::SideBySide => ((self.width / 2).max(1), self.height),
+            _ => (self.width, self.height),
+        }
+    }
+
+    /// The area auto-fit fits the document to: a third of the viewport each
+    /// way when tiling, so the whole 3×3 grid shows
+    fn fit_area(&self) -> (usize, usize) {
+        let (w, h) = self.viewport();
+        match self.tile {
+            true => ((w / 3).max(1), (h / 3).max(1)),
+            false => (w, h),
+        }
+    }
+
+    /// Start re-reading input `index` on a background thread, so large files
+    /// don't stall the window; [`Viewer::finish_reload`] applies the result.
+    fn reload(&mut self, index: usize) {
+        let now = Instant::now();
+        let input = &mut self.inputs[index];
+        if input.loading.is_some() {
+            // Pick up whatever changed once the current load is done.
+            input.due = Some(now);
+            return;
+        }
+        self.next_ticket += 1;
+        let ticket = self.next_ticket;
+        input.last_read = Some(now);
+        input.loading = Some(Loading {
+            ticket,
+            started: now,
+            size: input.source.size(),
+        });
+
+        let source = input.source.clone();
+        let unchanged = input.tree.as_ref().and(input.hash);
+        let opts = Arc::clone(&self.svg_opts);
+        let defines = Arc::clone(&self.config.defines);
+        let shown = input.shown.clone();
+        let resources = self.resources.clone();
+        let limits = self.config.limits;
+        let tx = self.queue.0.clone();
+        thread::spawn(move || {
+            let result = input::load_changed(
+                &source,
+                unchanged,
+                &opts,
+                &defines,
+                &shown,
+                resources.as_deref(),
+                limits.as_ref(),
+            );
+            let _ = tx.send(Wake::Loaded(Box::new(Loaded {
+                index,
+                ticket,
+                result,
+            })));
+        });
+    }
+
+    /// Apply a finished load, fitting it to the window if it is the one shown
+    /// (or with [`Config::anchor_id`] or [`Config::anchor`], keeping the part
+    /// anchored in place, or with [`Config::keep_view`], the view as it was).
+    /// Returns `true` if a new tree was loaded or the error pane came or went;
+    /// unchanged contents are skipped.
+    fn finish_reload(&mut self, loaded: Loaded) -> bool {
+        let index = loaded.index;
+        let Some(input) = self.inputs.get_mut(index) else {
+            return false;
+        };
+        if input.loading.as_ref().map(|l| l.ticket) != Some(loaded.ticket) {
+            // The input was replaced while loading
+            return false;
+        }
+        let started = input.loading.take().map(|l| l.started);
+        let path = input.source.path().to_path_buf();
+        let parsed = match loaded.result {
+            Ok(Some(parsed)) => parsed,
+            Ok(None) => {
+                let input = &mut self.inputs[index];
+                let shown = shows_error(input);
+                input.retries = 0;
+                // Back as it was before failing
+                return input.error.take().is_some() && shown;
+            }
+            Err(e) => {
+                // The file may have been read half-written, and the write that
+                // completes it may already have been reported, so try again.
+                let input = &mut self.inputs[index];
+                // Generators read whole files, so fail the same way again.
+                let retried = input.source.watched().is_some() && !input.source.generated();
+                let gave_up = retried && input.retries == MAX_RETRIES;
+                if retried && input.retries < MAX_RETRIES {
+                    input.due = Some(Instant::now() + retry_delay(input.retries));
+                    input.retries += 1;
+                }
+                if self.config.fail_on_error {
+                    self.failure = Some(Failure::new(
+                        Code::Invalid,
+                        format!("{}: {e}", path.display()),
+                    ));
+                    self.quit = true;
+                }
+                let input = &mut self.inputs[index];
+                let shown =
+                    (input.error.as_ref() != Some(&e) && input.source.generated()) || gave_up;
+                input.error = Some(e.clone());
+                self.emit(Event::LoadFailed(path, e));
+                return shown;
+            }
+        };
+        let input = &mut self.inputs[index];
+        let first = input.tree.is_none();
+        let old_size = input.tree.as_ref().map(|t| t.size());
+        input.retries = 0;
+        input.error = None;
+        let changed = match &input.tree {
+            Some(_) => diff::damage(&input.leaves, &parsed.leaves),
+            None => Damage::Full,
+        };
+        // The old frame can only be patched at the same size.
+        let damage = match &input.tree {
+            Some(old) if old.size() == parsed.tree.size() => changed,
+            _ => Damage::Full,
+        };
+        // Where the anchored element was and is, if it is in both versions
+        let held = self.config.anchor_id.as_ref().and_then(|id| {
+            let old = input.tree.as_ref()?;
+            let bbox = |tree: &usvg::Tree| {
+                let node = tree.node_by_id(id)?;
+                Some((node.abs_bounding_box(), tree.size()))
+            };
+            Some((bbox(old)?, bbox(&parsed.tree)?))
+        });
+        input.damage = input.damage.join(damage);
+        let patchable = !matches!(input.damage, Damage::Full);
+        if index == self.active {
+            // Patching the old frame in place is cheap, so swap the content
+            // in at the old view and let the fit follow on the next frame.
+            if patchable && self.mode() == Mode::Flip && !self.tile {
+                self.held = self.inputs[index].frame.as_ref().map(Frame::transform);
+            }
+            match (old_size, changed, held) {
+                // A view given before there was anything to show
+                (None, _, _) if !self.state.auto_fit => {}
+                // Nothing to see changed, so leave the view be.
+                (Some(_), Damage::None, _) if self.config.zoom_to_changes => {}
+                (Some(_), Damage::Region(rect), _) if self.config.zoom_to_changes => {
+                    self.frame_changes(rect, &parsed.tree);
+                }
+                (_, _, Some((old, new))) => {
+                    let (w, h) = self.fit_area();
+                    self.state.hold(old, new, w, h);
+                }
+                (Some(old), _, _) if self.config.anchor.any() => {
+                    let (w, h) = self.fit_area();
+                    let new = parsed.tree.size();
+                    self.state.anchor(self.config.anchor, old, new, w, h);
+                }
+                (Some(old), _, _) if self.config.keep_view && similar(old, parsed.tree.size()) => {}
+                _ => self.state.reset(),
+            }
+        }
+
+        let info = DocInfo::new(&path, &parsed.tree);
+        for hooks in &mut self.hooks {
+            if first {
+                hooks.on_load(&info);
+            } else {
+                hooks.on_reload(&info);
+            }
+        }
+        let input = &mut self.inputs[index];
+        if input.render_time.is_none() {
+            // A slow parse suggests a slow render.
+            input.render_time = started.map(|t| t.elapsed());
+        }
+        input.tree = Some(parsed.tree);
+        input.view_box = parsed.view_box;
+        input.meta = parsed.meta;
+        // Look again, as what's under the cursor may have changed.
+        self.tooltip = None;
+        input.hash = Some(parsed.hash);
+        input.leaves = parsed.leaves;
+        input.deps = parsed.deps;
+        self.find_again();
+
+        if index == self.active && !self.config.exports.is_empty() {
+            self.loads += 1;
+            self.export();
+        }
+        self.emit(if first {
+            Event::Loaded(path)
+        } else {
+            Event::Reloaded(path)
+        });
+        true
+    }
+
+    /// Reload input `index` as soon as the rate limit allows
+    fn schedule_reload(&mut self, index: usize) {
+        let now = Instant::now();
+        let input = &mut self.inputs[index];
+        input.retries = 0;
+        let earliest = match (input.last_read, self.config.reload_interval) {
+            (Some(read), Some(interval)) => (read + interval).max(now),
+            _ => now,
+        };
+        input.due = Some(input.due.map_or(earliest, |due| due.min(earliest)));
+    }
+
+    /// Apply any actions queued by hooks or sent over the channel,
+    /// returning `true` if anything was applied
+    fn apply_actions(&mut self, watcher: &Watcher) -> bool {
+        let mut actions = std::mem::take(&mut self.actions);
+        actions.extend(self.hooks.iter_mut().flat_map(|h| h.take_actions()));
+        let changed = !actions.is_empty();
+        for action in actions {
+            self.apply(action, watcher);
+        }
+        changed
+    }
+
+    fn apply(&mut self, action: Action, watcher: &Watcher) {
+        match action {
+            Action::Reload => {
+                for i in 0..self.inputs.len() {
+                    self.reload(i);
+                }
+            }
+            Action::Open(path) => {
+                let path = match std::path::absolute(&path) {
+                    Ok(path) => path,
+                    Err(e) => return eprintln!("open {}: {e}", path.display()),
+                };
+                // Fitted anew, whether shown as it was from the cache at once
+                // or once it loads; the reload below only replaces a cached
+                // one if the file has changed since.
+                self.state.reset();
+                let input = match self.cache.take(&path) {
+                    Some(cached) => cached,
+                    None => Input::new(Source::File(path.clone())),
+                };
+                let old = std::mem::replace(&mut self.inputs[self.active], input);
+                if let Some(old) = old.source.watched()
+                    && !self.inputs.iter().any(|i| i.source.watched() == Some(old))
+                {
+                    watcher.unwatch(old);
+                }
+                self.cache.put(old);
+                if let Err(e) = self.watch_input(watcher, self.active) {
+                    eprintln!("open {}: {e}", path.display());
+                }
+                self.reload(self.active);
+            }
+            Action::OpenTab(path) => {
+                let path = match std::path::absolute(&path) {
+                    Ok(path) => path,
+                    Err(e) => return eprintln!("open-tab {}: {e}", path.display()),
+                };
+                let open = self
+                    .inputs
+                    .iter()
+                    .position(|i| i.source.watched() == Some(&path));
+                let index = match open {
+                    Some(index) => index,
+                    None => {
+                        let input = self
+                            .cache
+                            .take(&path)
+                            .unwrap_or_else(|| Input::new(Source::File(path.clone())));
+                        self.inputs.push(input);
+                        let index = self.inputs.len() - 1;
+                        if let Err(e) = self.watch_input(watcher, index) {
+                            eprintln!("open-tab {}: {e}", path.display());
+                        }
+                        index
+                    }
+                };
+                if index != self.active {
+                    self.switch_to(index);
+                }
+                self.reload(index);
+            }
+            Action::Reset => self.state.reset(),
+            Action::Quit => self.quit = true,
+            // From scripts, plugins and embedders as well as the command
+            // line, so State checks it for all of them
+            Action::SetZoom(zoom) => {
+                if let Err(e) = self.state.set_zoom(zoom) {
+                    eprintln!("set-zoom: {e}");
+                }
+            }
+            Action::SetPan(x, y) => {
+                if let Err(e) = self.state.set_pan(x, y) {
+                    eprintln!("set-pan: {e}");
+                }
+            }
+            Action::SetBackground(color) => self.canvas.background = color,
+            Action::Focus(id) => {
+                let Some(tree) = self.inputs[self.active].tree.as_ref() else {
+                    return;
+                };
+                match tree.node_by_id(&id) {
+                    Some(node) => {
+                        let size = tree.size();
+                        let bbox = node.abs_bounding_box();
+                        let (fit_w, fit_h) = self.fit_area();
+                        self.state.update_fit_scale(tree, fit_w, fit_h);
+                        let (w, h) = self.viewport();
+                        self.state.focus(bbox, size, w, h);
+                    }
+                    None => eprintln!("focus: no element with id '{id}'"),
+                }
+            }
+            Action::Draw(shape) => self.overlays.push(shape),
+            Action::ClearOverlays => self.overlays.clear(),
+            Action::Highlight(rule) => {
+                self.highlights.rules.push(rule);
+                self.find_again();
+            }
+            Action::ClearHighlights => {
+                self.highlights.rules.clear();
+                self.find_again();
+            }
+            Action::Hide(selector) => {
+                self.inputs[self.active].shown.hidden.push(selector);
+                self.reload(self.active);
+            }
+            Action::Solo(selector) => {
+                self.inputs[self.active].shown.solo.push(selector);
+                self.reload(self.active);
+            }
+            Action::ShowAll => {
+                self.inputs[self.active].shown = Shown::default();
+                self.reload(self.active);
+            }
+            Action::Screenshot(path, reply) => {
+                let result = self.screenshot(&path);
+                match reply {
+                    Some(reply) => {
+                        let _ = reply.send(result);
+                    }
+                    None => {
+                        if let Err(e) = result {
+                            eprintln!("screenshot {}: {e}", path.display());
+                        }
+                    }
+                }
+            }
+            Action::Crop(x, y, w, h) => match tiny_skia::Rect::from_xywh(x, y, w, h) {
+                Some(rect) => self
+                    .crop
+                    .get_or_insert_with(|| Crop::new(self.config.crop_aspect))
+                    .set(rect),
+                None => eprintln!("crop: invalid rectangle"),
+            },
+            Action::CropAspect(aspect) => {
+                let crop = self
+                    .crop
+                    .get_or_insert_with(|| Crop::new(self.config.crop_aspect));
+                crop.aspect = aspect;
+                if let Some(rect) = crop.rect {
+                    crop.set(rect);
+                }
+            }
+            Action::ExportReview(path, reply) => {
+                let result = self.export_review(&path);
+                match reply {
+                    Some(reply) => {
+                        let _ = reply.send(result);
+                    }
+                    None => match result {
+                        Ok(()) => eprintln!("saved review to {}", path.display()),
+                        Err(e) => eprintln!("export-review {}: {e}", path.display()),
+                    },
+                }
+            }
+            Action::ExportCrop(path, reply) => {
+                let result = self.export_crop(&path);
+                match reply {
+                    Some(reply) => {
+                        let _ = reply.send(result);
+                    }
+                    None => match result {
+                        Ok(()) => eprintln!("saved crop to {}", path.display()),
+                        Err(e) => eprintln!("export-crop {}: {e}", path.display()),
+                    },
+                }
+            }
+            Action::GetState(reply) => {
+                let _ = reply.send(self.snapshot());
+            }
+            Action::Subscribe(tx) => self.subscribers.push(tx),
+        }
+    }
+
+    /// Switch to the next input with Tab, the one before with Shift+Tab, or
+    /// one of the first nine with its number key. Each keeps a view of its
+    /// own, unlike flipping with `` ` ``, which keeps the view to compare
+    /// in place. Returns `true` if it switched.
+    fn handle_switch_keys(&mut self, window: &dyn Window, pressed: &[Key]) -> bool {
+        let n = self.inputs.len();
+        if n < 2 {
+            return false;
+        }
+        let shift = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+        let target = pressed.iter().find_map(|key| match key {
+            Key::Tab if shift => Some((self.active + n - 1) % n),
+            Key::Tab => Some((self.active + 1) % n),
+            key => NUMBER_KEYS.iter().position(|k| k == key).filter(|&i| i < n),
+        });
+        match target {
+            Some(index) if index != self.active => {
+                self.switch_to(index);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Show input `index` in the view it was left at, keeping the shown
+    /// one's view for when it is switched back to
+    fn switch_to(&mut self, index: usize) {
+        let view = self.inputs[index]
+            .view
+            .take()
+            .unwrap_or_else(|| opening_view(&self.config));
+        self.inputs[self.active].view = Some(std::mem::replace(&mut self.state, view));
+        self.active = index;
+        self.find_again();
+    }
+
+    /// Handle viewer-level key bindings, returning `true` if the view changed
+    fn handle_keys(&mut self, pressed: &[Key]) -> bool {
+        let mut changed = false;
+        for key in pressed {
+            let n = self.inputs.len();
+            let step = match key {
+                Key::Backquote | Key::N => Some(1),
+                Key::P => Some(n - 1),
+                _ => None,
+            };
+            if let Some(step) = step
+                && n > 1
+            {
+                // Keep pan and zoom so the documents can be compared in
+                // place: the view goes along, and the input flipped away
+                // from keeps it too, for switching back to with Tab.
+                self.inputs[self.active].view = Some(self.state.clone());
+                self.active = (self.active + step) % n;
+                self.inputs[self.active].view = None;
+                self.find_again();
+                changed = true;
+            }
+            if *key == Key::C && self.inputs.len() > 1 {
+                self.compare = self.compare.next();
+                changed = true;
+            }
+            if *key == Key::F {
+                self.state.toggle_fit();
+                changed = true;
+            }
+            if *key == Key::Slash {
+                self.search = Some(Search::new());
+                changed = true;
+            }
+            if *key == Key::D {
+                self.pen = match self.pen {
+                    Some(_) => None,
+                    None => Some(Pen::default()),
+                };
+                changed = true;
+            }
+            if *key == Key::Enter && self.pen.is_some() && self.crop.is_none() {
+                let path = review::default_path(self.inputs[self.active].source.path());
+                self.actions.push(Action::ExportReview(path, None));
+            }
+            if *key == Key::Delete && self.pen.is_some() {
+                let input = &mut self.inputs[self.active];
+                if input.marks.pop().is_some() {
+                    annotate::save(input.source.path(), &input.marks);
+                    changed = true;
+                }
+            }
+            if *key == Key::Backspace
+                && let Some((before, _)) = self.before_changes.take()
+            {
+                self.state = before;
+                changed = true;
+            }
+            if *key == Key::A {
+                self.angle = match self.angle {
+                    Some(_) => None,
+                    None => Some(Angle::default()),
+                };
+                changed = true;
+            }
+            if *key == Key::X {
+                self.crop = match self.crop {
+                    Some(_) => None,
+                    None => Some(Crop::new(self.config.crop_aspect)),
+                };
+                changed = true;
+            }
+            if *key == Key::B {
+                self.show_bounds = !self.show_bounds;
+                changed = true;
+            }
+            if *key == Key::G {
+                self.show_grid = !self.show_grid;
+                changed = true;
+            }
+            if *key == Key::M {
+                self.loupe = match self.loupe {
+                    Some(_) => None,
+                    None => Some(Loupe::new(self.config.loupe_zoom, self.config.loupe_radius)),
+                };
+                changed = true;
+            }
+            if *key == Key::O {
+                self.minimap = match self.minimap {
+                    Some(_) => None,
+                    None => Some(Minimap::new()),
+                };
+                changed = true;
+            }
+            if *key == Key::S {
+                self.tile = !self.tile;
+                changed = true;
+            }
+            if *key == Key::W {
+                self.canvas.world_grid = !self.canvas.world_grid;
+                changed = true;
+            }
+            if *key == Key::U {
+                self.show_rulers = !self.show_rulers;
+                changed = true;
+            }
+            if *key == Key::T {
+                self.canvas.checkerboard = match self.canvas.checkerboard {
+                    Some(_) => None,
+                    None => Some(self.config.checkerboard),
+                };
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Pan and zoom to `rect`, what a reload changed in `tree`, the shown
+    /// input's new tree, keeping the view it leaves for Backspace to go back
+    /// to. While the changes last framed are still in view, that stays the
+    /// one from before them.
+    fn frame_changes(&mut self, rect: tiny_skia::Rect, tree: &usvg::Tree) {
+        let before = match self.before_changes.take() {
+            Some((before, framed)) if framed == self.state => before,
+            _ => self.state.clone(),
+        };
+        // Not so close that a one-pixel change fills the window
+        let size = tree.size();
+        let (w, h) = (
+            rect.width().max(size.width() * MIN_FRAMED),
+            rect.height().max(size.height() * MIN_FRAMED),
+        );
+        let (x, y) = (
+            rect.x() - (w - rect.width()) / 2.0,
+            rect.y() - (h - rect.height()) / 2.0,
+        );
+        let rect = tiny_skia::Rect::from_xywh(x, y, w, h).unwrap_or(rect);
+        let (fit_w, fit_h) = self.fit_area();
+        self.state.update_fit_scale(tree, fit_w, fit_h);
+        let (w, h) = self.viewport();
+        self.state.focus(rect, size, w, h);
+        self.before_changes = Some((before, self.state.clone()));
+    }
+
+    /// Tell subscribers about the keys in `pressed` that nothing is bound
+    /// to and the mouse buttons pressed since the last frame
+    fn report_input(&mut self, window: &dyn Window, pressed: &[Key]) {
+        for &key in pressed {
+            if !self.bound(key) {
+                self.emit(Event::Key(format!("{key:?}")));
+            }
+        }
+        let at = window.get_mouse_pos(MouseMode::Discard);
+        let buttons = [
+            (Button::Left, MouseButton::Left),
+            (Button::Middle, MouseButton::Middle),
+            (Button::Right, MouseButton::Right),
+        ];
+        for (i, (button, which)) in buttons.into_iter().enumerate() {
+            let down = window.get_mouse_down(which);
+            let was_down = std::mem::replace(&mut self.buttons_down[i], down);
+            if let (true, false, Some(at)) = (down, was_down, at) {
+                let point = self.doc_point(Some(at)).map(|(_, point)| point);
+                self.emit(Event::Click { button, at, point });
+            }
+        }
+    }
+
+    /// Whether pressing `key` does anything in the viewer as it is now
+    fn bound(&self, key: Key) -> bool {
+        match key {
+            Key::Backquote | Key::C | Key::N | Key::P | Key::Tab => self.inputs.len() > 1,
+            key if NUMBER_KEYS.contains(&key) => {
+                NUMBER_KEYS.iter().position(|k| *k == key) < Some(self.inputs.len())
+            }
+            Key::Left | Key::Right | Key::Up | Key::Down => self.crop.is_some(),
+            Key::Enter => self.crop.is_some() || self.pen.is_some(),
+            Key::Backspace => self.config.zoom_to_changes,
+            Key::Delete => self.pen.is_some(),
+            Key::Z => self.presenter.is_some(),
+            Key::V | Key::Y => cfg!(all(
+                unix,
+                not(any(target_os = "macos", target_os = "redox"))
+            )),
+            Key::A
+            | Key::B
+            | Key::D
+            | Key::F
+            | Key::G
+            | Key::M
+            | Key::O
+            | Key::S
+            | Key::T
+            | Key::U
+            | Key::W
+            | Key::X
+            | Key::Slash => true,
+            Key::H
+            | Key::J
+            | Key::K
+            | Key::L
+            | Key::Equal
+            | Key::Minus
+            | Key::NumPadPlus
+            | Key::NumPadMinus
+            | Key::R
+            | Key::Home
+            | Key::Escape => true,
+            Key::LeftShift
+            | Key::RightShift
+            | Key::LeftCtrl
+            | Key::RightCtrl
+            | Key::LeftAlt
+            | Key::RightAlt
+            | Key::LeftSuper
+            | Key::RightSuper => true,
+            _ => false,
+        }
+    }
+
+    /// Follow the mouse with the loupe, guides and the wipe split, and pan,
+    /// zoom and reset the view with it, returning `true` if the view changed
+    fn handle_mouse(&mut self, window: &dyn Window) -> bool {
+        let mut changed = false;
+        let cursor = window.get_mouse_pos(MouseMode::Discard);
+        if cursor != self.cursor {
+            self.keyboard_focus = false;
+        }
+        if self.loupe.is_some() || self.config.large_cursor {
+            changed |= cursor != self.cursor;
+        }
+        if let Some(presenter) = &mut self.presenter {
+            changed |= presenter.track(cursor, Instant::now());
+        }
+        if let Some(loupe) = &mut self.loupe
+            && let Some((_, dy)) = window.get_scroll_wheel()
+            && dy != 0.0
+        {
+            changed |= loupe.scroll(dy);
+        }
+        if self.angle.is_some() {
+            // Arms follow the cursor.
+            changed |= cursor != self.cursor;
+        }
+        if cursor != self.cursor {
+            self.rested_since = Instant::now();
+            // Hide the tooltip until the cursor rests again.
+            changed |= self.tooltip.take().flatten().is_some();
+        } else if self.tooltip.is_none() && self.rested_since.elapsed() >= TOOLTIP_DELAY {
+            let tip = self.tip_at(cursor);
+            changed |= tip.is_some();
+            self.tooltip = Some(tip);
+        }
+        self.cursor = cursor;
+        if self.loupe.is_none()
+            && let Some((_, dy)) = window.get_scroll_wheel()
+            && dy != 0.0
+            && let Some(size) = self.tree().map(|t| t.size())
+        {
+            let (w, h) = self.viewport();
+            let about = self.zoom_anchor();
+            self.state
+                .zoom_about(WHEEL_ZOOM.powf(dy.signum()), about, size, w, h);
+            changed = true;
+        }
+        let middle = window.get_mouse_down(MouseButton::Middle);
+        if middle && !self.middle_down {
+            self.state.reset();
+            changed = true;
+        }
+        self.middle_down = middle;
+        let down = window.get_mouse_down(MouseButton::Left);
+        let pressed = down && !self.mouse_down;
+        self.mouse_down = down;
+        let ctrl = window.is_key_down(Key::LeftCtrl) || window.is_key_down(Key::RightCtrl);
+        if pressed && ctrl && self.follow_link(cursor) {
+            return true;
+        }
+        let shift = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+        if self.pen.is_some() {
+            return self.draw_mark(pressed, down, shift) || changed;
+        }
+        if self.drag_selection(pressed && shift, down) {
+            return true;
+        }
+        if self.drag_minimap(window, pressed, down) {
+            return true;
+        }
+        if self.crop.is_some() {
+            return self.drag_crop(pressed, down) || changed;
+        }
+        if self.angle.is_some() {
+            let point = self
+                .doc_point(cursor)
+                .filter(|(index, _)| *index == self.active);
+            if pressed && let (Some(angle), Some((_, point))) = (&mut self.angle, point) {
+                angle.click(point);
+                return true;
+            }
+            // Clicks only place points, rather than dragging anything.
+            return changed;
+        }
+        if self.show_rulers && self.drag_guide(window, pressed, down) {
+            return true;
+        }
+        if self.mode() == Mode::Wipe {
+            return self.drag_split(window) || changed;
+        }
+        self.drag_pan(window, pressed, down) || changed
+    }
+
+    /// Pan the view along with the pointer while the left button is held,
+    /// returning `true` if it moved
+    fn drag_pan(&mut self, window: &dyn Window, pressed: bool, down: bool) -> bool {
+        let at = window.get_mouse_pos(MouseMode::Pass);
+        if pressed {
+            self.panning = at;
+        }
+        if !down {
+            self.panning = None;
+        }
+        let (Some(from), Some(to)) = (self.panning, at) else {
+            return false;
+        };
+        self.panning = Some(to);
+        if from == to {
+            return false;
+        }
+        self.state.pan.0 += to.0 - from.0;
+        self.state.pan.1 += to.1 - from.1;
+        self.state.auto_fit = false;
+        true
+    }
+
+    /// The input shown under screen point `at`, and the page coordinates there
+    fn doc_point(&self, at: Option<(f32, f32)>) -> Option<(usize, (f32, f32))> {
+        let (x, y) = at?;
+        let (w, h) = self.viewport();
+        // Side by side, the right half shows the other input.
+        let (index, x, w) = if self.mode() == Mode::SideBySide && x >= w as f32 {
+            (self.other(), x - w as f32, self.width - w)
+        } else {
+            (self.active, x, w)
+        };
+        let tree = self.inputs[index].tree.as_ref()?;
+        let inverse = self.state.transform(tree.size(), w, h).invert()?;
+        let mut point = tiny_skia::Point::from_xy(x, y);
+        inverse.map_point(&mut point);
+        Some((index, (point.x, point.y)))
+    }
+
+    /// The title and description of the element under screen point `at`,
+    /// and where it links to
+    fn tip_at(&self, at: Option<(f32, f32)>) -> Option<String> {
+        let (index, point) = self.doc_point(at)?;
+        let input = &self.inputs[index];
+        let tree = input.tree.as_ref()?;
+        let tip = input.meta.tip_at(tree, point);
+        let link = input
+            .meta
+            .link_at(tree, point)
+            .map(|href| format!("Ctrl+click: {href}"));
+        match (tip, link) {
+            (Some(tip), Some(link)) => Some(format!("{tip}\n{link}")),
+            (tip, link) => tip.map(str::to_owned).or(link),
+        }
+    }
+
+    /// Follow the link under screen point `at`, returning `false` if there
+    /// is none. Links within the document focus their target element.
+    fn follow_link(&mut self, at: Option<(f32, f32)>) -> bool {
+        let Some((index, point)) = self.doc_point(at) else {
+            return false;
+        };
+        let input = &self.inputs[index];
+        let Some(href) = input
+            .tree
+            .as_ref()
+            .and_then(|t| input.meta.link_at(t, point))
+        else {
+            return false;
+        };
+        match href.strip_prefix('#') {
+            Some(id) => self.actions.push(Action::Focus(id.to_string())),
+            None if self.config.limits.is_some() => {
+                eprintln!("{href}: links out of the document aren't followed with --untrusted");
+            }
+            None => {
+                let target = browser::resolve(href, input.source.path());
+                if let Err(e) = browser::open(&target) {
+                    eprintln!("{e}");
+                }
+            }
+        }
+        true
+    }
+
+    /// Drag out a rectangle to measure from where the button was pressed,
+    /// if `start`, until it is released. Returns `true` while doing so.
+    fn drag_selection(&mut self, start: bool, down: bool) -> bool {
+        let point = match self.doc_point(self.cursor) {
+            Some((index, point)) if index == self.active => Some(point),
+            _ => None,
+        };
+        if start && let Some(point) = point {
+            self.selection = Some(Selection::new(point));
+        }
+        let Some(selection) = &mut self.selection else {
+            return false;
+        };
+        if !down {
+            self.selection = None;
+        } else if let Some(point) = point {
+            selection.end = point;
+        }
+        true
+    }
+
+    /// Centre the view on the point pressed or dragged over in the minimap.
+    /// Returns `true` while doing so.
+    fn drag_minimap(&mut self, window: &dyn Window, pressed: bool, down: bool) -> bool {
+        let viewport = self.viewport();
+        let viewport = (viewport.0 as f32, viewport.1 as f32);
+        let Some(size) = self.inputs[self.active].tree.as_ref().map(|t| t.size()) else {
+            return false;
+        };
+        let Some(map) = &mut self.minimap else {
+            return false;
+        };
+        let Some(at) = window.get_mouse_pos(MouseMode::Clamp) else {
+            return false;
+        };
+        if pressed {
+            map.dragging = minimap::contains(size, viewport, at);
+        }
+        if !down {
+            map.dragging = false;
+        }
+        if !map.dragging {
+            return false;
+        }
+        if let Some(point) = minimap::point(size, viewport, at) {
+            self.state.centre_on(point, size);
+        }
+        true
+    }
+
+    /// Pull guides out of the rulers and move them, dropping them back on a
+    /// ruler to delete them. Returns `true` while a guide is held.
+    fn drag_guide(&mut self, window: &dyn Window, pressed: bool, down: bool) -> bool {
+        let index = self.active;
+        let Some(transform) = self.view_transform(index) else {
+            return false;
+        };
+        let Some(at) = window.get_mouse_pos(MouseMode::Clamp) else {
+            return false;
+        };
+        let input = &mut self.inputs[index];
+        if pressed {
+            let axis = match at {
+                (x, y) if x < RULER && y < RULER => None,
+                (_, y) if y < RULER => Some(Axis::Horizontal),
+                (x, _) if x < RULER => Some(Axis::Vertical),
+                _ => None,
+            };
+            self.dragging = match axis {
+                Some(axis) => {
+                    input.guides.push(Guide { axis, pos: 0.0 });
+                    Some(input.guides.len() - 1)
+                }
+                None => guides::grab(&input.guides, transform, at),
+            };
+        }
+        let Some(i) = self.dragging else {
+            return false;
+        };
+        let Some(guide) = input.guides.get_mut(i) else {
+            self.dragging = None;
+            return false;
+        };
+        if down {
+            guides::drag(guide, transform, &self.config.units, at);
+            return true;
+        }
+        self.dragging = None;
+        if guides::over_ruler(guide.axis, at) {
+            input.guides.remove(i);
+        }
+        guides::save(input.source.path(), &input.guides);
+        true
+    }
+
+    /// Move the wipe split while the left button is held, returning `true` if it moved
+    fn drag_split(&mut self, window: &dyn Window) -> bool {
+        if self.mode() != Mode::Wipe || !window.get_mouse_down(MouseButton::Left) {
+            return false;
+        }
+        let Some((x, _)) = window.get_mouse_pos(MouseMode::Clamp) else {
+            return false;
+        };
+        let split = x / self.width as f32;
+        let moved = split != self.split;
+        self.split = split;
+        moved
+    }
+
+    fn title(&self) -> String {
+        // Screen pixels per user unit, as a percentage
+        let scale = match self.view_transform(self.active) {
+            Some(t) => format!(" {}", self.config.units.percent(t.sx)),
+            None => String::new(),
+        };
+        format!(
+            "{} [{}{scale}] {}",
+            self.inputs_title(),
+            self.state.mode_name(),
+            self.watching.indicator()
+        )
+    }
+
+    /// The title naming the inputs shown
+    fn inputs_title(&self) -> String {
+        let name = self.config.title.as_deref().unwrap_or("svgtail");
+        match self.inputs.len() {
+            1 => name.to_string(),
+            n => {
+                let label = self.inputs[self.active].source.label();
+                let title = format!("{name} - {label} ({}/{n})", self.active + 1);
+                match self.compare {
+                    Mode::Flip => title,
+                    mode => {
+                        let other = self.inputs[self.other()].source.label();
+                        format!("{title} {} {other}", mode.name())
+                    }
+                }
+            }
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            path: self.path().to_path_buf(),
+            doc_size: self.tree().map(|t| (t.size().width(), t.size().height())),
+            window_size: (self.width, self.height),
+            zoom: self.state.zoom,
+            pan: self.state.pan,
+            fit_scale: self.state.fit_scale,
+            auto_fit: self.state.auto_fit,
+        }
+    }
+
+    fn screenshot(&mut self, path: &Path) -> Result<(), String> {
+        self.draw_shown()?;
+        let mut pixmap = self.render_pixmap().ok_or("no document loaded")?;
+        if self.config.bake_annotations {
+            self.draw_marks(&mut pixmap);
+        }
+        if let Some(dither) = &self.config.dither {
+            dither.apply_pixmap(&mut pixmap);
+        }
+        match &self.config.profile {
+            Some(profile) => profile.save_png(&pixmap, path),
+            None => pixmap.save_png(path).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Save the view with the review marks drawn in, over a caption naming
+    /// the shown file, the time and the zoom
+    fn export_review(&mut self, path: &Path) -> Result<(), String> {
+        self.draw_shown()?;
+        let mut pixmap = self.render_pixmap().ok_or("no document loaded")?;
+        self.draw_marks(&mut pixmap);
+        let zoom = self
+            .view_transform(self.active)
+            .map_or(String::new(), |t| self.config.units.percent(t.sx));
+        let lines = [
+            self.path().display().to_string(),
+            format!("{}   zoom {zoom}", review::utc(SystemTime::now())),
+        ];
+        let pixmap = review::with_caption(&pixmap, &lines, &self.svg_opts)
+            .ok_or("view is too large to caption")?;
+        export::save(
+            pixmap,
+            path,
+            self.config.dither.as_ref(),
+            self.config.profile.as_deref(),
+        )
+    }
+
+    /// Save the crop of the shown document, as SVG for a `.svg` path and
+    /// otherwise as PNG at the current zoom
+    fn export_crop(&self, path: &Path) -> Result<(), String> {
+        let rect = self
+            .crop
+            .as_ref()
+            .and_then(|c| c.rect)
+            .ok_or("no crop placed")?;
+        let tree = self.inputs[self.active]
+            .tree
+            .as_ref()
+            .ok_or("no document loaded")?;
+        if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"))
+        {
+            return std::fs::write(path, crop::to_svg(tree, rect)).map_err(|e| e.to_string());
+        }
+        let scale = self.view_transform(self.active).map_or(1.0, |t| t.sx);
+        let pixmap = match self.config.limits {
+            Some(limits) => {
+                let (tree, canvas) = (tree.clone(), self.canvas.clone());
+                limits.draw(move || crop::to_pixmap(&tree, rect, scale, &canvas))?
+            }
+            None => crop::to_pixmap(tree, rect, scale, &self.canvas),
+        };
+        let pixmap = pixmap.ok_or("crop is too small")?;
+        match &self.config.profile {
+            Some(profile) => profile.save_png(&pixmap, path),
+            None => pixmap.save_png(path).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Arrow keys nudge the crop, or resize it with Shift, by one unit or
+    /// ten with Ctrl. Enter saves it beside the document as PNG, or SVG with
+    /// Shift. Returns `true` if the crop changed.
+    fn handle_crop_keys(&mut self, window: &dyn Window) -> bool {
+        let Some(crop) = &mut self.crop else {
+            return false;
+        };
+        let shift = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+        let ctrl = window.is_key_down(Key::LeftCtrl) || window.is_key_down(Key::RightCtrl);
+        let step = if ctrl { 10.0 } else { 1.0 };
+        let mut changed = false;
+        for key in window.get_keys_pressed(KeyRepeat::Yes) {
+            let (dx, dy) = match key {
+                Key::Left => (-step, 0.0),
+                Key::Right => (step, 0.0),
+                Key::Up => (0.0, -step),
+                Key::Down => (0.0, step),
+                Key::Enter => {
+                    let doc = self.inputs[self.active].source.path();
+                    let path = crop::default_path(doc, if shift { "svg" } else { "png" });
+                    self.actions.push(Action::ExportCrop(path, None));
+                    return changed;
+                }
+                _ => continue,
+            };
+            crop.nudge(dx, dy, shift);
+            changed = true;
+        }
+        changed
+    }
+
+    /// Type into the search bar while it is open, taking every key from the
+    /// rest of the viewer: Enter and Shift+Enter go to the next and previous
+    /// match, and Escape closes it. Returns `true` if anything changed.
+    fn handle_search_keys(&mut self, window: &dyn Window) -> bool {
+        let Some(search) = &mut self.search else {
+            return false;
+        };
+        let shift = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+        let mut typed = false;
+        let mut centre = None;
+        for key in window.get_keys_pressed(KeyRepeat::Yes) {
+            match key {
+                Key::Escape => {
+                    self.search = None;
+                    self.closed_bar = true;
+                    return true;
+                }
+                Key::Enter | Key::NumPadEnter => centre = search.step(!shift),
+                Key::Backspace => typed |= search.query.pop().is_some(),
+                key => {
+                    if let Some(c) = search::typed(key, shift) {
+                        search.query.push(c);
+                        typed = true;
+                    }
+                }
+            }
+        }
+        if typed {
+            search.restart();
+            if let Some(tree) = self.inputs[self.active].tree.as_ref() {
+                search.find(tree);
+            }
+            centre = search.current();
+        }
+        if let (Some(rect), Some(tree)) = (centre, self.inputs[self.active].tree.as_ref()) {
+            let middle = (
+                rect.x() + rect.width() / 2.0,
+                rect.y() + rect.height() / 2.0,
+            );
+            self.state.centre_on(middle, tree.size());
+        }
+        typed || centre.is_some()
+    }
+
+    /// Look for the search's query and the highlighted elements again in
+    /// the shown input, as it changes
+    fn find_again(&mut self) {
+        let Some(tree) = &self.inputs[self.active].tree else {
+            return;
+        };
+        if let Some(search) = &mut self.search {
+            search.find(tree);
+        }
+        self.highlights.find(tree);
+    }
+
+    /// Whether Escape is held to quit, rather than having closed the search
+    /// or a note
+    fn escaping(&mut self, window: &dyn Window) -> bool {
+        let down = window.is_key_down(Key::Escape);
+        self.closed_bar &= down;
+        down && !self.typing() && !self.closed_bar
+    }
+
+    /// Whether keys type into the search bar or a note, rather than
+    /// reaching the rest of the viewer
+    fn typing(&self) -> bool {
+        self.search.is_some() || self.pen.as_ref().is_some_and(|p| p.typing.is_some())
+    }
+
+    /// Zoom in on the cursor while `z` is held in presenter mode, and back to
+    /// the view from before once it is let go. Returns `true` if the view
+    /// changed.
+    fn peek(&mut self, window: &dyn Window) -> bool {
+        let Some(size) = self.tree().map(|t| t.size()) else {
+            return false;
+        };
+        let (w, h) = self.viewport();
+        let about = self.zoom_anchor();
+        let Some(presenter) = &mut self.presenter else {
+            return false;
+        };
+        match (window.is_key_down(Key::Z), presenter.peek.take()) {
+            (true, None) => {
+                presenter.peek = Some(self.state.clone());
+                self.state
+                    .zoom_about(presenter::PEEK_ZOOM, about, size, w, h);
+                true
+            }
+            (true, Some(before)) => {
+                presenter.peek = Some(before);
+                false
+            }
+            (false, Some(before)) => {
+                self.state = before;
+                true
+            }
+            (false, None) => false,
+        }
+    }
+
+    /// Copy the view to the clipboard and the shown file's path to the
+    /// primary selection with `y`, and open an SVG pasted with `v`, or with
+    /// Shift+`v` from the primary selection
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "redox"))))]
+    fn handle_clipboard_keys(&mut self, window: &dyn Window, pressed: &[Key]) {
+        if pressed.contains(&Key::Y)
+            && let Err(e) = self.copy_view()
+        {
+            eprintln!("copy: {e}");
+        }
+        if pressed.contains(&Key::V) {
+            let shift = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+            let selection = match shift {
+                true => clipboard::Selection::Primary,
+                false => clipboard::Selection::Clipboard,
+            };
+            match clipboard::paste_svg(selection) {
+                Ok(path) => self.actions.push(Action::Open(path)),
+                Err(e) => eprintln!("paste: {e}"),
+            }
+        }
+    }
+
+    /// Put the view, review marks and all, on the clipboard as PNG, and the
+    /// shown file's path on the primary selection
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "redox"))))]
+    fn copy_view(&mut self) -> Result<(), String> {
+        self.draw_shown()?;
+        let mut pixmap = self.render_pixmap().ok_or("no document loaded")?;
+        self.draw_marks(&mut pixmap);
+        let png = pixmap.encode_png().map_err(|e| e.to_string())?;
+        clipboard::copy(clipboard::Selection::Clipboard, &["image/png"], png)?;
+        let path = self.path().display().to_string();
+        clipboard::copy(
+            clipboard::Selection::Primary,
+            clipboard::TEXT,
+            path.into_bytes(),
+        )
+    }
+
+    /// Drag out a review mark, an arrow with `arrow`, or click to start a
+    /// note. Returns `true` while doing so or once one is placed.
+    fn draw_mark(&mut self, pressed: bool, down: bool, arrow: bool) -> bool {
+        let Some(transform) = self.view_transform(self.active) else {
+            return false;
+        };
+        let point = self
+            .doc_point(self.cursor)
+            .filter(|(index, _)| *index == self.active)
+            .map(|(_, point)| point);
+        let Some(pen) = &mut self.pen else {
+            return false;
+        };
+        if pressed
+            && pen.typing.is_none()
+            && let (Some(point), Some(at)) = (point, self.cursor)
+        {
+            pen.press(point, at, arrow);
+        }
+        if !pen.dragging() {
+            return false;
+        }
+        if down {
+            if let Some(point) = point {
+                pen.drag(point, transform);
+            }
+        } else if let Some(mark) = self.cursor.and_then(|at| pen.release(at)) {
+            let input = &mut self.inputs[self.active];
+            input.marks.push(mark);
+            annotate::save(input.source.path(), &input.marks);
+        }
+        true
+    }
+
+    /// Type a note while one is placed: Enter keeps it, unless it is empty,
+    /// and Escape throws it away. Returns `true` if anything changed.
+    fn handle_note_keys(&mut self, window: &dyn Window) -> bool {
+        let Some((at, text)) = self.pen.as_mut().and_then(|p| p.typing.as_mut()) else {
+            return false;
+        };
+        let shift = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+        let mut changed = false;
+        for key in window.get_keys_pressed(KeyRepeat::Yes) {
+            match key {
+                Key::Escape => {
+                    self.closed_bar = true;
+                    if let Some(pen) = &mut self.pen {
+                        pen.typing = None;
+                    }
+                    return true;
+                }
+                Key::Enter | Key::NumPadEnter => {
+                    let note = annotate::Mark::Note {
+                        at: *at,
+                        text: text.trim().to_string(),
+                    };
+                    let keep = !text.trim().is_empty();
+                    if let Some(pen) = &mut self.pen {
+                        pen.typing = None;
+                    }
+                    if keep {
+                        let input = &mut self.inputs[self.active];
+                        input.marks.push(note);
+                        annotate::save(input.source.path(), &input.marks);
+                    }
+                    return true;
+                }
+                Key::Backspace => changed |= text.pop().is_some(),
+                key => {
+                    if let Some(c) = search::typed(key, shift) {
+                        text.push(c);
+                        changed = true;
+                    }
+                }
+            }
+        }
+        changed
+    }
+
+    /// Place the crop by dragging. Returns `true` while doing so.
+    fn drag_crop(&mut self, pressed: bool, down: bool) -> bool {
+        let point = self
+            .doc_point(self.cursor)
+            .filter(|(index, _)| *index == self.active)
+            .map(|(_, point)| point);
+        let Some(crop) = &mut self.crop else {
+            return false;
+        };
+        if pressed && let Some(point) = point {
+            crop.press(point);
+        }
+        if !down {
+            crop.release();
+            return false;
+        }
+        if let Some(point) = point {
+            crop.drag(point);
+        }
+        crop.dragging()
+    }
+
+    /// Tell subscribers about pan/zoom changes since the last report
+    fn report_view(&mut self) {
+        let view = (self.state.zoom, self.state.pan);
+        if view != self.reported_view {
+            self.reported_view = view;
+            self.emit(Event::ViewChanged {
+                zoom: view.0,
+                pan: view.1,
+            });
+        }
+    }
+
+    /// Render the window contents into `buffer`
+    fn render(&mut self, buffer: &mut Vec<u32>) {
+        let mut pixmap = match self.render_pixmap() {
+            Some(pixmap) => pixmap,
+            None => self.blank(self.width, self.height),
+        };
+        self.draw_rulers(&mut pixmap);
+        self.draw_selection(&mut pixmap);
+        self.draw_angle(&mut pixmap);
+        if let (Some(crop), Some(transform)) = (&self.crop, self.view_transform(self.active)) {
+            crop.draw(&mut pixmap, transform, &self.config.units, &self.svg_opts);
+        }
+        if let (Some(search), Some(transform)) = (&self.search, self.view_transform(self.active)) {
+            search.draw(&mut pixmap, transform, &self.svg_opts);
+        }
+        self.draw_marks(&mut pixmap);
+        if let (Some(pen), Some(transform)) = (&self.pen, self.view_transform(self.active)) {
+            pen.draw(&mut pixmap, transform, &self.svg_opts);
+        }
+        self.draw_minimap(&mut pixmap);
+        self.draw_loupe(&mut pixmap);
+        self.draw_way_home(&mut pixmap);
+        if let (Some(Some(tip)), Some(at)) = (&self.tooltip, self.cursor) {
+            overlay::tooltip(&mut pixmap, at, tip, &self.svg_opts);
+        }
+        self.draw_hud(&mut pixmap);
+        self.draw_error(&mut pixmap);
+        if let (true, Some(at)) = (self.config.large_cursor, self.cursor) {
+            access::draw_cursor(&mut pixmap, at);
+        }
+        if let Some(presenter) = &self.presenter {
+            presenter.draw(&mut pixmap, self.cursor, Instant::now());
+        }
+        let transform = self.held.or_else(|| self.view_transform(self.active));
+        for f in &mut self.post_processes {
+            f(&mut pixmap, transform);
+        }
+        render::to_buffer(&pixmap, self.canvas.background, buffer);
+        if let Some(contrast) = self.config.contrast {
+            contrast.apply(buffer);
+        }
+        if let Some(profile) = &self.config.profile {
+            profile.apply(buffer);
+        }
+        if let Some(dither) = &self.config.dither {
+            dither.apply(buffer, self.width);
+        }
+    }
+
+    /// Whether a shown input is only rendered coarsely so far
+    fn refining(&self) -> bool {
+        let coarse = |i: &Input| i.frame.as_ref().is_some_and(|f| f.coarse);
+        if self.tile {
+            // Tiling doesn't use the frames.
+            return false;
+        }
+        match self.mode() {
+            Mode::Flip => coarse(&self.inputs[self.active]),
+            _ => self.inputs.iter().any(coarse),
+        }
+    }
+
+    /// Whether a shown input is still loading
+    fn loading(&self) -> bool {
+        match self.mode() {
+            Mode::Flip => self.inputs[self.active].loading.is_some(),
+            _ => self.inputs.iter().any(|i| i.loading.is_some()),
+        }
+    }
+
+    /// The rectangle being measured, with its size beside the cursor
+    fn draw_selection(&self, pixmap: &mut tiny_skia::Pixmap) {
+        let Some(selection) = &self.selection else {
+            return;
+        };
+        let Some(transform) = self.view_transform(self.active) else {
+            return;
+        };
+        overlay::draw(pixmap, &[selection.shape()], transform, &self.svg_opts);
+        if let Some((x, y)) = self.cursor {
+            let text = selection.readout(transform.sx, &self.config.units);
+            guides::readout(pixmap, (x + 12.0, y + 12.0), &text, &self.svg_opts);
+        }
+    }
+
+    /// The angle being measured, with its size beside the vertex
+    fn draw_angle(&self, pixmap: &mut tiny_skia::Pixmap) {
+        let Some(angle) = &self.angle else {
+            return;
+        };
+        let Some(transform) = self.view_transform(self.active) else {
+            return;
+        };
+        let cursor = self.doc_point(self.cursor).map(|(_, point)| point);
+        overlay::draw(pixmap, &angle.shapes(cursor), transform, &self.svg_opts);
+        if let Some((vertex, degrees)) = angle.degrees(cursor) {
+            let mut at = tiny_skia::Point::from_xy(vertex.0, vertex.1);
+            transform.map_point(&mut at);
+            let text = format!("{}°", self.config.units.number(degrees, 1));
+            guides::readout(pixmap, (at.x + 12.0, at.y + 12.0), &text, &self.svg_opts);
+        }
+    }
+
+    /// The minimap, if shown, in the corner of the shown input's viewport
+    fn draw_minimap(&mut self, pixmap: &mut tiny_skia::Pixmap) {
+        let (w, h) = self.viewport();
+        let Some(transform) = self.view_transform(self.active) else {
+            return;
+        };
+        let Some(map) = &mut self.minimap else {
+            return;
+        };
+        let input = &self.inputs[self.active];
+        let Some(tree) = &input.tree else {
+            return;
+        };
+        let viewport = (w as f32, h as f32);
+        map.draw(pixmap, tree, input.hash, transform, viewport, &self.canvas);
+    }
+
+    /// Magnify the document under the cursor, or the middle of the view
+    /// once the keyboard moves it or the cursor leaves, if the loupe is on
+    fn draw_loupe(&self, pixmap: &mut tiny_skia::Pixmap) {
+        let Some(loupe) = &self.loupe else {
+            return;
+        };
+        let (w, h) = self.viewport();
+        let centre = match self.cursor {
+            Some(at) if !self.keyboard_focus => at,
+            _ => (w as f32 / 2.0, h as f32 / 2.0),
+        };
+        let x = centre.0;
+        // Side by side, the right half shows the other input.
+        let (index, left, width) = if self.mode() == Mode::SideBySide && x >= w as f32 {
+            (self.other(), w, self.width - w)
+        } else {
+            (self.active, 0, w)
+        };
+        let Some(tree) = &self.inputs[index].tree else {
+            return;
+        };
+        let transform = self.state.transform(tree.size(), width, h);
+        loupe.draw(pixmap, tree, transform, &self.canvas, centre, left as f32);
+    }
+
+    /// Zoom a manual view by `ratio` for a monitor that many times as dense,
+    /// so the document keeps its physical size; a fitted view is refitted
+    /// anyway
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "redox"))))]
+    fn keep_physical_size(&mut self, ratio: f32) {
+        if self.state.auto_fit || ratio == 1.0 {
+            return;
+        }
+        let Some(size) = self.tree().map(|t| t.size()) else {
+            return;
+        };
+        let (w, h) = self.viewport();
+        let centre = (w as f32 / 2.0, h as f32 / 2.0);
+        self.state.zoom_about(ratio, centre, size, w, h);
+    }
+
+    /// What keyboard zoom keeps still, in viewport pixels: the point under
+    /// the cursor, or the middle of the viewport
+    fn zoom_anchor(&self) -> (f32, f32) {
+        let (w, h) = self.viewport();
+        match self.cursor {
+            // The right half of side by side shows the same view.
+            Some((x, y)) if x >= w as f32 => (x - w as f32, y),
+            Some(at) => at,
+            None => (w as f32 / 2.0, h as f32 / 2.0),
+        }
+    }
+
+    /// How input `index` is mapped into its viewport, if it is loaded
+    fn view_transform(&self, index: usize) -> Option<tiny_skia::Transform> {
+        let tree = self.inputs[index].tree.as_ref()?;
+        let (w, h) = self.viewport();
+        Some(self.state.transform(tree.size(), w, h))
+    }
+
+    /// Rulers and guides, with the position of a guide being dragged
+    fn draw_rulers(&self, pixmap: &mut tiny_skia::Pixmap) {
+        if !self.show_rulers {
+            return;
+        }
+        let Some(transform) = self.view_transform(self.active) else {
+            return;
+        };
+        let input = &self.inputs[self.active];
+        let (w, h) = self.viewport();
+        let (w, h) = (w as u32, h as u32);
+        guides::draw(pixmap, &input.guides, transform, w, h);
+        let units = &self.config.units;
+        guides::draw_rulers(pixmap, transform, units, w, h, &self.svg_opts);
+        let dragged = self.dragging.and_then(|i| input.guides.get(i));
+        if let (Some(guide), Some((x, y))) = (dragged, self.cursor) {
+            let (name, scale) = match guide.axis {
+                Axis::Horizontal => ("y", transform.sy),
+                Axis::Vertical => ("x", transform.sx),
+            };
+            let text = format!("{name} = {}", units.length(guide.pos, scale));
+            guides::readout(pixmap, (x + 12.0, y + 12.0), &text, &self.svg_opts);
+        }
+    }
+
+    /// With the page panned or zoomed entirely out of view, an arrow at the
+    /// edge of the window pointing back to it
+    fn draw_way_home(&self, pixmap: &mut tiny_skia::Pixmap) {
+        let Some(tree) = &self.inputs[self.active].tree else {
+            return;
+        };
+        let (w, h) = self.viewport();
+        let transform = self.state.transform(tree.size(), w, h);
+        let size = tree.size();
+        let Some(page) = tiny_skia::Rect::from_xywh(0.0, 0.0, size.width(), size.height())
+            .and_then(|r| r.transform(transform))
+        else {
+            return;
+        };
+        let visible = page.right() > 0.0
+            && page.bottom() > 0.0
+            && page.left() < w as f32
+            && page.top() < h as f32;
+        if visible {
+            return;
+        }
+        let target = (
+            page.left() + page.width() / 2.0,
+            page.top() + page.height() / 2.0,
+        );
+        overlay::way_home(pixmap, target, (w as f32, h as f32), &self.svg_opts);
+    }
+
+    /// The shown input's review marks
+    fn draw_marks(&self, pixmap: &mut tiny_skia::Pixmap) {
+        if let Some(transform) = self.view_transform(self.active) {
+            let marks = &self.inputs[self.active].marks;
+            annotate::draw(pixmap, marks, transform, &self.svg_opts);
+        }
+    }
+
+    /// Progress of loads taking long enough to notice, and whether the
+    /// watcher is down, in the top-left corner
+    fn draw_hud(&self, pixmap: &mut tiny_skia::Pixmap) {
+        let white = tiny_skia::Color::WHITE;
+        let mut y = 8.0;
+        if let Status::Down(e) = &self.watching {
+            let line = format!(
+                "{}: {e}; changes aren't followed",
+                self.watching.indicator()
+            );
+            overlay::text(pixmap, (8.0, y), &line, white, &self.svg_opts);
+            y += 20.0;
+        }
+        if !self.loading() {
+            return;
+        }
+        for input in &self.inputs {
+            let Some(loading) = &input.loading else {
+                continue;
+            };
+            let elapsed = loading.started.elapsed();
+            if elapsed < HUD_DELAY {
+                continue;
+            }
+            let units = &self.config.units;
+            let size = match loading.size {
+                Some(bytes) => format!(" ({} MB)", units.number(bytes as f32 / 1e6, 1)),
+                None => String::new(),
+            };
+            let line = format!(
+                "Loading {}{size}… {} s",
+                input.source.label(),
+                units.number(elapsed.as_secs_f32(), 1)
+            );
+            overlay::text(pixmap, (8.0, y), &line, white, &self.svg_opts);
+            y += 20.0;
+        }
+    }
+
+    /// Why the shown document's generator failed, or its file still didn't
+    /// load after retrying, over the last version that did
+    fn draw_error(&self, pixmap: &mut tiny_skia::Pixmap) {
+        let input = &self.inputs[self.active];
+        if let (Some(error), true) = (&input.error, shows_error(input)) {
+            let title = format!("{} failed to update", input.source.label());
+            overlay::error_pane(pixmap, &title, error, &self.svg_opts);
+        }
+    }
+
+    /// A pixmap filled with the background colour
+    fn blank(&self, width: usize, height: usize) -> tiny_skia::Pixmap {
+        let mut pixmap = tiny_skia::Pixmap::new(width as u32, height as u32)
+            .expect("pixmap size must be non-zero");
+        pixmap.fill(self.canvas.background);
+        pixmap
+    }
+
+    /// Outlines of the shown document's page and, where it differs, its viewBox
+    fn bounds(&self) -> Vec<Shape> {
+        let input = &self.inputs[self.active];
+        let Some(tree) = &input.tree else {
+            return Vec::new();
+        };
+        let size = tree.size();
+        let page_rect = tiny_skia::Rect::from_xywh(0.0, 0.0, size.width(), size.height());
+        let page = Shape::Rect {
+            x: 0.0,
+            y: 0.0,
+            w: size.width(),
+            h: size.height(),
+            color: tiny_skia::Color::from_rgba8(0x00, 0xCC, 0xFF, 0xFF),
+        };
+        let view_box = input
+            .view_box
+            .filter(|vb| Some(*vb) != page_rect)
+            .map(|vb| Shape::Rect {
+                x: vb.x(),
+                y: vb.y(),
+                w: vb.width(),
+                h: vb.height(),
+                color: tiny_skia::Color::from_rgba8(0xFF, 0x00, 0xCC, 0xFF),
+            });
+        std::iter::once(page).chain(view_box).collect()
+    }
+
+    /// The input the shown one is compared against
+    fn other(&self) -> usize {
+        (self.active + 1) % self.inputs.len()
+    }
+
+    /// Render input `index` at the shared view into a `width` × `height` pixmap,
+    /// reusing its cached frame where the document is unchanged
+    fn render_input(&mut self, index: usize, width: usize, height: usize) -> tiny_skia::Pixmap {
+        let canvas = &self.canvas;
+        let input = &mut self.inputs[index];
+        let Some(tree) = &input.tree else {
+            return self.blank(width, height);
+        };
+        let (w, h) = (width as u32, height as u32);
+        let transform = self
+            .held
+            .unwrap_or_else(|| self.state.transform(tree.size(), width, height));
+        if self.config.limits.is_some() {
+            return self.render_untrusted(index, width, height, transform);
+        }
+        if self.tile {
+            // Not cached, so damage is kept for when the frame is next used.
+            return render::render_tiled(tree, w, h, transform, canvas);
+        }
+        if self.resizing.is_some()
+            && let Some(frame) = &input.frame
+        {
+            // The cache and damage are left for the render once it settles.
+            return frame.rescaled(w, h, transform);
+        }
+        let damage = std::mem::replace(&mut input.damage, Damage::None);
+        // Slow documents are shown coarse first, then refined on the next
+        // frame unless the view has moved on by then.
+        let slow = input.render_time.is_some_and(|t| t >= PROGRESSIVE_AFTER);
+        let render_time = &mut input.render_time;
+        let mut full = || {
+            let start = Instant::now();
+            let frame = Frame::new(tree, w, h, transform, canvas);
+            *render_time = Some(start.elapsed());
+            frame
+        };
+        match &mut input.frame {
+            Some(frame) if frame.matches(w, h, transform, canvas) && frame.coarse => {
+                *frame = full();
+            }
+            Some(frame) if frame.matches(w, h, transform, canvas) => match damage {
+                Damage::None => {}
+                Damage::Region(rect) => frame.repaint(tree, rect),
+                Damage::Full if slow => *frame = Frame::coarse(tree, w, h, transform, canvas),
+                Damage::Full => *frame = full(),
+            },
+            _ if slow => input.frame = Some(Frame::coarse(tree, w, h, transform, canvas)),
+            _ => input.frame = Some(full()),
+        }
+        let frame = input.frame.as_ref().expect("frame was just rendered");
+        frame.pixmap.clone()
+    }
+
+    /// Render untrusted input `index` as [`Self::render_input`] does, but on
+    /// another thread, so one too slow to draw can be given up on without
+    /// holding up the window. Until its frame arrives, the last one stands
+    /// in, resampled, or else a blank does.
+    fn render_untrusted(
+        &mut self,
+        index: usize,
+        width: usize,
+        height: usize,
+        transform: tiny_skia::Transform,
+    ) -> tiny_skia::Pixmap {
+        let (w, h) = (width as u32, height as u32);
+        let tile = self.tile;
+        let input = &self.inputs[index];
+        let stand_in = input.frame.as_ref().filter(|f| f.tiled == tile);
+        if let Some(frame) = stand_in
+            && frame.matches(w, h, transform, &self.canvas)
+            && matches!(input.damage, Damage::None)
+        {
+            return frame.pixmap.clone();
+        }
+        // One at a time: once it is in, the next frame starts another if the
+        // view has moved on.
+        if input.drawing.is_none()
+            && let Some(tree) = &input.tree
+        {
+            self.next_ticket += 1;
+            let ticket = self.next_ticket;
+            let tree = tree.clone();
+            let canvas = self.canvas.clone();
+            let tx = self.queue.0.clone();
+            let cancel = untrusted::spawn(
+                move || draw_frame(&tree, w, h, transform, &canvas, tile),
+                move |frame| {
+                    let _ = tx.send(Wake::Rendered(Box::new(Rendered {
+                        index,
+                        ticket,
+                        frame,
+                    })));
+                },
+            );
+            let input = &mut self.inputs[index];
+            input.damage = Damage::None;
+            input.drawing = Some(Drawing {
+                ticket,
+                started: Instant::now(),
+                cancel,
+            });
+        }
+        let input = &self.inputs[index];
+        match input.frame.as_ref().filter(|f| f.tiled == tile) {
+            Some(frame) => frame.rescaled(w, h, transform),
+            None => self.blank(width, height),
+        }
+    }
+
+    /// Bring the frames of the shown untrusted inputs up to date, waiting for
+    /// them here rather than drawing them in the background, so a one-off
+    /// capture of the view has the document in it or else says why not
+    fn draw_shown(&mut self) -> Result<(), String> {
+        let Some(limits) = self.config.limits else {
+            return Ok(());
+        };
+        let (w, h) = self.viewport();
+        let mut shown = vec![(self.active, w, h)];
+        match self.mode() {
+            Mode::Flip => {}
+            Mode::SideBySide => shown.push((self.other(), self.width - w, h)),
+            _ => shown.push((self.other(), w, h)),
+        }
+        let tile = self.tile;
+        for (index, width, height) in shown {
+            let input = &self.inputs[index];
+            let Some(tree) = &input.tree else {
+                continue;
+            };
+            let (w, h) = (width as u32, height as u32);
+            let transform = self
+                .held
+                .unwrap_or_else(|| self.state.transform(tree.size(), width, height));
+            if input
+                .frame
+                .as_ref()
+                .is_some_and(|f| f.tiled == tile && f.matches(w, h, transform, &self.canvas))
+                && matches!(input.damage, Damage::None)
+            {
+                continue;
+            }
+            let tree = tree.clone();
+            let canvas = self.canvas.clone();
+            let frame = limits.draw(move || draw_frame(&tree, w, h, transform, &canvas, tile))?;
+            let input = &mut self.inputs[index];
+            if let Some(drawing) = input.drawing.take() {
+                drawing.cancel.store(true, Ordering::Relaxed);
+            }
+            input.frame = Some(frame);
+            input.damage = Damage::None;
+        }
+        Ok(())
+    }
+
+    /// Give up on untrusted documents still being drawn after
+    /// [`Limits::render_timeout`], showing why as a load error until a
+    /// change makes them worth another try. Their renders are left to finish
+    /// on their own, and dropped. Returns `true` if any were given up on.
+    fn drop_slow(&mut self) -> bool {
+        let Some(limits) = self.config.limits else {
+            return false;
+        };
+        let mut dropped = false;
+        for i in 0..self.inputs.len() {
+            let input = &mut self.inputs[i];
+            if input
+                .drawing
+                .as_ref()
+                .is_none_or(|d| d.started.elapsed() < limits.render_timeout)
+            {
+                continue;
+            }
+            let error = limits.too_slow();
+            if let Some(drawing) = input.drawing.take() {
+                drawing.cancel.store(true, Ordering::Relaxed);
+            }
+            input.tree = None;
+            input.frame = None;
+            input.retries = MAX_RETRIES;
+            input.error = Some(error.clone());
+            let path = input.source.path().to_path_buf();
+            self.emit(Event::LoadFailed(path, error));
+            dropped = true;
+        }
+        dropped
+    }
+
+    /// Render an input next to the shown one at the view it gets when flipped
+    /// to with backtick, `n` or `p`, so flipping shows it at once. Renders at most one, returning
+    /// `true` if it did.
+    fn preload(&mut self) -> bool {
+        if self.mode() != Mode::Flip || self.tile || self.resizing.is_some() || self.held.is_some()
+        {
+            return false;
+        }
+        let n = self.inputs.len();
+        let (w, h) = self.viewport();
+        let (fit_w, fit_h) = self.fit_area();
+        for i in [(self.active + 1) % n, (self.active + n - 1) % n] {
+            let input = &self.inputs[i];
+            let Some(tree) = &input.tree else {
+                continue;
+            };
+            if i == self.active {
+                continue;
+            }
+            let mut state = self.state.clone();
+            state.update_fit_scale(tree, fit_w, fit_h);
+            let transform = state.transform(tree.size(), w, h);
+            let ready = input.frame.as_ref().is_some_and(|f| {
+                !f.coarse && f.matches(w as u32, h as u32, transform, &self.canvas)
+            });
+            if ready && matches!(input.damage, Damage::None) {
+                continue;
+            }
+            let shown = std::mem::replace(&mut self.state, state);
+            self.render_input(i, w, h);
+            self.state = shown;
+            return true;
+        }
+        false
+    }
+
+    /// Render the document with overlays and hooks applied
+    fn render_pixmap(&mut self) -> Option<tiny_skia::Pixmap> {
+        let tree = self.inputs[self.active].tree.as_ref()?;
+        let (w, h) = self.viewport();
+        let transform = self
+            .held
+            .unwrap_or_else(|| self.state.transform(tree.size(), w, h));
+        let mut pixmap = self.render_input(self.active, w, h);
+        match self.mode() {
+            Mode::Flip => {}
+            Mode::SideBySide => {
+                let other = self.render_input(self.other(), self.width - w, h);
+                pixmap = compare::side_by_side(&pixmap, &other)?;
+            }
+            mode => {
+                let other = self.render_input(self.other(), w, h);
+                compare::blend(mode, &mut pixmap, &other, self.split);
+            }
+        }
+        self.highlights.draw(&mut pixmap, transform, &self.svg_opts);
+        overlay::draw(&mut pixmap, &self.overlays, transform, &self.svg_opts);
+        if self.show_grid {
+            overlay::pixel_grid(&mut pixmap, transform, w as u32);
+        }
+        if self.show_bounds {
+            overlay::draw(&mut pixmap, &self.bounds(), transform, &self.svg_opts);
+        }
+        for hooks in &mut self.hooks {
+            hooks.on_frame(&mut pixmap, transform);
+        }
+        Some(pixmap)
+    }
+
+    /// A watcher on everything there is to watch, feeding the queue
+    fn watch(&mut self) -> Result<Watcher, String> {
+        let events = self.queue.0.clone();
+        let status = self.queue.0.clone();
+        Watcher::spawn(
+            self.watched(),
+            move |res| {
+                let _ = events.send(Wake::Watch(res));
+            },
+            move |s| {
+                let _ = status.send(Wake::Watching(s));
+            },
+        )
+    }
+
+    /// The watched inputs' paths, the files and directories given to watch
+    /// as well and the base of `--glob`
+    fn watched(&mut self) -> Vec<(PathBuf, RecursiveMode)> {
+        let mut paths: Vec<_> = (0..self.inputs.len())
+            .flat_map(|i| self.input_paths(i))
+            .collect();
+        let recursive = |path: &PathBuf| (path.clone(), RecursiveMode::Recursive);
+        paths.extend(self.config.also_watch.iter().map(recursive));
+        paths.extend(
+            self.config
+                .glob
+                .iter()
+                .map(|pattern| recursive(&pattern.base)),
+        );
+        paths
+    }
+
+    /// Watch input `index`'s paths as well
+    fn watch_input(&mut self, watcher: &Watcher, index: usize) -> Result<(), String> {
+        for (path, mode) in self.input_paths(index) {
+            watcher.watch(&path, mode)?;
+        }
+        Ok(())
+    }
+
+    /// What to watch for input `index`: its file and, through a symlink,
+    /// the file it leads to and the directory holding the link, where
+    /// repointing it shows up, and the files it draws from
+    fn input_paths(&mut self, index: usize) -> Vec<(PathBuf, RecursiveMode)> {
+        let input = &mut self.inputs[index];
+        // One since deleted would stop the watcher starting
+        let deps = input.deps.iter().filter(|d| d.exists()).cloned();
+        let Some(path) = input.source.watched() else {
+            return deps.map(|d| (d, RecursiveMode::NonRecursive)).collect();
+        };
+        let mut paths: Vec<PathBuf> = std::iter::once(path.to_path_buf()).chain(deps).collect();
+        input.link = watch::link_target(path);
+        if let Some(target) = &input.link {
+            let dir = path
+                .parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            paths.extend([target.clone(), dir.to_path_buf()]);
+        }
+        paths
+            .into_iter()
+            .map(|path| (path, RecursiveMode::NonRecursive))
+            .collect()
+    }
+
+    /// Take what has arrived on the queue, returning `true` if it changed
+    /// what is shown. File events are noted in `reload`; `heal` is set if the
+    /// watcher needs rebuilding.
+    fn drain(&mut self, first: Option<Wake>, reload: &mut [bool], heal: &mut bool) -> bool {
+        let mut changed = false;
+        // The newest file written that matches `--glob`, if not already shown
+        let mut switch = None;
+        let woken: Vec<Wake> = first.into_iter().chain(self.queue.1.try_iter()).collect();
+        for wake in woken {
+            if !matches!(
+                wake,
+                Wake::Loaded(_) | Wake::Rendered(_) | Wake::Watching(_)
+            ) {
+                self.last_activity = Instant::now();
+            }
+            match wake {
+                Wake::Action(action) => self.actions.push(action),
+                Wake::Loaded(loaded) => {
+                    let i = loaded.index;
+                    let deps = self.inputs.get(i).map(|input| input.deps.clone());
+                    if self.finish_reload(*loaded)
+                        && (i == self.active || self.mode() != Mode::Flip)
+                    {
+                        changed = true;
+                    }
+                    // Draws from other files now: watch those instead.
+                    *heal |= deps.is_some_and(|deps| deps != self.inputs[i].deps);
+                }
+                Wake::Rendered(rendered) => {
+                    let i = rendered.index;
+                    if let Some(input) = self.inputs.get_mut(i)
+                        && input.drawing.as_ref().map(|d| d.ticket) == Some(rendered.ticket)
+                    {
+                        input.drawing = None;
+                        input.frame = Some(rendered.frame);
+                        changed = true;
+                    }
+                }
+                Wake::Watch(Ok(events)) => {
+                    // The backend dropped events (e.g. inotify queue overflow)
+                    *heal |= events.iter().any(|e| e.need_rescan());
+                    for e in events
+                        .iter()
+                        .filter(|e| self.config.reload_on.fires(&e.kind))
+                    {
+                        if let Some(pattern) = &self.config.glob
+                            && let Some(written) = e.paths.iter().rfind(|p| {
+                                pattern.matches(p)
+                                    && p.is_file()
+                                    && !self.inputs.iter().any(|i| i.source.watched() == Some(p))
+                            })
+                        {
+                            switch = Some(written.clone());
+                        }
+                        let also = &self.config.also_watch;
+                        if e.paths
+                            .iter()
+                            .any(|p| also.iter().any(|a| p.starts_with(a)))
+                        {
+                            reload.fill(true);
+                        }
+                        for (i, input) in self.inputs.iter_mut().enumerate() {
+                            if e.paths
+                                .iter()
+                                .any(|p| input.deps.iter().any(|d| watch::is_path(p, d)))
+                            {
+                                // Its own bytes are the same, so it mustn't
+                                // be skipped as unchanged.
+                                input.hash = None;
+                                reload[i] = true;
+                            }
+                            let Some(path) = input.source.watched() else {
+                                continue;
+                            };
+                            let link = input.link.as_deref();
+                            if e.paths
+                                .iter()
+                                .any(|p| watch::is_path(p, path) || Some(p.as_path()) == link)
+                            {
+                                reload[i] = true;
+                                if self.config.quit_on_delete && !path.exists() {
+                                    self.quit = true;
+                                }
+                                // Repointed (or newly a link): watch the new
+                                // target instead.
+                                *heal |= watch::link_target(path).as_deref() != link;
+                            }
+                        }
+                    }
+                }
+                Wake::Watch(Err(errors)) => {
+                    for e in errors {
+                        eprintln!("watch: {e}");
+                    }
+                    *heal = true;
+                }
+                Wake::Watching(status) => {
+                    match &status {
+                        Status::Down(e) if status != self.watching => {
+                            eprintln!("watch: {e}; restarting");
+                        }
+                        Status::Down(_) => {}
+                        // Changes may have been missed while it was down,
+                        // to files inputs draw from as well as their own.
+                        Status::Watching => {
+                            reload.fill(true);
+                            for input in self.inputs.iter_mut().filter(|i| !i.deps.is_empty()) {
+                                input.hash = None;
+                            }
+                        }
+                    }
+                    changed |= status != self.watching;
+                    self.watching = status;
+                }
+            }
+        }
+        if let Some(path) = switch {
+            self.actions.push(Action::Open(path));
+        }
+        changed
+    }
+
+    /// Take what has arrived on the queue, starting the reloads it calls for
+    /// and any that are due, and restart the watcher if it has failed.
+    /// Returns `true` if it changed what is shown.
+    fn update_inputs(&mut self, woken: Option<Wake>, watcher: &Watcher) -> bool {
+        let mut reload = vec![false; self.inputs.len()];
+        let mut heal = false;
+        let mut changed = self.drain(woken, &mut reload, &mut heal);
+        changed |= self.drop_slow();
+        if heal {
+            // Start over with a fresh watcher rather than risk going deaf;
+            // everything reloads once it is running.
+            watcher.restart(self.watched());
+        }
+        for (i, reload) in reload.into_iter().enumerate() {
+            if reload {
+                self.schedule_reload(i);
+            }
+        }
+        let now = Instant::now();
+        for i in 0..self.inputs.len() {
+            let input = &mut self.inputs[i];
+            if input.loading.is_none() && input.due.is_some_and(|due| due <= now) {
+                input.due = None;
+                self.reload(i);
+            }
+        }
+        changed
+    }
+
+    pub fn run(mut self) -> Result<(), Failure> {
+        // Fail before opening a window rather than flashing one up.
+        self.start()?;
+        let mut window = minifb::Window::new(
+            &self.title(),
+            self.width,
+            self.height,
+            WindowOptions {
+                resize: true,
+                ..Default::default()
+            },
+        )
+        .map_err(|e| format!("{e:?}"))?;
+        // The loop paces itself below, waking early for queued work.
+        window.set_target_fps(0);
+        self.show(&mut window)
+    }
+
+    /// Run the viewer as [`Viewer::run`] does, but in `window` rather than
+    /// one of its own, until it closes
+    pub fn run_in(mut self, window: &mut dyn Window) -> Result<(), Failure> {
+        self.start()?;
+        self.show(window)
+    }
+
+    /// Start loading the inputs, waiting for them with `--fail-on-error`
+    /// and failing if any don't load
+    fn start(&mut self) -> Result<(), Failure> {
+        for i in 0..self.inputs.len() {
+            self.reload(i);
+        }
+        if self.config.fail_on_error {
+            while self.inputs.iter().any(|i| i.loading.is_some()) {
+                let Ok(wake) = self.queue.1.recv() else {
+                    break;
+                };
+                // Nothing is watched yet, so nothing asks for a reload; the
+                // flags are sized all the same so `drain` can index them.
+                let mut reload = vec![false; self.inputs.len()];
+                self.drain(Some(wake), &mut reload, &mut false);
+            }
+            if let Some(failure) = self.failure.take() {
+                return Err(failure);
+            }
+        }
+        Ok(())
+    }
+
+    /// Show the inputs in `window` and follow their changes until it closes
+    fn show(mut self, window: &mut dyn Window) -> Result<(), Failure> {
+        let mut title = self.title();
+        window.set_title(&title);
+        let frame_interval = Duration::from_secs_f64(1.0 / self.config.max_fps as f64);
+        #[cfg(all(unix, not(any(target_os = "macos", target_os = "redox"))))]
+        let mut visibility = window
+            .minifb()
+            .and_then(crate::visibility::Visibility::track);
+        #[cfg(all(unix, not(any(target_os = "macos", target_os = "redox"))))]
+        let mut monitors = crate::monitor::Monitors::track();
+        let mut was_visible = true;
+
+        let watcher = self.watch()?;
+
+        let mut dirty = true;
+        let mut buffers = Buffers::new(self.width, self.height);
+        #[cfg(feature = "gl")]
+        if self.config.gl {
+            match window
+                .minifb()
+                .map(|window| crate::gl::Gl::new(window, true))
+            {
+                Some(Ok(gl)) => buffers.gl = Some(gl),
+                Some(Err(e)) => eprintln!("gl: {e}; presenting without OpenGL"),
+                None => {}
+            }
+        }
+        let mut last_frame = Instant::now();
+        let mut was_active = false;
+        let mut last_tick = Instant::now();
+        let mut mouse = window.get_mouse_pos(MouseMode::Pass);
+        // What woke the loop from its wait, handled at the top of the next iteration
+        let mut woken: Option<Wake> = None;
+
+        while window.is_open() && !self.escaping(window) && !self.quit {
+            // 1) Drain the queue; reload each input at most once per iteration.
+            if self.update_inputs(woken.take(), &watcher) {
+                dirty = true;
+            }
+            if self.loading() && last_frame.elapsed() >= HUD_REFRESH {
+                // Keep the load progress ticking
+                dirty = true;
+            }
+            // 2) Resize
+            let (new_w, new_h) = window.get_size();
+            let resized = new_w != self.width || new_h != self.height;
+            if resized {
+                self.width = new_w.max(1);
+                self.height = new_h.max(1);
+                self.resizing = Some(Instant::now());
+                dirty = true;
+            } else if self.resizing.is_some_and(|t| t.elapsed() >= RESIZE_SETTLE) {
+                // Settled: render properly at the final size.
+                self.resizing = None;
+                dirty = true;
+            }
+
+            #[cfg(all(unix, not(any(target_os = "macos", target_os = "redox"))))]
+            if let (Some(monitors), Some(window)) = (&mut monitors, window.minifb())
+                && let Some((from, to)) = monitors.moved(window)
+            {
+                self.keep_physical_size(to.scale / from.scale);
+                dirty = true;
+            }
+
+            // 3) Input and queued actions
+            let pressed = window.get_keys_pressed(KeyRepeat::No);
+            for key in &pressed {
+                let name = format!("{key:?}");
+                for hooks in &mut self.hooks {
+                    hooks.on_key(&name);
+                }
+            }
+            // While searching or writing a note, keys type into it alone.
+            let typing = self.typing();
+            self.report_input(window, if typing { &[] } else { &pressed });
+            if self.search.is_some() {
+                if self.handle_search_keys(window) {
+                    dirty = true;
+                }
+            } else if typing {
+                if self.handle_note_keys(window) {
+                    dirty = true;
+                }
+            } else if self.handle_keys(&pressed) {
+                dirty = true;
+            }
+            if self.handle_mouse(window) {
+                dirty = true;
+            }
+            if !typing && self.handle_crop_keys(window) {
+                dirty = true;
+            }
+            if !typing && self.handle_switch_keys(window, &pressed) {
+                dirty = true;
+            }
+            if !typing && self.peek(window) {
+                dirty = true;
+            }
+            #[cfg(all(unix, not(any(target_os = "macos", target_os = "redox"))))]
+            if !typing {
+                self.handle_clipboard_keys(window, &pressed);
+            }
+            let now = Instant::now();
+            // Don't jump after a slow frame; let movement catch up gradually.
+            let dt = (now - last_tick).min(MAX_STEP);
+            last_tick = now;
+            let size = self.tree().map(|t| t.size());
+            let (w, h) = self.viewport();
+            if !typing
+                && self
+                    .state
+                    .handle_input(window, dt, size, (w, h), self.zoom_anchor())
+            {
+                self.keyboard_focus = true;
+                dirty = true;
+            }
+            if self.apply_actions(&watcher) {
+                self.last_activity = Instant::now();
+                dirty = true;
+            }
+            let new_mouse = window.get_mouse_pos(MouseMode::Pass);
+            if !pressed.is_empty() || !window.get_keys().is_empty() || new_mouse != mouse {
+                self.last_activity = Instant::now();
+            }
+            mouse = new_mouse;
+            if let Some(idle) = self.config.idle_exit
+                && self.last_activity.elapsed() >= idle
+            {
+                self.quit = true;
+            }
+
+            // 4) Fit scale only when needed
+            if dirty && let Some(t) = self.inputs[self.active].tree.as_ref() {
+                let (w, h) = self.fit_area();
+                if resized && self.config.refit_on_resize {
+                    self.state.refit(t, w, h);
+                } else {
+                    self.state.update_fit_scale(t, w, h);
+                }
+            }
+
+            // 5) Present if dirty
+            if self.title() != title {
+                title = self.title();
+                window.set_title(&title);
+            }
+            // Regaining focus can leave the window blank (e.g. under i3), but
+            // the last frame is still good, so show it again without rendering.
+            let active = window.is_active();
+            let refocused = active && !was_active;
+            was_active = active;
+            // Nothing is drawn while the window can't be seen; changes pile
+            // up in `dirty` and are rendered once it is shown again.
+            #[cfg(all(unix, not(any(target_os = "macos", target_os = "redox"))))]
+            let visible = visibility.as_mut().is_none_or(|v| v.visible());
+            #[cfg(not(all(unix, not(any(target_os = "macos", target_os = "redox")))))]
+            let visible = true;
+            let shown = visible && !was_visible;
+            was_visible = visible;
+            // With the GPU scaling the last frame to the window, nothing is
+            // drawn until the size settles.
+            let scaled = self.resizing.is_some() && buffers.scales();
+            let background = crate::color::to_rgb(self.canvas.background);
+            if dirty && visible && !scaled && last_frame.elapsed() >= frame_interval {
+                self.render(&mut buffers.back);
+                buffers.swap((self.width, self.height));
+                last_frame = Instant::now();
+                // A reload shown at the old view still needs fitting, and
+                // a coarse frame refining.
+                dirty = self.held.take().is_some() || self.refining();
+                self.report_view();
+                buffers.present(window, background)?;
+                // Stand-ins shown while resizing or refining would only make
+                // e-paper flash, or a consumer do the work twice.
+                if !dirty && self.resizing.is_none() {
+                    for sink in &mut self.sinks {
+                        if let Err(e) = sink.push(&buffers.front, buffers.size.0) {
+                            eprintln!("{}: {e}", sink.name());
+                        }
+                    }
+                }
+            } else if refocused || shown || (scaled && resized) {
+                buffers.present(window, background)?;
+            } else {
+                // Pump window events once (non-blocking)
+                window.update();
+            }
+            // Idle, so get the inputs flipped to next ready.
+            let preloaded = !dirty && visible && self.preload();
+
+            // 6) Sleep until the next thing to do, or until woken by the queue.
+            // minifb can only be polled, so the window is still checked for
+            // input regularly, but less often when it isn't focused.
+            let poll = if active && visible {
+                self.config.poll_interval.unwrap_or(frame_interval)
+            } else {
+                self.config.idle_poll
+            };
+            let mut deadline = Instant::now() + poll;
+            let mut wake_at = |t: Instant| deadline = deadline.min(t);
+            if preloaded {
+                // Straight on to the next one, if any
+                wake_at(Instant::now());
+            }
+            if dirty && visible {
+                wake_at(last_frame + frame_interval);
+            }
+            if self.loading() {
+                wake_at(last_frame + HUD_REFRESH);
+            }
+            if let Some(resized) = self.resizing {
+                wake_at(resized + RESIZE_SETTLE);
+            }
+            for due in self.inputs.iter().filter_map(|i| i.due) {
+                wake_at(due);
+            }
+            if let Some(limits) = self.config.limits {
+                for drawing in self.inputs.iter().filter_map(|i| i.drawing.as_ref()) {
+                    wake_at(drawing.started + limits.render_timeout);
+                }
+            }
+            if let Some(idle) = self.config.idle_exit {
+                wake_at(self.last_activity + idle);
+            }
+            match self
+                .queue
+                .1
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            {
+                Ok(wake) => woken = Some(wake),
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    unreachable!("the viewer holds a sender")
+                }
+            }
+        }
+
+        self.failure.map_or(Ok(()), Err)
+    }
+
+    /// Watch and reload the inputs as [`Viewer::run`] does, but without a
+    /// window, only exporting the shown one each time it loads
+    pub fn run_headless(mut self) -> Result<(), Failure> {
+        let events = self.subscribe();
+        for i in 0..self.inputs.len() {
+            self.reload(i);
+        }
+        let watcher = self.watch()?;
+        let mut woken: Option<Wake> = None;
+        // The last load error printed
+        let mut reported: Option<String> = None;
+        loop {
+            self.update_inputs(woken.take(), &watcher);
+            if self.apply_actions(&watcher) {
+                self.last_activity = Instant::now();
+            }
+            for event in events.try_iter() {
+                match event {
+                    Event::Loaded(_) | Event::Reloaded(_) => reported = None,
+                    // Said once rather than again for each retry
+                    Event::LoadFailed(path, e) if reported.as_ref() != Some(&e) => {
+                        eprintln!("{}: {e}", path.display());
+                        reported = Some(e);
+                    }
+                    _ => {}
+                }
+            }
+            let idle = self
+                .config
+                .idle_exit
+                .is_some_and(|idle| self.last_activity.elapsed() >= idle);
+            if self.quit || idle {
+                break;
+            }
+
+            // Sleep until a retry is due, or until woken by the queue.
+            let deadline = self
+                .inputs
+                .iter()
+                .filter_map(|i| i.due)
+                .chain(self.config.idle_exit.map(|idle| self.last_activity + idle))
+                .min();
+            woken = match deadline {
+                Some(deadline) => self
+                    .queue
+                    .1
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    .ok(),
+                None => self.queue.1.recv().ok(),
+            };
+        }
+
+        self.failure.map_or(Ok(()), Err)
+    }
+
+    /// Write the shown document to each export, saying why if it can't be
+    fn export(&self) {
+        let (Some(tree), path) = (self.tree(), self.path()) else {
+            return;
+        };
+        let (area, scale) = (self.config.export_area, self.config.export_scale);
+        for export in &self.config.exports {
+            let out = export.path(path, self.loads);
+            let pixmap = match self.config.limits {
+                Some(limits) => {
+                    let (export, tree, canvas) =
+                        (export.clone(), tree.clone(), self.canvas.clone());
+                    limits.draw(move || export.render(&tree, &canvas, area, scale))
+                }
+                None => Ok(export.render(tree, &self.canvas, area, scale)),
+            };
+            let result = pixmap
+                .and_then(|pixmap| pixmap.ok_or_else(|| "the exported area is empty".to_string()))
+                .and_then(|pixmap| {
+                    export::save(
+                        pixmap,
+                        &out,
+                        self.config.dither.as_ref(),
+                        self.config.profile.as_deref(),
+                    )
+                });
+            if let Err(e) = result {
+                eprintln!("{}: {e}", out.display());
+            }
+        }
+    }
+}
+
+/// Render `tree` into a frame, in a grid of copies if `tile`
+fn draw_frame(
+    tree: &usvg::Tree,
+    width: u32,
+    height: u32,
+    transform: tiny_skia::Transform,
+    canvas: &Canvas,
+    tile: bool,
+) -> Frame {
+    match tile {
+        true => Frame::tiled(tree, width, height, transform, canvas),
+        false => Frame::new(tree, width, height, transform, canvas),
+    }
+}
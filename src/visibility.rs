@@ -0,0 +1,73 @@
+//! Noticing when the window is minimized or covered, so it can stop rendering.
+//! minifb doesn't report this, so on X11 a second connection listens for the
+//! window's map and visibility notifications.
+
+use std::{mem, ptr};
+
+use x11_dl::xlib;
+
+pub struct Visibility {
+    xlib: xlib::Xlib,
+    display: *mut xlib::Display,
+    mapped: bool,
+    obscured: bool,
+}
+
+impl Visibility {
+    /// Start tracking `window`, or `None` if it isn't an X11 window
+    pub fn track(window: &minifb::Window) -> Option<Self> {
+        // minifb prefers Wayland when it is available, and a Wayland
+        // handle must not be mistaken for an X window id.
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            return None;
+        }
+        let xlib = xlib::Xlib::open().ok()?;
+        let handle = window.get_window_handle() as xlib::Window;
+        unsafe {
+            let display = (xlib.XOpenDisplay)(ptr::null());
+            if display.is_null() {
+                return None;
+            }
+            // Subscribe before asking, so a change in between isn't missed.
+            (xlib.XSelectInput)(
+                display,
+                handle,
+                xlib::VisibilityChangeMask | xlib::StructureNotifyMask,
+            );
+            let mut attrs: xlib::XWindowAttributes = mem::zeroed();
+            (xlib.XGetWindowAttributes)(display, handle, &mut attrs);
+            Some(Self {
+                // ICCCM has window managers unmap iconified windows themselves
+                mapped: attrs.map_state != xlib::IsUnmapped,
+                obscured: false,
+                xlib,
+                display,
+            })
+        }
+    }
+
+    /// Whether any of the window can currently be seen
+    pub fn visible(&mut self) -> bool {
+        unsafe {
+            while (self.xlib.XPending)(self.display) > 0 {
+                let mut event: xlib::XEvent = mem::zeroed();
+                (self.xlib.XNextEvent)(self.display, &mut event);
+                match event.get_type() {
+                    xlib::MapNotify => self.mapped = true,
+                    xlib::UnmapNotify => self.mapped = false,
+                    xlib::VisibilityNotify => {
+                        self.obscured = event.visibility.state == xlib::VisibilityFullyObscured;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        self.mapped && !self.obscured
+    }
+}
+
+impl Drop for Visibility {
+    fn drop(&mut self) {
+        unsafe { (self.xlib.XCloseDisplay)(self.display) };
+    }
+}
@@ -0,0 +1,207 @@
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime};
+
+use globset::{GlobBuilder, GlobMatcher};
+
+use notify_debouncer_full::{
+    DebounceEventResult, new_debouncer,
+    notify::{
+        RecursiveMode,
+        event::{AccessKind, AccessMode, EventKind, ModifyKind},
+    },
+};
+
+/// Which file events reload, from `--reload-on`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Trigger {
+    /// Anything but a file being opened
+    #[default]
+    Any,
+    /// A file written being closed, so a save made as a storm of writes
+    /// reloads once. Only inotify (Linux) reports it.
+    CloseWrite,
+    /// A file being created, as by tools that write a new one rather than
+    /// rewrite the old
+    Create,
+}
+
+impl Trigger {
+    /// Whether an event of `kind` reloads. Files being renamed into place
+    /// or removed always count, so saves by renaming and deletions are
+    /// followed whatever the trigger.
+    pub fn fires(self, kind: &EventKind) -> bool {
+        match (self, kind) {
+            (_, EventKind::Modify(ModifyKind::Name(_)) | EventKind::Remove(_)) => true,
+            (Trigger::Any, kind) => {
+                !matches!(kind, EventKind::Access(AccessKind::Open(AccessMode::Any)))
+            }
+            (Trigger::CloseWrite, kind) => matches!(
+                kind,
+                EventKind::Access(AccessKind::Close(AccessMode::Write))
+            ),
+            (Trigger::Create, kind) => matches!(kind, EventKind::Create(_)),
+        }
+    }
+}
+
+/// Whether `reported`, a path in a file event, is `watched`. FSEvents
+/// reports paths resolved through macOS's firmlinks (`/private/tmp` for
+/// `/tmp`), and on Windows and macOS file names are told apart without case,
+/// so a file an app saves as `Plot.svg` is still the `plot.svg` watched.
+pub fn is_path(reported: &Path, watched: &Path) -> bool {
+    if reported == watched {
+        return true;
+    }
+    if !cfg!(any(windows, target_os = "macos")) {
+        return false;
+    }
+    // The file itself may be gone, but its directory is still there.
+    let resolve = |path: &Path| {
+        let dir = path.parent()?.canonicalize().ok()?;
+        Some((dir, path.file_name()?.to_str()?.to_lowercase()))
+    };
+    resolve(reported).is_some_and(|r| resolve(watched) == Some(r))
+}
+
+/// The file `path` leads to through one or more symlinks, or `None` if it
+/// isn't a symlink (or leads nowhere)
+pub fn link_target(path: &Path) -> Option<PathBuf> {
+    let is_link = path.symlink_metadata().ok()?.file_type().is_symlink();
+    is_link.then(|| std::fs::canonicalize(path).ok()).flatten()
+}
+
+/// Wait for the watched path to be created before trying to render
+pub fn wait_for_creation(path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+    if path.exists() {
+        return Ok(());
+    }
+    eprintln!(
+        "file '{}' does not exist, waiting for it to be created...",
+        path.display()
+    );
+
+    let parent = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."))
+        .to_path_buf();
+
+    let (tx, rx) = mpsc::channel::<DebounceEventResult>();
+    let mut debouncer = new_debouncer(Duration::from_millis(200), None, move |res| {
+        let _ = tx.send(res);
+    })?;
+
+    debouncer.watch(&parent, RecursiveMode::NonRecursive)?;
+
+    loop {
+        match rx.recv() {
+            Ok(Ok(events)) => {
+                if events.iter().any(|e| e.paths.iter().any(|p| p == path)) && path.exists() {
+                    return Ok(());
+                }
+            }
+            Ok(Err(_)) => {
+                if path.exists() {
+                    return Ok(());
+                }
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// A glob of files to follow, switching to whichever last changed
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    /// The deepest directory without wildcards, watched recursively
+    pub base: PathBuf,
+    matcher: GlobMatcher,
+}
+
+impl Pattern {
+    /// `pattern` is taken relative to the current directory unless absolute.
+    /// `*` stays within a directory while `**` spans any number of them.
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let path = std::path::absolute(pattern).map_err(|e| format!("{pattern}: {e}"))?;
+        let is_literal = |c: &Component| {
+            !c.as_os_str()
+                .to_string_lossy()
+                .contains(['*', '?', '[', '{'])
+        };
+        let base: PathBuf = path.components().take_while(is_literal).collect();
+        let matcher = GlobBuilder::new(&path.to_string_lossy())
+            .literal_separator(true)
+            .build()
+            .map_err(|e| e.to_string())?
+            .compile_matcher();
+        Ok(Self { base, matcher })
+    }
+
+    /// SVG files anywhere under `dir`, for a directory given as PATH
+    pub fn within(dir: &Path) -> Result<Self, String> {
+        let base = std::path::absolute(dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+        let pattern = globset::escape(&base.to_string_lossy()) + "/**/*.{svg,svgz}";
+        let matcher = GlobBuilder::new(&pattern)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| e.to_string())?
+            .compile_matcher();
+        Ok(Self { base, matcher })
+    }
+
+    /// Whether `path` is a pattern rather than a file, as when one given
+    /// as PATH was quoted so the shell left it alone
+    pub fn is_pattern(path: &Path) -> bool {
+        path.to_string_lossy().contains(['*', '?', '[', '{']) && !path.exists()
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        self.matcher.is_match(path)
+    }
+
+    /// The most recently modified file matching the pattern
+    pub fn newest(&self) -> Option<PathBuf> {
+        let mut newest: Option<(SystemTime, PathBuf)> = None;
+        let mut dirs = vec![self.base.clone()];
+        while let Some(dir) = dirs.pop() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let Ok(meta) = std::fs::metadata(&path) else {
+                    continue;
+                };
+                if meta.is_dir() {
+                    dirs.push(path);
+                } else if self.matches(&path)
+                    && let Ok(modified) = meta.modified()
+                    && newest.as_ref().is_none_or(|(t, _)| modified > *t)
+                {
+                    newest = Some((modified, path));
+                }
+            }
+        }
+        newest.map(|(_, path)| path)
+    }
+
+    /// Wait for a file matching the pattern to exist, returning the newest
+    pub fn wait(&self) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        if let Some(path) = self.newest() {
+            return Ok(path);
+        }
+        eprintln!("no files match yet, waiting for one to be written...");
+        let (tx, rx) = mpsc::channel::<DebounceEventResult>();
+        let mut debouncer = new_debouncer(Duration::from_millis(200), None, move |res| {
+            let _ = tx.send(res);
+        })?;
+        debouncer.watch(&self.base, RecursiveMode::Recursive)?;
+        loop {
+            rx.recv()?.map_err(|mut errors| errors.remove(0))?;
+            if let Some(path) = self.newest() {
+                return Ok(path);
+            }
+        }
+    }
+}
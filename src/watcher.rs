@@ -0,0 +1,335 @@
+//! The file watcher, kept on a thread of its own that starts it afresh when
+//! notify's backend fails or panics. On its own a dead backend goes quiet,
+//! so nothing reloads again, and the next path watched or the watcher being
+//! dropped panics in whoever holds it.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::Duration;
+
+use notify_debouncer_full::{
+    DebounceEventHandler, DebounceEventResult, Debouncer, RecommendedCache, new_debouncer_opt,
+    notify::{self, EventHandler, RecommendedWatcher, RecursiveMode, WatcherKind},
+};
+
+/// How often the backend is checked on
+const CHECK: Duration = Duration::from_millis(250);
+
+/// How long to wait before trying again to start a watcher that failed to
+const RETRY: Duration = Duration::from_secs(1);
+
+/// Whether files are being watched
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+    Watching,
+    /// Stopped for this reason, and being restarted
+    Down(String),
+}
+
+impl Status {
+    pub fn indicator(&self) -> &'static str {
+        match self {
+            Status::Watching => "watching ✓",
+            Status::Down(_) => "watcher down ✗",
+        }
+    }
+}
+
+enum Command {
+    Watch(PathBuf, RecursiveMode, mpsc::Sender<Result<(), String>>),
+    Unwatch(PathBuf),
+    Restart(Vec<(PathBuf, RecursiveMode)>),
+}
+
+/// Watches paths from its supervising thread, which stops once this is
+/// dropped
+pub struct Watcher {
+    commands: mpsc::Sender<Command>,
+}
+
+type OnEvent = Arc<dyn Fn(DebounceEventResult) + Send + Sync>;
+
+impl Watcher {
+    /// Watch `paths`, calling `on_event` with each batch of debounced events
+    /// and `on_status` when the watcher goes down and each time it is back.
+    /// Fails if it can't be started at first.
+    pub fn spawn(
+        paths: Vec<(PathBuf, RecursiveMode)>,
+        on_event: impl Fn(DebounceEventResult) + Send + Sync + 'static,
+        on_status: impl FnMut(Status) + Send + 'static,
+    ) -> Result<Self, String> {
+        let (commands, received) = mpsc::channel();
+        let (started, first) = mpsc::channel();
+        thread::Builder::new()
+            .name("svgtail watcher".to_string())
+            .spawn(move || supervise(paths, received, Arc::new(on_event), on_status, started))
+            .map_err(|e| e.to_string())?;
+        first
+            .recv()
+            .unwrap_or_else(|_| Err("the watcher thread exited".to_string()))?;
+        Ok(Self { commands })
+    }
+
+    /// Watch `path` as well, kept through restarts
+    pub fn watch(&self, path: &Path, mode: RecursiveMode) -> Result<(), String> {
+        let (reply, result) = mpsc::channel();
+        self.send(Command::Watch(path.to_path_buf(), mode, reply))?;
+        result
+            .recv()
+            .unwrap_or_else(|_| Err("the watcher thread exited".to_string()))
+    }
+
+    pub fn unwatch(&self, path: &Path) {
+        let _ = self.send(Command::Unwatch(path.to_path_buf()));
+    }
+
+    /// Start over with a fresh watcher on `paths` alone, reporting
+    /// [`Status::Watching`] once it is running
+    pub fn restart(&self, paths: Vec<(PathBuf, RecursiveMode)>) {
+        let _ = self.send(Command::Restart(paths));
+    }
+
+    fn send(&self, command: Command) -> Result<(), String> {
+        self.commands
+            .send(command)
+            .map_err(|_| "the watcher thread exited".to_string())
+    }
+}
+
+/// Run watchers on `paths` until `commands` closes, starting a new one each
+/// time the last fails
+fn supervise(
+    mut paths: Vec<(PathBuf, RecursiveMode)>,
+    commands: mpsc::Receiver<Command>,
+    on_event: OnEvent,
+    mut on_status: impl FnMut(Status),
+    started: mpsc::Sender<Result<(), String>>,
+) {
+    let mut started = Some(started);
+    loop {
+        let (mut debouncer, alive) = match guard(|| start(&paths, &on_event)).and_then(|r| r) {
+            Ok(running) => {
+                match started.take() {
+                    Some(started) => {
+                        let _ = started.send(Ok(()));
+                    }
+                    None => on_status(Status::Watching),
+                }
+                running
+            }
+            Err(e) => {
+                if let Some(started) = started.take() {
+                    let _ = started.send(Err(e));
+                    return;
+                }
+                on_status(Status::Down(e));
+                // Keep track of what to watch until it is time to try again.
+                loop {
+                    match commands.recv_timeout(RETRY) {
+                        Ok(Command::Watch(path, mode, reply)) => {
+                            paths.push((path, mode));
+                            let _ = reply.send(Ok(()));
+                        }
+                        Ok(Command::Unwatch(path)) => paths.retain(|(p, _)| *p != path),
+                        Ok(Command::Restart(new)) => {
+                            paths = new;
+                            break;
+                        }
+                        Err(mpsc::RecvTimeoutError::Timeout) => break,
+                        Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+                continue;
+            }
+        };
+
+        let down = loop {
+            // Watching a path that isn't there is an error, but not a failure.
+            let stopped = || (!alive.all()).then(|| "the watcher stopped".to_string());
+            match commands.recv_timeout(CHECK) {
+                Ok(Command::Watch(path, mode, reply)) => {
+                    match guard(|| debouncer.watch(&path, mode)) {
+                        Ok(Ok(())) => {
+                            paths.push((path, mode));
+                            let _ = reply.send(Ok(()));
+                        }
+                        Ok(Err(e)) => {
+                            let _ = reply.send(Err(e.to_string()));
+                            if let Some(down) = stopped() {
+                                break Some(down);
+                            }
+                        }
+                        Err(panic) => {
+                            paths.push((path, mode));
+                            let _ = reply.send(Ok(()));
+                            break Some(panic);
+                        }
+                    }
+                }
+                Ok(Command::Unwatch(path)) => {
+                    paths.retain(|(p, _)| *p != path);
+                    if let Err(panic) = guard(|| debouncer.unwatch(&path)) {
+                        break Some(panic);
+                    }
+                }
+                Ok(Command::Restart(new)) => {
+                    paths = new;
+                    break None;
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    if let Some(down) = stopped() {
+                        break Some(down);
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    let _ = guard(|| drop(debouncer));
+                    return;
+                }
+            }
+        };
+        // A dead backend panics on being told to shut down.
+        let _ = guard(|| drop(debouncer));
+        if let Some(down) = down {
+            on_status(Status::Down(down));
+        }
+    }
+}
+
+/// A debouncer on `paths`, sending events to `on_event`, and what notes
+/// whether its threads are still running
+fn start(
+    paths: &[(PathBuf, RecursiveMode)],
+    on_event: &OnEvent,
+) -> Result<(Debouncer<Guarded, RecommendedCache>, Alive), String> {
+    let alive = Alive::default();
+    BACKEND.with(|b| *b.borrow_mut() = Some(Arc::clone(&alive.backend)));
+    let relay = Relay {
+        on_event: Arc::clone(on_event),
+        _canary: Canary::new(Arc::clone(&alive.debouncer)),
+    };
+    let mut debouncer = new_debouncer_opt::<_, Guarded, _>(
+        Duration::from_millis(200),
+        None,
+        relay,
+        RecommendedCache::new(),
+        notify::Config::default(),
+    )
+    .map_err(|e| e.to_string())?;
+    for (path, mode) in paths {
+        debouncer
+            .watch(path, *mode)
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+    }
+    Ok((debouncer, alive))
+}
+
+/// What `f` returns, or the message it panicked with
+fn guard<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(message)
+}
+
+fn message(panic: Box<dyn Any + Send>) -> String {
+    let what = match (panic.downcast_ref::<&str>(), panic.downcast_ref::<String>()) {
+        (Some(s), _) => s.to_string(),
+        (_, Some(s)) => s.clone(),
+        _ => "unknown".to_string(),
+    };
+    format!("the watcher panicked: {what}")
+}
+
+/// Whether the debouncer's thread and notify's are running. Each holds an
+/// event handler carrying a [`Canary`], dropped only as the thread ends: on
+/// panicking, or on being stopped.
+#[derive(Default)]
+struct Alive {
+    debouncer: Arc<AtomicBool>,
+    backend: Arc<AtomicBool>,
+}
+
+impl Alive {
+    fn all(&self) -> bool {
+        [&self.debouncer, &self.backend]
+            .into_iter()
+            .all(|alive| alive.load(Ordering::Relaxed))
+    }
+}
+
+/// Clears its flag once dropped along with the thread holding it
+struct Canary(Arc<AtomicBool>);
+
+impl Canary {
+    fn new(flag: Arc<AtomicBool>) -> Self {
+        flag.store(true, Ordering::Relaxed);
+        Self(flag)
+    }
+}
+
+impl Drop for Canary {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Passes debounced events on
+struct Relay {
+    on_event: OnEvent,
+    _canary: Canary,
+}
+
+impl DebounceEventHandler for Relay {
+    fn handle_event(&mut self, event: DebounceEventResult) {
+        (self.on_event)(event);
+    }
+}
+
+thread_local! {
+    /// The flag for the backend [`start`] is about to create, which the
+    /// debouncer builds out of reach
+    static BACKEND: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };
+}
+
+/// notify's backend, with its event handler carrying a [`Canary`]
+struct Guarded(RecommendedWatcher);
+
+struct GuardedHandler<F> {
+    handler: F,
+    _canary: Canary,
+}
+
+impl<F: EventHandler> EventHandler for GuardedHandler<F> {
+    fn handle_event(&mut self, event: notify::Result<notify::Event>) {
+        self.handler.handle_event(event);
+    }
+}
+
+impl notify::Watcher for Guarded {
+    fn new<F: EventHandler>(handler: F, config: notify::Config) -> notify::Result<Self> {
+        let flag = BACKEND.with(|b| b.borrow_mut().take()).unwrap_or_default();
+        let handler = GuardedHandler {
+            handler,
+            _canary: Canary::new(flag),
+        };
+        RecommendedWatcher::new(handler, config).map(Self)
+    }
+
+    fn watch(&mut self, path: &Path, mode: RecursiveMode) -> notify::Result<()> {
+        self.0.watch(path, mode)
+    }
+
+    fn unwatch(&mut self, path: &Path) -> notify::Result<()> {
+        self.0.unwatch(path)
+    }
+
+    fn configure(&mut self, option: notify::Config) -> notify::Result<bool> {
+        self.0.configure(option)
+    }
+
+    fn kind() -> WatcherKind {
+        RecommendedWatcher::kind()
+    }
+}
@@ -0,0 +1,121 @@
+//! What the viewer needs of a window: its size, keyboard and mouse input
+//! and somewhere to present frames. minifb's window provides it on screen;
+//! anything else implementing [`Window`], such as an in-memory framebuffer
+//! fed scripted input, can be driven by [`Viewer::run_in`].
+//!
+//! [`Viewer::run_in`]: crate::viewer::Viewer::run_in
+
+pub use minifb::{Key, KeyRepeat, MouseButton, MouseMode};
+
+/// A window the viewer draws into and takes input from, polled once per
+/// iteration of its loop. Input is read between calls to
+/// [`Window::update`] or [`Window::update_with_buffer`], which end a frame.
+pub trait Window {
+    /// Whether the window is still open; the viewer exits once it isn't
+    fn is_open(&self) -> bool;
+
+    /// Whether the window has keyboard focus
+    fn is_active(&mut self) -> bool;
+
+    /// Width and height in pixels
+    fn get_size(&self) -> (usize, usize);
+
+    fn set_title(&mut self, title: &str);
+
+    fn is_key_down(&self, key: Key) -> bool;
+
+    /// The keys held down
+    fn get_keys(&self) -> Vec<Key>;
+
+    /// The keys pressed since the last frame, and with [`KeyRepeat::Yes`]
+    /// those repeating while held
+    fn get_keys_pressed(&self, repeat: KeyRepeat) -> Vec<Key>;
+
+    /// Where the mouse is in window pixels, as minifb reports it: with
+    /// [`MouseMode::Discard`], `None` outside the window
+    fn get_mouse_pos(&self, mode: MouseMode) -> Option<(f32, f32)>;
+
+    fn get_mouse_down(&self, button: MouseButton) -> bool;
+
+    /// How far the wheel scrolled since the last frame, if it did
+    fn get_scroll_wheel(&self) -> Option<(f32, f32)>;
+
+    /// End the frame without drawing, handling pending events
+    fn update(&mut self);
+
+    /// End the frame by showing `buffer`, `width` × `height` pixels of
+    /// `0RGB`, stretched to the window if it is another size
+    fn update_with_buffer(
+        &mut self,
+        buffer: &[u32],
+        width: usize,
+        height: usize,
+    ) -> Result<(), String>;
+
+    /// The minifb window behind this one, if any, for what works with the
+    /// platform's window directly: OpenGL and following its visibility and
+    /// monitor
+    fn minifb(&self) -> Option<&minifb::Window> {
+        None
+    }
+}
+
+impl Window for minifb::Window {
+    fn is_open(&self) -> bool {
+        minifb::Window::is_open(self)
+    }
+
+    fn is_active(&mut self) -> bool {
+        minifb::Window::is_active(self)
+    }
+
+    fn get_size(&self) -> (usize, usize) {
+        minifb::Window::get_size(self)
+    }
+
+    fn set_title(&mut self, title: &str) {
+        minifb::Window::set_title(self, title);
+    }
+
+    fn is_key_down(&self, key: Key) -> bool {
+        minifb::Window::is_key_down(self, key)
+    }
+
+    fn get_keys(&self) -> Vec<Key> {
+        minifb::Window::get_keys(self)
+    }
+
+    fn get_keys_pressed(&self, repeat: KeyRepeat) -> Vec<Key> {
+        minifb::Window::get_keys_pressed(self, repeat)
+    }
+
+    fn get_mouse_pos(&self, mode: MouseMode) -> Option<(f32, f32)> {
+        minifb::Window::get_mouse_pos(self, mode)
+    }
+
+    fn get_mouse_down(&self, button: MouseButton) -> bool {
+        minifb::Window::get_mouse_down(self, button)
+    }
+
+    fn get_scroll_wheel(&self) -> Option<(f32, f32)> {
+        minifb::Window::get_scroll_wheel(self)
+    }
+
+    fn update(&mut self) {
+        minifb::Window::update(self);
+    }
+
+    fn update_with_buffer(
+        &mut self,
+        buffer: &[u32],
+        width: usize,
+        height: usize,
+    ) -> Result<(), String> {
+        minifb::Window::update_with_buffer(self, buffer, width, height)
+            .map_err(|e| format!("{e:?}"))
+    }
+
+    fn minifb(&self) -> Option<&minifb::Window> {
+        Some(self)
+    }
+}
@@ -0,0 +1,20 @@
+//! Control commands as read from stdin and the control socket
+
+use svgtail::action::Action;
+
+#[test]
+fn zooms_and_pans_only_by_usable_numbers() {
+    assert!(matches!("zoom 2".parse(), Ok(Action::SetZoom(2.0))));
+    assert!(matches!(
+        "pan 10 -20".parse(),
+        Ok(Action::SetPan(10.0, -20.0))
+    ));
+    for bad in ["zoom 0", "zoom -1", "zoom NaN", "zoom inf", "zoom 1e39"] {
+        let parsed: Result<Action, _> = bad.parse();
+        assert!(parsed.is_err(), "{bad}");
+    }
+    for bad in ["pan NaN 0", "pan 0 inf", "pan -inf 1"] {
+        let parsed: Result<Action, _> = bad.parse();
+        assert!(parsed.is_err(), "{bad}");
+    }
+}
@@ -0,0 +1,60 @@
+//! Rasterizing in parallel bands, and converting frames for the window
+
+use resvg::tiny_skia::{Color, ColorU8, IntRect, Pixmap, Transform};
+use resvg::usvg;
+use svgtail::render::{self, Canvas, Edge};
+
+#[test]
+fn blurs_and_shadows_cross_band_boundaries_as_in_one_pass() {
+    let doc = r##"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="600">
+        <filter id="shadow" x="-1" y="-1" width="3" height="3">
+            <feGaussianBlur stdDeviation="30"/>
+            <feOffset dy="150"/>
+        </filter>
+        <rect x="50" y="200" width="100" height="100" fill="blue" filter="url(#shadow)"/>
+    </svg>"##;
+    let tree = usvg::Tree::from_str(doc, &usvg::Options::default()).unwrap();
+    let canvas = Canvas {
+        background: Color::from_rgba8(0x33, 0x33, 0x33, 0xFF),
+        page: None,
+        edge: Edge::None,
+        checkerboard: None,
+        backdrop: None,
+        world_grid: false,
+    };
+    let area = IntRect::from_xywh(0, 0, 200, 600).unwrap();
+    // Enough threads for bands at their thinnest, whatever the machine
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(16)
+        .build()
+        .unwrap();
+    let banded = pool.install(|| render::render(&tree, area, Transform::identity(), &canvas));
+
+    let mut single = Pixmap::new(200, 600).unwrap();
+    single.fill(canvas.background);
+    resvg::render(&tree, Transform::identity(), &mut single.as_mut());
+    assert!(banded.data() == single.data());
+}
+
+#[test]
+fn converts_opaque_and_translucent_pixels_alike() {
+    // Odd-sized, so blocks of lanes leave some over, and half translucent
+    let mut pixmap = Pixmap::new(37, 5).unwrap();
+    pixmap.fill(Color::from_rgba8(0x12, 0x34, 0x56, 0xFF));
+    for px in pixmap.pixels_mut().iter_mut().skip(60).step_by(3) {
+        *px = ColorU8::from_rgba(0x80, 0x40, 0x20, 0x80).premultiply();
+    }
+    let background = Color::from_rgba8(0x33, 0x33, 0x33, 0xFF);
+    let mut buffer = Vec::new();
+    render::to_buffer(&pixmap, background, &mut buffer);
+    let expected: Vec<u32> = pixmap
+        .pixels()
+        .iter()
+        .map(|px| {
+            let a = u32::from(px.alpha());
+            let c = |v: u8| u32::from(v) * 255 / a;
+            c(px.red()) << 16 | c(px.green()) << 8 | c(px.blue())
+        })
+        .collect();
+    assert_eq!(buffer, expected);
+}
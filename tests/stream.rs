@@ -0,0 +1,45 @@
+//! Documents piped in one after another, split as they arrive
+
+use std::io::Write;
+use std::sync::{Arc, mpsc};
+use std::time::Duration;
+
+use svgtail::stream::Stream;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn splits_documents_at_the_root_svg_closing_and_at_nuls() {
+    let (reader, mut writer) = std::io::pipe().unwrap();
+    let (arrivals, arrived) = mpsc::channel();
+    let stream = Arc::new(Stream::default());
+    stream.read_from(reader, move || arrivals.send(()).is_ok());
+    let mut next = |written: &[&str]| {
+        for part in written {
+            writer.write_all(part.as_bytes()).unwrap();
+        }
+        arrived.recv_timeout(TIMEOUT).unwrap();
+        String::from_utf8(stream.latest().unwrap().to_vec()).unwrap()
+    };
+
+    // Nested, with the closing tag cut in two
+    let nested = "<svg><!-- </svg> --><svg/><svg></svg></svg>";
+    assert_eq!(
+        next(&["<svg><!-- </svg> --><svg/><svg></svg></s", "vg>"]),
+        nested
+    );
+    assert_eq!(
+        next(&["\n<?xml version=\"1.0\"?><svg>a\0"]),
+        "\n<?xml version=\"1.0\"?><svg>a"
+    );
+
+    // What is left counts once the writer is done
+    let (reader, mut writer) = std::io::pipe().unwrap();
+    let (arrivals, arrived) = mpsc::channel();
+    let stream = Arc::new(Stream::default());
+    stream.read_from(reader, move || arrivals.send(()).is_ok());
+    writer.write_all(b"<svg>unfinished").unwrap();
+    drop(writer);
+    arrived.recv_timeout(TIMEOUT).unwrap();
+    assert_eq!(&*stream.latest().unwrap(), b"<svg>unfinished");
+}
@@ -0,0 +1,561 @@
+//! Running the viewer in a window that exists only in memory, so tests can
+//! script its input frame by frame, change the files it watches and check
+//! what it shows: the view it reports and the frames it presents, against
+//! snapshots in `tests/snapshots`. `SVGTAIL_BLESS=1` rewrites the snapshots
+//! from what is shown.
+
+#![allow(dead_code)]
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, mpsc};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use resvg::tiny_skia::{Color, Pixmap};
+use resvg::usvg;
+
+use svgtail::action::{Action, Event, Snapshot};
+use svgtail::exit::Failure;
+use svgtail::input::Source;
+use svgtail::render::{Canvas, Checkerboard, Edge};
+use svgtail::state::{Anchor, Fit};
+use svgtail::template::Defines;
+use svgtail::units::{Unit, Units};
+use svgtail::viewer::{self, ActionSender, Config, Viewer};
+use svgtail::watch::Trigger;
+use svgtail::window::{Key, KeyRepeat, MouseButton, MouseMode, Window};
+
+/// Longest anything is waited for before the test fails
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long nothing must be presented for the view to count as settled:
+/// longer than the viewer waits for a resize to settle or a slow load to
+/// show progress
+const QUIET: Duration = Duration::from_millis(400);
+
+/// Window size tests start with
+pub const WIDTH: usize = 200;
+pub const HEIGHT: usize = 150;
+
+/// A frame the viewer presented, in `0RGB` pixels
+#[derive(Clone)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl Frame {
+    /// The colour at `(x, y)` as `0xRRGGBB`
+    pub fn pixel(&self, x: usize, y: usize) -> u32 {
+        self.pixels[y * self.width + x] & 0xFF_FFFF
+    }
+
+    fn to_pixmap(&self) -> Pixmap {
+        let mut pixmap = Pixmap::new(self.width as u32, self.height as u32).unwrap();
+        for (out, &p) in pixmap.data_mut().chunks_exact_mut(4).zip(&self.pixels) {
+            out.copy_from_slice(&[(p >> 16) as u8, (p >> 8) as u8, p as u8, 0xFF]);
+        }
+        pixmap
+    }
+}
+
+/// A change to the window's input, made at the start of a frame
+enum Change {
+    Press(Key),
+    Release(Key),
+    Mouse(Option<(f32, f32)>),
+    Button(MouseButton, bool),
+    Scroll(f32, f32),
+    Resize(usize, usize),
+}
+
+/// What the window shows and the input it is giving the viewer
+struct Screen {
+    open: bool,
+    size: (usize, usize),
+    title: String,
+    down: Vec<Key>,
+    /// Keys pressed this frame
+    pressed: Vec<Key>,
+    mouse: Option<(f32, f32)>,
+    buttons: [bool; 3],
+    scroll: Option<(f32, f32)>,
+    /// Changes for each frame to come
+    queue: VecDeque<Vec<Change>>,
+    /// Frames ended, and the one the last queued changes were made in
+    frames: u64,
+    changed_in: u64,
+    frame: Option<Frame>,
+    presented: Option<Instant>,
+}
+
+impl Screen {
+    /// End the frame, moving on to the next one's input
+    fn tick(&mut self) {
+        self.frames += 1;
+        self.pressed.clear();
+        self.scroll = None;
+        let Some(changes) = self.queue.pop_front() else {
+            return;
+        };
+        self.changed_in = self.frames;
+        for change in changes {
+            match change {
+                Change::Press(key) => {
+                    if !self.down.contains(&key) {
+                        self.down.push(key);
+                    }
+                    self.pressed.push(key);
+                }
+                Change::Release(key) => self.down.retain(|&k| k != key),
+                Change::Mouse(at) => self.mouse = at,
+                Change::Button(button, down) => self.buttons[index(button)] = down,
+                Change::Scroll(dx, dy) => self.scroll = Some((dx, dy)),
+                Change::Resize(w, h) => self.size = (w, h),
+            }
+        }
+    }
+}
+
+fn index(button: MouseButton) -> usize {
+    match button {
+        MouseButton::Left => 0,
+        MouseButton::Middle => 1,
+        MouseButton::Right => 2,
+    }
+}
+
+struct Shared {
+    screen: Mutex<Screen>,
+    /// Notified at the end of every frame
+    ticked: Condvar,
+}
+
+impl Shared {
+    fn screen(&self) -> MutexGuard<'_, Screen> {
+        // Still usable after the viewer panics, to close it.
+        self.screen.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wait until `done` holds of the screen, checking after every frame
+    fn wait(&self, what: &str, mut done: impl FnMut(&Screen) -> bool) {
+        let deadline = Instant::now() + TIMEOUT;
+        let mut screen = self.screen();
+        while !done(&screen) {
+            let left = deadline.saturating_duration_since(Instant::now());
+            assert!(!left.is_zero(), "timed out waiting for {what}");
+            screen = match self.ticked.wait_timeout(screen, left) {
+                Ok((screen, _)) => screen,
+                Err(e) => e.into_inner().0,
+            };
+        }
+    }
+}
+
+/// The window the viewer runs in
+struct VirtualWindow(Arc<Shared>);
+
+impl Window for VirtualWindow {
+    fn is_open(&self) -> bool {
+        self.0.screen().open
+    }
+
+    fn is_active(&mut self) -> bool {
+        true
+    }
+
+    fn get_size(&self) -> (usize, usize) {
+        self.0.screen().size
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.0.screen().title = title.to_string();
+    }
+
+    fn is_key_down(&self, key: Key) -> bool {
+        self.0.screen().down.contains(&key)
+    }
+
+    fn get_keys(&self) -> Vec<Key> {
+        self.0.screen().down.clone()
+    }
+
+    fn get_keys_pressed(&self, _repeat: KeyRepeat) -> Vec<Key> {
+        self.0.screen().pressed.clone()
+    }
+
+    fn get_mouse_pos(&self, mode: MouseMode) -> Option<(f32, f32)> {
+        let screen = self.0.screen();
+        let (x, y) = screen.mouse?;
+        let (w, h) = (screen.size.0 as f32, screen.size.1 as f32);
+        match mode {
+            MouseMode::Pass => Some((x, y)),
+            MouseMode::Clamp => Some((x.clamp(0.0, w - 1.0), y.clamp(0.0, h - 1.0))),
+            MouseMode::Discard => (x >= 0.0 && y >= 0.0 && x < w && y < h).then_some((x, y)),
+        }
+    }
+
+    fn get_mouse_down(&self, button: MouseButton) -> bool {
+        self.0.screen().buttons[index(button)]
+    }
+
+    fn get_scroll_wheel(&self) -> Option<(f32, f32)> {
+        self.0.screen().scroll
+    }
+
+    fn update(&mut self) {
+        self.0.screen().tick();
+        self.0.ticked.notify_all();
+    }
+
+    fn update_with_buffer(
+        &mut self,
+        buffer: &[u32],
+        width: usize,
+        height: usize,
+    ) -> Result<(), String> {
+        let mut screen = self.0.screen();
+        screen.frame = Some(Frame {
+            width,
+            height,
+            pixels: buffer[..width * height].to_vec(),
+        });
+        screen.presented = Some(Instant::now());
+        screen.tick();
+        drop(screen);
+        self.0.ticked.notify_all();
+        Ok(())
+    }
+}
+
+/// Configuration as the command line gives it by default, with a grey
+/// letterbox and no grid so frames are simple to check
+pub fn config() -> Config {
+    let checkerboard = Checkerboard {
+        colors: [Color::WHITE, Color::from_rgba8(0xCC, 0xCC, 0xCC, 0xFF)],
+        size: 8,
+    };
+    Config {
+        title: None,
+        window_size: (WIDTH, HEIGHT),
+        fit: Fit::Contain,
+        zoom: None,
+        max_fps: 60,
+        poll_interval: None,
+        idle_poll: viewer::IDLE_POLL,
+        quit_on_delete: false,
+        idle_exit: None,
+        reload_interval: None,
+        reload_on: Trigger::Any,
+        fail_on_error: false,
+        limits: None,
+        refit_on_resize: false,
+        highlights: Vec::new(),
+        zoom_to_changes: false,
+        anchor: Anchor::default(),
+        anchor_id: None,
+        keep_view: false,
+        also_watch: Vec::new(),
+        glob: None,
+        defines: Arc::new(Defines::default()),
+        exports: Vec::new(),
+        export_area: None,
+        export_scale: 1.0,
+        units: Units::new(Unit::Px, None),
+        crop_aspect: None,
+        bake_annotations: false,
+        #[cfg(feature = "gl")]
+        gl: false,
+        profile: None,
+        dither: None,
+        contrast: None,
+        large_cursor: false,
+        presenter: false,
+        loupe: false,
+        loupe_zoom: svgtail::loupe::ZOOM,
+        loupe_radius: svgtail::loupe::RADIUS,
+        canvas: Canvas {
+            background: Color::from_rgba8(0x33, 0x33, 0x33, 0xFF),
+            page: None,
+            edge: Edge::None,
+            checkerboard: None,
+            backdrop: None,
+            world_grid: false,
+        },
+        checkerboard,
+    }
+}
+
+/// A viewer running in a [`VirtualWindow`] on a thread of its own, showing
+/// documents written to a directory of their own
+pub struct Harness {
+    shared: Arc<Shared>,
+    sender: ActionSender,
+    events: mpsc::Receiver<Event>,
+    dir: PathBuf,
+    viewer: Option<JoinHandle<Result<(), Failure>>>,
+}
+
+impl Harness {
+    /// Write `docs`, pairs of file name and contents, and view them as
+    /// `svgtail` given their paths would, waiting until the first loads
+    pub fn new(docs: &[(&str, &str)]) -> Self {
+        Self::with_config(docs, config())
+    }
+
+    pub fn with_config(docs: &[(&str, &str)], config: Config) -> Self {
+        Self::with_setup(docs, config, |_| {})
+    }
+
+    /// [`Self::with_config`], with `setup` given the viewer before it runs,
+    /// to add hooks to it
+    pub fn with_setup(
+        docs: &[(&str, &str)],
+        config: Config,
+        setup: impl FnOnce(&mut Viewer) + Send + 'static,
+    ) -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let n = NEXT.fetch_add(1, Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("svgtail-test-{}-{n}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in docs {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+        let sources: Vec<Source> = docs
+            .iter()
+            .map(|(name, _)| Source::File(dir.join(name)))
+            .collect();
+
+        let shared = Arc::new(Shared {
+            screen: Mutex::new(Screen {
+                open: true,
+                size: (WIDTH, HEIGHT),
+                title: String::new(),
+                down: Vec::new(),
+                pressed: Vec::new(),
+                mouse: None,
+                buttons: [false; 3],
+                scroll: None,
+                queue: VecDeque::new(),
+                frames: 0,
+                changed_in: 0,
+                frame: None,
+                presented: None,
+            }),
+            ticked: Condvar::new(),
+        });
+        let (handles, received) = mpsc::channel();
+        let mut window = VirtualWindow(Arc::clone(&shared));
+        let viewer = thread::spawn(move || {
+            #[cfg_attr(not(feature = "text"), allow(unused_mut))]
+            let mut opts = usvg::Options::default();
+            // Text is left out of documents without fonts to lay it out in.
+            #[cfg(feature = "text")]
+            opts.fontdb_mut().load_system_fonts();
+            let mut viewer = Viewer::new(sources, opts, config);
+            setup(&mut viewer);
+            handles.send((viewer.sender(), viewer.subscribe())).unwrap();
+            viewer.run_in(&mut window)
+        });
+        let (sender, events) = received.recv().unwrap();
+        let mut harness = Self {
+            shared,
+            sender,
+            events,
+            dir,
+            viewer: Some(viewer),
+        };
+        if !docs.is_empty() {
+            harness.wait_for("the first load", |e| matches!(e, Event::Loaded(_)));
+        }
+        harness
+    }
+
+    /// Where `name` is among the documents
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    /// Replace the contents of `name`, as an editor saving it would
+    pub fn write(&self, name: &str, contents: &str) {
+        fs::write(self.path(name), contents).unwrap();
+    }
+
+    /// Make `changes`, one list per frame, and wait for the viewer to have
+    /// seen the last of them
+    fn input(&self, changes: Vec<Vec<Change>>) {
+        let mut screen = self.shared.screen();
+        screen.queue.extend(changes);
+        drop(screen);
+        self.shared.wait("input to be handled", |screen| {
+            screen.queue.is_empty() && screen.frames > screen.changed_in
+        });
+    }
+
+    /// Press `key` and let it go the next frame
+    pub fn press(&self, key: Key) {
+        self.input(vec![vec![Change::Press(key)], vec![Change::Release(key)]]);
+    }
+
+    /// Hold `key` down for `frames` frames
+    pub fn hold(&self, key: Key, frames: usize) {
+        let mut changes: Vec<Vec<Change>> = vec![vec![Change::Press(key)]];
+        changes.extend((1..frames).map(|_| Vec::new()));
+        changes.push(vec![Change::Release(key)]);
+        self.input(changes);
+    }
+
+    /// Move the mouse to `(x, y)` in window pixels
+    pub fn move_mouse(&self, x: f32, y: f32) {
+        self.input(vec![vec![Change::Mouse(Some((x, y)))]]);
+    }
+
+    /// Click `button` at `(x, y)` in window pixels
+    pub fn click(&self, button: MouseButton, x: f32, y: f32) {
+        self.input(vec![
+            vec![Change::Mouse(Some((x, y))), Change::Button(button, true)],
+            vec![Change::Button(button, false)],
+        ]);
+    }
+
+    /// Drag with `button` held from `from` to `to` in window pixels, over a
+    /// few frames
+    pub fn drag(&self, button: MouseButton, from: (f32, f32), to: (f32, f32)) {
+        let mut changes = vec![vec![
+            Change::Mouse(Some(from)),
+            Change::Button(button, true),
+        ]];
+        changes.extend((1..=4).map(|i| {
+            let t = i as f32 / 4.0;
+            let at = (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t);
+            vec![Change::Mouse(Some(at))]
+        }));
+        changes.push(vec![Change::Button(button, false)]);
+        self.input(changes);
+    }
+
+    pub fn scroll(&self, dy: f32) {
+        self.input(vec![vec![Change::Scroll(0.0, dy)]]);
+    }
+
+    /// Resize the window to `width` × `height`
+    pub fn resize(&self, width: usize, height: usize) {
+        self.input(vec![vec![Change::Resize(width, height)]]);
+    }
+
+    /// Send `action` to the viewer, waiting until it has been applied
+    pub fn send(&self, action: Action) {
+        self.sender.send(action).unwrap();
+        self.state();
+    }
+
+    /// For handing to a control server, as `svgtail --rpc` does, or sending
+    /// actions without waiting for them
+    pub fn sender(&self) -> ActionSender {
+        self.sender.clone()
+    }
+
+    /// The view, as the viewer reports it
+    pub fn state(&self) -> Snapshot {
+        let (tx, rx) = mpsc::channel();
+        self.sender.send(Action::GetState(tx)).unwrap();
+        rx.recv_timeout(TIMEOUT)
+            .expect("the viewer to report its state")
+    }
+
+    /// Wait for an event `matches` accepts, skipping any others, and return it
+    pub fn wait_for(&mut self, what: &str, matches: impl Fn(&Event) -> bool) -> Event {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.events.recv_timeout(left) {
+                Ok(event) if matches(&event) => return event,
+                Ok(_) => {}
+                Err(_) => panic!("timed out waiting for {what}"),
+            }
+        }
+    }
+
+    /// The events sent so far and not yet waited for
+    pub fn events(&self) -> Vec<Event> {
+        self.events.try_iter().collect()
+    }
+
+    /// Wait until nothing has been presented for a while, so the frame
+    /// shown is the finished one
+    pub fn settle(&self) {
+        let start = Instant::now();
+        self.shared.wait("the view to settle", |screen| {
+            screen.presented.unwrap_or(start).max(start).elapsed() >= QUIET
+        });
+    }
+
+    /// The frame shown once the view settles
+    pub fn frame(&self) -> Frame {
+        self.settle();
+        let screen = self.shared.screen();
+        screen
+            .frame
+            .clone()
+            .expect("a frame to have been presented")
+    }
+
+    pub fn title(&self) -> String {
+        self.shared.screen().title.clone()
+    }
+
+    /// Check the settled frame against `tests/snapshots/{name}.png`,
+    /// writing it there if there is none yet or `SVGTAIL_BLESS` is set. A
+    /// mismatch is saved beside it as `{name}.actual.png`.
+    pub fn assert_snapshot(&self, name: &str) {
+        let frame = self.frame().to_pixmap();
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots");
+        let path = dir.join(format!("{name}.png"));
+        let actual = dir.join(format!("{name}.actual.png"));
+        if std::env::var_os("SVGTAIL_BLESS").is_some() || !path.exists() {
+            fs::create_dir_all(&dir).unwrap();
+            frame.save_png(&path).unwrap();
+            let _ = fs::remove_file(&actual);
+            return;
+        }
+        let expected = Pixmap::load_png(&path).unwrap();
+        let same_size = (expected.width(), expected.height()) == (frame.width(), frame.height());
+        let differing = expected
+            .data()
+            .iter()
+            .zip(frame.data())
+            .filter(|(a, b)| a.abs_diff(**b) > 2)
+            .count();
+        if !same_size || differing > 0 {
+            frame.save_png(&actual).unwrap();
+            panic!(
+                "{name}: the frame differs from {}; see {}",
+                path.display(),
+                actual.display()
+            );
+        }
+        let _ = fs::remove_file(&actual);
+    }
+
+    /// Close the window and return how the viewer exited
+    pub fn close(mut self) -> Result<(), Failure> {
+        self.shared.screen().open = false;
+        let viewer = self.viewer.take().expect("the viewer to be running");
+        viewer.join().expect("the viewer not to panic")
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        self.shared.screen().open = false;
+        // A viewer that panicked has already failed the test.
+        if let Some(viewer) = self.viewer.take() {
+            let _ = viewer.join();
+        }
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
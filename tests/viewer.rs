@@ -0,0 +1,878 @@
+//! The viewer's loop driven through a window in memory: loading and
+//! reloading, fitting, panning and zooming, and what it tells subscribers
+
+mod support;
+
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use resvg::tiny_skia::Pixmap;
+use svgtail::action::{Action, Button, Event};
+use svgtail::state::{Anchor, Fit, Horizontal, Vertical};
+use svgtail::untrusted::Limits;
+use svgtail::viewer::Config;
+use svgtail::watch::{Pattern, Trigger};
+use svgtail::window::{Key, MouseButton};
+
+use support::{HEIGHT, Harness, WIDTH};
+
+const RED: u32 = 0xFF0000;
+const BLUE: u32 = 0x0000FF;
+const LETTERBOX: u32 = 0x333333;
+
+/// A `fill` rectangle 100 × 50 units, twice as wide as it is high
+fn wide(fill: &str) -> String {
+    format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50"><rect width="100" height="50" fill="{fill}"/></svg>"#
+    )
+}
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-3
+}
+
+#[test]
+fn fits_the_document_to_the_window() {
+    let h = Harness::new(&[("doc.svg", &wide("red"))]);
+    let state = h.state();
+    assert_eq!(state.doc_size, Some((100.0, 50.0)));
+    assert_eq!(state.window_size, (WIDTH, HEIGHT));
+    assert!(state.auto_fit);
+    assert!(close(state.fit_scale, 2.0), "{}", state.fit_scale);
+
+    // 200 × 100 pixels, letterboxed above and below
+    let frame = h.frame();
+    assert_eq!(frame.pixel(WIDTH / 2, HEIGHT / 2), RED);
+    assert_eq!(frame.pixel(WIDTH / 2, 10), LETTERBOX);
+    assert_eq!(frame.pixel(WIDTH / 2, HEIGHT - 10), LETTERBOX);
+    h.assert_snapshot("fitted");
+    assert!(h.title().contains("FIT"), "{}", h.title());
+    assert!(h.title().ends_with("watching ✓"), "{}", h.title());
+}
+
+#[test]
+fn fits_and_zooms_and_titles_as_configured() {
+    let config = Config {
+        fit: Fit::Height,
+        title: Some("plots".to_string()),
+        ..support::config()
+    };
+    let h = Harness::with_config(&[("doc.svg", &wide("red"))], config);
+    // 300 × 150 pixels, overflowing the sides
+    let state = h.state();
+    assert!(close(state.fit_scale, 3.0), "{}", state.fit_scale);
+    assert_eq!(h.frame().pixel(5, HEIGHT - 5), RED);
+    assert!(h.title().starts_with("plots ["), "{}", h.title());
+
+    let config = Config {
+        zoom: Some(0.5),
+        ..support::config()
+    };
+    let h = Harness::with_config(&[("doc.svg", &wide("red"))], config);
+    let state = h.state();
+    assert!(!state.auto_fit);
+    assert_eq!(state.fit_scale * state.zoom, 0.5);
+    assert!(h.title().contains("50%"), "{}", h.title());
+}
+
+#[test]
+fn reloads_when_the_file_changes() {
+    let mut h = Harness::new(&[("doc.svg", &wide("red"))]);
+    h.write("doc.svg", &wide("blue"));
+    h.wait_for("the reload", |e| matches!(e, Event::Reloaded(_)));
+    assert_eq!(h.frame().pixel(WIDTH / 2, HEIGHT / 2), BLUE);
+}
+
+#[test]
+fn reloads_at_once_however_seldom_input_is_polled() {
+    let config = Config {
+        poll_interval: Some(Duration::from_secs(10)),
+        ..support::config()
+    };
+    let mut h = Harness::with_config(&[("doc.svg", &wide("red"))], config);
+    let start = Instant::now();
+    h.write("doc.svg", &wide("blue"));
+    h.wait_for("the reload", |e| matches!(e, Event::Reloaded(_)));
+    assert!(
+        start.elapsed() < Duration::from_secs(5),
+        "{:?}",
+        start.elapsed()
+    );
+}
+
+#[test]
+fn keeps_showing_the_last_version_that_loaded() {
+    let mut h = Harness::new(&[("doc.svg", &wide("red"))]);
+    h.write("doc.svg", "<svg");
+    h.wait_for("the failed load", |e| matches!(e, Event::LoadFailed(..)));
+    assert_eq!(h.state().doc_size, Some((100.0, 50.0)));
+
+    h.write("doc.svg", &wide("blue"));
+    h.wait_for("the reload", |e| matches!(e, Event::Reloaded(_)));
+    assert_eq!(h.frame().pixel(WIDTH / 2, HEIGHT / 2), BLUE);
+}
+
+#[test]
+fn says_a_file_read_while_empty_is_empty() {
+    let mut h = Harness::new(&[("doc.svg", &wide("red"))]);
+    h.write("doc.svg", "");
+    let failed = h.wait_for("the failed load", |e| matches!(e, Event::LoadFailed(..)));
+    assert!(
+        matches!(&failed, Event::LoadFailed(_, e) if e == "the file is empty"),
+        "{failed:?}"
+    );
+    assert_eq!(h.frame().pixel(WIDTH / 2, HEIGHT / 2), RED);
+
+    h.write("doc.svg", &wide("blue"));
+    h.wait_for("the reload", |e| matches!(e, Event::Reloaded(_)));
+    assert_eq!(h.frame().pixel(WIDTH / 2, HEIGHT / 2), BLUE);
+}
+
+#[test]
+fn reads_utf16_and_says_which_encodings_it_cant() {
+    let mut h = Harness::new(&[("doc.svg", &wide("red"))]);
+    let utf16: Vec<u8> = [0xFEFF]
+        .into_iter()
+        .chain(wide("blue").encode_utf16())
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    fs::write(h.path("doc.svg"), utf16).unwrap();
+    h.wait_for("the reload", |e| matches!(e, Event::Reloaded(_)));
+    assert_eq!(h.frame().pixel(WIDTH / 2, HEIGHT / 2), BLUE);
+
+    let shift_jis = b"<?xml version=\"1.0\" encoding=\"Shift_JIS\"?>\
+        <svg xmlns=\"http://www.w3.org/2000/svg\"><text>\x82\xa0</text></svg>";
+    fs::write(h.path("doc.svg"), shift_jis).unwrap();
+    let failed = h.wait_for("the failed load", |e| matches!(e, Event::LoadFailed(..)));
+    assert!(
+        matches!(&failed, Event::LoadFailed(_, e) if e.contains("Shift_JIS")),
+        "{failed:?}"
+    );
+}
+
+#[test]
+fn draws_from_other_files_and_reloads_when_they_change() {
+    let parts = |fill: &str| {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><rect id="box" width="50" height="50" fill="{fill}"/></svg>"#
+        )
+    };
+    let doc = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50"><use href="parts.svg#box"/><image x="50" width="50" height="50" href="pic.svg"/></svg>"#;
+    let pic = r#"<svg xmlns="http://www.w3.org/2000/svg" width="50" height="50"><rect width="50" height="50" fill="blue"/></svg>"#;
+    let mut h = Harness::new(&[("doc.svg", doc)]);
+    h.write("parts.svg", &parts("red"));
+    h.write("pic.svg", pic);
+    h.write("doc.svg", &format!("{doc}\n"));
+    h.wait_for("the reload", |e| matches!(e, Event::Reloaded(_)));
+    assert_eq!(h.frame().pixel(WIDTH / 4, HEIGHT / 2), RED);
+    assert_eq!(h.frame().pixel(WIDTH * 3 / 4, HEIGHT / 2), BLUE);
+
+    h.write("parts.svg", &parts("blue"));
+    // Watching the new files starts the watcher over, which reloads too
+    while h.frame().pixel(WIDTH / 4, HEIGHT / 2) != BLUE {
+        h.wait_for("the reload", |e| matches!(e, Event::Reloaded(_)));
+    }
+}
+
+#[test]
+fn follows_the_newest_svg_written_anywhere_in_a_directory() {
+    let out = std::env::temp_dir().join(format!("svgtail-out-{}", std::process::id()));
+    fs::create_dir_all(out.join("run-2")).unwrap();
+    let config = Config {
+        glob: Some(Pattern::within(&out).unwrap()),
+        ..support::config()
+    };
+    let mut h = Harness::with_config(&[("doc.svg", &wide("red"))], config);
+    fs::write(out.join("run-2/notes.txt"), "not a drawing").unwrap();
+    fs::write(out.join("run-2/plot.svg"), wide("blue")).unwrap();
+    let plot = out.join("run-2/plot.svg");
+    h.wait_for(
+        "the switch",
+        |e| matches!(e, Event::Loaded(p) if *p == plot),
+    );
+    h.settle();
+    assert_eq!(h.frame().pixel(WIDTH / 2, HEIGHT / 2), BLUE);
+    fs::remove_dir_all(&out).unwrap();
+}
+
+#[test]
+fn refuses_untrusted_documents_that_expand_past_the_limits() {
+    let config = Config {
+        limits: Some(Limits::default()),
+        ..support::config()
+    };
+    let mut h = Harness::with_config(&[("doc.svg", &wide("red"))], config);
+    // Ten uses of ten uses of ... a rect, nine deep
+    let mut defs = r#"<rect id="g0" width="1" height="1"/>"#.to_string();
+    for i in 1..10 {
+        let uses = format!(r##"<use href="#g{}"/>"##, i - 1).repeat(10);
+        defs += &format!(r#"<g id="g{i}">{uses}</g>"#);
+    }
+    let bomb = format!(r#"<svg xmlns="http://www.w3.org/2000/svg"><defs>{defs}</defs></svg>"#);
+    h.write("doc.svg", &bomb);
+    let failed = h.wait_for("the failed load", |e| matches!(e, Event::LoadFailed(..)));
+    assert!(
+        matches!(&failed, Event::LoadFailed(_, e) if e.contains("elements")),
+        "{failed:?}"
+    );
+
+    let primitives = "<feOffset/>".repeat(10);
+    let rects = r#"<rect width="1" height="1" filter="url(#f)"/>"#.repeat(100);
+    let filtered = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg"><filter id="f">{primitives}</filter>{rects}</svg>"#
+    );
+    h.write("doc.svg", &filtered);
+    let failed = h.wait_for("the failed load", |e| matches!(e, Event::LoadFailed(..)));
+    assert!(
+        matches!(&failed, Event::LoadFailed(_, e) if e.contains("1000 filter primitives")),
+        "{failed:?}"
+    );
+    assert_eq!(h.frame().pixel(WIDTH / 2, HEIGHT / 2), RED);
+}
+
+#[test]
+fn screenshots_untrusted_documents_as_they_are_now() {
+    let config = Config {
+        limits: Some(Limits::default()),
+        ..support::config()
+    };
+    let mut h = Harness::with_config(&[("doc.svg", &wide("red"))], config);
+    h.write("doc.svg", &wide("blue"));
+    h.wait_for("the reload", |e| matches!(e, Event::Reloaded(_)));
+    // Taken at once, before the window has drawn the change
+    let shot = h.path("shot.png");
+    let (tx, rx) = std::sync::mpsc::channel();
+    h.sender()
+        .send(Action::Screenshot(shot.clone(), Some(tx)))
+        .unwrap();
+    rx.recv_timeout(Duration::from_secs(10)).unwrap().unwrap();
+    let png = Pixmap::load_png(&shot).unwrap();
+    let pixel = png.pixel(WIDTH as u32 / 2, HEIGHT as u32 / 2).unwrap();
+    assert_eq!((pixel.red(), pixel.blue()), (0, 0xFF));
+    let pixel = png.pixel(WIDTH as u32 / 2, 10).unwrap();
+    assert_eq!((pixel.red(), pixel.blue()), (0x33, 0x33));
+}
+
+#[test]
+fn refits_as_the_window_is_resized() {
+    let h = Harness::new(&[("doc.svg", &wide("red"))]);
+    h.resize(400, 400);
+    h.settle();
+    let state = h.state();
+    assert_eq!(state.window_size, (400, 400));
+    assert!(close(state.fit_scale, 4.0), "{}", state.fit_scale);
+    assert_eq!(h.frame().pixel(200, 200), RED);
+    h.assert_snapshot("resized");
+}
+
+#[test]
+fn zoom_keys_zoom_and_reset_fits_again() {
+    let h = Harness::new(&[("doc.svg", &wide("red"))]);
+    h.hold(Key::Equal, 5);
+    let zoomed = h.state();
+    assert!(zoomed.zoom > 1.0, "{}", zoomed.zoom);
+    assert!(!zoomed.auto_fit);
+    assert!(h.title().contains("MANUAL"), "{}", h.title());
+
+    h.hold(Key::Minus, 10);
+    assert!(h.state().zoom < zoomed.zoom);
+
+    h.press(Key::R);
+    let state = h.state();
+    assert!(state.auto_fit);
+    assert_eq!((state.zoom, state.pan), (1.0, (0.0, 0.0)));
+}
+
+#[test]
+fn pan_keys_move_the_view() {
+    let h = Harness::new(&[("doc.svg", &wide("red"))]);
+    h.hold(Key::L, 3);
+    let state = h.state();
+    assert!(state.pan.0 < 0.0, "{:?}", state.pan);
+    assert_eq!(state.pan.1, 0.0);
+    h.hold(Key::J, 3);
+    assert!(h.state().pan.1 < 0.0);
+}
+
+#[test]
+fn the_mouse_pans_zooms_about_the_cursor_and_resets() {
+    let h = Harness::new(&[("doc.svg", &wide("red"))]);
+    h.drag(MouseButton::Left, (100.0, 75.0), (140.0, 85.0));
+    let state = h.state();
+    assert!(!state.auto_fit);
+    assert_eq!(state.pan, (40.0, 10.0));
+
+    h.click(MouseButton::Middle, 100.0, 75.0);
+    let state = h.state();
+    assert!(state.auto_fit);
+    assert_eq!((state.zoom, state.pan), (1.0, (0.0, 0.0)));
+
+    // (75, 25) in the page stays under the cursor as the scale goes from 2
+    // to 2.4 pixels per unit
+    h.move_mouse(150.0, 75.0);
+    h.scroll(1.0);
+    let state = h.state();
+    assert!(close(state.zoom, 1.2), "{}", state.zoom);
+    assert!(
+        close(state.pan.0, -10.0) && close(state.pan.1, 0.0),
+        "{:?}",
+        state.pan
+    );
+}
+
+#[test]
+fn opening_another_file_fits_it() {
+    let mut h = Harness::new(&[("doc.svg", &wide("red"))]);
+    h.send(Action::SetZoom(3.0));
+    h.send(Action::SetPan(40.0, 10.0));
+    h.write("other.svg", &wide("blue"));
+    let other = h.path("other.svg");
+    h.send(Action::Open(other.clone()));
+    h.wait_for(
+        "the other file",
+        |e| matches!(e, Event::Loaded(p) if *p == other),
+    );
+    let state = h.state();
+    assert!(state.auto_fit && state.pan == (0.0, 0.0), "{state:?}");
+    assert!(close(state.zoom, 1.0) && close(state.fit_scale, 2.0));
+    let frame = h.frame();
+    assert_eq!(frame.pixel(WIDTH / 2, HEIGHT / 2), BLUE);
+    assert_eq!(frame.pixel(WIDTH / 2, 10), LETTERBOX);
+}
+
+#[test]
+fn fit_toggles_back_to_the_manual_view() {
+    let h = Harness::new(&[("doc.svg", &wide("red"))]);
+    h.send(Action::SetZoom(3.0));
+    h.send(Action::SetPan(10.0, -5.0));
+    h.press(Key::F);
+    assert!(h.state().auto_fit);
+    h.press(Key::F);
+    let state = h.state();
+    assert!(!state.auto_fit);
+    assert_eq!((state.zoom, state.pan), (3.0, (10.0, -5.0)));
+}
+
+#[test]
+fn shows_the_view_it_is_given() {
+    let h = Harness::new(&[(
+        "doc.svg",
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50">
+            <rect width="50" height="50" fill="red"/>
+            <rect x="50" width="50" height="50" fill="blue"/>
+        </svg>"#,
+    )]);
+    // Twice the fitted size, panned so the right half fills the window
+    h.send(Action::SetZoom(2.0));
+    h.send(Action::SetPan(-100.0, 0.0));
+    let frame = h.frame();
+    assert_eq!(frame.pixel(WIDTH / 2, HEIGHT / 2), BLUE);
+    assert_eq!(frame.pixel(WIDTH / 2, 10), BLUE);
+    h.assert_snapshot("zoomed");
+}
+
+#[test]
+fn tells_subscribers_about_unbound_keys_and_clicks() {
+    let mut h = Harness::new(&[("doc.svg", &wide("red"))]);
+    h.press(Key::F);
+    h.press(Key::Q);
+    let key = h.wait_for("a key", |e| matches!(e, Event::Key(_)));
+    assert!(matches!(&key, Event::Key(name) if name == "Q"), "{key:?}");
+
+    // The window's middle is the page's, at 2 pixels per unit
+    h.click(MouseButton::Right, 100.0, 75.0);
+    let click = h.wait_for("a click", |e| matches!(e, Event::Click { .. }));
+    let Event::Click { button, at, point } = click else {
+        unreachable!();
+    };
+    assert_eq!(button, Button::Right);
+    assert_eq!(at, (100.0, 75.0));
+    assert_eq!(point, Some((50.0, 25.0)));
+}
+
+#[test]
+fn closing_the_window_exits() {
+    let h = Harness::new(&[("doc.svg", &wide("red"))]);
+    assert!(h.close().is_ok());
+}
+
+#[test]
+fn reloads_on_the_events_it_is_told_to() {
+    let config = Config {
+        reload_on: Trigger::Create,
+        ..support::config()
+    };
+    let mut h = Harness::with_config(&[("doc.svg", &wide("red"))], config);
+    // Written in place, which only modifies the file
+    h.write("doc.svg", &wide("blue"));
+    thread::sleep(Duration::from_millis(600));
+    assert!(
+        !h.events().iter().any(|e| matches!(e, Event::Reloaded(_))),
+        "a modification reloaded"
+    );
+
+    // Written beside it and renamed over it
+    let new = h.path("new.svg");
+    fs::write(&new, wide("blue")).unwrap();
+    fs::rename(&new, h.path("doc.svg")).unwrap();
+    h.wait_for("the reload", |e| matches!(e, Event::Reloaded(_)));
+    assert_eq!(h.frame().pixel(WIDTH / 2, HEIGHT / 2), BLUE);
+}
+
+#[test]
+fn follows_the_bottom_edge_as_the_document_grows() {
+    let config = Config {
+        anchor: Anchor {
+            x: None,
+            y: Some(Vertical::Bottom),
+        },
+        ..support::config()
+    };
+    let mut h = Harness::with_config(&[("doc.svg", &wide("red"))], config);
+    // A blue row added below; fitted, the page ended 25 pixels from the bottom
+    h.write(
+        "doc.svg",
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="75">
+            <rect width="100" height="50" fill="red"/>
+            <rect y="50" width="100" height="25" fill="blue"/>
+        </svg>"#,
+    );
+    h.wait_for("the reload", |e| matches!(e, Event::Reloaded(_)));
+    let state = h.state();
+    assert!(!state.auto_fit);
+    assert!(close(state.fit_scale * state.zoom, 2.0), "{}", state.zoom);
+    let frame = h.frame();
+    assert_eq!(frame.pixel(WIDTH / 2, HEIGHT - 30), BLUE);
+    assert_eq!(frame.pixel(WIDTH / 2, HEIGHT - 10), LETTERBOX);
+    assert_eq!(frame.pixel(WIDTH / 2, 10), RED);
+}
+
+#[test]
+fn anchors_the_side_it_is_told_to() {
+    let config = Config {
+        anchor: Anchor {
+            x: Some(Horizontal::Left),
+            y: None,
+        },
+        ..support::config()
+    };
+    let mut h = Harness::with_config(&[("doc.svg", &wide("red"))], config);
+    // Half as wide again; kept in the middle, the blue would reach the right
+    h.write(
+        "doc.svg",
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="150" height="50">
+            <rect width="100" height="50" fill="red"/>
+            <rect x="100" width="50" height="50" fill="blue"/>
+        </svg>"#,
+    );
+    h.wait_for("the reload", |e| matches!(e, Event::Reloaded(_)));
+    let frame = h.frame();
+    assert_eq!(frame.pixel(5, HEIGHT / 2), RED);
+    assert_eq!(frame.pixel(WIDTH - 5, HEIGHT / 2), RED);
+    assert_eq!(frame.pixel(WIDTH / 2, 10), LETTERBOX);
+}
+
+#[test]
+fn keeps_the_anchored_element_in_place() {
+    let doc = |width: u32, x: u32| {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="50">
+                <rect width="{width}" height="50" fill="red"/>
+                <rect id="n" x="{x}" y="20" width="10" height="10" fill="blue"/>
+            </svg>"#
+        )
+    };
+    let config = Config {
+        anchor_id: Some("n".to_string()),
+        ..support::config()
+    };
+    let mut h = Harness::with_config(&[("doc.svg", &doc(100, 10))], config);
+    let at = (WIDTH / 2 - 70, HEIGHT / 2);
+    assert_eq!(h.frame().pixel(at.0, at.1), BLUE);
+
+    // Laid out again, wider and with the node further right
+    h.write("doc.svg", &doc(120, 70));
+    h.wait_for("the reload", |e| matches!(e, Event::Reloaded(_)));
+    let frame = h.frame();
+    assert_eq!(frame.pixel(at.0, at.1), BLUE);
+    assert_eq!(frame.pixel(at.0 - 15, at.1), RED);
+    assert!(!h.state().auto_fit);
+}
+
+#[test]
+fn keeps_the_view_until_the_size_changes_much() {
+    let config = Config {
+        keep_view: true,
+        ..support::config()
+    };
+    let mut h = Harness::with_config(&[("doc.svg", &wide("red"))], config);
+    h.send(Action::SetZoom(3.0));
+    h.send(Action::SetPan(10.0, -5.0));
+    h.write("doc.svg", &wide("blue"));
+    h.wait_for("the reload", |e| matches!(e, Event::Reloaded(_)));
+    let state = h.state();
+    assert_eq!((state.zoom, state.pan), (3.0, (10.0, -5.0)));
+    assert!(!state.auto_fit);
+
+    h.write(
+        "doc.svg",
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="50"/>"#,
+    );
+    h.wait_for("the reload", |e| matches!(e, Event::Reloaded(_)));
+    let state = h.state();
+    assert!(state.auto_fit);
+    assert_eq!((state.zoom, state.pan), (1.0, (0.0, 0.0)));
+}
+
+#[test]
+fn zooms_to_what_changed_and_back() {
+    let config = Config {
+        zoom_to_changes: true,
+        ..support::config()
+    };
+    let doc = |corner: &str| {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50">
+                <rect width="100" height="50" fill="red"/>
+                <rect x="80" y="30" width="20" height="20" fill="{corner}"/>
+            </svg>"#
+        )
+    };
+    let mut h = Harness::with_config(&[("doc.svg", &doc("red"))], config);
+    h.write("doc.svg", &doc("blue"));
+    h.wait_for("the reload", |e| matches!(e, Event::Reloaded(_)));
+    let state = h.state();
+    assert!(!state.auto_fit);
+    assert!(state.zoom > 2.0, "{}", state.zoom);
+    assert_eq!(h.frame().pixel(WIDTH / 2, HEIGHT / 2), BLUE);
+
+    h.press(Key::Backspace);
+    let state = h.state();
+    assert!(state.auto_fit);
+    assert_eq!((state.zoom, state.pan), (1.0, (0.0, 0.0)));
+}
+
+#[cfg(feature = "text")]
+#[test]
+fn searches_the_text_and_centres_on_each_match() {
+    let h = Harness::new(&[(
+        "doc.svg",
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50">
+            <text x="5" y="20" font-family="DejaVu Sans" font-size="10">alpha</text>
+            <text x="70" y="40" font-family="DejaVu Sans" font-size="10">beta</text>
+        </svg>"#,
+    )]);
+    h.press(Key::Slash);
+    // Typed, not taken as the bindings for angles, stepping files and panning
+    for key in [Key::A, Key::L, Key::P, Key::H] {
+        h.press(key);
+    }
+    let state = h.state();
+    assert!(state.pan.0 > 0.0, "{:?}", state.pan);
+
+    for _ in 0..3 {
+        h.press(Key::Backspace);
+    }
+    h.press(Key::Enter);
+    assert!(h.state().pan.0 < 0.0, "{:?}", h.state().pan);
+
+    // Closing the search leaves the viewer open, and the keys bound again
+    h.press(Key::Escape);
+    let before = h.state().pan;
+    h.hold(Key::H, 3);
+    assert!(h.state().pan.0 > before.0);
+}
+
+#[test]
+fn highlights_elements_by_id_across_reloads() {
+    let doc = |id: &str| {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50">
+                <rect id="{id}" x="25" y="12.5" width="50" height="25" fill="red"/>
+            </svg>"#
+        )
+    };
+    let config = Config {
+        highlights: vec!["id~=edge_.*:#00f:tint".parse().unwrap()],
+        ..support::config()
+    };
+    let mut h = Harness::with_config(&[("doc.svg", &doc("edge_1"))], config);
+    let tinted = h.frame().pixel(WIDTH / 2, HEIGHT / 2);
+    assert!(tinted != RED && tinted & 0xFF > 0, "{tinted:06x}");
+
+    h.write("doc.svg", &doc("node_1"));
+    h.wait_for("the reload", |e| matches!(e, Event::Reloaded(_)));
+    assert_eq!(h.frame().pixel(WIDTH / 2, HEIGHT / 2), RED);
+
+    // Outlined, added while running
+    h.send(Action::Highlight("id=node_1:#00f".parse().unwrap()));
+    assert_eq!(h.frame().pixel(WIDTH / 2, HEIGHT / 2), RED);
+    assert_eq!(h.frame().pixel(WIDTH / 2, 50), BLUE);
+}
+
+#[test]
+fn hides_and_solos_elements_across_reloads() {
+    let doc = |fill: &str| {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50">
+                <rect width="100" height="50" fill="blue"/>
+                <g class="top"><rect id="cover" width="100" height="50" fill="{fill}"/></g>
+            </svg>"#
+        )
+    };
+    let mut h = Harness::new(&[("doc.svg", &doc("red"))]);
+    assert_eq!(h.frame().pixel(WIDTH / 2, HEIGHT / 2), RED);
+
+    h.send(Action::Hide(".top".parse().unwrap()));
+    h.wait_for("the reload", |e| matches!(e, Event::Reloaded(_)));
+    assert_eq!(h.frame().pixel(WIDTH / 2, HEIGHT / 2), BLUE);
+
+    // Still hidden in the next version of the file
+    h.write("doc.svg", &doc("lime"));
+    h.wait_for("the reload", |e| matches!(e, Event::Reloaded(_)));
+    assert_eq!(h.frame().pixel(WIDTH / 2, HEIGHT / 2), BLUE);
+
+    h.send(Action::ShowAll);
+    h.wait_for("the reload", |e| matches!(e, Event::Reloaded(_)));
+    assert_eq!(h.frame().pixel(WIDTH / 2, HEIGHT / 2), 0x00FF00);
+
+    // Soloing the group leaves out the blue beneath it too
+    h.send(Action::Solo(".top".parse().unwrap()));
+    h.wait_for("the reload", |e| matches!(e, Event::Reloaded(_)));
+    assert_eq!(h.frame().pixel(WIDTH / 2, HEIGHT / 2), 0x00FF00);
+    h.send(Action::Hide("#cover".parse().unwrap()));
+    h.wait_for("the reload", |e| matches!(e, Event::Reloaded(_)));
+    let empty = h.frame().pixel(WIDTH / 2, HEIGHT / 2);
+    assert!(empty != BLUE && empty != 0x00FF00, "{empty:06x}");
+}
+
+#[test]
+fn draws_annotations_and_keeps_them_beside_the_document() {
+    const MARK: u32 = 0xFF3B30;
+    // One saved from before, along the top of the page, shown on opening
+    let mut h = Harness::new(&[("doc.svg", &wide("red"))]);
+    h.write("doc.annotations", "freehand 10 5 90 5\n");
+    h.send(Action::Open(h.path("doc.svg")));
+    h.wait_for("the load", |e| matches!(e, Event::Loaded(_)));
+    assert_eq!(h.frame().pixel(WIDTH / 2, 35), MARK);
+
+    h.press(Key::D);
+    h.drag(MouseButton::Left, (20.0, 75.0), (180.0, 75.0));
+    assert_eq!(h.frame().pixel(WIDTH / 2, 75), MARK);
+    let saved = fs::read_to_string(h.path("doc.annotations")).unwrap();
+    assert_eq!(saved.lines().count(), 2, "{saved}");
+
+    // Left out of screenshots unless baked in
+    let shot = h.path("shot.png");
+    h.send(Action::Screenshot(shot.clone(), None));
+    let png = Pixmap::load_png(&shot).unwrap();
+    let pixel = png.pixel(WIDTH as u32 / 2, 75).unwrap();
+    assert_eq!((pixel.red(), pixel.green()), (0xFF, 0));
+
+    // Drawn into a review image, above its caption
+    h.press(Key::Enter);
+    let review = h.path("doc-review.png");
+    let png = Pixmap::load_png(&review).unwrap();
+    assert_eq!(png.width() as usize, WIDTH);
+    assert!(png.height() as usize > HEIGHT + 20, "{}", png.height());
+    let pixel = png.pixel(WIDTH as u32 / 2, 75).unwrap();
+    assert_eq!((pixel.red(), pixel.green()), (0xFF, 0x3B));
+
+    h.press(Key::Delete);
+    h.press(Key::Delete);
+    assert_eq!(h.frame().pixel(WIDTH / 2, 75), RED);
+    assert!(!h.path("doc.annotations").exists());
+}
+
+#[test]
+fn presenter_mode_halos_the_cursor_and_zooms_while_z_is_held() {
+    let config = Config {
+        presenter: true,
+        ..support::config()
+    };
+    let mut h = Harness::with_config(&[("doc.svg", &wide("red"))], config);
+    h.move_mouse(50.0, 75.0);
+    h.settle();
+    let halo = h.frame().pixel(50, 75);
+    assert!(halo != RED && halo >> 8 & 0xFF > 0, "{halo:06x}");
+    assert_eq!(h.frame().pixel(150, 75), RED);
+
+    let before = h.state();
+    h.events();
+    h.hold(Key::Z, 3);
+    let peeked = h.wait_for("the zoom", |e| matches!(e, Event::ViewChanged { .. }));
+    assert!(matches!(peeked, Event::ViewChanged { zoom, .. } if close(zoom, 3.0)));
+    let after = h.state();
+    assert!(close(after.zoom, before.zoom) && after.pan == before.pan);
+    assert!(after.auto_fit);
+}
+
+#[test]
+fn switches_between_files_each_keeping_its_view() {
+    let mut h = Harness::new(&[
+        ("a.svg", &wide("red")),
+        ("b.svg", &wide("blue")),
+        ("c.svg", &wide("red")),
+    ]);
+    h.hold(Key::Equal, 5);
+    h.wait_for("the zoom", |e| matches!(e, Event::ViewChanged { .. }));
+    let zoomed = h.state();
+    assert!(!zoomed.auto_fit);
+
+    h.press(Key::Tab);
+    h.settle();
+    assert!(h.title().contains("b.svg (2/3)"), "{}", h.title());
+    assert!(h.state().auto_fit);
+    assert_eq!(h.frame().pixel(WIDTH / 2, 75), BLUE);
+
+    h.press(Key::Key3);
+    h.settle();
+    assert!(h.title().contains("c.svg (3/3)"), "{}", h.title());
+
+    h.press(Key::Key1);
+    h.settle();
+    assert!(h.title().contains("a.svg (1/3)"), "{}", h.title());
+    let back = h.state();
+    assert!(!back.auto_fit && close(back.zoom, zoomed.zoom) && back.pan == zoomed.pan);
+
+    // Flipping takes the view along, and both inputs keep it
+    h.press(Key::Backquote);
+    h.settle();
+    assert!(h.title().contains("b.svg (2/3)"), "{}", h.title());
+    assert!(!h.state().auto_fit && close(h.state().zoom, zoomed.zoom));
+    h.press(Key::Key3);
+    h.settle();
+    assert!(h.state().auto_fit);
+    for key in [Key::Key1, Key::Key2] {
+        h.press(key);
+        h.settle();
+        let view = h.state();
+        assert!(!view.auto_fit && close(view.zoom, zoomed.zoom) && view.pan == zoomed.pan);
+    }
+
+    // Files handed over by another instance open beside the rest
+    h.write("d.svg", &wide("blue"));
+    let d = h.path("d.svg");
+    h.send(Action::OpenTab(d.clone()));
+    h.wait_for("the new file", |e| matches!(e, Event::Loaded(p) if *p == d));
+    h.settle();
+    assert!(h.title().contains("d.svg (4/4)"), "{}", h.title());
+    assert!(h.state().auto_fit);
+    h.send(Action::OpenTab(h.path("a.svg")));
+    h.settle();
+    assert!(h.title().contains("a.svg (1/4)"), "{}", h.title());
+    assert!(close(h.state().zoom, zoomed.zoom));
+}
+
+#[test]
+fn ignores_views_that_cannot_be_drawn() {
+    let h = Harness::new(&[("doc.svg", &wide("red"))]);
+    h.send(Action::SetZoom(2.0));
+    for zoom in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+        h.send(Action::SetZoom(zoom));
+    }
+    for (x, y) in [
+        (f32::NAN, 0.0),
+        (0.0, f32::INFINITY),
+        (f32::NEG_INFINITY, 0.0),
+    ] {
+        h.send(Action::SetPan(x, y));
+    }
+    let state = h.state();
+    assert!(
+        close(state.zoom, 2.0) && state.pan == (0.0, 0.0),
+        "{state:?}"
+    );
+    assert_eq!(h.frame().pixel(WIDTH / 2, HEIGHT / 2), RED);
+}
+
+#[test]
+fn steps_through_files_keeping_the_view() {
+    let h = Harness::new(&[
+        ("a.svg", &wide("red")),
+        ("b.svg", &wide("blue")),
+        ("c.svg", &wide("red")),
+    ]);
+    h.send(Action::SetZoom(2.0));
+    h.press(Key::P);
+    h.settle();
+    assert!(h.title().contains("c.svg (3/3)"), "{}", h.title());
+    assert!(close(h.state().zoom, 2.0));
+    h.press(Key::N);
+    h.press(Key::N);
+    h.settle();
+    assert!(h.title().contains("b.svg (2/3)"), "{}", h.title());
+    assert!(close(h.state().zoom, 2.0));
+    assert_eq!(h.frame().pixel(WIDTH / 2, HEIGHT / 2), BLUE);
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+fn plugins_cannot_set_views_that_cannot_be_drawn() {
+    let plugin = std::env::temp_dir().join(format!("svgtail-plugin-{}.wat", std::process::id()));
+    fs::write(
+        &plugin,
+        r#"(module
+            (import "svgtail" "set_zoom" (func $zoom (param f32)))
+            (import "svgtail" "set_pan" (func $pan (param f32 f32)))
+            (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32) i32.const 0)
+            (func (export "on_load") (param i32 i32)
+                (call $zoom (f32.const 2))
+                (call $zoom (f32.const 0))
+                (call $zoom (f32.const -1))
+                (call $zoom (f32.const nan))
+                (call $zoom (f32.const inf))
+                (call $pan (f32.const nan) (f32.const 0))
+                (call $pan (f32.const 0) (f32.const -inf))))"#,
+    )
+    .unwrap();
+    let loaded = svgtail::plugin::Plugin::load(&plugin);
+    fs::remove_file(&plugin).unwrap();
+    let loaded = loaded.unwrap();
+    let mut h = Harness::with_setup(&[("doc.svg", &wide("red"))], support::config(), |viewer| {
+        viewer.add_hooks(Box::new(loaded));
+    });
+    h.wait_for(
+        "the zoom",
+        |e| matches!(e, Event::ViewChanged { zoom, .. } if close(*zoom, 2.0)),
+    );
+    let state = h.state();
+    assert!(
+        close(state.zoom, 2.0) && state.pan == (0.0, 0.0),
+        "{state:?}"
+    );
+    assert_eq!(h.frame().pixel(WIDTH / 2, HEIGHT / 2), RED);
+}
+
+#[cfg(feature = "rpc")]
+#[test]
+fn rpc_refuses_views_that_cannot_be_drawn() {
+    use std::io::{BufRead, BufReader, Write};
+
+    let h = Harness::new(&[("doc.svg", &wide("red"))]);
+    let addr = svgtail::rpc::serve("127.0.0.1:0", h.sender()).unwrap();
+    let stream = std::net::TcpStream::connect(addr).unwrap();
+    let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+    let mut call = |params: &str| {
+        let request = format!(r#"{{"jsonrpc":"2.0","id":1,"method":"setView","params":{params}}}"#);
+        writeln!(&stream, "{request}").unwrap();
+        lines.next().unwrap().unwrap()
+    };
+    for params in [
+        r#"{"zoom":0}"#,
+        r#"{"zoom":-1}"#,
+        r#"{"zoom":1e300}"#,
+        r#"{"fit":true,"pan":[1e300,0]}"#,
+    ] {
+        let reply = call(params);
+        assert!(reply.contains("-32602"), "{params}: {reply}");
+    }
+    assert!(h.state().auto_fit && h.state().pan == (0.0, 0.0));
+
+    let reply = call(r#"{"zoom":2,"pan":[10,-20]}"#);
+    assert!(reply.contains("result"), "{reply}");
+    let state = h.state();
+    assert!(close(state.zoom, 2.0) && state.pan == (10.0, -20.0));
+}
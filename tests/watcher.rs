@@ -0,0 +1,52 @@
+//! The supervised watcher starting over when its threads die
+
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use notify_debouncer_full::notify::RecursiveMode;
+use svgtail::watcher::{Status, Watcher};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn restarts_after_a_panic_and_watches_again() {
+    let dir = std::env::temp_dir().join(format!("svgtail-watcher-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("doc.svg");
+    fs::write(&file, "1").unwrap();
+
+    let (statuses, status) = mpsc::channel();
+    let (events, event) = mpsc::channel();
+    let panicked = AtomicBool::new(false);
+    let watcher = Watcher::spawn(
+        vec![(file.clone(), RecursiveMode::NonRecursive)],
+        move |result| {
+            // Takes the debouncer's thread down with it, as a fault in notify would
+            if !panicked.swap(true, Ordering::Relaxed) {
+                panic!("the first event");
+            }
+            let _ = events.send(result.is_ok());
+        },
+        move |s| {
+            let _ = statuses.send(s);
+        },
+    )
+    .unwrap();
+
+    fs::write(&file, "2").unwrap();
+    let down = status.recv_timeout(TIMEOUT).unwrap();
+    assert!(matches!(down, Status::Down(_)), "{down:?}");
+    assert_eq!(status.recv_timeout(TIMEOUT).unwrap(), Status::Watching);
+
+    fs::write(&file, "3").unwrap();
+    assert!(event.recv_timeout(TIMEOUT).unwrap());
+    assert!(
+        watcher
+            .watch(&dir.join("missing.svg"), RecursiveMode::NonRecursive)
+            .is_err()
+    );
+    drop(watcher);
+    let _ = fs::remove_dir_all(&dir);
+}